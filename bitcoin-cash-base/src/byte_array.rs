@@ -13,6 +13,7 @@ pub enum Function {
     Sha1,
     Ripemd160,
     Sha256,
+    Sha512,
     Hash256,
     Hash160,
     Num2Bin,
@@ -571,28 +572,16 @@ impl From<&[u8]> for ByteArray {
     }
 }
 
-macro_rules! array_impls {
-    ($($N:literal)+) => {
-        $(
-            impl From<[u8; $N]> for ByteArray {
-                fn from(array: [u8; $N]) -> Self {
-                    ByteArray::from_slice_unnamed(array.as_ref())
-                }
-            }
-            impl From<&[u8; $N]> for ByteArray {
-                fn from(array: &[u8; $N]) -> Self {
-                    ByteArray::from_slice_unnamed(array.as_ref())
-                }
-            }
-        )+
+impl<const N: usize> From<[u8; N]> for ByteArray {
+    fn from(array: [u8; N]) -> Self {
+        ByteArray::from_slice_unnamed(array.as_ref())
     }
 }
 
-array_impls! {
-     0  1  2  3  4  5  6  7  8  9
-    10 11 12 13 14 15 16 17 18 19
-    20 21 22 23 24 25 26 27 28 29
-    30 31 32
+impl<const N: usize> From<&[u8; N]> for ByteArray {
+    fn from(array: &[u8; N]) -> Self {
+        ByteArray::from_slice_unnamed(array.as_ref())
+    }
 }
 
 impl Hash for ByteArray {
@@ -968,4 +957,11 @@ mod tests {
             assert_eq!(right_preimage[0].preimage, None);
         }
     }
+
+    #[test]
+    fn test_from_array() {
+        assert_eq!(ByteArray::from([7u8; 64]).as_ref(), [7u8; 64].as_ref());
+        assert_eq!(ByteArray::from(&[1u8; 33]).len(), 33);
+        assert!(ByteArray::from([0u8; 0]).is_empty());
+    }
 }
@@ -12,6 +12,24 @@ pub fn read_var_int<R: io::Read>(read: &mut R) -> io::Result<u64> {
     }
 }
 
+// like read_var_int, but rejects encodings that aren't the shortest possible, as bitcoind does
+pub fn read_var_int_canonical<R: io::Read>(read: &mut R) -> io::Result<u64> {
+    let first_byte = read.read_u8()?;
+    let (number, min_number) = match first_byte {
+        0..=0xfc => return Ok(first_byte as u64),
+        0xfd => (read.read_u16::<LittleEndian>()? as u64, 0xfd),
+        0xfe => (read.read_u32::<LittleEndian>()? as u64, 0x1_0000),
+        0xff => (read.read_u64::<LittleEndian>()?, 0x1_0000_0000),
+    };
+    if number < min_number {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "non-canonical var int",
+        ));
+    }
+    Ok(number)
+}
+
 pub fn write_var_int<W: io::Write>(write: &mut W, number: u64) -> io::Result<()> {
     match number {
         0..=0xfc => write.write_u8(number as u8)?,
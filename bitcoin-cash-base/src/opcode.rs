@@ -11,10 +11,12 @@ lazy_static! {
         let mut map = HashMap::new();
         map.insert("OP_0".to_string(), Opcode::OP_0);
         map.insert("OP_1NEGATE".to_string(), Opcode::OP_1NEGATE);
+        // skips the undefined codes between OP_REVERSEBYTES and the token opcodes
         for code in 0x51..Opcode::FIRST_UNDEFINED_OP_VALUE as u8 {
-            let opcode: Opcode = num::FromPrimitive::from_u8(code)
-                .unwrap_or_else(|| panic!("Invalid opcode {}", code));
-            map.insert(format!("{:?}", opcode), opcode);
+            let opcode: Option<Opcode> = num::FromPrimitive::from_u8(code);
+            if let Some(opcode) = opcode {
+                map.insert(format!("{:?}", opcode), opcode);
+            }
         }
         map
     };
@@ -2418,6 +2420,152 @@ pub enum Opcode {
     /// ```
     OP_REVERSEBYTES = 0xbc,
 
+    /// ```text
+    /// OP_UTXOTOKENCATEGORY(input_idx: Integer) -> ByteArray
+    /// ```
+    ///
+    /// Pushes the token category of the output spent by the input at `input_idx`, followed by
+    /// the capability byte (0x01 mutable, 0x02 minting) if it has a mutable or minting NFT.
+    /// Pushes an empty array if the spent output has no tokens.
+    ///
+    /// Usage:
+    /// ```
+    /// # use bitcoin_cash::Opcode::*;
+    /// # struct Params;
+    /// # #[bitcoin_cash::script(DemoInputs)]
+    /// # fn demo(_: Params) {
+    /// let input_idx = 0;
+    ///
+    /// // category of the tokens spent by the first input
+    /// let category = OP_UTXOTOKENCATEGORY(input_idx);
+    ///
+    /// let expected = [0x11; 32];
+    /// OP_EQUALVERIFY(category, expected);
+    /// # }
+    /// ```
+    OP_UTXOTOKENCATEGORY = 0xce,
+
+    /// ```text
+    /// OP_UTXOTOKENCOMMITMENT(input_idx: Integer) -> ByteArray
+    /// ```
+    ///
+    /// Pushes the NFT commitment of the output spent by the input at `input_idx`, or an empty
+    /// array if it has no NFT or an empty commitment.
+    ///
+    /// Usage:
+    /// ```
+    /// # use bitcoin_cash::Opcode::*;
+    /// # struct Params;
+    /// # #[bitcoin_cash::script(DemoInputs)]
+    /// # fn demo(_: Params) {
+    /// let input_idx = 0;
+    ///
+    /// // commitment of the NFT spent by the first input
+    /// let commitment = OP_UTXOTOKENCOMMITMENT(input_idx);
+    ///
+    /// let expected = b"ticket";
+    /// OP_EQUALVERIFY(commitment, expected);
+    /// # }
+    /// ```
+    OP_UTXOTOKENCOMMITMENT = 0xcf,
+
+    /// ```text
+    /// OP_UTXOTOKENAMOUNT(input_idx: Integer) -> Integer
+    /// ```
+    ///
+    /// Pushes the fungible token amount of the output spent by the input at `input_idx`, 0 if
+    /// it has no fungible tokens.
+    ///
+    /// Usage:
+    /// ```
+    /// # use bitcoin_cash::Opcode::*;
+    /// # struct Params;
+    /// # #[bitcoin_cash::script(DemoInputs)]
+    /// # fn demo(_: Params) {
+    /// let input_idx = 0;
+    ///
+    /// // fungible tokens spent by the first input
+    /// let amount = OP_UTXOTOKENAMOUNT(input_idx);
+    ///
+    /// let expected = 1000;
+    /// OP_NUMEQUALVERIFY(amount, expected);
+    /// # }
+    /// ```
+    OP_UTXOTOKENAMOUNT = 0xd0,
+
+    /// ```text
+    /// OP_OUTPUTTOKENCATEGORY(output_idx: Integer) -> ByteArray
+    /// ```
+    ///
+    /// Pushes the token category of the output at `output_idx`, followed by the capability
+    /// byte (0x01 mutable, 0x02 minting) if it has a mutable or minting NFT. Pushes an empty
+    /// array if the output has no tokens.
+    ///
+    /// Usage:
+    /// ```
+    /// # use bitcoin_cash::Opcode::*;
+    /// # struct Params;
+    /// # #[bitcoin_cash::script(DemoInputs)]
+    /// # fn demo(_: Params) {
+    /// let output_idx = 0;
+    ///
+    /// // category of the tokens of the first output
+    /// let category = OP_OUTPUTTOKENCATEGORY(output_idx);
+    ///
+    /// let expected = [0x11; 32];
+    /// OP_EQUALVERIFY(category, expected);
+    /// # }
+    /// ```
+    OP_OUTPUTTOKENCATEGORY = 0xd1,
+
+    /// ```text
+    /// OP_OUTPUTTOKENCOMMITMENT(output_idx: Integer) -> ByteArray
+    /// ```
+    ///
+    /// Pushes the NFT commitment of the output at `output_idx`, or an empty array if it has no
+    /// NFT or an empty commitment.
+    ///
+    /// Usage:
+    /// ```
+    /// # use bitcoin_cash::Opcode::*;
+    /// # struct Params;
+    /// # #[bitcoin_cash::script(DemoInputs)]
+    /// # fn demo(_: Params) {
+    /// let output_idx = 0;
+    ///
+    /// // commitment of the NFT of the first output
+    /// let commitment = OP_OUTPUTTOKENCOMMITMENT(output_idx);
+    ///
+    /// let expected = b"ticket";
+    /// OP_EQUALVERIFY(commitment, expected);
+    /// # }
+    /// ```
+    OP_OUTPUTTOKENCOMMITMENT = 0xd2,
+
+    /// ```text
+    /// OP_OUTPUTTOKENAMOUNT(output_idx: Integer) -> Integer
+    /// ```
+    ///
+    /// Pushes the fungible token amount of the output at `output_idx`, 0 if it has no fungible
+    /// tokens.
+    ///
+    /// Usage:
+    /// ```
+    /// # use bitcoin_cash::Opcode::*;
+    /// # struct Params;
+    /// # #[bitcoin_cash::script(DemoInputs)]
+    /// # fn demo(_: Params) {
+    /// let output_idx = 0;
+    ///
+    /// // fungible tokens of the first output
+    /// let amount = OP_OUTPUTTOKENAMOUNT(output_idx);
+    ///
+    /// let expected = 1000;
+    /// OP_NUMEQUALVERIFY(amount, expected);
+    /// # }
+    /// ```
+    OP_OUTPUTTOKENAMOUNT = 0xd3,
+
     /// The first op_code value after all defined opcodes
     FIRST_UNDEFINED_OP_VALUE,
 }
@@ -2829,6 +2977,30 @@ pub mod func {
     pub fn OP_REVERSEBYTES(array: BitcoinByteArray) -> BitcoinByteArray {
         BitcoinByteArray(b"MOCK".as_ref().into())
     }
+    #[inline(always)]
+    pub fn OP_UTXOTOKENCATEGORY(input_idx: BitcoinInteger) -> BitcoinByteArray {
+        BitcoinByteArray(b"MOCK".as_ref().into())
+    }
+    #[inline(always)]
+    pub fn OP_UTXOTOKENCOMMITMENT(input_idx: BitcoinInteger) -> BitcoinByteArray {
+        BitcoinByteArray(b"MOCK".as_ref().into())
+    }
+    #[inline(always)]
+    pub fn OP_UTXOTOKENAMOUNT(input_idx: BitcoinInteger) -> BitcoinInteger {
+        BitcoinInteger(Integer::ZERO)
+    }
+    #[inline(always)]
+    pub fn OP_OUTPUTTOKENCATEGORY(output_idx: BitcoinInteger) -> BitcoinByteArray {
+        BitcoinByteArray(b"MOCK".as_ref().into())
+    }
+    #[inline(always)]
+    pub fn OP_OUTPUTTOKENCOMMITMENT(output_idx: BitcoinInteger) -> BitcoinByteArray {
+        BitcoinByteArray(b"MOCK".as_ref().into())
+    }
+    #[inline(always)]
+    pub fn OP_OUTPUTTOKENAMOUNT(output_idx: BitcoinInteger) -> BitcoinInteger {
+        BitcoinInteger(Integer::ZERO)
+    }
 }
 
 impl Opcode {
@@ -3025,6 +3197,12 @@ impl Opcode {
                 &[],
             ),
             OP_REVERSEBYTES => u(&[ByteArray(None)], &[ByteArray(None)], &[Changed]),
+            OP_UTXOTOKENCATEGORY => u(&[Integer], &[ByteArray(None)], &[Changed]),
+            OP_UTXOTOKENCOMMITMENT => u(&[Integer], &[ByteArray(None)], &[Changed]),
+            OP_UTXOTOKENAMOUNT => u(&[Integer], &[Integer], &[Changed]),
+            OP_OUTPUTTOKENCATEGORY => u(&[Integer], &[ByteArray(None)], &[Changed]),
+            OP_OUTPUTTOKENCOMMITMENT => u(&[Integer], &[ByteArray(None)], &[Changed]),
+            OP_OUTPUTTOKENAMOUNT => u(&[Integer], &[Integer], &[Changed]),
             OP_0 | OP_1NEGATE | OP_1 | OP_2 | OP_3 | OP_4 | OP_5 | OP_6 | OP_7 | OP_8 | OP_9
             | OP_10 | OP_11 | OP_12 | OP_13 | OP_14 | OP_15 | OP_16 => u(&[], &[Integer], &[Added]),
 
@@ -20,10 +20,397 @@ fn parse_string_lit(lit: &syn::Lit) -> Option<String> {
     }
 }
 
+fn parse_path_lit(lit: &syn::Lit) -> Result<syn::Path, syn::Error> {
+    match lit {
+        syn::Lit::Str(path_str) => path_str.parse(),
+        _ => Err(syn::Error::new(
+            lit.span(),
+            "Invalid attribute, expected a path like this: validate=\"path::to::fn\"",
+        )),
+    }
+}
+
+fn validate_call(
+    crate_name: &TokenStream,
+    validator: &syn::Path,
+    value: TokenStream,
+    name: &str,
+) -> TokenStream {
+    quote! {
+        #validator(&#value).map_err(|err| #crate_name::error::Error::ValidationFailed {
+            field: #name,
+            source: Box::new(err),
+        })?;
+    }
+}
+
+enum OptionalEncoding {
+    Trailing,
+    Flag,
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    is_skipped: bool,
+    skip_default: Option<syn::Expr>,
+    is_big_endian: bool,
+    fixed_count: Option<syn::LitInt>,
+    optional: Option<OptionalEncoding>,
+    len_prefix: Option<TokenStream>,
+    validate: Option<syn::Path>,
+    tag: Option<TokenStream>,
+    with: Option<syn::Path>,
+}
+
+fn parse_field_options(field: &syn::Field) -> Result<FieldOptions, syn::Error> {
+    let mut options = FieldOptions::default();
+    for attr in &field.attrs {
+        if !attr.path.is_ident("bitcoin_code") {
+            continue;
+        }
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            _ => return Err(syn::Error::new(attr.span(), "Invalid attribute")),
+        };
+        for nested in list.nested.iter() {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("skip") => {
+                    options.is_skipped = true;
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(name_value))
+                    if name_value.path.is_ident("skip") =>
+                {
+                    let expr = match &name_value.lit {
+                        syn::Lit::Str(expr) => expr.parse::<syn::Expr>()?,
+                        _ => {
+                            return Err(syn::Error::new(
+                                name_value.lit.span(),
+                                "Invalid attribute, skip must be a string expression",
+                            ))
+                        }
+                    };
+                    options.is_skipped = true;
+                    options.skip_default = Some(expr);
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("big_endian") => {
+                    options.is_big_endian = true;
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(name_value))
+                    if name_value.path.is_ident("fixed_count") =>
+                {
+                    match &name_value.lit {
+                        syn::Lit::Int(count) => options.fixed_count = Some(count.clone()),
+                        _ => {
+                            return Err(syn::Error::new(
+                                name_value.lit.span(),
+                                "Invalid attribute, fixed_count must be an integer",
+                            ))
+                        }
+                    }
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(name_value))
+                    if name_value.path.is_ident("optional") =>
+                {
+                    let encoding = parse_string_lit(&name_value.lit);
+                    options.optional = match encoding.as_deref() {
+                        Some("trailing") => Some(OptionalEncoding::Trailing),
+                        Some("flag") => Some(OptionalEncoding::Flag),
+                        _ => {
+                            return Err(syn::Error::new(
+                                name_value.lit.span(),
+                                "Invalid attribute, optional must be \"trailing\" or \"flag\"",
+                            ))
+                        }
+                    };
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(name_value))
+                    if name_value.path.is_ident("len_prefix") =>
+                {
+                    let prefix = parse_string_lit(&name_value.lit);
+                    options.len_prefix = match prefix.as_deref() {
+                        Some("var") => Some(quote! {Var}),
+                        Some("u8") => Some(quote! {U8}),
+                        Some("u16") => Some(quote! {U16}),
+                        Some("u32") => Some(quote! {U32}),
+                        _ => {
+                            return Err(syn::Error::new(
+                                name_value.lit.span(),
+                                "Invalid attribute, len_prefix must be var, u8, u16 or u32",
+                            ))
+                        }
+                    };
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(name_value))
+                    if name_value.path.is_ident("validate") =>
+                {
+                    options.validate = Some(parse_path_lit(&name_value.lit)?);
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(name_value))
+                    if name_value.path.is_ident("with") =>
+                {
+                    options.with = Some(parse_path_lit(&name_value.lit)?);
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(name_value))
+                    if name_value.path.is_ident("tag") =>
+                {
+                    let lit = &name_value.lit;
+                    options.tag = match lit {
+                        syn::Lit::ByteStr(_) => Some(quote! {(*#lit)}),
+                        syn::Lit::Int(_) | syn::Lit::Bool(_) => Some(quote! {(#lit)}),
+                        _ => {
+                            return Err(syn::Error::new(
+                                lit.span(),
+                                "Invalid attribute, tag must be an int, bool or byte string",
+                            ))
+                        }
+                    };
+                }
+                _ => return Err(syn::Error::new(nested.span(), "Invalid attribute")),
+            }
+        }
+    }
+    let num_encodings = options.is_big_endian as usize
+        + options.fixed_count.is_some() as usize
+        + options.optional.is_some() as usize
+        + options.len_prefix.is_some() as usize
+        + options.with.is_some() as usize;
+    if num_encodings > 1 {
+        return Err(syn::Error::new(
+            field.span(),
+            "Invalid attribute, only one of big_endian, fixed_count, optional, len_prefix and with \
+             can be used",
+        ));
+    }
+    if options.tag.is_some() && num_encodings > options.is_big_endian as usize {
+        return Err(syn::Error::new(
+            field.span(),
+            "Invalid attribute, tag can only be combined with big_endian",
+        ));
+    }
+    if options.is_skipped && (num_encodings > 0 || options.tag.is_some()) {
+        return Err(syn::Error::new(
+            field.span(),
+            "Invalid attribute, skipped fields cannot have an encoding or tag",
+        ));
+    }
+    Ok(options)
+}
+
+fn ser_field(
+    crate_name: &TokenStream,
+    parts_ident: &TokenStream,
+    options: &FieldOptions,
+    value: &TokenStream,
+    field_name_str: &str,
+) -> TokenStream {
+    if let Some(with) = &options.with {
+        quote! {
+            #parts_ident.push(#with::ser(&#value).named(#field_name_str));
+        }
+    } else if options.is_big_endian {
+        quote! {
+            #parts_ident.push(
+                #crate_name::BigEndianCode::ser_be(&#value).named(#field_name_str)
+            );
+        }
+    } else if options.fixed_count.is_some() {
+        quote! {
+            #parts_ident.push(
+                #crate_name::ser_fixed_count(&#value).named(#field_name_str)
+            );
+        }
+    } else if let Some(prefix) = &options.len_prefix {
+        quote! {
+            #parts_ident.push(
+                #crate_name::ser_len_prefixed(
+                    #value.as_ref(),
+                    #crate_name::LenPrefix::#prefix,
+                )
+                .named(#field_name_str)
+            );
+        }
+    } else if let Some(encoding) = &options.optional {
+        match encoding {
+            OptionalEncoding::Trailing => {
+                quote! {
+                    if let Some(value) = &#value {
+                        #parts_ident.push(value.ser().named(#field_name_str));
+                    }
+                }
+            }
+            OptionalEncoding::Flag => {
+                quote! {
+                    #parts_ident.push(
+                        #crate_name::ser_optional_flag(#value.as_ref())
+                            .named(#field_name_str)
+                    );
+                }
+            }
+        }
+    } else {
+        quote! {
+            #parts_ident.push(#value.ser().named(#field_name_str));
+        }
+    }
+}
+
+// streams a field into `__writer`; fields with a custom encoding write their `ser` output
+fn encode_field(
+    crate_name: &TokenStream,
+    options: &FieldOptions,
+    value: &TokenStream,
+) -> TokenStream {
+    let parts_ident = quote! {__encoded_parts};
+    if options.fixed_count.is_some() {
+        quote! {
+            for item in #value.iter() {
+                #crate_name::BitcoinCode::encode_to(item, __writer)?;
+            }
+        }
+    } else if let Some(OptionalEncoding::Trailing) = &options.optional {
+        quote! {
+            if let Some(value) = &#value {
+                #crate_name::BitcoinCode::encode_to(value, __writer)?;
+            }
+        }
+    } else if options.is_big_endian
+        || options.len_prefix.is_some()
+        || options.optional.is_some()
+        || options.with.is_some()
+    {
+        let ser_call = ser_field(crate_name, &parts_ident, options, value, "");
+        quote! {
+            let mut #parts_ident = Vec::with_capacity(1);
+            #ser_call
+            std::io::Write::write_all(__writer, &#parts_ident[0])?;
+        }
+    } else {
+        quote! {
+            #crate_name::BitcoinCode::encode_to(&#value, __writer)?;
+        }
+    }
+}
+
+fn field_size(
+    crate_name: &TokenStream,
+    options: &FieldOptions,
+    value: &TokenStream,
+) -> TokenStream {
+    if let Some(with) = &options.with {
+        quote! {#with::ser(&#value).len()}
+    } else if options.is_big_endian {
+        quote! {std::mem::size_of_val(&#value)}
+    } else if options.fixed_count.is_some() {
+        quote! {
+            #value.iter().map(#crate_name::BitcoinCode::encoded_size).sum::<usize>()
+        }
+    } else if let Some(prefix) = &options.len_prefix {
+        quote! {
+            #crate_name::len_prefixed_size(#value.as_ref(), #crate_name::LenPrefix::#prefix)
+        }
+    } else if let Some(encoding) = &options.optional {
+        let flag_size = match encoding {
+            OptionalEncoding::Trailing => 0usize,
+            OptionalEncoding::Flag => 1,
+        };
+        quote! {
+            #flag_size + #value.as_ref().map_or(0, #crate_name::BitcoinCode::encoded_size)
+        }
+    } else {
+        quote! {#crate_name::BitcoinCode::encoded_size(&#value)}
+    }
+}
+
+#[derive(Clone, Copy)]
+enum DeserMode {
+    // `deser_rest` from a ByteArray
+    Rest,
+    // `deser_slice` from a borrowed slice
+    Slice,
+    // `decode_from` an io::Read
+    Reader,
+}
+
+// the helpers for each mode are named `deser_x`, `deser_x_slice` and `decode_x_from`;
+// ByteArray and slice errors get the field and its offset added to their path
+fn deser_field(
+    crate_name: &TokenStream,
+    struct_name: &str,
+    options: &FieldOptions,
+    field_name: &syn::Ident,
+    field_type: &syn::Type,
+    mode: DeserMode,
+) -> TokenStream {
+    if options.is_skipped {
+        return quote! {};
+    }
+    let helper = |name: &str| {
+        let name = match mode {
+            DeserMode::Rest => name.to_string(),
+            DeserMode::Slice => format!("{}_slice", name),
+            DeserMode::Reader => format!("{}_from", name.replacen("deser_", "decode_", 1)),
+        };
+        syn::Ident::new(&name, proc_macro2::Span::call_site())
+    };
+    let input = match mode {
+        DeserMode::Reader => quote! {__reader},
+        _ => quote! {data},
+    };
+    let call = if let Some(with) = &options.with {
+        match mode {
+            DeserMode::Rest => quote! {#with::deser_rest(data)},
+            DeserMode::Slice => quote! {#crate_name::deser_slice_with(data, #with::deser_rest)},
+            // `__reader` is a PeekReader in structs with `with` fields
+            DeserMode::Reader => quote! {#with::decode_from(__reader)},
+        }
+    } else if options.is_big_endian {
+        let deser_be = match mode {
+            DeserMode::Rest => quote! {deser_be_rest},
+            DeserMode::Slice => quote! {deser_be_slice},
+            DeserMode::Reader => quote! {decode_be_from},
+        };
+        quote! {<#field_type as #crate_name::BigEndianCode>::#deser_be(#input)}
+    } else if let Some(count) = &options.fixed_count {
+        let deser_fixed_count = helper("deser_fixed_count");
+        quote! {#crate_name::#deser_fixed_count(#input, #count)}
+    } else if let Some(prefix) = &options.len_prefix {
+        let deser_len_prefixed = helper("deser_len_prefixed");
+        quote! {#crate_name::#deser_len_prefixed(#input, #crate_name::LenPrefix::#prefix)}
+    } else if let Some(encoding) = &options.optional {
+        let deser_optional = match encoding {
+            OptionalEncoding::Trailing => helper("deser_optional_trailing"),
+            OptionalEncoding::Flag => helper("deser_optional_flag"),
+        };
+        quote! {#crate_name::#deser_optional(#input)}
+    } else {
+        let deser = match mode {
+            DeserMode::Rest => quote! {deser_rest},
+            DeserMode::Slice => quote! {deser_slice},
+            DeserMode::Reader => quote! {decode_from},
+        };
+        quote! {<#field_type as #crate_name::BitcoinCode>::#deser(#input)}
+    };
+    match mode {
+        DeserMode::Reader => quote! {
+            let #field_name = #call?;
+        },
+        DeserMode::Rest | DeserMode::Slice => {
+            let field_name_str = field_name.to_string();
+            quote! {
+                let __offset = __data_len - data.len();
+                let (#field_name, data) = #call.map_err(|err| {
+                    #crate_name::deser_error_in_field(err, #struct_name, #field_name_str, __offset)
+                })?;
+            }
+        }
+    }
+}
+
 fn generate(item_struct: syn::ItemStruct) -> Result<TokenStream, syn::Error> {
     let struct_name = &item_struct.ident;
 
     let mut crate_name = quote! {bitcoin_cash};
+    let mut struct_validator = None;
 
     for attr in &item_struct.attrs {
         match attr.parse_meta()? {
@@ -48,6 +435,9 @@ fn generate(item_struct: syn::ItemStruct) -> Result<TokenStream, syn::Error> {
                                             syn::Ident::new(&crate_name_str, name_value.lit.span())
                                                 .to_token_stream();
                                     }
+                                    "validate" => {
+                                        struct_validator = Some(parse_path_lit(&name_value.lit)?);
+                                    }
                                     _ => {
                                         return Err(syn::Error::new(
                                             name_value.span(),
@@ -76,14 +466,9 @@ fn generate(item_struct: syn::ItemStruct) -> Result<TokenStream, syn::Error> {
         }
     }
 
-    let fields = match &item_struct.fields {
-        syn::Fields::Named(fields) => fields,
-        syn::Fields::Unnamed(_) => {
-            return Err(syn::Error::new(
-                struct_name.span(),
-                "Cannot use macro for unnamed struct fields",
-            ))
-        }
+    let (fields, is_tuple) = match &item_struct.fields {
+        syn::Fields::Named(fields) => (&fields.named, false),
+        syn::Fields::Unnamed(fields) => (&fields.unnamed, true),
         syn::Fields::Unit => {
             return Err(syn::Error::new(
                 struct_name.span(),
@@ -93,61 +478,167 @@ fn generate(item_struct: syn::ItemStruct) -> Result<TokenStream, syn::Error> {
     };
 
     let parts_ident = quote! {__serialize_parts};
+    let struct_name_str = struct_name.to_string();
 
     let mut ser_calls = Vec::new();
     let mut deser_calls = Vec::new();
+    let mut deser_slice_calls = Vec::new();
+    let mut encode_calls = Vec::new();
+    let mut size_terms = Vec::new();
+    let mut decode_calls = Vec::new();
     let mut field_idents = Vec::new();
+    let mut has_trailing = false;
+    let mut has_with = false;
+    let mut skipped_fields = Vec::new();
 
-    for field in fields.named.iter() {
-        let mut is_skipped = false;
-        for attr in &field.attrs {
-            match attr.parse_meta()? {
-                syn::Meta::Path(_) => {
-                    return Err(syn::Error::new(attr.span(), "Invalid attribute"))
-                }
-                syn::Meta::NameValue(_) => {
-                    return Err(syn::Error::new(attr.span(), "Invalid attribute"))
-                }
-                syn::Meta::List(list) => {
-                    let params = list
-                        .nested
-                        .iter()
-                        .map(|param| param.to_token_stream().to_string())
-                        .collect::<Vec<_>>();
-                    match params
-                        .iter()
-                        .map(|param| param.as_str())
-                        .collect::<Vec<_>>()
-                        .as_slice()
-                    {
-                        ["skip"] => is_skipped = true,
-                        _ => return Err(syn::Error::new(list.nested.span(), "Invalid attribute")),
-                    }
-                }
-            }
-        }
-        let field_name = field.ident.as_ref().unwrap();
-        field_idents.push(field_name);
+    for (field_idx, field) in fields.iter().enumerate() {
+        let options = parse_field_options(field)?;
+        // tuple fields are bound to `field_0`, `field_1`, ..., which also name their parts
+        let (member, field_name) = match &field.ident {
+            Some(ident) => (syn::Member::Named(ident.clone()), ident.clone()),
+            None => (
+                syn::Member::Unnamed(field_idx.into()),
+                syn::Ident::new(&format!("field_{}", field_idx), field.span()),
+            ),
+        };
+        let field_name = &field_name;
+        field_idents.push(field_name.clone());
         let field_name_str = field_name.to_string();
         let field_type = &field.ty;
-        if !is_skipped {
-            ser_calls.push(quote! {
-                #parts_ident.push(self.#field_name.ser().named(#field_name_str));
+        let is_trailing = matches!(options.optional, Some(OptionalEncoding::Trailing));
+        if has_trailing && !is_trailing && !options.is_skipped {
+            return Err(syn::Error::new(
+                field_name.span(),
+                "Only optional trailing fields can follow an optional trailing field",
+            ));
+        }
+        has_trailing |= is_trailing;
+        has_with |= options.with.is_some();
+        if options.is_skipped {
+            // skipped fields are rebuilt after all other fields, so they can refer to them
+            let default = match &options.skip_default {
+                Some(expr) => quote! {#expr},
+                None => quote! {Default::default()},
+            };
+            skipped_fields.push(quote! {
+                let #field_name: #field_type = #default;
             });
-            deser_calls.push(quote! {
-                let (#field_name, data) = <#field_type as #crate_name::BitcoinCode>::deser_rest(data)?;
-            })
         } else {
-            deser_calls.push(quote! {
-                let #field_name = Default::default();
-            })
+            // tags always serialize their constant, whatever the field holds
+            let value = match &options.tag {
+                Some(tag) => quote! {{
+                    let tag: #field_type = #tag;
+                    tag
+                }},
+                None => quote! {self.#member},
+            };
+            ser_calls.push(ser_field(
+                &crate_name,
+                &parts_ident,
+                &options,
+                &value,
+                &field_name_str,
+            ));
+            encode_calls.push(encode_field(&crate_name, &options, &value));
+            size_terms.push(field_size(&crate_name, &options, &value));
+        }
+        for (calls, mode) in [
+            (&mut deser_calls, DeserMode::Rest),
+            (&mut deser_slice_calls, DeserMode::Slice),
+            (&mut decode_calls, DeserMode::Reader),
+        ] {
+            calls.push(deser_field(
+                &crate_name,
+                &struct_name_str,
+                &options,
+                field_name,
+                field_type,
+                mode,
+            ));
+        }
+        if let Some(tag) = &options.tag {
+            let check_tag = quote! {
+                if #field_name != #tag {
+                    return Err(#crate_name::error::Error::InvalidTag {
+                        field: #field_name_str,
+                    });
+                }
+            };
+            deser_calls.push(check_tag.clone());
+            deser_slice_calls.push(check_tag.clone());
+            decode_calls.push(check_tag);
+        }
+        if let Some(validator) = &options.validate {
+            if options.is_skipped {
+                return Err(syn::Error::new(
+                    field_name.span(),
+                    "Invalid attribute, skipped fields cannot be validated",
+                ));
+            }
+            let validate_field = validate_call(
+                &crate_name,
+                validator,
+                quote! {#field_name},
+                &field_name_str,
+            );
+            deser_calls.push(validate_field.clone());
+            deser_slice_calls.push(validate_field.clone());
+            decode_calls.push(validate_field);
         }
     }
 
-    let capacity = fields.named.len();
+    deser_calls.extend(skipped_fields.iter().cloned());
+    deser_slice_calls.extend(skipped_fields.iter().cloned());
+    decode_calls.extend(skipped_fields);
+
+    let struct_validate_call = struct_validator
+        .map(|validator| validate_call(&crate_name, &validator, quote! {item}, &struct_name_str));
+
+    let capacity = fields.len();
+    let construct_item = if is_tuple {
+        quote! {#struct_name(#(#field_idents),*)}
+    } else {
+        quote! {#struct_name { #(#field_idents),* }}
+    };
+
+    // `with` codecs may peek at their first byte, which has to be read by a later field
+    let (wrap_reader, finish_reader) = if has_with {
+        (
+            quote! {
+                let mut __peek_reader = #crate_name::PeekReader::new(__reader);
+                let __reader = &mut __peek_reader;
+            },
+            quote! {__peek_reader.finish()?;},
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+    let decode_from = quote! {
+        fn decode_from<__R: std::io::Read>(__reader: &mut __R) -> std::result::Result<Self, #crate_name::error::Error> {
+            #wrap_reader
+            #(#decode_calls)*
+            #finish_reader
+            let item = #construct_item;
+            #struct_validate_call
+            Ok(item)
+        }
+    };
+
+    let mut generics = item_struct.generics.clone();
+    let type_params = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    let where_clause = generics.make_where_clause();
+    for type_param in type_params {
+        where_clause
+            .predicates
+            .push(syn::parse_quote! {#type_param: #crate_name::BitcoinCode});
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let result = quote! {
-        impl #crate_name::BitcoinCode for #struct_name {
+        impl #impl_generics #crate_name::BitcoinCode for #struct_name #ty_generics #where_clause {
             fn ser(&self) -> #crate_name::ByteArray {
                 let mut #parts_ident = Vec::with_capacity(#capacity);
                 #(#ser_calls)*
@@ -155,11 +646,30 @@ fn generate(item_struct: syn::ItemStruct) -> Result<TokenStream, syn::Error> {
             }
 
             fn deser_rest(data: #crate_name::ByteArray) -> std::result::Result<(Self, #crate_name::ByteArray), #crate_name::error::Error> {
+                let __data_len = data.len();
                 #(#deser_calls)*
-                Ok((
-                    #struct_name { #(#field_idents),* },
-                    data,
-                ))
+                let item = #construct_item;
+                #struct_validate_call
+                Ok((item, data))
+            }
+
+            fn deser_slice(data: &[u8]) -> std::result::Result<(Self, &[u8]), #crate_name::error::Error> {
+                let __data_len = data.len();
+                #(#deser_slice_calls)*
+                let item = #construct_item;
+                #struct_validate_call
+                Ok((item, data))
+            }
+
+            fn encode_to<__W: std::io::Write>(&self, __writer: &mut __W) -> std::result::Result<(), #crate_name::error::Error> {
+                #(#encode_calls)*
+                Ok(())
+            }
+
+            #decode_from
+
+            fn encoded_size(&self) -> usize {
+                0 #(+ #size_terms)*
             }
         }
     };
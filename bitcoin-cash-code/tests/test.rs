@@ -1,4 +1,10 @@
-use bitcoin_cash::{BitcoinCode, ByteArray, Script, Sha256d};
+use bitcoin_cash::{
+    encoding_utils,
+    error::{Error, Result},
+    BitcoinCode, ByteArray, Hashed, Script, Sha256d, Tx, TxInput, TxOutpoint, TxOutput, UnhashedTx,
+    VarInt,
+};
+use std::io::Cursor;
 
 #[test]
 fn test_tx() {
@@ -35,3 +41,549 @@ fn test_tx() {
         raw: ByteArray,
     }
 }
+
+#[test]
+fn test_generic() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Tagged<T, U> {
+        pub tag: u8,
+        pub value: T,
+        pub items: Vec<U>,
+    }
+
+    let tagged = Tagged {
+        tag: 7,
+        value: 0x0102_0304u32,
+        items: vec![1u16, 2],
+    };
+    let ser = tagged.ser();
+    assert_eq!(ser.as_slice(), &[7, 4, 3, 2, 1, 2, 1, 0, 2, 0]);
+    assert_eq!(Tagged::<u32, u16>::deser(ser)?, tagged);
+    Ok(())
+}
+
+#[test]
+fn test_fixed_count() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Header {
+        /// doc comments on fields are ignored by the derive
+        pub counts: Vec<u16>,
+        #[bitcoin_code(fixed_count = 2)]
+        pub pair: Vec<u8>,
+        pub triple: [u8; 3],
+    }
+
+    let header = Header {
+        counts: vec![1],
+        pair: vec![2, 3],
+        triple: [4, 5, 6],
+    };
+    let ser = header.ser();
+    assert_eq!(ser.as_slice(), &[1, 1, 0, 2, 3, 4, 5, 6]);
+    assert_eq!(Header::deser(ser)?, header);
+    Ok(())
+}
+
+#[test]
+fn test_array() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Swap {
+        pub outpoints: [TxOutpoint; 2],
+        pub sig: [u8; 64],
+    }
+
+    let swap = Swap {
+        outpoints: [
+            TxOutpoint {
+                tx_hash: Sha256d::digest(b"a".to_vec()),
+                vout: 0,
+            },
+            TxOutpoint {
+                tx_hash: Sha256d::digest(b"b".to_vec()),
+                vout: 1,
+            },
+        ],
+        sig: [7; 64],
+    };
+    let ser = swap.ser();
+    assert_eq!(ser.len(), 2 * 36 + 64);
+    assert_eq!(swap.encoded_size(), ser.len());
+    assert_eq!(Swap::deser(ser.clone())?, swap);
+    assert_eq!(Swap::deser_slice(&ser)?.0, swap);
+    assert_eq!(Swap::decode_from(&mut Cursor::new(ser.to_vec()))?, swap);
+    assert!(Swap::deser(ser.split(100)?.0).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_optional() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Message {
+        pub version: u8,
+        #[bitcoin_code(optional = "flag")]
+        pub flagged: Option<u16>,
+        #[bitcoin_code(optional = "trailing")]
+        pub extension: Option<u8>,
+    }
+
+    let message = Message {
+        version: 1,
+        flagged: Some(2),
+        extension: Some(3),
+    };
+    assert_eq!(message.ser().as_slice(), &[1, 1, 2, 0, 3]);
+    assert_eq!(Message::deser(message.ser())?, message);
+
+    let message = Message {
+        version: 1,
+        flagged: None,
+        extension: None,
+    };
+    assert_eq!(message.ser().as_slice(), &[1, 0]);
+    assert_eq!(Message::deser(message.ser())?, message);
+    assert!(Message::deser([1, 2].into()).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_len_prefix() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Record {
+        #[bitcoin_code(len_prefix = "u8")]
+        pub short: Vec<u8>,
+        #[bitcoin_code(len_prefix = "u16")]
+        pub medium: ByteArray,
+        #[bitcoin_code(len_prefix = "var")]
+        pub long: Vec<u8>,
+    }
+
+    let record = Record {
+        short: vec![1, 2],
+        medium: vec![3].into(),
+        long: vec![],
+    };
+    assert_eq!(record.ser().as_slice(), &[2, 1, 2, 1, 0, 3, 0]);
+    assert_eq!(Record::deser(record.ser())?, record);
+    Ok(())
+}
+
+#[test]
+fn test_big_endian() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Amounts {
+        #[bitcoin_code(big_endian)]
+        pub network_order: u64,
+        pub little_endian: u16,
+    }
+
+    let amounts = Amounts {
+        network_order: 0x0102,
+        little_endian: 0x0304,
+    };
+    assert_eq!(amounts.ser().as_slice(), &[0, 0, 0, 0, 0, 0, 1, 2, 4, 3]);
+    assert_eq!(Amounts::deser(amounts.ser())?, amounts);
+    Ok(())
+}
+
+#[test]
+fn test_validate() -> Result<()> {
+    fn validate_version(version: &u8) -> Result<()> {
+        if *version > 2 {
+            return Err(Error::Msg(format!("unknown version {}", version)));
+        }
+        Ok(())
+    }
+
+    fn validate_range(range: &Range) -> Result<()> {
+        if range.start > range.end {
+            return Err(Error::Msg("range is reversed".to_string()));
+        }
+        Ok(())
+    }
+
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    #[bitcoin_code(validate = "validate_range")]
+    pub struct Range {
+        #[bitcoin_code(validate = "validate_version")]
+        pub version: u8,
+        pub start: u8,
+        pub end: u8,
+    }
+
+    assert_eq!(
+        Range::deser([1, 2, 3].into())?,
+        Range {
+            version: 1,
+            start: 2,
+            end: 3,
+        }
+    );
+    match Range::deser([3, 2, 3].into()) {
+        Err(Error::ValidationFailed { field, .. }) => assert_eq!(field, "version"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    match Range::deser([1, 3, 2].into()) {
+        Err(Error::ValidationFailed { field, .. }) => assert_eq!(field, "Range"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_deser_slice() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Entry {
+        #[bitcoin_code(big_endian)]
+        pub id: u32,
+        pub script: Script,
+        #[bitcoin_code(len_prefix = "u8")]
+        pub payload: Vec<u8>,
+        #[bitcoin_code(optional = "flag")]
+        pub hash: Option<Sha256d>,
+    }
+
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Entries {
+        pub entries: Vec<Entry>,
+        #[bitcoin_code(optional = "trailing")]
+        pub extension: Option<u8>,
+    }
+
+    let entries = Entries {
+        entries: vec![
+            Entry {
+                id: 1,
+                script: Script::default(),
+                payload: vec![2, 3],
+                hash: Some(Sha256d::new([4; 32])),
+            },
+            Entry {
+                id: 5,
+                script: Script::default(),
+                payload: vec![],
+                hash: None,
+            },
+        ],
+        extension: None,
+    };
+    let mut ser = entries.ser().to_vec();
+    ser.push(6);
+    let (deser, rest) = Entries::deser_slice(&ser[..ser.len() - 1])?;
+    assert_eq!(deser, entries);
+    assert!(rest.is_empty());
+    let (deser, rest) = Entries::deser_slice(&ser)?;
+    assert_eq!(deser.extension, Some(6));
+    assert!(rest.is_empty());
+    assert!(Entries::deser_slice(&ser[..10]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_tuple_struct() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct TokenId(Sha256d);
+
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Amount(pub TokenId, #[bitcoin_code(big_endian)] pub u64);
+
+    let amount = Amount(TokenId(Sha256d::new([1; 32])), 2);
+    let ser = amount.ser();
+    assert_eq!(&ser[32..], &[0, 0, 0, 0, 0, 0, 0, 2]);
+    assert_eq!(Amount::deser(ser.clone())?, amount);
+    assert_eq!(Amount::deser_slice(&ser)?.0, amount);
+    Ok(())
+}
+
+#[test]
+fn test_tag() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Envelope {
+        #[bitcoin_code(tag = 0xE3E1F3E8u32)]
+        pub magic: u32,
+        #[bitcoin_code(tag = b"SLP\0")]
+        pub lokad_id: [u8; 4],
+        #[bitcoin_code(tag = 1, big_endian)]
+        pub version: u16,
+        pub payload: u8,
+    }
+
+    let envelope = Envelope {
+        magic: 0xE3E1F3E8,
+        lokad_id: *b"SLP\0",
+        version: 1,
+        payload: 7,
+    };
+    let ser = envelope.ser();
+    assert_eq!(
+        ser.as_slice(),
+        &[0xe8, 0xf3, 0xe1, 0xe3, b'S', b'L', b'P', 0, 0, 1, 7]
+    );
+    assert_eq!(Envelope::deser(ser.clone())?, envelope);
+    let mut bad_magic = ser.to_vec();
+    bad_magic[0] = 0;
+    match Envelope::deser_slice(&bad_magic) {
+        Err(Error::InvalidTag { field }) => assert_eq!(field, "magic"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_streaming() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Record {
+        #[bitcoin_code(tag = 0xE3E1F3E8u32)]
+        pub magic: u32,
+        #[bitcoin_code(big_endian)]
+        pub height: u32,
+        #[bitcoin_code(fixed_count = 2)]
+        pub pair: Vec<u8>,
+        #[bitcoin_code(len_prefix = "u16")]
+        pub payload: ByteArray,
+        #[bitcoin_code(optional = "flag")]
+        pub flagged: Option<u8>,
+        pub txs: Vec<Tx>,
+        #[bitcoin_code(optional = "trailing")]
+        pub extension: Option<u64>,
+    }
+
+    let tx = UnhashedTx {
+        inputs: vec![TxInput::new(TxOutpoint::default(), Script::default(), 0)],
+        outputs: vec![TxOutput {
+            value: 1_000,
+            script: Script::default(),
+            token: None,
+        }],
+        ..Default::default()
+    }
+    .hashed();
+    let mut record = Record {
+        magic: 0xE3E1F3E8,
+        height: 700_000,
+        pair: vec![1, 2],
+        payload: vec![3, 4, 5].into(),
+        flagged: Some(6),
+        txs: vec![tx.clone(), tx],
+        extension: None,
+    };
+    for extension in [None, Some(7)] {
+        record.extension = extension;
+        let mut encoded = Vec::new();
+        record.encode_to(&mut encoded)?;
+        assert_eq!(encoded, record.ser().to_vec());
+        assert_eq!(record.encoded_size(), encoded.len());
+        let decoded = Record::decode_from(&mut Cursor::new(&encoded))?;
+        assert_eq!(decoded, record);
+        assert_eq!(decoded.txs[0].hash(), record.txs[0].hash());
+    }
+    let mut encoded = Vec::new();
+    record.encode_to(&mut encoded)?;
+    encoded.truncate(20);
+    assert!(Record::decode_from(&mut Cursor::new(&encoded)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_default_decode_from() -> Result<()> {
+    // a manual impl without `decode_from`, like ones outside of this crate
+    #[derive(Debug, PartialEq)]
+    struct Note {
+        kind: u16,
+        text: ByteArray,
+    }
+
+    impl BitcoinCode for Note {
+        fn ser(&self) -> ByteArray {
+            self.kind.ser().concat(self.text.ser())
+        }
+
+        fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+            let (kind, rest) = u16::deser_rest(data)?;
+            let (text, rest) = ByteArray::deser_rest(rest)?;
+            Ok((Note { kind, text }, rest))
+        }
+    }
+
+    let notes = vec![
+        Note {
+            kind: 1,
+            text: b"first".to_vec().into(),
+        },
+        Note {
+            kind: 0x0203,
+            text: ByteArray::from_slice_unnamed(&[]),
+        },
+    ];
+    let mut encoded = notes.ser().to_vec();
+    let len = encoded.len();
+    encoded.push(0xff);
+    let mut reader = Cursor::new(&encoded);
+    assert_eq!(Vec::<Note>::decode_from(&mut reader)?, notes);
+    assert_eq!(reader.position() as usize, len);
+    encoded.truncate(len - 1);
+    assert!(Vec::<Note>::decode_from(&mut Cursor::new(&encoded)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_deser_error_path() -> Result<()> {
+    let input = TxInput::new(TxOutpoint::default(), Script::default(), 0);
+    let tx = UnhashedTx {
+        inputs: vec![input.clone(), input],
+        ..Default::default()
+    };
+    let mut ser = tx.ser().to_vec();
+    // 4 version + 1 num inputs + 41 first input + 36 outpoint of the second input
+    let script_offset = 4 + 1 + 41 + 36;
+    ser[script_offset] = 80;
+    for result in [
+        UnhashedTx::deser(ser.clone().into()).map(|_| ()),
+        UnhashedTx::deser_slice(&ser).map(|_| ()),
+    ] {
+        match result {
+            Err(err @ Error::DeserializeFailed { .. }) => {
+                let msg = err.to_string();
+                assert!(msg.starts_with(&format!(
+                    "Deserialization failed at byte {} in UnhashedTx.inputs[1].script:",
+                    script_offset,
+                )));
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_strict_encoding() -> Result<()> {
+    // two bytes with a 3 byte CompactSize length
+    let non_minimal = ByteArray::from(vec![0xfd, 2, 0, 1, 2]);
+    assert_eq!(Vec::<u8>::deser(non_minimal.clone())?, vec![1, 2]);
+    match Vec::<u8>::deser_strict(non_minimal.clone()) {
+        Err(Error::NonCanonicalVarInt { value }) => assert_eq!(value, 2),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    let result = bitcoin_cash::with_strict_encoding(|| {
+        Vec::<u8>::decode_from(&mut Cursor::new(non_minimal.to_vec()))
+    });
+    assert!(matches!(result, Err(Error::NonCanonicalVarInt { .. })));
+    assert!(!bitcoin_cash::is_strict_encoding());
+
+    let too_large = ByteArray::from(vec![0xfe, 0, 0, 0, 0x10]);
+    assert!(matches!(
+        ByteArray::deser_strict(too_large),
+        Err(Error::CompactSizeTooLarge { .. })
+    ));
+    assert_eq!(Vec::<u8>::deser_strict(vec![2, 1, 2].into())?, vec![1, 2]);
+    Ok(())
+}
+
+#[test]
+fn test_with() -> Result<()> {
+    // amounts in units of 1000 sats, stored as a single byte
+    mod kilo_sats {
+        use bitcoin_cash::{error::Result, BitcoinCode, ByteArray};
+
+        pub fn ser(amount: &u64) -> ByteArray {
+            ((*amount / 1000) as u8).ser()
+        }
+
+        pub fn deser_rest(data: ByteArray) -> Result<(u64, ByteArray)> {
+            let (kilo_sats, rest) = u8::deser_rest(data)?;
+            Ok((kilo_sats as u64 * 1000, rest))
+        }
+
+        pub fn decode_from<R: std::io::Read>(reader: &mut R) -> Result<u64> {
+            Ok(u8::decode_from(reader)? as u64 * 1000)
+        }
+    }
+
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct Payout {
+        #[bitcoin_code(with = "kilo_sats")]
+        pub amount: u64,
+        pub vout: u32,
+    }
+
+    let payout = Payout {
+        amount: 42_000,
+        vout: 1,
+    };
+    let ser = payout.ser();
+    assert_eq!(ser.as_slice(), &[42, 1, 0, 0, 0]);
+    assert_eq!(payout.encoded_size(), 5);
+    assert_eq!(Payout::deser(ser.clone())?, payout);
+    assert_eq!(Payout::deser_slice(&ser)?.0, payout);
+    assert_eq!(Payout::decode_from(&mut Cursor::new(ser.to_vec()))?, payout);
+    let mut encoded = Vec::new();
+    payout.encode_to(&mut encoded)?;
+    assert_eq!(encoded, ser.to_vec());
+    Ok(())
+}
+
+#[test]
+fn test_skip_default() -> Result<()> {
+    #[derive(BitcoinCode, Debug, PartialEq)]
+    pub struct CachedOutpoint {
+        #[bitcoin_code(skip = "Sha256d::digest(tx_hash.ser().concat(vout.ser()))")]
+        pub hash: Sha256d,
+        pub tx_hash: Sha256d,
+        pub vout: u32,
+        #[bitcoin_code(skip)]
+        pub note: String,
+    }
+
+    let tx_hash = Sha256d::digest(b"tx".to_vec());
+    let outpoint = CachedOutpoint {
+        hash: Sha256d::digest(tx_hash.ser().concat(7u32.ser())),
+        tx_hash,
+        vout: 7,
+        note: "ignored".to_string(),
+    };
+    let ser = outpoint.ser();
+    assert_eq!(ser.len(), 36);
+    let expected = CachedOutpoint {
+        note: String::new(),
+        ..outpoint
+    };
+    assert_eq!(CachedOutpoint::deser(ser.clone())?, expected);
+    assert_eq!(CachedOutpoint::deser_slice(&ser)?.0, expected);
+    assert_eq!(
+        CachedOutpoint::decode_from(&mut Cursor::new(ser.to_vec()))?,
+        expected
+    );
+    Ok(())
+}
+
+#[test]
+fn test_var_int() -> Result<()> {
+    #[derive(BitcoinCode, PartialEq, Debug)]
+    pub struct Entry {
+        pub services: VarInt,
+        pub port: u16,
+    }
+
+    let entry = Entry {
+        services: VarInt(0x1_0000_0000),
+        port: 8333,
+    };
+    let ser = entry.ser();
+    assert_eq!(ser.to_vec(), vec![0xff, 0, 0, 0, 0, 1, 0, 0, 0, 0x8d, 0x20]);
+    assert_eq!(entry.encoded_size(), ser.len());
+    assert_eq!(Entry::deser(ser.clone())?, entry);
+    assert_eq!(Entry::deser_slice(&ser)?.0, entry);
+    assert_eq!(Entry::decode_from(&mut Cursor::new(ser.to_vec()))?, entry);
+
+    // unlike length prefixes, var-ints are canonical even outside of strict encoding
+    let non_canonical = vec![0xfd, 5, 0, 0x8d, 0x20];
+    assert!(Entry::deser(non_canonical.clone().into()).is_err());
+    assert!(matches!(
+        VarInt::deser(non_canonical[..3].to_vec().into()),
+        Err(Error::NonCanonicalVarInt { value: 5 })
+    ));
+    assert!(Entry::deser_slice(&non_canonical).is_err());
+    assert!(Entry::decode_from(&mut Cursor::new(non_canonical.clone())).is_err());
+    assert!(encoding_utils::read_var_int_canonical(&mut &non_canonical[..]).is_err());
+    assert_eq!(encoding_utils::read_var_int(&mut &non_canonical[..])?, 5);
+    Ok(())
+}
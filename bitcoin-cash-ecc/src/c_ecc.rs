@@ -1,7 +1,8 @@
 use bitcoin_cash::{
     error::{Error, Result},
-    ByteArray, Function, Pubkey, ECC,
+    decode_recoverable_sig, encode_recoverable_sig, ByteArray, Function, Pubkey, ECC,
 };
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
 use secp256k1::{All, Message, PublicKey, Secp256k1, SecretKey, Signature};
 
 #[derive(Clone)]
@@ -9,6 +10,25 @@ pub struct CECC {
     pub curve: Secp256k1<All>,
 }
 
+struct SecretKeyGuard(SecretKey);
+
+#[cfg(feature = "zeroize")]
+impl Drop for SecretKeyGuard {
+    fn drop(&mut self) {
+        // secp256k1::SecretKey doesn't expose its bytes mutably, overwrite the whole key instead.
+        unsafe { std::ptr::write_volatile(&mut self.0, secp256k1::key::ONE_KEY) };
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+fn parse_secret_key(secret_key: &[u8]) -> Result<SecretKeyGuard> {
+    let sk = SecretKey::from_slice(secret_key).map_err(|_| Error::InvalidSize {
+        expected: 32,
+        actual: secret_key.len(),
+    })?;
+    Ok(SecretKeyGuard(sk))
+}
+
 impl Default for CECC {
     fn default() -> Self {
         CECC {
@@ -20,15 +40,12 @@ impl Default for CECC {
 impl ECC for CECC {
     fn sign(&self, secret_key: &[u8], msg_array: impl Into<ByteArray>) -> Result<ByteArray> {
         let msg_array = msg_array.into();
-        let sk = SecretKey::from_slice(secret_key).map_err(|_| Error::InvalidSize {
-            expected: 32,
-            actual: secret_key.len(),
-        })?;
+        let sk = parse_secret_key(secret_key)?;
         let msg = Message::from_slice(&msg_array).map_err(|_| Error::InvalidSize {
             expected: 32,
             actual: msg_array.len(),
         })?;
-        let sig = self.curve.sign(&msg, &sk).serialize_der().to_vec();
+        let sig = self.curve.sign(&msg, &sk.0).serialize_der().to_vec();
         Ok(msg_array.apply_function(sig, Function::EcdsaSign))
     }
 
@@ -50,12 +67,9 @@ impl ECC for CECC {
     }
 
     fn derive_pubkey(&self, secret_key: &[u8]) -> Result<Pubkey> {
-        let sk = SecretKey::from_slice(secret_key).map_err(|_| Error::InvalidSize {
-            expected: 32,
-            actual: secret_key.len(),
-        })?;
+        let sk = parse_secret_key(secret_key)?;
         Ok(Pubkey::new(
-            PublicKey::from_secret_key(&self.curve, &sk).serialize(),
+            PublicKey::from_secret_key(&self.curve, &sk.0).serialize(),
         ))
     }
 
@@ -65,4 +79,82 @@ impl ECC for CECC {
         sig.normalize_s();
         Ok(sig.serialize_der().to_vec())
     }
+
+    fn secret_key_tweak_add(
+        &self,
+        secret_key: &[u8],
+        tweak: &[u8],
+    ) -> Result<bitcoin_cash::SecretKey> {
+        let mut sk = parse_secret_key(secret_key)?;
+        sk.0.add_assign(tweak).map_err(|_| Error::InvalidTweak)?;
+        bitcoin_cash::SecretKey::from_slice(&sk.0[..])
+    }
+
+    fn secret_key_tweak_mul(
+        &self,
+        secret_key: &[u8],
+        tweak: &[u8],
+    ) -> Result<bitcoin_cash::SecretKey> {
+        let mut sk = parse_secret_key(secret_key)?;
+        sk.0.mul_assign(tweak).map_err(|_| Error::InvalidTweak)?;
+        bitcoin_cash::SecretKey::from_slice(&sk.0[..])
+    }
+
+    fn pubkey_tweak_add(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey> {
+        let mut pk = PublicKey::from_slice(pubkey.as_slice()).map_err(|_| Error::InvalidPubkey)?;
+        pk.add_exp_assign(&self.curve, tweak)
+            .map_err(|_| Error::InvalidTweak)?;
+        Ok(Pubkey::new(pk.serialize()))
+    }
+
+    fn pubkey_tweak_mul(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey> {
+        let mut pk = PublicKey::from_slice(pubkey.as_slice()).map_err(|_| Error::InvalidPubkey)?;
+        pk.mul_assign(&self.curve, tweak)
+            .map_err(|_| Error::InvalidTweak)?;
+        Ok(Pubkey::new(pk.serialize()))
+    }
+
+    fn combine_pubkeys(&self, pubkeys: &[Pubkey]) -> Result<Pubkey> {
+        let mut combined: Option<PublicKey> = None;
+        for pubkey in pubkeys {
+            let pk = PublicKey::from_slice(pubkey.as_slice()).map_err(|_| Error::InvalidPubkey)?;
+            combined = Some(match combined {
+                Some(combined) => combined.combine(&pk).map_err(|_| Error::InvalidPubkey)?,
+                None => pk,
+            });
+        }
+        let combined = combined.ok_or(Error::InvalidPubkey)?;
+        Ok(Pubkey::new(combined.serialize()))
+    }
+
+    fn sign_recoverable(&self, secret_key: &[u8], msg_hash: &[u8]) -> Result<Vec<u8>> {
+        let sk = parse_secret_key(secret_key)?;
+        let msg = parse_message(msg_hash)?;
+        let (recovery_id, sig) = self
+            .curve
+            .sign_recoverable(&msg, &sk.0)
+            .serialize_compact();
+        Ok(encode_recoverable_sig(recovery_id.to_i32() as u8, &sig))
+    }
+
+    fn recover_pubkey(&self, msg_hash: &[u8], sig: &[u8]) -> Result<Pubkey> {
+        let msg = parse_message(msg_hash)?;
+        let (recovery_id, sig) = decode_recoverable_sig(sig)?;
+        let recovery_id =
+            RecoveryId::from_i32(recovery_id.into()).map_err(|_| Error::InvalidSignatureFormat)?;
+        let sig = RecoverableSignature::from_compact(sig, recovery_id)
+            .map_err(|_| Error::InvalidSignatureFormat)?;
+        let pubkey = self
+            .curve
+            .recover(&msg, &sig)
+            .map_err(|_| Error::InvalidSignatureFormat)?;
+        Ok(Pubkey::new(pubkey.serialize()))
+    }
+}
+
+fn parse_message(msg_hash: &[u8]) -> Result<Message> {
+    Message::from_slice(msg_hash).map_err(|_| Error::InvalidSize {
+        expected: 32,
+        actual: msg_hash.len(),
+    })
 }
@@ -0,0 +1,78 @@
+use crate::SelectedECC;
+use bitcoin_cash::{error::Result, ByteArray, ECCObject, Pubkey, SecretKey, ECC};
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct DynECC(Arc<dyn ECCObject>);
+
+impl DynECC {
+    pub fn new(ecc: impl ECCObject + 'static) -> Self {
+        DynECC(Arc::new(ecc))
+    }
+
+    #[cfg(feature = "c_ecc")]
+    pub fn c_ecc() -> Self {
+        Self::new(crate::CECC::default())
+    }
+
+    #[cfg(feature = "rust_ecc")]
+    pub fn rust_ecc() -> Self {
+        Self::new(crate::RustECC)
+    }
+
+    pub fn inner(&self) -> &dyn ECCObject {
+        self.0.as_ref()
+    }
+}
+
+impl Default for DynECC {
+    fn default() -> Self {
+        Self::new(SelectedECC::default())
+    }
+}
+
+impl ECC for DynECC {
+    fn sign(&self, secret_key: &[u8], msg_array: impl Into<ByteArray>) -> Result<ByteArray> {
+        self.0.dyn_sign(secret_key, msg_array.into())
+    }
+
+    fn verify(&self, pubkey: &[u8], msg_array: &[u8], sig: &[u8]) -> Result<bool> {
+        self.0.dyn_verify(pubkey, msg_array, sig)
+    }
+
+    fn derive_pubkey(&self, secret_key: &[u8]) -> Result<Pubkey> {
+        self.0.dyn_derive_pubkey(secret_key)
+    }
+
+    fn normalize_sig(&self, sig: &[u8]) -> Result<Vec<u8>> {
+        self.0.dyn_normalize_sig(sig)
+    }
+
+    fn secret_key_tweak_add(&self, secret_key: &[u8], tweak: &[u8]) -> Result<SecretKey> {
+        self.0.dyn_secret_key_tweak_add(secret_key, tweak)
+    }
+
+    fn secret_key_tweak_mul(&self, secret_key: &[u8], tweak: &[u8]) -> Result<SecretKey> {
+        self.0.dyn_secret_key_tweak_mul(secret_key, tweak)
+    }
+
+    fn pubkey_tweak_add(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey> {
+        self.0.dyn_pubkey_tweak_add(pubkey, tweak)
+    }
+
+    fn pubkey_tweak_mul(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey> {
+        self.0.dyn_pubkey_tweak_mul(pubkey, tweak)
+    }
+
+    fn combine_pubkeys(&self, pubkeys: &[Pubkey]) -> Result<Pubkey> {
+        self.0.dyn_combine_pubkeys(pubkeys)
+    }
+
+    fn sign_recoverable(&self, secret_key: &[u8], msg_hash: &[u8]) -> Result<Vec<u8>> {
+        self.0.dyn_sign_recoverable(secret_key, msg_hash)
+    }
+
+    fn recover_pubkey(&self, msg_hash: &[u8], sig: &[u8]) -> Result<Pubkey> {
+        self.0.dyn_recover_pubkey(msg_hash, sig)
+    }
+}
@@ -0,0 +1,152 @@
+use crate::SelectedECC;
+use bitcoin_cash::{
+    encode_recoverable_sig,
+    error::{Error, Result},
+    ByteArray, Function, Pubkey, SecretKey, ECC,
+};
+use std::sync::Arc;
+
+pub trait ExternalSigner {
+    fn sign(&self, key_id: &[u8], msg_hash: &[u8]) -> Result<Vec<u8>>;
+
+    fn pubkey(&self, key_id: &[u8]) -> Result<Pubkey>;
+}
+
+struct FnSigner<S, P> {
+    sign_fn: S,
+    pubkey_fn: P,
+}
+
+impl<S, P> ExternalSigner for FnSigner<S, P>
+where
+    S: Fn(&[u8], &[u8]) -> Result<Vec<u8>>,
+    P: Fn(&[u8]) -> Result<Pubkey>,
+{
+    fn sign(&self, key_id: &[u8], msg_hash: &[u8]) -> Result<Vec<u8>> {
+        (self.sign_fn)(key_id, msg_hash)
+    }
+
+    fn pubkey(&self, key_id: &[u8]) -> Result<Pubkey> {
+        (self.pubkey_fn)(key_id)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ExternalECC {
+    signer: Option<Arc<dyn ExternalSigner + Send + Sync>>,
+    ecc: SelectedECC,
+}
+
+impl ExternalECC {
+    pub fn new(signer: impl ExternalSigner + Send + Sync + 'static) -> Self {
+        ExternalECC {
+            signer: Some(Arc::new(signer)),
+            ecc: SelectedECC::default(),
+        }
+    }
+
+    pub fn from_fns(
+        sign_fn: impl Fn(&[u8], &[u8]) -> Result<Vec<u8>> + Send + Sync + 'static,
+        pubkey_fn: impl Fn(&[u8]) -> Result<Pubkey> + Send + Sync + 'static,
+    ) -> Self {
+        Self::new(FnSigner { sign_fn, pubkey_fn })
+    }
+
+    fn signer(&self) -> Result<&(dyn ExternalSigner + Send + Sync)> {
+        self.signer
+            .as_deref()
+            .ok_or_else(|| Error::Msg("No external signer configured".to_string()))
+    }
+}
+
+fn secret_keys_unavailable<T>() -> Result<T> {
+    Err(Error::Msg(
+        "Secret keys are not available to the external signer backend".to_string(),
+    ))
+}
+
+impl ECC for ExternalECC {
+    fn sign(&self, key_id: &[u8], msg_array: impl Into<ByteArray>) -> Result<ByteArray> {
+        let msg_array = msg_array.into();
+        if msg_array.len() != 32 {
+            return Err(Error::InvalidSize {
+                expected: 32,
+                actual: msg_array.len(),
+            });
+        }
+        let sig = self.signer()?.sign(key_id, &msg_array)?;
+        let sig = self.ecc.normalize_sig(&sig)?;
+        Ok(msg_array.apply_function(sig, Function::EcdsaSign))
+    }
+
+    fn verify(&self, pubkey: &[u8], msg_array: &[u8], sig: &[u8]) -> Result<bool> {
+        self.ecc.verify(pubkey, msg_array, sig)
+    }
+
+    fn derive_pubkey(&self, key_id: &[u8]) -> Result<Pubkey> {
+        self.signer()?.pubkey(key_id)
+    }
+
+    fn normalize_sig(&self, sig: &[u8]) -> Result<Vec<u8>> {
+        self.ecc.normalize_sig(sig)
+    }
+
+    fn secret_key_tweak_add(&self, _key_id: &[u8], _tweak: &[u8]) -> Result<SecretKey> {
+        secret_keys_unavailable()
+    }
+
+    fn secret_key_tweak_mul(&self, _key_id: &[u8], _tweak: &[u8]) -> Result<SecretKey> {
+        secret_keys_unavailable()
+    }
+
+    fn pubkey_tweak_add(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey> {
+        self.ecc.pubkey_tweak_add(pubkey, tweak)
+    }
+
+    fn pubkey_tweak_mul(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey> {
+        self.ecc.pubkey_tweak_mul(pubkey, tweak)
+    }
+
+    fn combine_pubkeys(&self, pubkeys: &[Pubkey]) -> Result<Pubkey> {
+        self.ecc.combine_pubkeys(pubkeys)
+    }
+
+    fn sign_recoverable(&self, key_id: &[u8], msg_hash: &[u8]) -> Result<Vec<u8>> {
+        let signer = self.signer()?;
+        let sig = self.ecc.normalize_sig(&signer.sign(key_id, msg_hash)?)?;
+        let pubkey = signer.pubkey(key_id)?;
+        let compact = der_to_compact(&sig)?;
+        for recovery_id in 0..4 {
+            let recoverable = encode_recoverable_sig(recovery_id, &compact);
+            if let Ok(recovered) = self.ecc.recover_pubkey(msg_hash, &recoverable) {
+                if recovered == pubkey {
+                    return Ok(recoverable);
+                }
+            }
+        }
+        Err(Error::InvalidSignatureFormat)
+    }
+
+    fn recover_pubkey(&self, msg_hash: &[u8], sig: &[u8]) -> Result<Pubkey> {
+        self.ecc.recover_pubkey(msg_hash, sig)
+    }
+}
+
+fn der_to_compact(sig: &[u8]) -> Result<[u8; 64]> {
+    let mut compact = [0; 64];
+    let mut rest = sig.get(2..).ok_or(Error::InvalidSignatureFormat)?;
+    for part in compact.chunks_mut(32) {
+        if rest.len() < 2 || rest[0] != 0x02 {
+            return Err(Error::InvalidSignatureFormat);
+        }
+        let len = rest[1] as usize;
+        let int = rest.get(2..2 + len).ok_or(Error::InvalidSignatureFormat)?;
+        let int = &int[int.iter().take_while(|&&b| b == 0).count()..];
+        if int.len() > 32 {
+            return Err(Error::InvalidSignatureFormat);
+        }
+        part[32 - int.len()..].copy_from_slice(int);
+        rest = &rest[2 + len..];
+    }
+    Ok(compact)
+}
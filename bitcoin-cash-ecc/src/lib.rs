@@ -4,6 +4,15 @@ mod c_ecc;
 #[cfg(feature = "rust_ecc")]
 mod rust_ecc;
 
+mod dyn_ecc;
+mod external_ecc;
 mod polyfill;
 
+#[cfg(feature = "c_ecc")]
+pub use c_ecc::CECC;
+#[cfg(feature = "rust_ecc")]
+pub use rust_ecc::RustECC;
+
+pub use dyn_ecc::*;
+pub use external_ecc::*;
 pub use polyfill::*;
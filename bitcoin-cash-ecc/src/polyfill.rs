@@ -1,4 +1,4 @@
-#[cfg(feature = "rust_ecc")]
+#[cfg(all(feature = "rust_ecc", not(feature = "c_ecc")))]
 pub type SelectedECC = crate::rust_ecc::RustECC;
 
 #[cfg(feature = "c_ecc")]
@@ -1,9 +1,10 @@
 use bitcoin_cash::{
     error::{Error, Result},
-    ByteArray, Function, Pubkey, ECC,
+    decode_recoverable_sig, encode_recoverable_sig, ByteArray, Function, Pubkey, ECC,
 };
-use secp256k1::{Message, PublicKey, PublicKeyFormat, SecretKey, Signature};
+use libsecp256k1::{Message, PublicKey, PublicKeyFormat, RecoveryId, SecretKey, Signature};
 
+#[derive(Clone)]
 pub struct RustECC;
 
 impl Default for RustECC {
@@ -23,7 +24,7 @@ impl ECC for RustECC {
             expected: 32,
             actual: msg_array.len(),
         })?;
-        let mut sig = secp256k1::sign(&msg, &sk).0;
+        let mut sig = libsecp256k1::sign(&msg, &sk).0;
         sig.normalize_s();
         let sig = sig.serialize_der().as_ref().to_vec();
         Ok(msg_array.apply_function(sig, Function::EcdsaSign))
@@ -37,7 +38,7 @@ impl ECC for RustECC {
         let sig = Signature::parse_der(sig_ser).map_err(|_| Error::InvalidSignatureFormat)?;
         let pubkey = PublicKey::parse_slice(pubkey, Some(PublicKeyFormat::Compressed))
             .map_err(|_| Error::InvalidPubkey)?;
-        Ok(secp256k1::verify(&msg, &sig, &pubkey))
+        Ok(libsecp256k1::verify(&msg, &sig, &pubkey))
     }
 
     fn derive_pubkey(&self, secret_key: &[u8]) -> Result<Pubkey> {
@@ -56,4 +57,88 @@ impl ECC for RustECC {
         sig.normalize_s();
         Ok(sig.serialize_der().as_ref().to_vec())
     }
+
+    fn secret_key_tweak_add(
+        &self,
+        secret_key: &[u8],
+        tweak: &[u8],
+    ) -> Result<bitcoin_cash::SecretKey> {
+        let mut sk = SecretKey::parse_slice(secret_key).map_err(|_| Error::InvalidSize {
+            expected: 32,
+            actual: secret_key.len(),
+        })?;
+        let tweak = SecretKey::parse_slice(tweak).map_err(|_| Error::InvalidTweak)?;
+        sk.tweak_add_assign(&tweak).map_err(|_| Error::InvalidTweak)?;
+        Ok(bitcoin_cash::SecretKey::new(sk.serialize()))
+    }
+
+    fn secret_key_tweak_mul(
+        &self,
+        secret_key: &[u8],
+        tweak: &[u8],
+    ) -> Result<bitcoin_cash::SecretKey> {
+        let mut sk = SecretKey::parse_slice(secret_key).map_err(|_| Error::InvalidSize {
+            expected: 32,
+            actual: secret_key.len(),
+        })?;
+        let tweak = SecretKey::parse_slice(tweak).map_err(|_| Error::InvalidTweak)?;
+        sk.tweak_mul_assign(&tweak).map_err(|_| Error::InvalidTweak)?;
+        Ok(bitcoin_cash::SecretKey::new(sk.serialize()))
+    }
+
+    fn pubkey_tweak_add(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey> {
+        let mut pk = PublicKey::parse_slice(pubkey.as_slice(), Some(PublicKeyFormat::Compressed))
+            .map_err(|_| Error::InvalidPubkey)?;
+        let tweak = SecretKey::parse_slice(tweak).map_err(|_| Error::InvalidTweak)?;
+        pk.tweak_add_assign(&tweak).map_err(|_| Error::InvalidTweak)?;
+        Ok(Pubkey::new(pk.serialize_compressed()))
+    }
+
+    fn pubkey_tweak_mul(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey> {
+        let mut pk = PublicKey::parse_slice(pubkey.as_slice(), Some(PublicKeyFormat::Compressed))
+            .map_err(|_| Error::InvalidPubkey)?;
+        let tweak = SecretKey::parse_slice(tweak).map_err(|_| Error::InvalidTweak)?;
+        pk.tweak_mul_assign(&tweak).map_err(|_| Error::InvalidTweak)?;
+        Ok(Pubkey::new(pk.serialize_compressed()))
+    }
+
+    fn combine_pubkeys(&self, pubkeys: &[Pubkey]) -> Result<Pubkey> {
+        let pks = pubkeys
+            .iter()
+            .map(|pubkey| {
+                PublicKey::parse_slice(pubkey.as_slice(), Some(PublicKeyFormat::Compressed))
+                    .map_err(|_| Error::InvalidPubkey)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let combined = PublicKey::combine(&pks).map_err(|_| Error::InvalidPubkey)?;
+        Ok(Pubkey::new(combined.serialize_compressed()))
+    }
+
+    fn sign_recoverable(&self, secret_key: &[u8], msg_hash: &[u8]) -> Result<Vec<u8>> {
+        let sk = SecretKey::parse_slice(secret_key).map_err(|_| Error::InvalidSize {
+            expected: 32,
+            actual: secret_key.len(),
+        })?;
+        let msg = parse_message(msg_hash)?;
+        let (sig, recovery_id) = libsecp256k1::sign(&msg, &sk);
+        Ok(encode_recoverable_sig(recovery_id.serialize(), &sig.serialize()))
+    }
+
+    fn recover_pubkey(&self, msg_hash: &[u8], sig: &[u8]) -> Result<Pubkey> {
+        let msg = parse_message(msg_hash)?;
+        let (recovery_id, sig) = decode_recoverable_sig(sig)?;
+        let recovery_id =
+            RecoveryId::parse(recovery_id).map_err(|_| Error::InvalidSignatureFormat)?;
+        let sig = Signature::parse_slice(sig).map_err(|_| Error::InvalidSignatureFormat)?;
+        let pubkey = libsecp256k1::recover(&msg, &sig, &recovery_id)
+            .map_err(|_| Error::InvalidSignatureFormat)?;
+        Ok(Pubkey::new(pubkey.serialize_compressed()))
+    }
+}
+
+fn parse_message(msg_hash: &[u8]) -> Result<Message> {
+    Message::parse_slice(msg_hash).map_err(|_| Error::InvalidSize {
+        expected: 32,
+        actual: msg_hash.len(),
+    })
 }
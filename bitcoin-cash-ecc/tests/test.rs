@@ -0,0 +1,564 @@
+use bitcoin_cash::{
+    decrypt_with_secret, encrypt_to_pubkey,
+    error::{Error, Result},
+    schnorr_verify, Address, BackendFuture, BitcoinCode, ChainBackend, ExtendedSecretKey, FeeRate,
+    FlipstarterCampaign, Hashed, KeyPair, MuSigKeyAgg, MuSigNonce, MuSigSession, MultisigSignatory,
+    Op, Ops, P2PKHSignatory, P2pkhSignatory, Prefix, Pubkey, Script, SecretKey, Sha256d,
+    SigAlgorithm, SigHashFlags, TokenCategory, TokenData, Tx, TxBuilder, TxHistoryEntry, TxOutpoint,
+    TxOutput, UnhashedTx, UnsignedTx, UnsignedTxInput, Utxo, Wallet, ECC, HARDENED_INDEX,
+    MIN_RELAY_FEE_RATE, PLEDGE_SIG_HASH_FLAGS,
+};
+use bitcoin_cash_ecc::{init_ecc, DynECC, ExternalECC};
+use hex_literal::hex;
+use std::sync::Mutex;
+
+#[test]
+fn test_schnorr_verify_vector() -> Result<()> {
+    let ecc = init_ecc();
+    let pubkey = Pubkey::new(hex!(
+        "0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"
+    ));
+    let sig = hex!(
+        "787A848E71043D280C50470E8E1532B2DD5D20EE912A45DBDD2BD1DFBF187EF6"
+        "7031A98831859DC34DFFEEDDA86831842CCD0079E1F92AF177F7F22CC1DCED05"
+    );
+    assert!(schnorr_verify(&ecc, &pubkey, &[0; 32], &sig)?);
+    assert!(!schnorr_verify(&ecc, &pubkey, &[1; 32], &sig)?);
+    Ok(())
+}
+
+#[test]
+fn test_musig() -> Result<()> {
+    let ecc = init_ecc();
+    let secret_keys = [[1u8; 32], [2u8; 32], [3u8; 32]];
+    let pubkeys = secret_keys
+        .iter()
+        .map(|sk| ecc.derive_pubkey(sk))
+        .collect::<Result<Vec<_>>>()?;
+    let key_agg = MuSigKeyAgg::new(&ecc, pubkeys)?;
+    let msg = [0x42; 32];
+    let nonces = [[4u8; 32], [5u8; 32], [6u8; 32]]
+        .iter()
+        .map(|nonce| MuSigNonce::new(&ecc, nonce))
+        .collect::<Result<Vec<_>>>()?;
+    let commitments = nonces.iter().map(|nonce| nonce.commitment()).collect::<Vec<_>>();
+    let public_nonces = nonces.iter().map(|nonce| *nonce.public_nonce()).collect();
+    let session = MuSigSession::new(&ecc, &key_agg, &commitments, public_nonces, &msg)?;
+    assert!(matches!(
+        session.verify_partial(&ecc, 3, &[1; 32]),
+        Err(Error::InvalidSignerIndex { signer_idx: 3, num_signers: 3 })
+    ));
+    let mut partial_sigs = Vec::new();
+    for (signer_idx, (secret_key, nonce)) in secret_keys.iter().zip(nonces).enumerate() {
+        let partial_sig = session.partial_sign(&ecc, signer_idx, secret_key, nonce)?;
+        assert!(session.verify_partial(&ecc, signer_idx, &partial_sig)?);
+        partial_sigs.push(partial_sig);
+    }
+    let sig = session.aggregate(&partial_sigs, msg.as_ref());
+    assert!(schnorr_verify(&ecc, key_agg.agg_pubkey(), &msg, &sig)?);
+    assert!(!schnorr_verify(&ecc, &key_agg.pubkeys()[0], &msg, &sig)?);
+    Ok(())
+}
+
+#[test]
+fn test_ecies() -> Result<()> {
+    let ecc = init_ecc();
+    let secret_key = [7u8; 32];
+    let pubkey = ecc.derive_pubkey(&secret_key)?;
+    let message = b"Bitcoin Cash encrypted message";
+    let encrypted = encrypt_to_pubkey(&ecc, &pubkey, &[8u8; 32], message)?;
+    assert!(base64::decode(&encrypted).unwrap().starts_with(b"BIE1"));
+    assert_eq!(decrypt_with_secret(&ecc, &secret_key, &encrypted)?, message.to_vec());
+    assert!(decrypt_with_secret(&ecc, &[9u8; 32], &encrypted).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_external_ecc() -> Result<()> {
+    let secret_key = [3u8; 32];
+    let ecc = ExternalECC::from_fns(
+        move |key_id, msg_hash| {
+            assert_eq!(key_id, b"hsm-key-1");
+            Ok(init_ecc().sign(&secret_key, msg_hash)?.to_vec())
+        },
+        move |_key_id| init_ecc().derive_pubkey(&secret_key),
+    );
+    let pubkey = ecc.derive_pubkey(b"hsm-key-1")?;
+    let sig = ecc.sign(b"hsm-key-1", [0x11; 32].as_ref())?;
+    assert!(ecc.verify(pubkey.as_slice(), &[0x11; 32], &sig)?);
+    assert!(ecc.secret_key_tweak_add(b"hsm-key-1", &[1; 32]).is_err());
+    assert!(ExternalECC::default().sign(b"hsm-key-1", [0x11; 32].as_ref()).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_dyn_ecc() -> Result<()> {
+    let secret_key = [5u8; 32];
+    let expected_pubkey = init_ecc().derive_pubkey(&secret_key)?;
+    let mut backends = vec![DynECC::default()];
+    #[cfg(feature = "c_ecc")]
+    backends.push(DynECC::c_ecc());
+    #[cfg(feature = "rust_ecc")]
+    backends.push(DynECC::rust_ecc());
+    for ecc in backends {
+        assert_eq!(ecc.derive_pubkey(&secret_key)?, expected_pubkey);
+        let sig = ecc.sign(&secret_key, [0x22; 32].as_ref())?;
+        assert!(ecc.verify(expected_pubkey.as_slice(), &[0x22; 32], &sig)?);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_recoverable_sig() -> Result<()> {
+    let secret_key = [7u8; 32];
+    let msg_hash = [0x33; 32];
+    let mut backends = vec![DynECC::default()];
+    #[cfg(feature = "c_ecc")]
+    backends.push(DynECC::c_ecc());
+    #[cfg(feature = "rust_ecc")]
+    backends.push(DynECC::rust_ecc());
+    let expected_pubkey = init_ecc().derive_pubkey(&secret_key)?;
+    let expected_sig = init_ecc().sign_recoverable(&secret_key, &msg_hash)?;
+    assert_eq!(expected_sig.len(), 65);
+    for ecc in backends {
+        let sig = ecc.sign_recoverable(&secret_key, &msg_hash)?;
+        assert_eq!(sig, expected_sig);
+        assert_eq!(ecc.recover_pubkey(&msg_hash, &sig)?, expected_pubkey);
+        assert_ne!(ecc.recover_pubkey(&[0x34; 32], &sig)?, expected_pubkey);
+        assert!(ecc.recover_pubkey(&msg_hash, &sig[1..]).is_err());
+    }
+    let external = ExternalECC::from_fns(
+        move |_key_id, msg_hash| Ok(init_ecc().sign(&secret_key, msg_hash)?.to_vec()),
+        move |_key_id| init_ecc().derive_pubkey(&secret_key),
+    );
+    assert_eq!(
+        external.sign_recoverable(b"hsm-key-1", &msg_hash)?,
+        expected_sig
+    );
+    Ok(())
+}
+
+#[test]
+fn test_secret_key_tweaks() -> Result<()> {
+    let scalar = |value: u8| {
+        let mut scalar = [0; 32];
+        scalar[31] = value;
+        scalar
+    };
+    let minus_one = hex!("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140");
+    let secret_key = [7u8; 32];
+    let tweak = [9u8; 32];
+    let expected_add = init_ecc().secret_key_tweak_add(&secret_key, &tweak)?;
+    let expected_mul = init_ecc().secret_key_tweak_mul(&secret_key, &tweak)?;
+    let mut backends = vec![DynECC::default()];
+    #[cfg(feature = "c_ecc")]
+    backends.push(DynECC::c_ecc());
+    #[cfg(feature = "rust_ecc")]
+    backends.push(DynECC::rust_ecc());
+    for ecc in backends {
+        let add = |secret_key: &[u8], tweak: &[u8]| ecc.secret_key_tweak_add(secret_key, tweak);
+        let mul = |secret_key: &[u8], tweak: &[u8]| ecc.secret_key_tweak_mul(secret_key, tweak);
+        assert_eq!(add(&scalar(1), &scalar(2))?.as_slice(), scalar(3));
+        assert_eq!(mul(&scalar(2), &scalar(3))?.as_slice(), scalar(6));
+        // modulo the curve order
+        assert_eq!(add(&minus_one, &scalar(2))?.as_slice(), scalar(1));
+        assert_eq!(mul(&minus_one, &minus_one)?.as_slice(), scalar(1));
+        assert!(add(&minus_one, &scalar(1)).is_err());
+        assert_eq!(add(&secret_key, &tweak)?, expected_add);
+        assert_eq!(mul(&secret_key, &tweak)?, expected_mul);
+        // tweaking the pubkey gives the pubkey of the tweaked key
+        let pubkey = ecc.derive_pubkey(&secret_key)?;
+        let tweaked_pubkey = ecc.pubkey_tweak_add(&pubkey, &tweak)?;
+        assert_eq!(tweaked_pubkey, expected_add.pubkey(&ecc)?);
+        let tweaked_pubkey = ecc.pubkey_tweak_mul(&pubkey, &tweak)?;
+        assert_eq!(tweaked_pubkey, expected_mul.pubkey(&ecc)?);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_bip32_tweaks() -> Result<()> {
+    // BIP32 test vector 1, m/0H and m/0H/1
+    let minus_one = hex!("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140");
+    let parent_secret = hex!("edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea");
+    let child_secret = hex!("3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368");
+    let parent_pubkey = hex!("035a784662a4a20a65bf6aab9ae98a6c068a81c52e4b032c0fb5400c706cfccc56");
+    let child_pubkey = hex!("03501e454bf00751f24b1b489aa925215d66af2234e3891c3b21a52bedb3cd711c");
+    let mut backends = vec![DynECC::default()];
+    #[cfg(feature = "c_ecc")]
+    backends.push(DynECC::c_ecc());
+    #[cfg(feature = "rust_ecc")]
+    backends.push(DynECC::rust_ecc());
+    for ecc in backends {
+        let master = ExtendedSecretKey::from_seed(&hex!("000102030405060708090a0b0c0d0e0f"));
+        let parent = master.derive_child(&ecc, HARDENED_INDEX)?;
+        assert_eq!(parent.secret_key().as_slice(), parent_secret);
+        let pubkey = ecc.derive_pubkey(&parent_secret)?;
+        assert_eq!(pubkey.as_slice(), parent_pubkey);
+        assert_eq!(parent.derive_child(&ecc, 1)?.secret_key().as_slice(), child_secret);
+        // the normal child's tweak, recovered as child - parent
+        let neg_parent = ecc.secret_key_tweak_mul(&parent_secret, &minus_one)?;
+        let tweak = ecc.secret_key_tweak_add(&child_secret, neg_parent.as_slice())?;
+        assert_eq!(ecc.pubkey_tweak_add(&pubkey, tweak.as_slice())?.as_slice(), child_pubkey);
+        let neg_pubkey = ecc.pubkey_tweak_mul(&pubkey, &minus_one)?;
+        assert_eq!(neg_pubkey, neg_parent.pubkey(&ecc)?);
+        assert_eq!(neg_pubkey.as_slice()[1..], parent_pubkey[1..]);
+    }
+    Ok(())
+}
+
+fn build_two_input_tx(pubkey: &Pubkey) -> Result<UnsignedTx<'static>> {
+    let address = Address::from_pk(Prefix::default(), pubkey);
+    let mut builder = TxBuilder::new_simple();
+    for vout in 0..2 {
+        builder.add_input(
+            UnsignedTxInput {
+                prev_out: TxOutpoint {
+                    tx_hash: Default::default(),
+                    vout,
+                },
+                sequence: 0xffff_ffff,
+                value: 10_000,
+                token: None,
+            },
+            address.p2pkh_script()?,
+            P2PKHSignatory {
+                pubkey: *pubkey,
+                sig_hash_flags: SigHashFlags::DEFAULT,
+            },
+        );
+    }
+    builder.add_leftover_output(address.clone().into());
+    builder.build()
+}
+
+#[test]
+fn test_merge_unsigned_tx() -> Result<()> {
+    let ecc = init_ecc();
+    let secret_key = [9u8; 32];
+    let pubkey = ecc.derive_pubkey(&secret_key)?;
+    let mut copy_a = build_two_input_tx(&pubkey)?;
+    let mut copy_b = build_two_input_tx(&pubkey)?;
+    for (unsigned_tx, input_idx) in [(&mut copy_a, 0), (&mut copy_b, 1)] {
+        let preimage = &unsigned_tx.preimages()[input_idx][0];
+        let sig = ecc.sign(&secret_key, bitcoin_cash::Sha256d::digest(preimage.ser()))?;
+        unsigned_tx.sign_input_dyn(input_idx, Box::new(sig))?;
+    }
+    let mut conflicting = build_two_input_tx(&pubkey)?;
+    conflicting.sign_input_dyn(0, Box::new(bitcoin_cash::ByteArray::from(vec![1, 2, 3])))?;
+    assert!(copy_a.merge(conflicting).is_err());
+    let mut other_tx = build_two_input_tx(&pubkey)?.into_tx_builder();
+    other_tx.add_output(TxOutput {
+        value: 1000,
+        script: Address::from_pk(Prefix::default(), &pubkey).into(),
+        token: None,
+    });
+    assert!(copy_a.merge(other_tx.build()?).is_err());
+    copy_a.merge(copy_b)?;
+    assert!(copy_a.is_input_signed(0) == Some(true) && copy_a.is_input_signed(1) == Some(true));
+    let tx = copy_a.complete_tx()?;
+    assert_eq!(tx.inputs.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_flipstarter() -> Result<()> {
+    let ecc = init_ecc();
+    let recipient = Address::from_pk(Prefix::default(), &ecc.derive_pubkey(&[1; 32])?);
+    let campaign = FlipstarterCampaign::new(vec![TxOutput {
+        value: 15_000,
+        script: recipient.into(),
+        token: None,
+    }]);
+    let mut pledges = Vec::new();
+    for (idx, secret_key) in [[2u8; 32], [3u8; 32]].iter().enumerate() {
+        let pubkey = ecc.derive_pubkey(secret_key)?;
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let input = UnsignedTxInput {
+            prev_out: TxOutpoint {
+                tx_hash: Default::default(),
+                vout: idx as u32,
+            },
+            sequence: 0xffff_ffff,
+            value: 8_000,
+            token: None,
+        };
+        let default_signatory = P2PKHSignatory {
+            pubkey,
+            sig_hash_flags: SigHashFlags::DEFAULT,
+        };
+        assert!(campaign
+            .pledge_tx(input.clone(), address.p2pkh_script()?, default_signatory)
+            .is_err());
+        let signatory = P2PKHSignatory {
+            pubkey,
+            sig_hash_flags: PLEDGE_SIG_HASH_FLAGS,
+        };
+        let inflated_input = UnsignedTxInput {
+            value: 80_000,
+            ..input.clone()
+        };
+        let (mut unsigned_tx, input_ref) =
+            campaign.pledge_tx(input, address.p2pkh_script()?, signatory.clone())?;
+        let preimage = unsigned_tx.input_preimages(input_ref);
+        let sig = ecc.sign(secret_key, bitcoin_cash::Sha256d::digest(preimage.ser()))?;
+        unsigned_tx.sign_input(input_ref, sig.clone())?;
+        let pledge = campaign.pledge_from_tx(unsigned_tx)?;
+        assert!(campaign.verify_p2pkh_pledge(&ecc, &pledge)?);
+        pledges.push(pledge);
+
+        // a pledge claiming more than its signature commits to
+        let (mut unsigned_tx, input_ref) =
+            campaign.pledge_tx(inflated_input, address.p2pkh_script()?, signatory)?;
+        unsigned_tx.sign_input(input_ref, sig)?;
+        let inflated = campaign.pledge_from_tx(unsigned_tx)?;
+        assert!(!campaign.verify_p2pkh_pledge(&ecc, &inflated)?);
+        assert!(matches!(
+            campaign.assemble(&ecc, vec![inflated], MIN_RELAY_FEE_RATE),
+            Err(Error::InvalidPledge { .. })
+        ));
+    }
+    assert!(campaign.assemble(&ecc, pledges[..1].to_vec(), MIN_RELAY_FEE_RATE).is_err());
+    assert!(campaign
+        .assemble(&ecc, vec![pledges[0].clone(), pledges[0].clone()], MIN_RELAY_FEE_RATE)
+        .is_err());
+    let tx = campaign.assemble(&ecc, pledges, MIN_RELAY_FEE_RATE)?;
+    assert_eq!(tx.inputs.len(), 2);
+    assert_eq!(tx.outputs, campaign.outputs());
+    Ok(())
+}
+
+#[test]
+fn test_p2pkh_key_signatory() -> Result<()> {
+    let ecc = init_ecc();
+    let keypair = KeyPair::from_secret_key(&ecc, SecretKey::new([4; 32]))?;
+    for signatory in [
+        P2pkhSignatory::new(keypair.clone(), SigHashFlags::DEFAULT),
+        P2pkhSignatory::new_schnorr(keypair.clone(), SigHashFlags::DEFAULT),
+    ] {
+        let mut builder = TxBuilder::new_simple();
+        let input_ref = builder.add_input(
+            UnsignedTxInput {
+                prev_out: TxOutpoint {
+                    tx_hash: Default::default(),
+                    vout: 0,
+                },
+                sequence: 0xffff_ffff,
+                value: 10_000,
+                token: None,
+            },
+            signatory.lock_script(),
+            signatory.clone(),
+        );
+        builder.add_leftover_output(signatory.address().into());
+        let mut unsigned_tx = builder.build()?;
+        let estimated_size = unsigned_tx.estimated_size();
+        let preimage = unsigned_tx.input_preimages(input_ref).clone();
+        signatory.sign_input(&ecc, &mut unsigned_tx, input_ref)?;
+        let tx = unsigned_tx.complete_tx()?;
+        assert!(tx.ser().len() <= estimated_size);
+        let sig_hash = bitcoin_cash::Sha256d::digest(preimage.ser());
+        let sig = signatory.sign(&ecc, &preimage)?;
+        match signatory.sig_algorithm {
+            SigAlgorithm::Ecdsa => {
+                assert!(ecc.verify(keypair.pubkey().as_slice(), sig_hash.as_slice(), &sig)?)
+            }
+            SigAlgorithm::Schnorr => {
+                // deterministic nonces
+                assert_eq!(sig, signatory.sign(&ecc, &preimage)?);
+                assert_eq!(sig.len(), 64);
+                assert!(schnorr_verify(
+                    &ecc,
+                    keypair.pubkey(),
+                    sig_hash.as_slice(),
+                    &sig
+                )?);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_multisig_signatory() -> Result<()> {
+    let ecc = init_ecc();
+    let secret_keys = [[11u8; 32], [12u8; 32], [13u8; 32]];
+    let pubkeys = secret_keys
+        .iter()
+        .map(|secret_key| ecc.derive_pubkey(secret_key))
+        .collect::<Result<Vec<_>>>()?;
+    let signatory = MultisigSignatory::new(2, pubkeys.clone(), SigHashFlags::DEFAULT)?;
+    assert!(signatory
+        .pubkeys()
+        .windows(2)
+        .all(|pair| pair[0].as_slice() < pair[1].as_slice()));
+    let address = signatory.address(Prefix::default())?;
+    assert_eq!(
+        Address::from_multisig(Prefix::default(), 2, &pubkeys)?,
+        address
+    );
+    assert!(MultisigSignatory::new(3, pubkeys[..2].to_vec(), SigHashFlags::DEFAULT).is_err());
+
+    let mut builder = TxBuilder::new_simple();
+    let input_ref = builder.add_input(
+        UnsignedTxInput {
+            prev_out: TxOutpoint {
+                tx_hash: Default::default(),
+                vout: 0,
+            },
+            sequence: 0xffff_ffff,
+            value: 10_000,
+            token: None,
+        },
+        signatory.lock_script(),
+        signatory.clone(),
+    );
+    builder.add_leftover_output(address.clone().into());
+    let mut unsigned_tx = builder.build()?;
+    let estimated_size = unsigned_tx.estimated_size();
+    let preimages = unsigned_tx.input_preimages(input_ref).to_vec();
+    let sigs = vec![
+        signatory.sign(&ecc, &secret_keys[2], &preimages[0])?,
+        signatory.sign(&ecc, &secret_keys[0], &preimages[1])?,
+    ];
+    unsigned_tx.sign_input(input_ref, sigs)?;
+    let tx = unsigned_tx.complete_tx()?;
+    assert!(tx.ser().len() <= estimated_size);
+    let ops = tx.inputs[0].script.ops();
+    assert_eq!(ops.len(), 4);
+    assert_eq!(
+        ops[3].op,
+        Op::PushByteArray {
+            array: signatory.script().ser_ops(),
+            is_minimal: false,
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_extended_secret_key() -> Result<()> {
+    // BIP32 test vector 1, m/0H/1
+    let ecc = init_ecc();
+    let master = ExtendedSecretKey::from_seed(&hex!("000102030405060708090a0b0c0d0e0f"));
+    let child = master.derive_path(&ecc, &[HARDENED_INDEX, 1])?;
+    assert_eq!(
+        child.secret_key().as_slice(),
+        hex!("3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368"),
+    );
+    assert_eq!(
+        child.chain_code(),
+        &hex!("2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19"),
+    );
+    Ok(())
+}
+
+struct WalletBackend {
+    utxos: Vec<(Script, Utxo)>,
+    broadcast_txs: Mutex<Vec<UnhashedTx>>,
+}
+
+impl ChainBackend for WalletBackend {
+    fn utxos<'a>(&'a self, lock_script: &'a Script) -> BackendFuture<'a, Vec<Utxo>> {
+        let utxos = self
+            .utxos
+            .iter()
+            .filter(|(script, _)| script == lock_script)
+            .map(|(_, utxo)| utxo.clone())
+            .collect();
+        Box::pin(async move { Ok(utxos) })
+    }
+
+    fn history<'a>(&'a self, lock_script: &'a Script) -> BackendFuture<'a, Vec<TxHistoryEntry>> {
+        let history = self
+            .utxos
+            .iter()
+            .filter(|(script, _)| script == lock_script)
+            .map(|(_, utxo)| TxHistoryEntry {
+                txid: utxo.outpoint.tx_hash.clone(),
+                height: utxo.height,
+            })
+            .collect();
+        Box::pin(async move { Ok(history) })
+    }
+
+    fn tx<'a>(&'a self, _txid: &'a Sha256d) -> BackendFuture<'a, Tx> {
+        Box::pin(async move { Err(Error::UnsupportedByBackend { method: "tx" }) })
+    }
+
+    fn broadcast_raw_tx<'a>(&'a self, raw_tx: &'a [u8]) -> BackendFuture<'a, Sha256d> {
+        Box::pin(async move {
+            let tx = UnhashedTx::deser(raw_tx.to_vec().into())?;
+            self.broadcast_txs.lock().unwrap().push(tx);
+            Ok(Sha256d::digest(raw_tx.to_vec()))
+        })
+    }
+
+    fn estimate_fee(&self, _target_blocks: u32) -> BackendFuture<'_, FeeRate> {
+        Box::pin(async move { Ok(FeeRate::from_sats_per_byte(1)) })
+    }
+}
+
+#[test]
+fn test_wallet() -> Result<()> {
+    let ecc = init_ecc();
+    let account_key = ExtendedSecretKey::from_seed(&[7; 32]);
+    let keys = Wallet::new(
+        init_ecc(),
+        account_key.clone(),
+        WalletBackend {
+            utxos: vec![],
+            broadcast_txs: Mutex::new(vec![]),
+        },
+    );
+    let utxo = |hash: u8, value: u64, height: Option<u32>| Utxo {
+        outpoint: TxOutpoint {
+            tx_hash: Sha256d::new([hash; 32]),
+            vout: 0,
+        },
+        value,
+        height,
+        token: None,
+    };
+    let token = TokenData::fungible(TokenCategory::from_hash(Sha256d::new([9; 32])), 100);
+    let backend = WalletBackend {
+        utxos: vec![
+            (keys.receive_address(0)?.into(), utxo(1, 3_000, None)),
+            (keys.receive_address(3)?.into(), utxo(2, 10_000, Some(100))),
+            (
+                keys.receive_address(4)?.into(),
+                Utxo {
+                    token: Some(token),
+                    ..utxo(3, 50_000, Some(101))
+                },
+            ),
+        ],
+        broadcast_txs: Mutex::new(vec![]),
+    };
+    let wallet = Wallet::new(ecc, account_key, backend);
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    runtime.block_on(async {
+        assert_eq!(wallet.balance().await?, 13_000);
+        let history = wallet.history().await?;
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].height, Some(100));
+        assert_eq!(history[2].height, None);
+
+        let recipient = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        wallet.send_to(&recipient, 8_000).await?;
+        wallet.send_to(&recipient, 12_000).await?;
+        let txs = wallet.backend().broadcast_txs.lock().unwrap().clone();
+        assert_eq!(txs[0].inputs.len(), 1);
+        assert_eq!(txs[0].inputs[0].prev_out.tx_hash, Sha256d::new([2; 32]));
+        assert_eq!(txs[0].outputs[0].value, 8_000);
+        assert_eq!(txs[0].outputs[1].script, wallet.change_address()?.into());
+        assert_eq!(txs[1].inputs.len(), 2);
+        assert_eq!(txs[1].outputs[0].value, 12_000);
+        // the token utxo would cover it, but spending it would burn the tokens
+        assert!(matches!(
+            wallet.send_to(&recipient, 20_000).await,
+            Err(Error::InsufficientInputAmount { .. })
+        ));
+        Ok(())
+    })
+}
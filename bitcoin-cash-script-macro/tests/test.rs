@@ -641,3 +641,36 @@ fn test_lifetime() {
     }
     Params { x: &5 }.script();
 }
+
+#[test]
+fn test_token_introspection() {
+    struct Params;
+    #[bitcoin_cash::script(Inputs)]
+    fn script(_: Params) {
+        let input_idx = 0;
+        let spent_amount = OP_UTXOTOKENAMOUNT(input_idx);
+        let output_idx = 0;
+        let amount = OP_OUTPUTTOKENAMOUNT(output_idx);
+        OP_NUMEQUALVERIFY(spent_amount, amount);
+        let input_idx = 0;
+        let spent_category = OP_UTXOTOKENCATEGORY(input_idx);
+        let output_idx = 0;
+        let category = OP_OUTPUTTOKENCATEGORY(output_idx);
+        OP_EQUAL(spent_category, category);
+    }
+    assert_eq!(
+        Params.script().script_ops().collect::<Vec<_>>(),
+        vec![
+            &Op::from_int(0),
+            &Op::Code(OP_UTXOTOKENAMOUNT),
+            &Op::from_int(0),
+            &Op::Code(OP_OUTPUTTOKENAMOUNT),
+            &Op::Code(OP_NUMEQUALVERIFY),
+            &Op::from_int(0),
+            &Op::Code(OP_UTXOTOKENCATEGORY),
+            &Op::from_int(0),
+            &Op::Code(OP_OUTPUTTOKENCATEGORY),
+            &Op::Code(OP_EQUAL),
+        ]
+    );
+}
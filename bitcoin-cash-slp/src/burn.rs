@@ -0,0 +1,182 @@
+use crate::{slp_send_output, SlpError, SlpResult, SlpTokenType, SlpTx, SlpTxType, TokenId};
+use bitcoin_cash::TxOutput;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SlpBurns {
+    // token base units destroyed by the tx
+    pub amount: u64,
+    // indices of spent mint batons that weren't passed on
+    pub mint_baton_inputs: Vec<usize>,
+}
+
+impl SlpBurns {
+    pub fn is_empty(&self) -> bool {
+        self.amount == 0 && self.mint_baton_inputs.is_empty()
+    }
+}
+
+// inputs of another token type or token id than the tx's are burned; the group token consumed
+// by an NFT1 child genesis is reported as well, as it's burned too
+pub fn detect_burns(tx: &SlpTx) -> SlpBurns {
+    let tx_action = tx
+        .outputs
+        .iter()
+        .map(|output| output.token.action)
+        .find(|action| action.tx_type().is_some())
+        .filter(|_| tx.slp_data.is_some());
+    let tx_token_type = tx_action.and_then(|action| action.token_type());
+    let tx_type = tx_action.and_then(|action| action.tx_type());
+    let tx_token_id = tx.slp_data.as_ref().map(|slp_data| &slp_data.token_id);
+    let creates_mint_baton = tx.outputs.iter().any(|output| output.token.is_mint_baton);
+    let mut burns = SlpBurns::default();
+    let mut sendable_amount = 0u64;
+    for input in &tx.inputs {
+        let token = &input.token;
+        let input_token_type = token.action.token_type();
+        if input_token_type.is_none() {
+            continue;
+        }
+        let is_same_token =
+            input_token_type == tx_token_type && input.token_id.as_ref() == tx_token_id;
+        if token.is_mint_baton {
+            if !(is_same_token && tx_type == Some(SlpTxType::MINT) && creates_mint_baton) {
+                burns.mint_baton_inputs.push(input.index);
+            }
+        } else if is_same_token && tx_type == Some(SlpTxType::SEND) {
+            sendable_amount = sendable_amount.saturating_add(token.amount);
+        } else {
+            burns.amount = burns.amount.saturating_add(token.amount);
+        }
+    }
+    if tx_type == Some(SlpTxType::SEND) {
+        // amounts assigned to outputs the tx doesn't have aren't in `outputs`, so they count
+        // as burned as well
+        let sent_amount = tx
+            .outputs
+            .iter()
+            .fold(0u64, |sum, output| sum.saturating_add(output.token.amount));
+        let burned_amount = if sent_amount > sendable_amount {
+            // SENDs of more than their inputs are invalid, destroying all inputs
+            sendable_amount
+        } else {
+            sendable_amount - sent_amount
+        };
+        burns.amount = burns.amount.saturating_add(burned_amount);
+    }
+    burns
+}
+
+// an intentional burn is a SEND paying out less than its inputs; the rest of the inputs
+// goes to output 1, which should be the sender's token change address
+pub fn slp_burn_output(
+    slp_token_type: SlpTokenType,
+    token_id: &TokenId,
+    input_amount: u64,
+    burn_amount: u64,
+) -> SlpResult<TxOutput> {
+    let change_amount = input_amount
+        .checked_sub(burn_amount)
+        .ok_or(SlpError::InvalidParams {
+            reason: "can't burn more tokens than the inputs hold",
+        })?;
+    Ok(slp_send_output(slp_token_type, token_id, &[change_amount]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_burns, SlpBurns};
+    use crate::{SlpAction, SlpData, SlpToken, SlpTx, SlpTxInput, SlpTxOutput, TokenId};
+    use bitcoin_cash::{Hashed, Script, Sha256d, TxInput, TxOutpoint, TxOutput};
+
+    fn slp_tx(inputs: &[(u64, bool, SlpAction)], outputs: &[(u64, SlpAction)]) -> SlpTx {
+        let token_id = TokenId::from_hash(Sha256d::digest(b"token".to_vec()));
+        SlpTx {
+            version: 2,
+            inputs: inputs
+                .iter()
+                .enumerate()
+                .map(|(index, &(amount, is_mint_baton, action))| SlpTxInput {
+                    index,
+                    token: SlpToken {
+                        amount,
+                        is_mint_baton,
+                        action,
+                    },
+                    token_id: Some(token_id.clone()),
+                    input: TxInput::new(TxOutpoint::default(), Script::default(), 0),
+                    prev_script: Script::default(),
+                })
+                .collect(),
+            outputs: outputs
+                .iter()
+                .map(|&(amount, action)| SlpTxOutput {
+                    token: SlpToken {
+                        amount,
+                        is_mint_baton: false,
+                        action,
+                    },
+                    output: TxOutput {
+                        value: 546,
+                        script: Script::default(),
+                        token: None,
+                    },
+                })
+                .collect(),
+            lock_time: 0,
+            slp_data: Some(SlpData { token_id }),
+            tx_hash: Sha256d::default(),
+        }
+    }
+
+    #[test]
+    fn test_detect_burns() {
+        use SlpAction::*;
+        let tx = slp_tx(
+            &[(10, false, SlpV1Send), (5, false, SlpV1Mint)],
+            &[(0, SlpV1Send), (12, SlpV1Send)],
+        );
+        assert_eq!(detect_burns(&tx).amount, 3);
+
+        let tx = slp_tx(
+            &[(10, false, SlpV1Send)],
+            &[(0, SlpV1Send), (10, SlpV1Send)],
+        );
+        assert!(detect_burns(&tx).is_empty());
+
+        // too much sent, or sending the wrong token type, burns everything
+        let tx = slp_tx(
+            &[(10, false, SlpV1Send)],
+            &[(0, SlpV1Send), (11, SlpV1Send)],
+        );
+        assert_eq!(detect_burns(&tx).amount, 10);
+        let tx = slp_tx(
+            &[(10, false, SlpNft1GroupSend)],
+            &[(0, SlpV1Send), (10, SlpV1Send)],
+        );
+        assert_eq!(detect_burns(&tx).amount, 10);
+        let tx = slp_tx(
+            &[
+                (10, false, SlpV1Send),
+                (4, false, SlpUnknownTokenType(0x02)),
+            ],
+            &[(0, SlpV1Send), (10, SlpV1Send)],
+        );
+        assert_eq!(detect_burns(&tx).amount, 4);
+        let mut tx = slp_tx(
+            &[(10, false, SlpV1Send), (6, false, SlpV1Send)],
+            &[(0, SlpV1Send), (10, SlpV1Send)],
+        );
+        tx.inputs[1].token_id = Some(TokenId::from_hash(Sha256d::digest(b"other".to_vec())));
+        assert_eq!(detect_burns(&tx).amount, 6);
+
+        let mut tx = slp_tx(&[(7, false, SlpV1Send), (0, true, SlpV1Genesis)], &[]);
+        tx.slp_data = None;
+        assert_eq!(
+            detect_burns(&tx),
+            SlpBurns {
+                amount: 7,
+                mint_baton_inputs: vec![1],
+            }
+        );
+    }
+}
@@ -1,17 +1,39 @@
-use bitcoin_cash::{ByteArray, Hashed, Op, Opcode, Script, Sha256d, TaggedOp, TxInput, TxOutput, UnhashedTx, UnsignedTxInput, error};
-use serde::{Deserialize, Serialize};
+use bitcoin_cash::{ByteArray, Hashed, Op, Opcode, Script, Sha256, Sha256d, TaggedOp, TxInput, TxOutput, UnhashedTx, UnsignedTxInput, error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Deserialize, Serialize, Clone, Debug, Hash, PartialEq, Eq)]
+mod burn;
+mod mint_baton;
+mod nft1;
+mod postage;
+mod send_chunks;
+mod slp_error;
+mod slp_lint;
+mod slp_parse;
+mod token_amount;
+
+pub use burn::*;
+pub use mint_baton::*;
+pub use nft1::*;
+pub use postage::*;
+pub use send_chunks::*;
+pub use slp_error::*;
+pub use slp_lint::*;
+pub use slp_parse::*;
+pub use token_amount::*;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub struct TokenId(Sha256d);
 
-#[derive(Deserialize, Serialize, Copy, Clone, Debug, Hash)]
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum SlpTokenType {
-    Fungible = 1,
-    Nft1Child = 0x41,
-    Nft1Group = 0x81,
+    Fungible,
+    Nft1Child,
+    Nft1Group,
+    // token types defined after this crate, so parsing them doesn't fail
+    Unknown(u8),
 }
 
-#[derive(Copy, Clone, Debug, Hash)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum SlpTxType {
     GENESIS,
     SEND,
@@ -38,6 +60,8 @@ pub struct SlpData {
 pub struct SlpTxInput {
     pub index: usize,
     pub token: SlpToken,
+    // the token of the spent output, None if it has none
+    pub token_id: Option<TokenId>,
     pub input: TxInput,
     pub prev_script: Script,
 }
@@ -69,6 +93,8 @@ pub enum SlpAction {
     SlpNft1GroupSend,
     SlpNft1UniqueChildGenesis,
     SlpNft1UniqueChildSend,
+    // any action on a token type this crate doesn't know
+    SlpUnknownTokenType(u8),
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -78,7 +104,7 @@ pub struct SlpUtxo {
     pub slp_data: Option<SlpData>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct TokenMeta {
     pub ticker: String,
     pub name: String,
@@ -93,7 +119,8 @@ pub struct SlpGenesisParams<'a> {
     pub token_ticker: &'a str,
     pub token_name: &'a str,
     pub token_document_url: &'a str,
-    pub token_document_hash: &'a str,
+    // the SHA256 of the document, in the byte order `sha256sum` prints
+    pub token_document_hash: Option<Sha256>,
     pub decimals: u8,
     pub mint_baton_vout: Option<u8>,
     pub initial_token_mint_quantity: u64,
@@ -116,7 +143,7 @@ pub fn slp_amount_ops<'a>(output_amounts: impl IntoIterator<Item = &'a u64>) ->
 pub fn slp_genesis_output(params: SlpGenesisParams<'_>) -> TxOutput {
     let byte_arrays = vec![
         ByteArray::from_slice("lokad_id", b"SLP\0"),
-        ByteArray::new("token_type", vec![params.slp_token_type as u8]),
+        ByteArray::new("token_type", vec![params.slp_token_type.to_u8()]),
         ByteArray::new(
             "transaction_type",
             SlpTxType::GENESIS.to_string().into_bytes(),
@@ -129,7 +156,10 @@ pub fn slp_genesis_output(params: SlpGenesisParams<'_>) -> TxOutput {
         ),
         ByteArray::new(
             "token_document_hash",
-            params.token_document_hash.as_bytes().to_vec(),
+            params
+                .token_document_hash
+                .map(|hash| hash.as_slice().to_vec())
+                .unwrap_or_default(),
         ),
         ByteArray::new("decimals", params.decimals.to_be_bytes().as_ref()),
         ByteArray::new(
@@ -154,6 +184,7 @@ pub fn slp_genesis_output(params: SlpGenesisParams<'_>) -> TxOutput {
     TxOutput {
         value: 0,
         script: Script::new(ops.into_iter().map(TaggedOp::from_op).collect::<Vec<_>>()),
+        token: None,
     }
 }
 
@@ -169,7 +200,7 @@ pub fn slp_send_output(
             is_minimal: false,
         },
         Op::PushByteArray {
-            array: ByteArray::new("token_type", vec![slp_token_type as u8]),
+            array: ByteArray::new("token_type", vec![slp_token_type.to_u8()]),
             is_minimal: false,
         },
         Op::PushByteArray {
@@ -185,9 +216,112 @@ pub fn slp_send_output(
     TxOutput {
         value: 0,
         script: Script::new(ops.into_iter().map(TaggedOp::from_op).collect::<Vec<_>>()),
+        token: None,
+    }
+}
+
+// NFT1 children have no mint baton, so they can't be minted
+pub fn slp_mint_output(
+    slp_token_type: SlpTokenType,
+    token_id: &TokenId,
+    mint_baton_vout: Option<u8>,
+    additional_token_quantity: u64,
+) -> SlpResult<TxOutput> {
+    if slp_token_type == SlpTokenType::Nft1Child {
+        return Err(SlpError::InvalidParams {
+            reason: "NFT1 children can't be minted",
+        });
+    }
+    let ops = vec![
+        Op::Code(Opcode::OP_RETURN),
+        Op::PushByteArray {
+            array: ByteArray::from_slice("lokad_id", b"SLP\0"),
+            is_minimal: false,
+        },
+        Op::PushByteArray {
+            array: ByteArray::new("token_type", vec![slp_token_type.to_u8()]),
+            is_minimal: false,
+        },
+        Op::PushByteArray {
+            array: ByteArray::new("transaction_type", SlpTxType::MINT.to_string().into_bytes()),
+            is_minimal: false,
+        },
+        Op::PushByteArray {
+            array: ByteArray::new("token_id", token_id.to_vec()),
+            is_minimal: false,
+        },
+        // an empty push ends the minting, as the baton is destroyed
+        Op::PushByteArray {
+            array: ByteArray::new(
+                "mint_baton_vout",
+                mint_baton_vout
+                    .map(|vout| vout.to_be_bytes().to_vec())
+                    .unwrap_or_default(),
+            ),
+            is_minimal: false,
+        },
+        Op::PushByteArray {
+            array: ByteArray::new(
+                "additional_token_quantity",
+                additional_token_quantity.to_be_bytes().as_ref(),
+            ),
+            is_minimal: false,
+        },
+    ];
+    Ok(TxOutput {
+        value: 0,
+        script: Script::new(ops.into_iter().map(TaggedOp::from_op).collect::<Vec<_>>()),
+        token: None,
+    })
+}
+
+// document hashes used to be given as a str; this accepts what a hash could sensibly have
+// been passed as: nothing, 32 raw bytes or 64 hex digits
+#[deprecated(note = "set SlpGenesisParams::token_document_hash to an Option<Sha256> directly")]
+pub fn document_hash_from_str(token_document_hash: &str) -> SlpResult<Option<Sha256>> {
+    match token_document_hash.len() {
+        0 => Ok(None),
+        32 => Ok(Some(Sha256::from_slice(token_document_hash.as_bytes())?)),
+        64 => Ok(Some(Sha256::from_hex_be(token_document_hash).map_err(|_| {
+            SlpError::InvalidParams {
+                reason: "token document hash isn't valid hex",
+            }
+        })?)),
+        _ => Err(SlpError::InvalidParams {
+            reason: "token document hash must be 32 bytes",
+        }),
     }
 }
 
+// SlpGenesisParams as they were when the document hash was a str
+#[deprecated(note = "use SlpGenesisParams, which takes the document hash as an Option<Sha256>")]
+pub struct SlpGenesisStrParams<'a> {
+    pub slp_token_type: SlpTokenType,
+    pub token_ticker: &'a str,
+    pub token_name: &'a str,
+    pub token_document_url: &'a str,
+    pub token_document_hash: &'a str,
+    pub decimals: u8,
+    pub mint_baton_vout: Option<u8>,
+    pub initial_token_mint_quantity: u64,
+}
+
+// the document hash is converted with document_hash_from_str
+#[deprecated(note = "use slp_genesis_output with SlpGenesisParams")]
+#[allow(deprecated)]
+pub fn slp_genesis_output_from_str(params: SlpGenesisStrParams<'_>) -> SlpResult<TxOutput> {
+    Ok(slp_genesis_output(SlpGenesisParams {
+        slp_token_type: params.slp_token_type,
+        token_ticker: params.token_ticker,
+        token_name: params.token_name,
+        token_document_url: params.token_document_url,
+        token_document_hash: document_hash_from_str(params.token_document_hash)?,
+        decimals: params.decimals,
+        mint_baton_vout: params.mint_baton_vout,
+        initial_token_mint_quantity: params.initial_token_mint_quantity,
+    }))
+}
+
 impl std::fmt::Display for SlpTxType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         write!(f, "{:?}", self)
@@ -217,6 +351,111 @@ impl TokenId {
     }
 }
 
+// token ids are shown like txids, i.e. as the byte-reversed hex of the GENESIS tx hash
+impl std::fmt::Display for TokenId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.0.to_hex_le())
+    }
+}
+
+impl std::str::FromStr for TokenId {
+    type Err = SlpError;
+
+    fn from_str(s: &str) -> SlpResult<Self> {
+        if s.len() != 64 {
+            return Err(SlpError::InvalidParams {
+                reason: "token id must be 64 hex digits",
+            });
+        }
+        Ok(TokenId(Sha256d::from_hex_le(s)?))
+    }
+}
+
+impl Serialize for TokenId {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            self.to_string().serialize(serializer)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenId {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            s.parse().map_err(serde::de::Error::custom)
+        } else {
+            Ok(TokenId(Sha256d::deserialize(deserializer)?))
+        }
+    }
+}
+
+impl SlpTokenType {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            SlpTokenType::Fungible => 1,
+            SlpTokenType::Nft1Child => 0x41,
+            SlpTokenType::Nft1Group => 0x81,
+            SlpTokenType::Unknown(token_type) => token_type,
+        }
+    }
+
+    pub fn is_known(self) -> bool {
+        !matches!(self, SlpTokenType::Unknown(_))
+    }
+}
+
+impl From<u8> for SlpTokenType {
+    fn from(token_type: u8) -> Self {
+        match token_type {
+            1 => SlpTokenType::Fungible,
+            0x41 => SlpTokenType::Nft1Child,
+            0x81 => SlpTokenType::Nft1Group,
+            _ => SlpTokenType::Unknown(token_type),
+        }
+    }
+}
+
+impl SlpAction {
+    // the token type of the tx that created the token, None for non-SLP outputs
+    pub fn token_type(self) -> Option<SlpTokenType> {
+        match self {
+            SlpAction::SlpV1Genesis | SlpAction::SlpV1Mint | SlpAction::SlpV1Send => {
+                Some(SlpTokenType::Fungible)
+            }
+            SlpAction::SlpNft1GroupGenesis
+            | SlpAction::SlpNft1GroupMint
+            | SlpAction::SlpNft1GroupSend => Some(SlpTokenType::Nft1Group),
+            SlpAction::SlpNft1UniqueChildGenesis | SlpAction::SlpNft1UniqueChildSend => {
+                Some(SlpTokenType::Nft1Child)
+            }
+            SlpAction::SlpUnknownTokenType(token_type) => Some(SlpTokenType::Unknown(token_type)),
+            _ => None,
+        }
+    }
+
+    pub fn tx_type(self) -> Option<SlpTxType> {
+        match self {
+            SlpAction::SlpV1Genesis
+            | SlpAction::SlpNft1GroupGenesis
+            | SlpAction::SlpNft1UniqueChildGenesis => Some(SlpTxType::GENESIS),
+            SlpAction::SlpV1Mint | SlpAction::SlpNft1GroupMint => Some(SlpTxType::MINT),
+            SlpAction::SlpV1Send
+            | SlpAction::SlpNft1GroupSend
+            | SlpAction::SlpNft1UniqueChildSend => Some(SlpTxType::SEND),
+            _ => None,
+        }
+    }
+}
+
 impl Default for SlpAction {
     fn default() -> Self {
         SlpAction::NonSlp
@@ -233,3 +472,97 @@ impl SlpTx {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        slp_genesis_output, slp_mint_output, SlpGenesisParams, SlpResult, SlpTokenType, TokenId,
+    };
+    use bitcoin_cash::{Hashed, Op, Sha256, Sha256d};
+
+    #[test]
+    fn test_token_id_hex() -> SlpResult<()> {
+        let hex = "959a6818cba5af8aba391d3f7649f5f6a5ceb6cdcd2c2a3dcb5d2fbfc4b08e98";
+        let token_id: TokenId = hex.parse()?;
+        assert_eq!(token_id.to_string(), hex);
+        assert_eq!(token_id.as_slice_be()[0], 0x98);
+        assert_eq!(serde_json::to_string(&token_id).unwrap(), format!("{:?}", hex));
+        assert_eq!(
+            serde_json::from_str::<TokenId>(&format!("{:?}", hex)).unwrap(),
+            token_id,
+        );
+        assert!("abcd".parse::<TokenId>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_document_hash_from_str() -> SlpResult<()> {
+        use super::document_hash_from_str;
+        let hash = Sha256::digest(b"document".to_vec());
+        assert_eq!(document_hash_from_str("")?, None);
+        assert_eq!(document_hash_from_str(&hash.to_hex_be())?, Some(hash));
+        assert!(document_hash_from_str("not a hash").is_err());
+        assert!(document_hash_from_str(&"xy".repeat(32)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_slp_mint_output() -> SlpResult<()> {
+        let token_id = TokenId::from_hash(Sha256d::digest(b"token".to_vec()));
+        let output = slp_mint_output(SlpTokenType::Fungible, &token_id, Some(2), 1000)?;
+        let pushes = output
+            .script
+            .ops_arc()
+            .iter()
+            .filter_map(|op| match &op.op {
+                Op::PushByteArray { array, .. } => Some(array.to_vec()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(pushes.len(), 6);
+        assert_eq!(pushes[0], b"SLP\0");
+        assert_eq!(pushes[1], vec![0x01]);
+        assert_eq!(pushes[2], b"MINT");
+        assert_eq!(pushes[3], token_id.to_vec());
+        assert_eq!(pushes[4], vec![2]);
+        assert_eq!(pushes[5], 1000u64.to_be_bytes().to_vec());
+
+        let output = slp_mint_output(SlpTokenType::Nft1Group, &token_id, None, 1)?;
+        let ops = output.script.ops_arc();
+        assert!(matches!(&ops[5].op, Op::PushByteArray { array, .. } if array.is_empty()));
+        assert!(slp_mint_output(SlpTokenType::Nft1Child, &token_id, Some(2), 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_slp_genesis_output_from_str() -> SlpResult<()> {
+        use super::{slp_genesis_output_from_str, SlpGenesisStrParams};
+        let hash = Sha256::digest(b"document".to_vec());
+        let hash_hex = hash.to_hex_be();
+        let str_params = |token_document_hash| SlpGenesisStrParams {
+            slp_token_type: SlpTokenType::Fungible,
+            token_ticker: "TEST",
+            token_name: "Test",
+            token_document_url: "",
+            token_document_hash,
+            decimals: 2,
+            mint_baton_vout: Some(2),
+            initial_token_mint_quantity: 1000,
+        };
+        let output = slp_genesis_output(SlpGenesisParams {
+            slp_token_type: SlpTokenType::Fungible,
+            token_ticker: "TEST",
+            token_name: "Test",
+            token_document_url: "",
+            token_document_hash: Some(hash),
+            decimals: 2,
+            mint_baton_vout: Some(2),
+            initial_token_mint_quantity: 1000,
+        });
+        assert_eq!(slp_genesis_output_from_str(str_params(&hash_hex))?, output);
+        assert!(slp_genesis_output_from_str(str_params("not a hash")).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{SlpTx, SlpTxType, SlpUtxo, TokenId};
+use bitcoin_cash::{Sha256d, TxOutpoint};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MintBatonStatus {
+    // no tx of the history created a mint baton for the token
+    Unknown,
+    Unspent(TxOutpoint),
+    // the token can't be minted anymore
+    Burned { burning_tx_hash: Sha256d },
+}
+
+impl MintBatonStatus {
+    pub fn is_burned(&self) -> bool {
+        matches!(self, MintBatonStatus::Burned { .. })
+    }
+}
+
+pub fn find_mint_baton<'a>(utxos: &'a [SlpUtxo], token_id: &TokenId) -> Option<&'a SlpUtxo> {
+    utxos.iter().find(|utxo| {
+        utxo.slp_token.is_mint_baton
+            && utxo
+                .slp_data
+                .as_ref()
+                .is_some_and(|slp_data| &slp_data.token_id == token_id)
+    })
+}
+
+// follows the mint baton of `token_id` through `txs`, which have to be ordered such that
+// each tx comes after the txs it spends, e.g. in block order
+pub fn track_mint_baton<'a>(
+    txs: impl IntoIterator<Item = &'a SlpTx>,
+    token_id: &TokenId,
+) -> MintBatonStatus {
+    let mut status = MintBatonStatus::Unknown;
+    for tx in txs {
+        let spends_baton = match &status {
+            MintBatonStatus::Unspent(outpoint) => tx
+                .inputs
+                .iter()
+                .any(|input| &input.input.prev_out == outpoint),
+            _ => false,
+        };
+        let is_issuance = tx
+            .slp_data
+            .as_ref()
+            .is_some_and(|slp_data| &slp_data.token_id == token_id)
+            && tx.outputs.iter().any(|output| {
+                matches!(
+                    output.token.action.tx_type(),
+                    Some(SlpTxType::GENESIS) | Some(SlpTxType::MINT)
+                )
+            });
+        let new_baton_vout = tx
+            .outputs
+            .iter()
+            .position(|output| output.token.is_mint_baton)
+            .filter(|_| is_issuance);
+        match new_baton_vout {
+            Some(vout) => {
+                status = MintBatonStatus::Unspent(TxOutpoint {
+                    tx_hash: tx.tx_hash.clone(),
+                    vout: vout as u32,
+                })
+            }
+            None if spends_baton || is_issuance => {
+                // a GENESIS without baton or a MINT ending the minting burn it as well
+                status = MintBatonStatus::Burned {
+                    burning_tx_hash: tx.tx_hash.clone(),
+                }
+            }
+            None => {}
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{track_mint_baton, MintBatonStatus};
+    use crate::{
+        slp_genesis_output, slp_mint_output, slp_send_output, SlpGenesisParams, SlpPrevOutput,
+        SlpResult, SlpTokenType, SlpTx, TokenId,
+    };
+    use bitcoin_cash::{Hashed, Script, Sha256d, TxInput, TxOutpoint, TxOutput, UnhashedTx};
+
+    fn slp_tx(op_return: TxOutput, spent: Option<(&SlpTx, u32)>, tag: &[u8]) -> SlpResult<SlpTx> {
+        let (inputs, prev_slp_outputs) = match spent {
+            Some((prev_tx, vout)) => {
+                let outpoint = TxOutpoint {
+                    tx_hash: prev_tx.tx_hash.clone(),
+                    vout,
+                };
+                let prev_slp_output = SlpPrevOutput {
+                    token: prev_tx.outputs[vout as usize].token,
+                    token_id: prev_tx.slp_data.as_ref().unwrap().token_id.clone(),
+                };
+                let input = TxInput::new(outpoint, Script::default(), 0);
+                (vec![input], vec![Some(prev_slp_output)])
+            }
+            None => (vec![], vec![]),
+        };
+        let mut outputs = vec![op_return];
+        outputs.extend((0..3).map(|_| TxOutput {
+            value: 546,
+            script: Script::default(),
+            token: None,
+        }));
+        let tx = UnhashedTx {
+            version: 2,
+            inputs,
+            outputs,
+            lock_time: 0,
+        };
+        SlpTx::from_tx(tx, Sha256d::digest(tag.to_vec()), &prev_slp_outputs)
+    }
+
+    #[test]
+    fn test_track_mint_baton() -> SlpResult<()> {
+        let genesis = slp_tx(
+            slp_genesis_output(SlpGenesisParams {
+                slp_token_type: SlpTokenType::Fungible,
+                token_ticker: "TOK",
+                token_name: "Token",
+                token_document_url: "",
+                token_document_hash: None,
+                decimals: 0,
+                mint_baton_vout: Some(2),
+                initial_token_mint_quantity: 100,
+            }),
+            None,
+            b"genesis",
+        )?;
+        let token_id = TokenId::from_hash(genesis.tx_hash.clone());
+        let mint_output = slp_mint_output(SlpTokenType::Fungible, &token_id, Some(3), 50)?;
+        let mint = slp_tx(mint_output, Some((&genesis, 2)), b"mint")?;
+        let send_output = slp_send_output(SlpTokenType::Fungible, &token_id, &[0]);
+        let send = slp_tx(send_output, Some((&mint, 3)), b"send")?;
+
+        assert_eq!(track_mint_baton(&[], &token_id), MintBatonStatus::Unknown);
+        assert_eq!(
+            track_mint_baton(&[genesis.clone(), mint.clone()], &token_id),
+            MintBatonStatus::Unspent(TxOutpoint {
+                tx_hash: mint.tx_hash.clone(),
+                vout: 3,
+            }),
+        );
+        let status = track_mint_baton(&[genesis, mint, send.clone()], &token_id);
+        assert_eq!(
+            status,
+            MintBatonStatus::Burned {
+                burning_tx_hash: send.tx_hash,
+            },
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{
+    slp_genesis_output, slp_send_output, SlpAction, SlpError, SlpGenesisParams, SlpResult,
+    SlpTokenType, SlpUtxo, TokenId,
+};
+use bitcoin_cash::{Sha256, TxOutput};
+
+pub const MAX_SLP_SEND_OUTPUTS: usize = 19;
+
+pub struct Nft1ChildGenesisParams<'a> {
+    pub token_ticker: &'a str,
+    pub token_name: &'a str,
+    pub token_document_url: &'a str,
+    pub token_document_hash: Option<Sha256>,
+}
+
+pub fn nft1_group_genesis_output(params: SlpGenesisParams<'_>) -> TxOutput {
+    slp_genesis_output(SlpGenesisParams {
+        slp_token_type: SlpTokenType::Nft1Group,
+        ..params
+    })
+}
+
+// a child NFT is indivisible, can't be minted again, and always has a quantity of one;
+// the tx has to spend a group token in input 0, see check_nft1_child_genesis_inputs
+pub fn nft1_child_genesis_output(params: Nft1ChildGenesisParams<'_>) -> TxOutput {
+    slp_genesis_output(SlpGenesisParams {
+        slp_token_type: SlpTokenType::Nft1Child,
+        token_ticker: params.token_ticker,
+        token_name: params.token_name,
+        token_document_url: params.token_document_url,
+        token_document_hash: params.token_document_hash,
+        decimals: 0,
+        mint_baton_vout: None,
+        initial_token_mint_quantity: 1,
+    })
+}
+
+// sends the child NFT to the output at `vout`, which has to be between 1 and 19
+pub fn nft1_child_send_output(child_token_id: &TokenId, vout: usize) -> SlpResult<TxOutput> {
+    if vout == 0 || vout > MAX_SLP_SEND_OUTPUTS {
+        return Err(SlpError::InvalidParams {
+            reason: "NFT1 child can only be sent to outputs 1 to 19",
+        });
+    }
+    let mut output_amounts = vec![0; vout];
+    output_amounts[vout - 1] = 1;
+    Ok(slp_send_output(
+        SlpTokenType::Nft1Child,
+        child_token_id,
+        &output_amounts,
+    ))
+}
+
+// splits group tokens into outputs of one each, so every child genesis burns exactly one
+pub fn nft1_group_fan_out_output(
+    group_token_id: &TokenId,
+    num_outputs: usize,
+) -> SlpResult<TxOutput> {
+    if num_outputs == 0 || num_outputs > MAX_SLP_SEND_OUTPUTS {
+        return Err(SlpError::InvalidParams {
+            reason: "NFT1 group fan-out needs 1 to 19 outputs",
+        });
+    }
+    Ok(slp_send_output(
+        SlpTokenType::Nft1Group,
+        group_token_id,
+        &vec![1; num_outputs],
+    ))
+}
+
+// the child genesis burns the group token in input 0; spending more than one there
+// would burn the rest along with it
+pub fn check_nft1_child_genesis_inputs(
+    inputs: &[SlpUtxo],
+    group_token_id: &TokenId,
+) -> SlpResult<()> {
+    let first_input = inputs.first().ok_or(SlpError::InvalidTx {
+        reason: "NFT1 child genesis has no inputs",
+    })?;
+    let is_group_token = matches!(
+        first_input.slp_token.action,
+        SlpAction::SlpNft1GroupGenesis | SlpAction::SlpNft1GroupMint | SlpAction::SlpNft1GroupSend
+    );
+    let is_same_group = first_input
+        .slp_data
+        .as_ref()
+        .map(|slp_data| &slp_data.token_id == group_token_id)
+        .unwrap_or(false);
+    if !is_group_token || !is_same_group || first_input.slp_token.is_mint_baton {
+        return Err(SlpError::InvalidTx {
+            reason: "input 0 of an NFT1 child genesis has to spend a group token",
+        });
+    }
+    if first_input.slp_token.amount != 1 {
+        return Err(SlpError::InvalidTx {
+            reason: "input 0 of an NFT1 child genesis has to spend exactly one group token",
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_nft1_child_genesis_inputs, nft1_child_genesis_output, nft1_child_send_output,
+        Nft1ChildGenesisParams,
+    };
+    use crate::{SlpAction, SlpData, SlpResult, SlpToken, SlpUtxo, TokenId};
+    use bitcoin_cash::{Hashed, Op, Sha256, Sha256d, TxOutpoint, UnsignedTxInput};
+
+    fn group_utxo(group_token_id: &TokenId, amount: u64) -> SlpUtxo {
+        SlpUtxo {
+            input: UnsignedTxInput {
+                prev_out: TxOutpoint {
+                    tx_hash: Sha256d::digest(b"group".to_vec()),
+                    vout: 1,
+                },
+                sequence: 0xffff_ffff,
+                value: 546,
+                token: None,
+            },
+            slp_token: SlpToken {
+                amount,
+                is_mint_baton: false,
+                action: SlpAction::SlpNft1GroupSend,
+            },
+            slp_data: Some(SlpData {
+                token_id: group_token_id.clone(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_nft1_child() -> SlpResult<()> {
+        let output = nft1_child_genesis_output(Nft1ChildGenesisParams {
+            token_ticker: "NFT",
+            token_name: "Child",
+            token_document_url: "",
+            token_document_hash: Some(Sha256::digest(b"document".to_vec())),
+        });
+        let pushes = output
+            .script
+            .ops_arc()
+            .iter()
+            .filter_map(|op| match &op.op {
+                Op::PushByteArray { array, .. } => Some(array.to_vec()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(pushes[1], vec![0x41]);
+        assert_eq!(pushes[6], Sha256::digest(b"document".to_vec()).as_slice());
+        assert_eq!(pushes[7], vec![0]);
+        assert_eq!(pushes[8], Vec::<u8>::new());
+        assert_eq!(pushes[9], 1u64.to_be_bytes().to_vec());
+
+        let group_token_id = TokenId::from_hash(Sha256d::digest(b"group".to_vec()));
+        check_nft1_child_genesis_inputs(&[group_utxo(&group_token_id, 1)], &group_token_id)?;
+        assert!(check_nft1_child_genesis_inputs(
+            &[group_utxo(&group_token_id, 2)],
+            &group_token_id
+        )
+        .is_err());
+        let other_group = TokenId::from_hash(Sha256d::digest(b"other".to_vec()));
+        assert!(
+            check_nft1_child_genesis_inputs(&[group_utxo(&other_group, 1)], &group_token_id)
+                .is_err()
+        );
+        assert!(check_nft1_child_genesis_inputs(&[], &group_token_id).is_err());
+
+        let child_token_id = TokenId::from_hash(Sha256d::digest(b"child".to_vec()));
+        assert!(nft1_child_send_output(&child_token_id, 0).is_err());
+        assert!(nft1_child_send_output(&child_token_id, 2).is_ok());
+        Ok(())
+    }
+}
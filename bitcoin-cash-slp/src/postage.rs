@@ -0,0 +1,245 @@
+// the Post Office protocol, where a server pays the BCH fee of token txs in exchange for a
+// "stamp", an amount of tokens paid to the server within the same tx
+use crate::{
+    parse_slp_script, slp_send_output, SlpError, SlpMessage, SlpPrevOutput, SlpRecipient,
+    SlpResult, SlpTokenType, SlpTx, TokenId, MAX_SLP_SEND_OUTPUTS, SLP_DUST_AMOUNT,
+};
+use bitcoin_cash::{
+    BitcoinCode, Op, Script, SigHashFlags, TxBuilder, TxOutput, UnhashedTx, UnlockScriptKind,
+};
+use serde::{Deserialize, Serialize};
+
+// the user's inputs commit to all outputs, but let the post office add its fee inputs
+pub const POSTAGE_SIG_HASH_FLAGS: SigHashFlags =
+    SigHashFlags::DEFAULT.union(SigHashFlags::ANYONECANPAY);
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PostageStamp {
+    pub name: String,
+    pub symbol: String,
+    pub token_id: TokenId,
+    pub decimals: u8,
+    // token base units per `weight` bytes of tx
+    pub rate: u64,
+}
+
+// the rates a post office publishes
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PostageRates {
+    pub version: u32,
+    pub address: String,
+    pub weight: u64,
+    #[serde(rename = "transactionttl")]
+    pub transaction_ttl: u64,
+    pub stamps: Vec<PostageStamp>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PostOffice {
+    pub rates: PostageRates,
+    pub lock_script: Script,
+}
+
+impl PostageRates {
+    pub fn stamp(&self, token_id: &TokenId) -> Option<&PostageStamp> {
+        self.stamps.iter().find(|stamp| &stamp.token_id == token_id)
+    }
+
+    // stamps are paid per started `weight` bytes of the tx, including the post office's
+    // fee input
+    pub fn stamp_amount(&self, token_id: &TokenId, tx_size: usize) -> SlpResult<u64> {
+        let stamp = self.stamp(token_id).ok_or(SlpError::InvalidPostage {
+            reason: "the post office doesn't accept this token",
+        })?;
+        if self.weight == 0 {
+            return Err(SlpError::InvalidPostage {
+                reason: "postage weight must not be 0",
+            });
+        }
+        let tx_size = tx_size as u64;
+        let num_stamps = tx_size / self.weight + u64::from(tx_size % self.weight != 0);
+        num_stamps
+            .checked_mul(stamp.rate)
+            .ok_or(SlpError::InvalidPostage {
+                reason: "stamp amount overflows",
+            })
+    }
+}
+
+// the outputs of a SEND paying `recipients` (including any token change) and the stamp;
+// the token inputs then have to be signed with POSTAGE_SIG_HASH_FLAGS
+pub fn postage_send_outputs(
+    slp_token_type: SlpTokenType,
+    token_id: &TokenId,
+    recipients: &[SlpRecipient],
+    stamp: SlpRecipient,
+) -> SlpResult<Vec<TxOutput>> {
+    if recipients.len() + 1 > MAX_SLP_SEND_OUTPUTS {
+        return Err(SlpError::InvalidParams {
+            reason: "a postage SEND can pay at most 18 recipients",
+        });
+    }
+    let recipients = recipients.iter().chain(std::iter::once(&stamp));
+    let amounts = recipients
+        .clone()
+        .map(|recipient| recipient.amount)
+        .collect::<Vec<_>>();
+    let mut outputs = vec![slp_send_output(slp_token_type, token_id, &amounts)];
+    outputs.extend(recipients.map(|recipient| TxOutput {
+        value: SLP_DUST_AMOUNT,
+        script: recipient.lock_script.clone(),
+        token: None,
+    }));
+    Ok(outputs)
+}
+
+impl PostOffice {
+    // checks a user's tx before the post office pays its fee: all inputs must be signed
+    // with POSTAGE_SIG_HASH_FLAGS and it must be a valid SEND paying enough stamps to
+    // `lock_script`; `prev_slp_outputs` has the tokens spent by each input, as for
+    // SlpTx::from_tx
+    pub fn check_postage_tx(
+        &self,
+        tx: &UnhashedTx,
+        prev_slp_outputs: &[Option<SlpPrevOutput>],
+    ) -> SlpResult<()> {
+        if tx.inputs.is_empty() {
+            return Err(SlpError::InvalidPostage {
+                reason: "postage tx has no inputs",
+            });
+        }
+        for input in &tx.inputs {
+            let sig_hash_flags = match input.script.ops_arc().first().map(|op| &op.op) {
+                Some(Op::PushByteArray { array, .. }) => array.last().copied(),
+                _ => None,
+            };
+            if sig_hash_flags.map(SigHashFlags::from_u8) != Some(POSTAGE_SIG_HASH_FLAGS) {
+                return Err(SlpError::InvalidPostage {
+                    reason: "inputs must sign with ALL|FORKID|ANYONECANPAY",
+                });
+            }
+        }
+        let message = match tx.outputs.first() {
+            Some(output) => parse_slp_script(&output.script)?,
+            None => None,
+        };
+        let token_id = match message {
+            Some(SlpMessage::Send { token_id, .. }) => token_id,
+            _ => {
+                return Err(SlpError::InvalidPostage {
+                    reason: "postage tx must be an SLP SEND",
+                })
+            }
+        };
+        // a SEND spending fewer tokens than it sends is invalid and pays no stamps at all
+        let slp_tx = SlpTx::from_tx(tx.clone(), tx.txid(), prev_slp_outputs)?;
+        if slp_tx.slp_data.is_none() {
+            return Err(SlpError::InvalidPostage {
+                reason: "postage tx isn't a valid SLP tx",
+            });
+        }
+        let paid_amount = slp_tx
+            .outputs
+            .iter()
+            .filter(|output| output.output.script == self.lock_script)
+            .fold(0u64, |sum, output| sum.saturating_add(output.token.amount));
+        let tx_size = tx.ser().len() + UnlockScriptKind::P2PKHEcdsa.input_size();
+        if paid_amount < self.rates.stamp_amount(&token_id, tx_size)? {
+            return Err(SlpError::InvalidPostage {
+                reason: "postage tx doesn't pay enough stamps",
+            });
+        }
+        Ok(())
+    }
+
+    // a builder of the checked tx, to which the post office adds its fee inputs
+    pub fn postage_tx_builder(
+        &self,
+        tx: UnhashedTx,
+        prev_slp_outputs: &[Option<SlpPrevOutput>],
+    ) -> SlpResult<TxBuilder<'static>> {
+        self.check_postage_tx(&tx, prev_slp_outputs)?;
+        Ok(TxBuilder::from_tx(tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{postage_send_outputs, PostOffice, PostageRates};
+    use crate::{SlpAction, SlpPrevOutput, SlpRecipient, SlpResult, SlpToken, SlpTokenType};
+    use bitcoin_cash::{ByteArray, Op, Script, TxInput, TxOutpoint, UnhashedTx};
+
+    const RATES: &str = r#"{
+        "version": 1,
+        "address": "simpleledger:qrz9mtq0n3aak5v9jkq6vj7ewltyz8gjcyq2y7vz2a",
+        "weight": 365,
+        "transactionttl": 30,
+        "stamps": [{
+            "name": "Token",
+            "symbol": "TOK",
+            "tokenId": "959a6818cba5af8aba391d3f7649f5f6a5ceb6cdcd2c2a3dcb5d2fbfc4b08e98",
+            "decimals": 2,
+            "rate": 100
+        }]
+    }"#;
+
+    fn signed_input(sig_hash_type: u8) -> TxInput {
+        let sig = [vec![0x30; 71], vec![sig_hash_type]].concat();
+        let script = Script::from_ops(vec![
+            Op::from_array(ByteArray::from_slice_unnamed(&sig)),
+            Op::from_array(ByteArray::from_slice_unnamed(&[2; 33])),
+        ]);
+        TxInput::new(TxOutpoint::default(), script, 0xffff_ffff)
+    }
+
+    #[test]
+    fn test_postage() -> SlpResult<()> {
+        let rates: PostageRates = serde_json::from_str(RATES).unwrap();
+        let token_id = rates.stamps[0].token_id.clone();
+        assert_eq!(rates.stamp_amount(&token_id, 365)?, 100);
+        assert_eq!(rates.stamp_amount(&token_id, 366)?, 200);
+        let post_office = PostOffice {
+            rates,
+            lock_script: Script::from_ops(vec![Op::from_array(b"post office".to_vec())]),
+        };
+
+        let tx = |stamp_amount, sig_hash_type| -> SlpResult<UnhashedTx> {
+            let recipient = SlpRecipient {
+                lock_script: Script::default(),
+                amount: 1000,
+            };
+            let stamp = SlpRecipient {
+                lock_script: post_office.lock_script.clone(),
+                amount: stamp_amount,
+            };
+            let outputs =
+                postage_send_outputs(SlpTokenType::Fungible, &token_id, &[recipient], stamp)?;
+            Ok(UnhashedTx {
+                version: 2,
+                inputs: vec![signed_input(sig_hash_type)],
+                outputs,
+                lock_time: 0,
+            })
+        };
+        let prev_output = |amount| {
+            Some(SlpPrevOutput {
+                token: SlpToken {
+                    amount,
+                    is_mint_baton: false,
+                    action: SlpAction::SlpV1Send,
+                },
+                token_id: token_id.clone(),
+            })
+        };
+        let tx_builder = post_office.postage_tx_builder(tx(200, 0xc1)?, &[prev_output(1200)])?;
+        assert_eq!(tx_builder.outputs().len(), 3);
+        let check = |tx, prev_output| post_office.check_postage_tx(&tx, &[prev_output]);
+        assert!(check(tx(100, 0xc1)?, prev_output(1200)).is_err());
+        assert!(check(tx(200, 0x41)?, prev_output(1200)).is_err());
+        // the inputs don't have the tokens the tx sends
+        assert!(check(tx(200, 0xc1)?, prev_output(1199)).is_err());
+        assert!(check(tx(200, 0xc1)?, None).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{slp_send_output, SlpError, SlpResult, SlpTokenType, TokenId, MAX_SLP_SEND_OUTPUTS};
+use bitcoin_cash::{Script, TxBuilder, TxOutput};
+
+// BCH value of the outputs carrying tokens
+pub const SLP_DUST_AMOUNT: u64 = 546;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlpRecipient {
+    pub lock_script: Script,
+    pub amount: u64,
+}
+
+// one SEND tx of a chain paying more recipients than fit into a single SEND
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlpSendChunk {
+    pub recipients: Vec<SlpRecipient>,
+    // tokens left after this chunk; they go to the last token output, which the next chunk
+    // spends
+    pub change_amount: u64,
+}
+
+impl SlpSendChunk {
+    pub fn output_amounts(&self) -> Vec<u64> {
+        let mut amounts = self
+            .recipients
+            .iter()
+            .map(|recipient| recipient.amount)
+            .collect::<Vec<_>>();
+        if self.change_amount > 0 {
+            amounts.push(self.change_amount);
+        }
+        amounts
+    }
+
+    pub fn change_vout(&self) -> Option<usize> {
+        if self.change_amount > 0 {
+            Some(self.recipients.len() + 1)
+        } else {
+            None
+        }
+    }
+
+    // adds the OP_RETURN, the recipients and the token change to `tx_builder`; the inputs,
+    // i.e. the previous chunk's token change plus BCH for the fee, are up to the caller, as
+    // a chunk's txid is only known once it's signed
+    pub fn add_outputs(
+        &self,
+        tx_builder: &mut TxBuilder<'_>,
+        slp_token_type: SlpTokenType,
+        token_id: &TokenId,
+        token_change_script: &Script,
+    ) {
+        tx_builder.add_output(slp_send_output(
+            slp_token_type,
+            token_id,
+            &self.output_amounts(),
+        ));
+        for recipient in &self.recipients {
+            tx_builder.add_output(TxOutput {
+                value: SLP_DUST_AMOUNT,
+                script: recipient.lock_script.clone(),
+                token: None,
+            });
+        }
+        if self.change_amount > 0 {
+            tx_builder.add_output(TxOutput {
+                value: SLP_DUST_AMOUNT,
+                script: token_change_script.clone(),
+                token: None,
+            });
+        }
+    }
+}
+
+// splits the payment to `recipients` into SENDs of at most 19 outputs, each but the last
+// passing the remaining tokens on to the next one
+pub fn chunk_slp_send(
+    recipients: &[SlpRecipient],
+    input_amount: u64,
+) -> SlpResult<Vec<SlpSendChunk>> {
+    if recipients.is_empty() {
+        return Err(SlpError::InvalidParams {
+            reason: "a SEND needs at least one recipient",
+        });
+    }
+    let sum = |recipients: &[SlpRecipient]| {
+        recipients
+            .iter()
+            .map(|recipient| recipient.amount as u128)
+            .sum::<u128>()
+    };
+    if sum(recipients) > input_amount as u128 {
+        return Err(SlpError::InvalidParams {
+            reason: "recipients receive more tokens than the inputs hold",
+        });
+    }
+    let mut chunks = Vec::new();
+    let mut rest = recipients;
+    let mut remaining_amount = input_amount;
+    loop {
+        // the last chunk can use all outputs for recipients if it leaves no change
+        let is_last = rest.len() < MAX_SLP_SEND_OUTPUTS
+            || (rest.len() == MAX_SLP_SEND_OUTPUTS && sum(rest) == remaining_amount as u128);
+        let num_recipients = if is_last {
+            rest.len()
+        } else {
+            MAX_SLP_SEND_OUTPUTS - 1
+        };
+        let (chunk, next) = rest.split_at(num_recipients);
+        // can't underflow, as all recipients together get at most `input_amount`
+        remaining_amount -= sum(chunk) as u64;
+        chunks.push(SlpSendChunk {
+            recipients: chunk.to_vec(),
+            change_amount: remaining_amount,
+        });
+        if is_last {
+            return Ok(chunks);
+        }
+        rest = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_slp_send, SlpRecipient};
+    use crate::{SlpResult, SlpTokenType, TokenId};
+    use bitcoin_cash::{Hashed, Script, Sha256d, TxBuilder};
+
+    fn recipients(num: usize) -> Vec<SlpRecipient> {
+        (0..num)
+            .map(|_| SlpRecipient {
+                lock_script: Script::default(),
+                amount: 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_slp_send() -> SlpResult<()> {
+        let chunks = chunk_slp_send(&recipients(40), 100)?;
+        let sizes = chunks.iter().map(|chunk| chunk.recipients.len());
+        assert_eq!(sizes.collect::<Vec<_>>(), vec![18, 18, 4]);
+        let changes = chunks.iter().map(|chunk| chunk.change_amount);
+        assert_eq!(changes.collect::<Vec<_>>(), vec![82, 64, 60]);
+        assert_eq!(chunks[0].change_vout(), Some(19));
+        assert_eq!(chunks[0].output_amounts().len(), 19);
+
+        let chunks = chunk_slp_send(&recipients(19), 19)?;
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].change_vout(), None);
+        assert_eq!(chunk_slp_send(&recipients(19), 20)?.len(), 2);
+        assert!(chunk_slp_send(&recipients(3), 2).is_err());
+
+        let token_id = TokenId::from_hash(Sha256d::digest(b"token".to_vec()));
+        let mut tx_builder = TxBuilder::new_simple();
+        chunks[0].add_outputs(
+            &mut tx_builder,
+            SlpTokenType::Fungible,
+            &token_id,
+            &Script::default(),
+        );
+        assert_eq!(tx_builder.outputs().len(), 20);
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use crate::SlpViolation;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SlpError {
+    #[error("Invalid SLP parameters: {reason}")]
+    InvalidParams { reason: &'static str },
+
+    #[error("Invalid SLP script: {violation}")]
+    InvalidScript { violation: SlpViolation },
+
+    #[error("Invalid SLP tx: {reason}")]
+    InvalidTx { reason: &'static str },
+
+    #[error("Invalid token amount {amount:?}: {reason}")]
+    InvalidAmount {
+        amount: String,
+        reason: &'static str,
+    },
+
+    #[error("Invalid postage: {reason}")]
+    InvalidPostage { reason: &'static str },
+
+    #[error(transparent)]
+    Bitcoin(#[from] bitcoin_cash::error::Error),
+}
+
+pub type SlpResult<T> = std::result::Result<T, SlpError>;
@@ -0,0 +1,270 @@
+use crate::{SlpTokenType, MAX_SLP_SEND_OUTPUTS};
+use bitcoin_cash::{Op, Opcode, Script};
+use std::fmt;
+
+pub const SLP_LOKAD_ID: &[u8] = b"SLP\0";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SlpViolationKind {
+    // opcodes, including OP_0..OP_16, can't stand in for pushes
+    NotAPush,
+    MissingPush,
+    UnexpectedPush,
+    WrongSize { size: usize, expected: &'static str },
+    InvalidValue { reason: &'static str },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlpViolation {
+    // index of the offending op in the script, OP_RETURN being 0
+    pub op_idx: usize,
+    pub field: &'static str,
+    pub kind: SlpViolationKind,
+}
+
+struct SlpLint<'a> {
+    ops: Vec<&'a Op>,
+    violations: Vec<SlpViolation>,
+}
+
+// checks an OP_RETURN script against the push rules of the SLP token type 1 and NFT1
+// specs; scripts of other token types are only checked up to the token type. Returns an
+// empty Vec for conforming scripts.
+pub fn lint_slp_script(script: &Script) -> Vec<SlpViolation> {
+    let mut lint = SlpLint {
+        ops: script.ops_arc().iter().map(|op| &op.op).collect(),
+        violations: Vec::new(),
+    };
+    lint.lint_script();
+    lint.violations
+}
+
+impl<'a> SlpLint<'a> {
+    fn lint_script(&mut self) {
+        if !matches!(self.ops.first(), Some(Op::Code(Opcode::OP_RETURN))) {
+            return self.invalid(0, "op_return", "script must start with OP_RETURN");
+        }
+        match self.push(1, "lokad_id", &[4], "4 bytes") {
+            Some(SLP_LOKAD_ID) => {}
+            Some(_) => {
+                return self.invalid(1, "lokad_id", "not the SLP lokad id");
+            }
+            None => return,
+        }
+        let token_type = match self.push(2, "token_type", &[1], "1 byte") {
+            Some(token_type) => SlpTokenType::from(token_type[0]),
+            None => return,
+        };
+        if !token_type.is_known() {
+            return;
+        }
+        let tx_type = match self.push(3, "transaction_type", &[], "") {
+            Some(tx_type) => tx_type,
+            None => return,
+        };
+        let num_pushes = match tx_type {
+            b"GENESIS" => self.lint_genesis(token_type),
+            b"MINT" if token_type == SlpTokenType::Nft1Child => {
+                return self.invalid(3, "transaction_type", "NFT1 children can't be minted");
+            }
+            b"MINT" => self.lint_mint(),
+            b"SEND" => self.lint_send(),
+            _ => {
+                return self.invalid(3, "transaction_type", "unknown transaction type");
+            }
+        };
+        for op_idx in num_pushes + 1..self.ops.len() {
+            self.violation(op_idx, "", SlpViolationKind::UnexpectedPush);
+        }
+    }
+
+    // these return the number of pushes of the message, excluding OP_RETURN
+    fn lint_genesis(&mut self, token_type: SlpTokenType) -> usize {
+        self.push(4, "token_ticker", &[], "");
+        self.push(5, "token_name", &[], "");
+        self.push(6, "token_document_url", &[], "");
+        self.push(7, "token_document_hash", &[0, 32], "0 or 32 bytes");
+        if let Some(decimals) = self.push(8, "decimals", &[1], "1 byte") {
+            if decimals[0] > 9 {
+                self.invalid(8, "decimals", "decimals must be at most 9");
+            } else if token_type == SlpTokenType::Nft1Child && decimals[0] != 0 {
+                self.invalid(8, "decimals", "NFT1 children must have 0 decimals");
+            }
+        }
+        if let Some(mint_baton_vout) = self.mint_baton_vout(9) {
+            if token_type == SlpTokenType::Nft1Child && !mint_baton_vout.is_empty() {
+                self.invalid(
+                    9,
+                    "mint_baton_vout",
+                    "NFT1 children can't have a mint baton",
+                );
+            }
+        }
+        if let Some(quantity) = self.push(10, "initial_token_mint_quantity", &[8], "8 bytes") {
+            if token_type == SlpTokenType::Nft1Child && quantity != 1u64.to_be_bytes() {
+                self.invalid(
+                    10,
+                    "initial_token_mint_quantity",
+                    "NFT1 children must have a quantity of 1",
+                );
+            }
+        }
+        10
+    }
+
+    fn lint_mint(&mut self) -> usize {
+        self.push(4, "token_id", &[32], "32 bytes");
+        self.mint_baton_vout(5);
+        self.push(6, "additional_token_quantity", &[8], "8 bytes");
+        6
+    }
+
+    fn lint_send(&mut self) -> usize {
+        self.push(4, "token_id", &[32], "32 bytes");
+        let num_amounts = self.ops.len().saturating_sub(5);
+        if num_amounts == 0 {
+            self.violation(5, "token_output_quantity", SlpViolationKind::MissingPush);
+        }
+        for op_idx in 5..5 + num_amounts.min(MAX_SLP_SEND_OUTPUTS) {
+            self.push(op_idx, "token_output_quantity", &[8], "8 bytes");
+        }
+        4 + num_amounts.min(MAX_SLP_SEND_OUTPUTS)
+    }
+
+    fn mint_baton_vout(&mut self, op_idx: usize) -> Option<&'a [u8]> {
+        let mint_baton_vout = self.push(op_idx, "mint_baton_vout", &[0, 1], "0 or 1 bytes")?;
+        if mint_baton_vout.first().is_some_and(|&vout| vout < 2) {
+            self.invalid(
+                op_idx,
+                "mint_baton_vout",
+                "mint baton vout must be at least 2",
+            );
+        }
+        Some(mint_baton_vout)
+    }
+
+    // the pushed bytes, if the op is a push of one of `sizes` (any size if empty)
+    fn push(
+        &mut self,
+        op_idx: usize,
+        field: &'static str,
+        sizes: &[usize],
+        expected: &'static str,
+    ) -> Option<&'a [u8]> {
+        let array = match self.ops.get(op_idx).copied() {
+            Some(Op::PushByteArray { array, .. }) => array,
+            Some(_) => {
+                self.violation(op_idx, field, SlpViolationKind::NotAPush);
+                return None;
+            }
+            None => {
+                self.violation(op_idx, field, SlpViolationKind::MissingPush);
+                return None;
+            }
+        };
+        if !sizes.is_empty() && !sizes.contains(&array.len()) {
+            let kind = SlpViolationKind::WrongSize {
+                size: array.len(),
+                expected,
+            };
+            self.violation(op_idx, field, kind);
+            return None;
+        }
+        Some(array)
+    }
+
+    fn invalid(&mut self, op_idx: usize, field: &'static str, reason: &'static str) {
+        self.violation(op_idx, field, SlpViolationKind::InvalidValue { reason })
+    }
+
+    fn violation(&mut self, op_idx: usize, field: &'static str, kind: SlpViolationKind) {
+        self.violations.push(SlpViolation {
+            op_idx,
+            field,
+            kind,
+        });
+    }
+}
+
+impl fmt::Display for SlpViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "op {}", self.op_idx)?;
+        if !self.field.is_empty() {
+            write!(f, " ({})", self.field)?;
+        }
+        match &self.kind {
+            SlpViolationKind::NotAPush => write!(f, ": must be a data push"),
+            SlpViolationKind::MissingPush => write!(f, ": push is missing"),
+            SlpViolationKind::UnexpectedPush => write!(f, ": unexpected extra push"),
+            SlpViolationKind::WrongSize { size, expected } => {
+                write!(f, ": pushes {} bytes, expected {}", size, expected)
+            }
+            SlpViolationKind::InvalidValue { reason } => write!(f, ": {}", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint_slp_script, SlpViolation, SlpViolationKind};
+    use crate::{slp_send_output, SlpTokenType, TokenId};
+    use bitcoin_cash::{ByteArray, Hashed, Op, Opcode, Script, Sha256d};
+
+    fn push(data: &[u8]) -> Op {
+        Op::PushByteArray {
+            array: ByteArray::from_slice_unnamed(data),
+            is_minimal: false,
+        }
+    }
+
+    #[test]
+    fn test_lint_slp_script() {
+        let token_id = TokenId::from_hash(Sha256d::digest(b"token".to_vec()));
+        let send = slp_send_output(SlpTokenType::Fungible, &token_id, &[1, 2]);
+        assert_eq!(lint_slp_script(&send.script), vec![]);
+
+        let mut ops = vec![
+            Op::Code(Opcode::OP_RETURN),
+            push(b"SLP\0"),
+            push(&[1]),
+            push(b"MINT"),
+            push(&token_id.to_vec()),
+            Op::Code(Opcode::OP_2),
+            push(&[0; 7]),
+        ];
+        let violations = lint_slp_script(&Script::from_ops(ops.clone()));
+        assert_eq!(
+            violations,
+            vec![
+                SlpViolation {
+                    op_idx: 5,
+                    field: "mint_baton_vout",
+                    kind: SlpViolationKind::NotAPush,
+                },
+                SlpViolation {
+                    op_idx: 6,
+                    field: "additional_token_quantity",
+                    kind: SlpViolationKind::WrongSize {
+                        size: 7,
+                        expected: "8 bytes",
+                    },
+                },
+            ],
+        );
+        assert_eq!(
+            violations[1].to_string(),
+            "op 6 (additional_token_quantity): pushes 7 bytes, expected 8 bytes",
+        );
+
+        // unknown token types are forward compatible and not checked any further
+        ops[2] = push(&[0x02]);
+        assert_eq!(lint_slp_script(&Script::from_ops(ops)), vec![]);
+
+        let amounts = vec![1; 20];
+        let send = slp_send_output(SlpTokenType::Fungible, &token_id, &amounts);
+        let violations = lint_slp_script(&send.script);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].op_idx, 24);
+        assert_eq!(violations[0].kind, SlpViolationKind::UnexpectedPush);
+    }
+}
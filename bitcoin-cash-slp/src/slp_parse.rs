@@ -0,0 +1,379 @@
+use crate::{
+    lint_slp_script, SlpAction, SlpData, SlpError, SlpResult, SlpToken, SlpTokenType, SlpTx,
+    SlpTxInput, SlpTxOutput, SlpTxType, TokenId, TokenMeta, SLP_LOKAD_ID,
+};
+use bitcoin_cash::{Hashed, Op, Opcode, Script, Sha256d, UnhashedTx};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SlpMessage {
+    Genesis {
+        meta: TokenMeta,
+        mint_baton_vout: Option<u8>,
+        initial_token_mint_quantity: u64,
+    },
+    Mint {
+        slp_token_type: SlpTokenType,
+        token_id: TokenId,
+        mint_baton_vout: Option<u8>,
+        additional_token_quantity: u64,
+    },
+    Send {
+        slp_token_type: SlpTokenType,
+        token_id: TokenId,
+        output_amounts: Vec<u64>,
+    },
+    // only the token type of messages of unknown token types is parsed
+    UnknownTokenType(u8),
+}
+
+// the tokens on the output an input spends
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SlpPrevOutput {
+    pub token: SlpToken,
+    pub token_id: TokenId,
+}
+
+// None for scripts that aren't SLP OP_RETURNs at all, an error for SLP OP_RETURNs that
+// violate the spec
+pub fn parse_slp_script(script: &Script) -> SlpResult<Option<SlpMessage>> {
+    let ops = script.ops_arc();
+    let is_slp = match (ops.first().map(|op| &op.op), ops.get(1).map(|op| &op.op)) {
+        (Some(Op::Code(Opcode::OP_RETURN)), Some(Op::PushByteArray { array, .. })) => {
+            &array[..] == SLP_LOKAD_ID
+        }
+        _ => false,
+    };
+    if !is_slp {
+        return Ok(None);
+    }
+    if let Some(violation) = lint_slp_script(script).into_iter().next() {
+        return Err(SlpError::InvalidScript { violation });
+    }
+    // the lint ensures all pushes are present and have the right sizes
+    let pushes = ops[1..]
+        .iter()
+        .map(|op| match &op.op {
+            Op::PushByteArray { array, .. } => &array[..],
+            _ => &[],
+        })
+        .collect::<Vec<_>>();
+    let slp_token_type = SlpTokenType::from(pushes[1][0]);
+    if !slp_token_type.is_known() {
+        return Ok(Some(SlpMessage::UnknownTokenType(pushes[1][0])));
+    }
+    let amount = |push: &[u8]| u64::from_be_bytes(push.try_into().expect("amounts are 8 bytes"));
+    let token_id = |push: &[u8]| -> SlpResult<TokenId> {
+        Ok(TokenId::from_hash(Sha256d::from_slice_le(push)?))
+    };
+    let text = |push: &[u8]| String::from_utf8_lossy(push).into_owned();
+    let message = match pushes[2] {
+        b"GENESIS" => SlpMessage::Genesis {
+            meta: TokenMeta {
+                ticker: text(pushes[3]),
+                name: text(pushes[4]),
+                document_url: text(pushes[5]),
+                document_hash: pushes[6].to_vec(),
+                decimals: pushes[7][0],
+                slp_token_type,
+            },
+            mint_baton_vout: pushes[8].first().copied(),
+            initial_token_mint_quantity: amount(pushes[9]),
+        },
+        b"MINT" => SlpMessage::Mint {
+            slp_token_type,
+            token_id: token_id(pushes[3])?,
+            mint_baton_vout: pushes[4].first().copied(),
+            additional_token_quantity: amount(pushes[5]),
+        },
+        _ => SlpMessage::Send {
+            slp_token_type,
+            token_id: token_id(pushes[3])?,
+            output_amounts: pushes[4..].iter().map(|&push| amount(push)).collect(),
+        },
+    };
+    Ok(Some(message))
+}
+
+impl SlpTx {
+    // assigns the tokens of `tx` to its outputs following the SLP rules; `prev_slp_outputs`
+    // has the tokens of the output spent by each input, None if it has none. Invalid SLP txs
+    // are treated like non-SLP txs, i.e. they burn the tokens of their inputs.
+    pub fn from_tx(
+        tx: UnhashedTx,
+        tx_hash: Sha256d,
+        prev_slp_outputs: &[Option<SlpPrevOutput>],
+    ) -> SlpResult<SlpTx> {
+        if prev_slp_outputs.len() != tx.inputs.len() {
+            return Err(SlpError::InvalidParams {
+                reason: "prev_slp_outputs must have one entry per input",
+            });
+        }
+        let parsed = match tx.outputs.first() {
+            Some(output) => parse_slp_script(&output.script),
+            None => Ok(None),
+        };
+        let num_outputs = tx.outputs.len();
+        let validated = match parsed {
+            Ok(Some(message)) => validate_slp_tx(message, &tx_hash, prev_slp_outputs, num_outputs),
+            Ok(None) => None,
+            Err(SlpError::InvalidScript { .. }) => {
+                Some((vec![token(SlpAction::SlpParseError); num_outputs], None))
+            }
+            Err(err) => return Err(err),
+        };
+        let (output_tokens, slp_data) = validated.unwrap_or_else(|| {
+            let spends_tokens = prev_slp_outputs
+                .iter()
+                .flatten()
+                .any(|prev| prev.token.amount > 0 || prev.token.is_mint_baton);
+            let action = if spends_tokens {
+                SlpAction::NonSlpBurn
+            } else {
+                SlpAction::NonSlp
+            };
+            (vec![token(action); num_outputs], None)
+        });
+        let inputs = tx
+            .inputs
+            .into_iter()
+            .zip(prev_slp_outputs)
+            .enumerate()
+            .map(|(index, (input, prev))| SlpTxInput {
+                index,
+                token: prev.as_ref().map(|prev| prev.token).unwrap_or_default(),
+                token_id: prev.as_ref().map(|prev| prev.token_id.clone()),
+                prev_script: input.lock_script.clone().unwrap_or_default(),
+                input,
+            })
+            .collect();
+        let outputs = tx
+            .outputs
+            .into_iter()
+            .zip(output_tokens)
+            .map(|(output, token)| SlpTxOutput { token, output })
+            .collect();
+        Ok(SlpTx {
+            version: tx.version,
+            inputs,
+            outputs,
+            lock_time: tx.lock_time,
+            slp_data,
+            tx_hash,
+        })
+    }
+}
+
+// the output tokens and SLP data of a valid SLP tx, None if it's invalid
+fn validate_slp_tx(
+    message: SlpMessage,
+    tx_hash: &Sha256d,
+    prev_slp_outputs: &[Option<SlpPrevOutput>],
+    num_outputs: usize,
+) -> Option<(Vec<SlpToken>, Option<SlpData>)> {
+    let mut token_inputs = prev_slp_outputs.iter().flatten();
+    let is_input_of = |prev: &SlpPrevOutput, token_type, token_id: &TokenId| {
+        prev.token.action.token_type() == Some(token_type) && &prev.token_id == token_id
+    };
+    let (slp_token_type, tx_type, token_id) = match &message {
+        SlpMessage::Genesis { meta, .. } => {
+            if meta.slp_token_type == SlpTokenType::Nft1Child {
+                // a child genesis has to spend group tokens in input 0
+                let group_input = prev_slp_outputs.first()?.as_ref()?;
+                if group_input.token.action.token_type() != Some(SlpTokenType::Nft1Group)
+                    || group_input.token.is_mint_baton
+                    || group_input.token.amount == 0
+                {
+                    return None;
+                }
+            }
+            let token_id = TokenId::from_hash(tx_hash.clone());
+            (meta.slp_token_type, SlpTxType::GENESIS, token_id)
+        }
+        SlpMessage::Mint {
+            slp_token_type,
+            token_id,
+            ..
+        } => {
+            if !token_inputs.any(|prev| {
+                prev.token.is_mint_baton && is_input_of(prev, *slp_token_type, token_id)
+            }) {
+                return None;
+            }
+            (*slp_token_type, SlpTxType::MINT, token_id.clone())
+        }
+        SlpMessage::Send {
+            slp_token_type,
+            token_id,
+            output_amounts,
+        } => {
+            let input_sum = token_inputs
+                .filter(|prev| !prev.token.is_mint_baton)
+                .filter(|prev| is_input_of(prev, *slp_token_type, token_id))
+                .map(|prev| prev.token.amount as u128)
+                .sum::<u128>();
+            let output_sum = output_amounts
+                .iter()
+                .map(|&amount| amount as u128)
+                .sum::<u128>();
+            if output_sum > input_sum {
+                return None;
+            }
+            (*slp_token_type, SlpTxType::SEND, token_id.clone())
+        }
+        SlpMessage::UnknownTokenType(token_type) => {
+            let action = SlpAction::SlpUnknownTokenType(*token_type);
+            return Some((vec![token(action); num_outputs], None));
+        }
+    };
+    let mut tokens = vec![token(slp_action(slp_token_type, tx_type)); num_outputs];
+    // tokens assigned to outputs the tx doesn't have are lost
+    let (mint_baton_vout, amounts) = match message {
+        SlpMessage::Genesis {
+            mint_baton_vout,
+            initial_token_mint_quantity,
+            ..
+        } => (mint_baton_vout, vec![initial_token_mint_quantity]),
+        SlpMessage::Mint {
+            mint_baton_vout,
+            additional_token_quantity,
+            ..
+        } => (mint_baton_vout, vec![additional_token_quantity]),
+        SlpMessage::Send { output_amounts, .. } => (None, output_amounts),
+        SlpMessage::UnknownTokenType(_) => unreachable!(),
+    };
+    for (token, amount) in tokens.iter_mut().skip(1).zip(amounts) {
+        token.amount = amount;
+    }
+    if let Some(token) = mint_baton_vout.and_then(|vout| tokens.get_mut(vout as usize)) {
+        token.is_mint_baton = true;
+    }
+    Some((tokens, Some(SlpData { token_id })))
+}
+
+fn slp_action(slp_token_type: SlpTokenType, tx_type: SlpTxType) -> SlpAction {
+    use SlpAction::*;
+    match (slp_token_type, tx_type) {
+        (SlpTokenType::Fungible, SlpTxType::GENESIS) => SlpV1Genesis,
+        (SlpTokenType::Fungible, SlpTxType::MINT) => SlpV1Mint,
+        (SlpTokenType::Fungible, _) => SlpV1Send,
+        (SlpTokenType::Nft1Group, SlpTxType::GENESIS) => SlpNft1GroupGenesis,
+        (SlpTokenType::Nft1Group, SlpTxType::MINT) => SlpNft1GroupMint,
+        (SlpTokenType::Nft1Group, _) => SlpNft1GroupSend,
+        (SlpTokenType::Nft1Child, SlpTxType::GENESIS) => SlpNft1UniqueChildGenesis,
+        (SlpTokenType::Nft1Child, _) => SlpNft1UniqueChildSend,
+        (SlpTokenType::Unknown(token_type), _) => SlpUnknownTokenType(token_type),
+    }
+}
+
+fn token(action: SlpAction) -> SlpToken {
+    SlpToken {
+        amount: 0,
+        is_mint_baton: false,
+        action,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_slp_script, SlpMessage, SlpPrevOutput};
+    use crate::{
+        detect_burns, slp_genesis_output, slp_send_output, SlpAction, SlpError, SlpGenesisParams,
+        SlpResult, SlpToken, SlpTokenType, SlpTx, TokenId,
+    };
+    use bitcoin_cash::{
+        Hashed, Op, Opcode, Script, Sha256d, TxInput, TxOutpoint, TxOutput, UnhashedTx,
+    };
+
+    fn tx(outputs: Vec<TxOutput>, num_inputs: usize) -> UnhashedTx {
+        let input = TxInput::new(TxOutpoint::default(), Script::default(), 0);
+        let mut outputs = outputs;
+        outputs.extend((0..3).map(|_| TxOutput {
+            value: 546,
+            script: Script::default(),
+            token: None,
+        }));
+        UnhashedTx {
+            version: 2,
+            inputs: vec![input; num_inputs],
+            outputs,
+            lock_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_genesis_then_send() -> SlpResult<()> {
+        let genesis = slp_genesis_output(SlpGenesisParams {
+            slp_token_type: SlpTokenType::Fungible,
+            token_ticker: "TOK",
+            token_name: "Token",
+            token_document_url: "",
+            token_document_hash: None,
+            decimals: 2,
+            mint_baton_vout: Some(2),
+            initial_token_mint_quantity: 1000,
+        });
+        match parse_slp_script(&genesis.script)? {
+            Some(SlpMessage::Genesis { meta, .. }) => assert_eq!(meta.ticker, "TOK"),
+            message => panic!("unexpected message {:?}", message),
+        }
+        let genesis_hash = Sha256d::digest(b"genesis".to_vec());
+        let genesis_tx = SlpTx::from_tx(tx(vec![genesis], 1), genesis_hash.clone(), &[None])?;
+        let token_id = TokenId::from_hash(genesis_hash);
+        assert_eq!(genesis_tx.slp_data.as_ref().unwrap().token_id, token_id);
+        assert_eq!(genesis_tx.outputs[1].token.amount, 1000);
+        assert!(genesis_tx.outputs[2].token.is_mint_baton);
+
+        let prev = Some(SlpPrevOutput {
+            token: genesis_tx.outputs[1].token,
+            token_id: token_id.clone(),
+        });
+        let send = slp_send_output(SlpTokenType::Fungible, &token_id, &[600, 300]);
+        let send_tx = SlpTx::from_tx(
+            tx(vec![send], 1),
+            Sha256d::default(),
+            std::slice::from_ref(&prev),
+        )?;
+        let amounts = send_tx.outputs.iter().map(|output| output.token.amount);
+        assert_eq!(amounts.collect::<Vec<_>>(), vec![0, 600, 300, 0]);
+        assert_eq!(send_tx.outputs[1].token.action, SlpAction::SlpV1Send);
+        assert_eq!(detect_burns(&send_tx).amount, 100);
+
+        // sending more than the inputs makes the tx invalid, burning all of the inputs
+        let send = slp_send_output(SlpTokenType::Fungible, &token_id, &[1001]);
+        let send_tx = SlpTx::from_tx(tx(vec![send], 1), Sha256d::default(), &[prev])?;
+        assert!(send_tx.slp_data.is_none());
+        assert_eq!(send_tx.outputs[1].token.action, SlpAction::NonSlpBurn);
+        assert_eq!(detect_burns(&send_tx).amount, 1000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_slp_script() -> SlpResult<()> {
+        let script = Script::from_ops(vec![
+            Op::Code(Opcode::OP_RETURN),
+            Op::from_array(b"SLP\0".to_vec()),
+            Op::Code(Opcode::OP_1),
+        ]);
+        assert!(matches!(
+            parse_slp_script(&script),
+            Err(SlpError::InvalidScript { .. })
+        ));
+        let output = TxOutput {
+            value: 0,
+            script,
+            token: None,
+        };
+        let slp_tx = SlpTx::from_tx(tx(vec![output], 0), Sha256d::default(), &[])?;
+        assert_eq!(
+            slp_tx.outputs[1].token,
+            SlpToken {
+                action: SlpAction::SlpParseError,
+                ..Default::default()
+            }
+        );
+        assert!(parse_slp_script(&Script::default())?.is_none());
+        Ok(())
+    }
+}
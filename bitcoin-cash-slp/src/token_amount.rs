@@ -0,0 +1,120 @@
+use crate::{SlpError, SlpResult, TokenMeta};
+use std::fmt;
+
+// an amount of token base units, displayed with the token's decimals, e.g. 150000000 base
+// units with 8 decimals display as "1.5"
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TokenAmount {
+    pub base_units: u64,
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+    pub fn new(base_units: u64, decimals: u8) -> Self {
+        TokenAmount {
+            base_units,
+            decimals,
+        }
+    }
+
+    // parses a plain decimal like "1.5"; rejects signs, exponents, more fractional digits
+    // than `decimals` (except trailing zeros) and amounts that don't fit into a u64
+    pub fn parse(amount: &str, decimals: u8) -> SlpResult<Self> {
+        let invalid = |reason| SlpError::InvalidAmount {
+            amount: amount.to_string(),
+            reason,
+        };
+        let (integer, fraction) = match amount.find('.') {
+            Some(idx) => (&amount[..idx], &amount[idx + 1..]),
+            None => (amount, ""),
+        };
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(invalid("no digits"));
+        }
+        if !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid("only digits and a single '.' are allowed"));
+        }
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > decimals as usize {
+            return Err(invalid("more fractional digits than the token's decimals"));
+        }
+        // concatenating the digits avoids multiplying by 10^decimals, which can overflow
+        let mut digits = String::with_capacity(integer.len() + decimals as usize);
+        digits.push_str(integer.trim_start_matches('0'));
+        digits.push_str(fraction);
+        digits.extend(std::iter::repeat('0').take(decimals as usize - fraction.len()));
+        let digits = digits.trim_start_matches('0');
+        let base_units = if digits.is_empty() {
+            0
+        } else {
+            digits
+                .parse()
+                .map_err(|_| invalid("amount doesn't fit into 64 bits"))?
+        };
+        Ok(TokenAmount::new(base_units, decimals))
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decimals = self.decimals as usize;
+        let digits = format!("{:0>width$}", self.base_units, width = decimals + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - decimals);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            write!(f, "{}", integer)
+        } else {
+            write!(f, "{}.{}", integer, fraction)
+        }
+    }
+}
+
+impl TokenMeta {
+    pub fn format_amount(&self, base_units: u64) -> String {
+        TokenAmount::new(base_units, self.decimals).to_string()
+    }
+
+    pub fn parse_amount(&self, amount: &str) -> SlpResult<u64> {
+        Ok(TokenAmount::parse(amount, self.decimals)?.base_units)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenAmount;
+    use crate::SlpResult;
+
+    #[test]
+    fn test_token_amount() -> SlpResult<()> {
+        assert_eq!(TokenAmount::new(150_000_000, 8).to_string(), "1.5");
+        assert_eq!(TokenAmount::new(5, 8).to_string(), "0.00000005");
+        assert_eq!(TokenAmount::new(1200, 2).to_string(), "12");
+        assert_eq!(
+            TokenAmount::new(u64::MAX, 0).to_string(),
+            u64::MAX.to_string()
+        );
+        assert_eq!(
+            TokenAmount::new(7, 25).to_string(),
+            "0.0000000000000000000000007"
+        );
+
+        assert_eq!(TokenAmount::parse("1.5", 8)?.base_units, 150_000_000);
+        assert_eq!(TokenAmount::parse(".5", 1)?.base_units, 5);
+        assert_eq!(TokenAmount::parse("007.10", 1)?.base_units, 71);
+        assert_eq!(TokenAmount::parse("0", 9)?.base_units, 0);
+        assert_eq!(
+            TokenAmount::parse("18446744073709551615", 0)?.base_units,
+            u64::MAX
+        );
+        for &invalid in &["", ".", "-1", "1e5", "1.2.3", " 1", "1.05"] {
+            assert!(TokenAmount::parse(invalid, 1).is_err(), "{:?}", invalid);
+        }
+        assert!(TokenAmount::parse("18446744073709551616", 0).is_err());
+        assert!(TokenAmount::parse("184467440737.09551616", 8).is_err());
+        Ok(())
+    }
+}
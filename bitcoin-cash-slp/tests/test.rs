@@ -0,0 +1,142 @@
+// runs vectors in the format of github.com/simpleledger/slp-unit-test-data, kept in tests/data
+use bitcoin_cash::{ByteArray, Hex, Script, Sha256d, UnhashedTx};
+use bitcoin_cash_slp::{
+    parse_slp_script, SlpError, SlpMessage, SlpPrevOutput, SlpResult, SlpTx, SlpViolationKind,
+    MAX_SLP_SEND_OUTPUTS,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const SCRIPT_TESTS: &str = include_str!("data/script_tests.json");
+const TX_INPUT_TESTS: &str = include_str!("data/tx_input_tests.json");
+
+// vectors this crate is known to get wrong, by msg/description
+const KNOWN_SCRIPT_FAILURES: &[&str] = &[];
+const KNOWN_TX_INPUT_FAILURES: &[&str] = &[];
+
+// an entry of script_tests.json; `code` is None for valid SLP messages
+#[derive(Deserialize)]
+struct ScriptTestVector {
+    msg: String,
+    script: String,
+    code: Option<u32>,
+}
+
+// an entry of tx_input_tests.json: `when` sets up the validity of the parent txs, `should`
+// has the txs whose validity is tested
+#[derive(Deserialize)]
+struct TxInputTestVector {
+    description: String,
+    when: Vec<TestTx>,
+    should: Vec<TestTx>,
+}
+
+#[derive(Deserialize)]
+struct TestTx {
+    tx: Hex<UnhashedTx>,
+    valid: bool,
+}
+
+#[test]
+fn test_script_vectors() -> SlpResult<()> {
+    let vectors: Vec<ScriptTestVector> = serde_json::from_str(SCRIPT_TESTS).unwrap();
+    let mut failures = Vec::new();
+    for vector in &vectors {
+        let script = hex::decode(&vector.script).unwrap();
+        if script_error_code(&script)? != vector.code {
+            failures.push(vector.msg.as_str());
+        }
+    }
+    assert_eq!(failures, KNOWN_SCRIPT_FAILURES);
+    Ok(())
+}
+
+// maps the outcome of parsing the script to the error codes of slp-unit-test-data
+fn script_error_code(script: &[u8]) -> SlpResult<Option<u32>> {
+    let script = match Script::deser_ops(ByteArray::from_slice_unnamed(script)) {
+        Ok(script) => script,
+        // scripts that aren't even valid Bitcoin scripts
+        Err(_) => return Ok(Some(1)),
+    };
+    let violation = match parse_slp_script(&script) {
+        Ok(Some(SlpMessage::UnknownTokenType(_))) => return Ok(Some(255)),
+        Ok(Some(_)) => return Ok(None),
+        // not an OP_RETURN or not the SLP lokad id
+        Ok(None) => return Ok(Some(3)),
+        Err(SlpError::InvalidScript { violation }) => violation,
+        Err(err) => return Err(err),
+    };
+    let code = match violation.kind {
+        _ if matches!(violation.field, "op_return" | "lokad_id") => 3,
+        SlpViolationKind::NotAPush => 2,
+        SlpViolationKind::WrongSize { .. } => 10,
+        SlpViolationKind::InvalidValue { .. } => 11,
+        SlpViolationKind::MissingPush => 12,
+        // only SEND can have pushes past OP_RETURN, lokad id, token type, tx type, token id
+        // and its maximum number of amounts
+        SlpViolationKind::UnexpectedPush if violation.op_idx >= 5 + MAX_SLP_SEND_OUTPUTS => 21,
+        SlpViolationKind::UnexpectedPush => 12,
+    };
+    Ok(Some(code))
+}
+
+#[test]
+fn test_tx_input_vectors() -> SlpResult<()> {
+    let vectors: Vec<TxInputTestVector> = serde_json::from_str(TX_INPUT_TESTS).unwrap();
+    let mut failures = Vec::new();
+    for vector in &vectors {
+        let mut prev_outputs = HashMap::new();
+        for test_tx in &vector.when {
+            // the vector decides about the validity of the parents, invalid ones have no tokens
+            let slp_tx = slp_tx_from_test_tx(test_tx, &prev_outputs)?;
+            if test_tx.valid {
+                insert_slp_outputs(&mut prev_outputs, &slp_tx);
+            }
+        }
+        for test_tx in &vector.should {
+            let slp_tx = slp_tx_from_test_tx(test_tx, &prev_outputs)?;
+            if slp_tx.slp_data.is_some() != test_tx.valid {
+                failures.push(vector.description.as_str());
+                break;
+            }
+        }
+    }
+    assert_eq!(failures, KNOWN_TX_INPUT_FAILURES);
+    Ok(())
+}
+
+fn slp_tx_from_test_tx(
+    test_tx: &TestTx,
+    prev_outputs: &HashMap<(Sha256d, u32), SlpPrevOutput>,
+) -> SlpResult<SlpTx> {
+    let tx: UnhashedTx = test_tx.tx.0.clone();
+    let tx_hash = tx.txid();
+    let prev_slp_outputs = tx
+        .inputs
+        .iter()
+        .map(|input| {
+            let prev_out = &input.prev_out;
+            prev_outputs
+                .get(&(prev_out.tx_hash.clone(), prev_out.vout))
+                .cloned()
+        })
+        .collect::<Vec<_>>();
+    SlpTx::from_tx(tx, tx_hash, &prev_slp_outputs)
+}
+
+fn insert_slp_outputs(prev_outputs: &mut HashMap<(Sha256d, u32), SlpPrevOutput>, slp_tx: &SlpTx) {
+    let slp_data = match &slp_tx.slp_data {
+        Some(slp_data) => slp_data,
+        None => return,
+    };
+    for (vout, output) in slp_tx.outputs.iter().enumerate() {
+        if output.token.amount == 0 && !output.token.is_mint_baton {
+            continue;
+        }
+        let prev_output = SlpPrevOutput {
+            token: output.token,
+            token_id: slp_data.token_id.clone(),
+        };
+        prev_outputs.insert((slp_tx.tx_hash.clone(), vout as u32), prev_output);
+    }
+}
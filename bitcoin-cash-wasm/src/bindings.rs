@@ -0,0 +1,40 @@
+use crate::{WasmOutput, WasmUtxo};
+use bitcoin_cash::{error::Error, BitcoinCode, ByteArray, UnhashedTx};
+use wasm_bindgen::prelude::*;
+
+fn js_error(err: Error) -> JsValue {
+    JsValue::from_str(&err.to_string())
+}
+
+#[wasm_bindgen(js_name = addressFromSecretKey)]
+pub fn address_from_secret_key(secret_key: &[u8]) -> Result<String, JsValue> {
+    crate::address_from_secret_key(secret_key).map_err(js_error)
+}
+
+// `utxos_json` and `outputs_json` are JSON arrays of `WasmUtxo` and `WasmOutput`;
+// returns the signed tx as hex
+#[wasm_bindgen(js_name = buildP2pkhTx)]
+pub fn build_p2pkh_tx(
+    secret_key: &[u8],
+    utxos_json: &str,
+    outputs_json: &str,
+    change_address: &str,
+    fee_per_kb: u64,
+) -> Result<String, JsValue> {
+    let build = || -> bitcoin_cash::error::Result<String> {
+        let utxos: Vec<WasmUtxo> = serde_json::from_str(utxos_json)?;
+        let outputs: Vec<WasmOutput> = serde_json::from_str(outputs_json)?;
+        let tx = crate::build_p2pkh_tx(secret_key, &utxos, &outputs, change_address, fee_per_kb)?;
+        Ok(tx.ser().hex())
+    };
+    build().map_err(js_error)
+}
+
+#[wasm_bindgen(js_name = txToJson)]
+pub fn tx_to_json(tx_hex: &str) -> Result<String, JsValue> {
+    let parse = || -> bitcoin_cash::error::Result<String> {
+        let tx = UnhashedTx::deser(ByteArray::from_slice_unnamed(&hex::decode(tx_hex)?))?;
+        Ok(serde_json::to_string(&tx)?)
+    };
+    parse().map_err(js_error)
+}
@@ -0,0 +1,101 @@
+use bitcoin_cash::{
+    error::Result, Address, Hashed, KeyPair, P2pkhSignatory, Prefix, SecretKey, Sha256d,
+    SigHashFlags, TxBuilder, TxOutpoint, UnhashedTx, UnsignedTxInput, DEFAULT_SEQUENCE,
+};
+use bitcoin_cash_ecc::RustECC;
+use serde::{Deserialize, Serialize};
+
+mod bindings;
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmUtxo {
+    // in display (little endian) hex, as shown by block explorers
+    pub tx_hash: String,
+    pub vout: u32,
+    pub value: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmOutput {
+    pub address: String,
+    pub value: u64,
+}
+
+pub fn address_from_secret_key(secret_key: &[u8]) -> Result<String> {
+    let keypair = KeyPair::from_secret_key(&RustECC, SecretKey::from_slice(secret_key)?)?;
+    Ok(Address::from_pk(Prefix::default(), keypair.pubkey())
+        .cash_addr()
+        .to_string())
+}
+
+// spends P2PKH utxos of `secret_key` to `outputs`, sending the rest to `change_address`
+pub fn build_p2pkh_tx(
+    secret_key: &[u8],
+    utxos: &[WasmUtxo],
+    outputs: &[WasmOutput],
+    change_address: &str,
+    fee_per_kb: u64,
+) -> Result<UnhashedTx> {
+    let ecc = RustECC;
+    let keypair = KeyPair::from_secret_key(&ecc, SecretKey::from_slice(secret_key)?)?;
+    let signatory = P2pkhSignatory::new(keypair, SigHashFlags::DEFAULT);
+    let mut builder = TxBuilder::new_simple();
+    builder.set_fee_per_kb(fee_per_kb);
+    let mut input_refs = Vec::with_capacity(utxos.len());
+    for utxo in utxos {
+        let input = UnsignedTxInput {
+            prev_out: TxOutpoint {
+                tx_hash: Sha256d::from_hex_le(&utxo.tx_hash)?,
+                vout: utxo.vout,
+            },
+            sequence: DEFAULT_SEQUENCE,
+            value: utxo.value,
+            token: None,
+        };
+        input_refs.push(builder.add_input(input, signatory.lock_script(), signatory.clone()));
+    }
+    for output in outputs {
+        builder.add_address_output(
+            &Address::from_cash_addr(output.address.as_str())?,
+            output.value,
+            None,
+        )?;
+    }
+    builder.add_leftover_output(Address::from_cash_addr(change_address)?.into());
+    let mut unsigned_tx = builder.build()?;
+    for input_ref in input_refs {
+        signatory.sign_input(&ecc, &mut unsigned_tx, input_ref)?;
+    }
+    unsigned_tx.complete_tx()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{address_from_secret_key, build_p2pkh_tx, WasmOutput, WasmUtxo};
+    use bitcoin_cash::{error::Result, BitcoinCode, UnhashedTx};
+
+    #[test]
+    fn test_build_p2pkh_tx() -> Result<()> {
+        let secret_key = [1; 32];
+        let address = address_from_secret_key(&secret_key)?;
+        let utxos = vec![WasmUtxo {
+            tx_hash: "11".repeat(32),
+            vout: 1,
+            value: 10_000,
+        }];
+        let outputs = vec![WasmOutput {
+            address: address.clone(),
+            value: 5_000,
+        }];
+        let tx = build_p2pkh_tx(&secret_key, &utxos, &outputs, &address, 1000)?;
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.inputs[0].prev_out.vout, 1);
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[0].value, 5_000);
+        assert!(tx.outputs[1].value < 5_000);
+        assert_eq!(UnhashedTx::deser(tx.ser())?.ser(), tx.ser());
+        Ok(())
+    }
+}
@@ -20,6 +20,9 @@ pub struct Address<'a> {
 pub enum AddressType {
     P2PKH = 0,
     P2SH = 8,
+    // signal that the receiving wallet supports CashTokens
+    P2PKHWithTokens = 16,
+    P2SHWithTokens = 24,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
@@ -44,6 +47,16 @@ pub enum CashAddrError {
     InvalidAddressType(u8),
 }
 
+impl AddressType {
+    pub fn is_p2sh(self) -> bool {
+        matches!(self, AddressType::P2SH | AddressType::P2SHWithTokens)
+    }
+
+    pub fn is_token_aware(self) -> bool {
+        matches!(self, AddressType::P2PKHWithTokens | AddressType::P2SHWithTokens)
+    }
+}
+
 impl Prefix {
     pub fn prefix_str(self) -> &'static str {
         match self {
@@ -178,6 +191,10 @@ impl<'a> Address<'a> {
         self.addr_type
     }
 
+    pub fn is_token_aware(&self) -> bool {
+        self.addr_type.is_token_aware()
+    }
+
     pub fn with_prefix<P: Into<AddressPrefix<'a>>>(&'a self, prefix: P) -> Address<'a> {
         Self::from_hash(prefix, self.addr_type, self.hash.clone())
     }
@@ -390,6 +407,8 @@ fn _from_cash_addr<'a>(
         match converted[0] {
             0 => AddressType::P2PKH,
             8 => AddressType::P2SH,
+            16 => AddressType::P2PKHWithTokens,
+            24 => AddressType::P2SHWithTokens,
             x => return Err(CashAddrError::InvalidAddressType(x)),
         },
         prefix,
@@ -527,4 +546,23 @@ mod tests {
         assert_eq!(new_addr.prefix_str(), "prelude");
         Ok(())
     }
+
+    #[test]
+    fn test_token_aware() -> Result<()> {
+        let addr =
+            Address::from_cash_addr("bitcoincash:qr7fzmep8g7h7ymfxy74lgc0v950j3r2959lhtxxsl")?;
+        let token_addr = Address::from_hash(
+            Prefix::BitcoinCash,
+            AddressType::P2PKHWithTokens,
+            addr.hash().clone(),
+        );
+        assert_eq!(
+            token_addr.cash_addr(),
+            "bitcoincash:zr7fzmep8g7h7ymfxy74lgc0v950j3r295z4y4gq0v"
+        );
+        let parsed = Address::from_cash_addr(token_addr.cash_addr())?;
+        assert_eq!(parsed.addr_type(), AddressType::P2PKHWithTokens);
+        assert!(parsed.is_token_aware() && !addr.is_token_aware());
+        Ok(())
+    }
 }
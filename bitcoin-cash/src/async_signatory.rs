@@ -0,0 +1,181 @@
+use crate::error::{Error, Result};
+use crate::{InputReference, Script, Signatory, SignatoryKind, TxOutput, TxPreimage, UnsignedTx};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub type SignFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+pub trait AsyncSigner {
+    type Signatures;
+
+    fn sign_preimages<'a>(
+        &'a self,
+        input_idx: usize,
+        preimages: &'a [TxPreimage],
+    ) -> SignFuture<'a, Self::Signatures>;
+}
+
+pub struct AsyncSignatory<S, D> {
+    signatory: S,
+    device: Arc<D>,
+}
+
+impl<S, D> AsyncSignatory<S, D>
+where
+    S: Signatory,
+    D: AsyncSigner<Signatures = S::Signatures>,
+{
+    pub fn new(signatory: S, device: Arc<D>) -> Self {
+        AsyncSignatory { signatory, device }
+    }
+
+    pub fn signatory(&self) -> &S {
+        &self.signatory
+    }
+
+    pub fn device(&self) -> &Arc<D> {
+        &self.device
+    }
+
+    pub async fn sign(
+        &self,
+        unsigned_tx: &mut UnsignedTx<'_>,
+        input_ref: InputReference<Self>,
+    ) -> Result<()> {
+        sign_input_async(unsigned_tx, input_ref, self.device.as_ref()).await
+    }
+}
+
+pub async fn sign_input_async<S, D>(
+    unsigned_tx: &mut UnsignedTx<'_>,
+    input_ref: InputReference<S>,
+    device: &D,
+) -> Result<()>
+where
+    S: Signatory,
+    D: AsyncSigner<Signatures = S::Signatures> + ?Sized,
+{
+    let input_idx = input_ref.input_idx();
+    let preimages = unsigned_tx
+        .preimages()
+        .get(input_idx)
+        .ok_or(Error::InputIndexOutOfBounds {
+            input_idx,
+            num_inputs: unsigned_tx.preimages().len(),
+        })?
+        .clone();
+    let sigs = device.sign_preimages(input_idx, &preimages).await?;
+    unsigned_tx.sign_input_dyn(input_idx, Box::new(sigs))
+}
+
+impl<S: Clone, D> Clone for AsyncSignatory<S, D> {
+    fn clone(&self) -> Self {
+        AsyncSignatory {
+            signatory: self.signatory.clone(),
+            device: Arc::clone(&self.device),
+        }
+    }
+}
+
+impl<S, D> Signatory for AsyncSignatory<S, D>
+where
+    S: Signatory,
+{
+    type Script = S::Script;
+    type Signatures = S::Signatures;
+    type Kind = S::Kind;
+
+    fn sig_hash_flags(&self) -> <Self::Kind as SignatoryKind>::SigHashFlags {
+        self.signatory.sig_hash_flags()
+    }
+
+    fn placeholder_signatures(&self) -> Self::Signatures {
+        self.signatory.placeholder_signatures()
+    }
+
+    fn build_script(
+        &self,
+        tx_preimages: &<Self::Kind as SignatoryKind>::TxPreimages,
+        estimated_size: Option<usize>,
+        sigs: Self::Signatures,
+        lock_script: &Script,
+        tx_outputs: &[TxOutput],
+    ) -> Self::Script {
+        self.signatory
+            .build_script(tx_preimages, estimated_size, sigs, lock_script, tx_outputs)
+    }
+
+    fn is_p2sh(&self) -> bool {
+        self.signatory.is_p2sh()
+    }
+
+    fn estimated_unlock_size(&self) -> Option<usize> {
+        self.signatory.estimated_unlock_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign_input_async, AsyncSignatory, AsyncSigner, SignFuture};
+    use crate::error::{Error, Result};
+    use crate::{
+        Address, ByteArray, InputReference, P2PKHSignatory, Prefix, Pubkey, SigHashFlags,
+        TxBuilder, TxOutpoint, TxPreimage, UnsignedTxInput,
+    };
+    use std::sync::Arc;
+
+    struct TestDevice;
+
+    impl AsyncSigner for TestDevice {
+        type Signatures = ByteArray;
+
+        fn sign_preimages<'a>(
+            &'a self,
+            _input_idx: usize,
+            _preimages: &'a [TxPreimage],
+        ) -> SignFuture<'a, ByteArray> {
+            Box::pin(async { Ok(ByteArray::from_slice_unnamed(&[1; 71])) })
+        }
+    }
+
+    #[test]
+    fn test_sign_input_async() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let signatory = AsyncSignatory::new(
+            P2PKHSignatory {
+                pubkey,
+                sig_hash_flags: SigHashFlags::DEFAULT,
+            },
+            Arc::new(TestDevice),
+        );
+        let mut builder = TxBuilder::new_simple();
+        let input_ref = builder.add_input(
+            UnsignedTxInput {
+                prev_out: TxOutpoint::default(),
+                sequence: 0xffff_ffff,
+                value: 10_000,
+                token: None,
+            },
+            address.p2pkh_script()?,
+            signatory.clone(),
+        );
+        builder.add_leftover_output(address.into());
+        let mut unsigned_tx = builder.build()?;
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        runtime.block_on(async {
+            let unknown_ref = InputReference::<P2PKHSignatory>::new(1);
+            assert!(matches!(
+                sign_input_async(&mut unsigned_tx, unknown_ref, &TestDevice).await,
+                Err(Error::InputIndexOutOfBounds {
+                    input_idx: 1,
+                    num_inputs: 1,
+                })
+            ));
+            signatory.sign(&mut unsigned_tx, input_ref).await
+        })?;
+        assert!(unsigned_tx.is_fully_signed());
+        Ok(())
+    }
+}
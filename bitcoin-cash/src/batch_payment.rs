@@ -0,0 +1,304 @@
+use crate::error::{Error, Result};
+use crate::{
+    Address, ConsolidationInput, FeeRate, InputReference, Signatory, TaggedScript, TxBuilder,
+    TxOutput, UnhashedTx, UnsignedTx, MAX_STANDARD_TX_SIZE,
+};
+use std::collections::VecDeque;
+
+pub struct BatchPaymentTx<'b, S> {
+    pub unsigned_tx: UnsignedTx<'b>,
+    pub input_refs: Vec<InputReference<S>>,
+    pub num_payments: usize,
+}
+
+// pays out many outputs over as many standard-size txs as needed; every tx after the
+// first spends the change of the previous one, so txs have to be signed in order
+pub struct BatchPayment<S: Signatory> {
+    payments: VecDeque<TxOutput>,
+    utxos: VecDeque<ConsolidationInput<S>>,
+    change_lock_script: TaggedScript<S::Script>,
+    change_signatory: S,
+    fee_rate: FeeRate,
+    max_tx_size: usize,
+    change_vout: Option<u32>,
+}
+
+impl<S> BatchPayment<S>
+where
+    S: Signatory + Clone + Sync + Send,
+{
+    pub fn new<'a>(
+        payments: impl IntoIterator<Item = (Address<'a>, u64)>,
+        utxos: Vec<ConsolidationInput<S>>,
+        change_lock_script: TaggedScript<S::Script>,
+        change_signatory: S,
+        fee_rate: FeeRate,
+    ) -> Self {
+        BatchPayment {
+            payments: payments
+                .into_iter()
+                .map(|(address, value)| TxOutput {
+                    value,
+                    script: address.into(),
+                    token: None,
+                })
+                .collect(),
+            utxos: utxos.into(),
+            change_lock_script,
+            change_signatory,
+            fee_rate,
+            max_tx_size: MAX_STANDARD_TX_SIZE,
+            change_vout: None,
+        }
+    }
+
+    pub fn set_max_tx_size(&mut self, max_tx_size: usize) {
+        self.max_tx_size = max_tx_size;
+    }
+
+    pub fn remaining_payments(&self) -> usize {
+        self.payments.len()
+    }
+
+    // prev_tx is the completed previous tx, whose change this tx spends
+    pub fn next_tx<'b>(
+        &mut self,
+        prev_tx: Option<&UnhashedTx>,
+    ) -> Result<Option<BatchPaymentTx<'b, S>>>
+    where
+        S: 'b,
+    {
+        if self.payments.is_empty() {
+            return Ok(None);
+        }
+        let mut tx_builder = TxBuilder::new_simple();
+        tx_builder.set_fee_rate(self.fee_rate);
+        tx_builder.add_change_output(self.change_lock_script.clone())?;
+        let mut input_refs = Vec::new();
+        if let Some(vout) = self.change_vout {
+            let prev_tx = prev_tx.ok_or(Error::InvalidBatchPayment {
+                reason: "the previous tx is required to spend its change",
+            })?;
+            input_refs.push(tx_builder.add_parent_output_input(
+                prev_tx,
+                vout,
+                self.change_lock_script.clone(),
+                self.change_signatory.clone(),
+            )?);
+        }
+        let mut payments = Vec::new();
+        let mut utxos = Vec::new();
+        let result = self
+            .fill_tx(&mut tx_builder, &mut input_refs, &mut payments, &mut utxos)
+            .and_then(|()| tx_builder.build());
+        let unsigned_tx = match result {
+            Ok(unsigned_tx) => unsigned_tx,
+            Err(err) => {
+                // put back what this tx took, so the batch can be retried
+                for payment in payments.into_iter().rev() {
+                    self.payments.push_front(payment);
+                }
+                for utxo in utxos.into_iter().rev() {
+                    self.utxos.push_front(utxo);
+                }
+                return Err(err);
+            }
+        };
+        self.change_vout = unsigned_tx.change_output_idx().map(|idx| idx as u32);
+        Ok(Some(BatchPaymentTx {
+            unsigned_tx,
+            input_refs,
+            num_payments: payments.len(),
+        }))
+    }
+
+    // moves payments and the utxos funding them into the tx until it's full
+    fn fill_tx<'b>(
+        &mut self,
+        tx_builder: &mut TxBuilder<'b>,
+        input_refs: &mut Vec<InputReference<S>>,
+        payments: &mut Vec<TxOutput>,
+        utxos: &mut Vec<ConsolidationInput<S>>,
+    ) -> Result<()>
+    where
+        S: 'b,
+    {
+        while let Some(payment) = self.payments.pop_front() {
+            tx_builder.add_output(payment.clone());
+            match self.fund(tx_builder, input_refs, utxos) {
+                Ok(true) => payments.push(payment),
+                Ok(false) => {
+                    self.payments.push_front(payment);
+                    let num_outputs = tx_builder.outputs().len();
+                    tx_builder.remove_output(num_outputs - 1)?;
+                    break;
+                }
+                Err(err) => {
+                    self.payments.push_front(payment);
+                    return Err(err);
+                }
+            }
+        }
+        if payments.is_empty() {
+            return Err(Error::TxTooLarge {
+                size: tx_builder.estimated_size(),
+                max_size: self.max_tx_size,
+            });
+        }
+        Ok(())
+    }
+
+    // adds utxos until the outputs are covered; false if the tx would become too large
+    fn fund<'b>(
+        &mut self,
+        tx_builder: &mut TxBuilder<'b>,
+        input_refs: &mut Vec<InputReference<S>>,
+        utxos: &mut Vec<ConsolidationInput<S>>,
+    ) -> Result<bool>
+    where
+        S: 'b,
+    {
+        loop {
+            let size = tx_builder.estimated_size();
+            if size > self.max_tx_size {
+                return Ok(false);
+            }
+            let required = tx_builder.known_output_sum() + tx_builder.required_fee(size);
+            let input_sum = tx_builder.input_sum();
+            if input_sum >= required {
+                return Ok(true);
+            }
+            let utxo = self
+                .utxos
+                .pop_front()
+                .ok_or(Error::InsufficientInputAmount {
+                    amount: required - input_sum,
+                })?;
+            let input_ref = tx_builder.add_input(
+                utxo.input.clone(),
+                utxo.lock_script.clone(),
+                utxo.signatory.clone(),
+            );
+            if tx_builder.estimated_size() > self.max_tx_size {
+                tx_builder.remove_input(input_ref.input_idx())?;
+                self.utxos.push_front(utxo);
+                return Ok(false);
+            }
+            input_refs.push(input_ref);
+            utxos.push(utxo);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BatchPayment;
+    use crate::error::{Error, Result};
+    use crate::{
+        Address, ConsolidationInput, P2PKHSignatory, Prefix, Pubkey, SigHashFlags, TxOutpoint,
+        UnsignedTxInput, DEFAULT_FEE_RATE,
+    };
+
+    #[test]
+    fn test_batch_payment() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let signatory = P2PKHSignatory {
+            pubkey,
+            sig_hash_flags: SigHashFlags::DEFAULT,
+        };
+        let utxos = (0..3)
+            .map(|vout| {
+                Ok(ConsolidationInput {
+                    input: UnsignedTxInput {
+                        prev_out: TxOutpoint {
+                            vout,
+                            ..Default::default()
+                        },
+                        sequence: 0xffff_ffff,
+                        value: 50_000,
+                        token: None,
+                    },
+                    lock_script: address.p2pkh_script()?,
+                    signatory: signatory.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let payments = (0..10)
+            .map(|_| (address.clone(), 10_000))
+            .collect::<Vec<_>>();
+        let mut batch = BatchPayment::new(
+            payments,
+            utxos,
+            address.p2pkh_script()?,
+            signatory,
+            DEFAULT_FEE_RATE,
+        );
+        // room for two inputs, four payments and the change output
+        batch.set_max_tx_size(10 + 2 * 149 + 5 * 34);
+
+        let first = batch.next_tx(None)?.unwrap();
+        assert_eq!(first.num_payments, 4);
+        assert_eq!(first.input_refs.len(), 1);
+        assert!(first.unsigned_tx.has_change());
+        assert!(matches!(
+            batch.next_tx(None),
+            Err(Error::InvalidBatchPayment { .. })
+        ));
+        let first_tx = first.unsigned_tx.partial_tx();
+        let second = batch.next_tx(Some(&first_tx))?.unwrap();
+        assert_eq!(second.num_payments, 4);
+        let second_tx = second.unsigned_tx.partial_tx();
+        assert_eq!(second_tx.inputs[0].prev_out.tx_hash, first_tx.txid());
+        let third = batch.next_tx(Some(&second_tx))?.unwrap();
+        assert_eq!(third.num_payments, 2);
+        assert_eq!(batch.remaining_payments(), 0);
+        assert!(batch
+            .next_tx(Some(&third.unsigned_tx.partial_tx()))?
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_payment_insufficient_funds() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let signatory = P2PKHSignatory {
+            pubkey,
+            sig_hash_flags: SigHashFlags::DEFAULT,
+        };
+        let utxo = ConsolidationInput {
+            input: UnsignedTxInput {
+                prev_out: TxOutpoint::default(),
+                sequence: 0xffff_ffff,
+                value: 50_000,
+                token: None,
+            },
+            lock_script: address.p2pkh_script()?,
+            signatory: signatory.clone(),
+        };
+        let payments = (0..10)
+            .map(|_| (address.clone(), 10_000))
+            .collect::<Vec<_>>();
+        let mut batch = BatchPayment::new(
+            payments,
+            vec![utxo],
+            address.p2pkh_script()?,
+            signatory,
+            DEFAULT_FEE_RATE,
+        );
+        assert!(matches!(
+            batch.next_tx(None),
+            Err(Error::InsufficientInputAmount { .. })
+        ));
+        assert_eq!(batch.remaining_payments(), 10);
+
+        // with room for only three payments, the utxo funds them
+        batch.set_max_tx_size(10 + 149 + 4 * 34);
+        let first = batch.next_tx(None)?.unwrap();
+        assert_eq!(first.num_payments, 3);
+        assert_eq!(first.input_refs.len(), 1);
+        assert_eq!(batch.remaining_payments(), 7);
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use crate::error::{Error, Result};
+use crate::{ByteArray, Hashed, Op, Opcode, Script, Sha256, TxBuilder, TxOutput};
+
+pub const BCMR_LOKAD_ID: &[u8] = b"BCMR";
+
+// an on-chain commitment to a Bitcoin Cash Metadata Registry: OP_RETURN "BCMR" <hash> <uri>*
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BcmrPublication {
+    // SHA256 of the registry JSON, in the byte order `sha256sum` prints
+    pub registry_hash: Sha256,
+    // where the registry can be fetched; by convention "https://" is left out
+    pub uris: Vec<String>,
+}
+
+impl BcmrPublication {
+    pub fn new(registry: &[u8], uris: Vec<String>) -> Self {
+        BcmrPublication {
+            registry_hash: Sha256::digest(registry.to_vec()),
+            uris,
+        }
+    }
+
+    pub fn verify_registry(&self, registry: &[u8]) -> bool {
+        Sha256::digest(registry.to_vec()) == self.registry_hash
+    }
+
+    // the pushes following the lokad id
+    pub fn pushes(&self) -> Vec<ByteArray> {
+        let mut pushes = vec![ByteArray::new(
+            "registry_hash",
+            self.registry_hash.as_slice().to_vec(),
+        )];
+        pushes.extend(
+            self.uris
+                .iter()
+                .map(|uri| ByteArray::new("uri", uri.as_bytes().to_vec())),
+        );
+        pushes
+    }
+
+    // unlike `add_to_tx_builder`, this doesn't check the data carrier size limit
+    pub fn to_output(&self) -> TxOutput {
+        let arrays =
+            std::iter::once(ByteArray::new("lokad_id", BCMR_LOKAD_ID)).chain(self.pushes());
+        let ops = std::iter::once(Op::Code(Opcode::OP_RETURN)).chain(arrays.map(|array| {
+            Op::PushByteArray {
+                array,
+                is_minimal: false,
+            }
+        }));
+        TxOutput {
+            value: 0,
+            script: Script::from_ops(ops),
+            token: None,
+        }
+    }
+
+    pub fn add_to_tx_builder(&self, tx_builder: &mut TxBuilder<'_>) -> Result<()> {
+        tx_builder.add_data_output(ByteArray::new("lokad_id", BCMR_LOKAD_ID), self.pushes())
+    }
+
+    // None for scripts that aren't BCMR publications, an error for malformed ones
+    pub fn from_script(script: &Script) -> Result<Option<Self>> {
+        let ops = script.ops_arc();
+        let mut pushes = Vec::with_capacity(ops.len());
+        for (idx, op) in ops.iter().enumerate() {
+            match (idx, &op.op) {
+                (0, Op::Code(Opcode::OP_RETURN)) => {}
+                (0, _) => return Ok(None),
+                (_, Op::PushByteArray { array, .. }) => pushes.push(array),
+                (1, _) => return Ok(None),
+                _ => {
+                    return Err(Error::InvalidBcmrOutput {
+                        reason: "BCMR outputs must only contain pushes",
+                    })
+                }
+            }
+        }
+        match pushes.first() {
+            Some(lokad_id) if &lokad_id[..] == BCMR_LOKAD_ID => {}
+            _ => return Ok(None),
+        }
+        let registry_hash = match pushes.get(1) {
+            Some(hash) if hash.len() == 32 => Sha256::from_slice(hash)?,
+            _ => {
+                return Err(Error::InvalidBcmrOutput {
+                    reason: "registry hash must be 32 bytes",
+                })
+            }
+        };
+        let uris = pushes[2..]
+            .iter()
+            .map(|uri| String::from_utf8(uri.to_vec()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| Error::InvalidBcmrOutput {
+                reason: "URIs must be UTF-8",
+            })?;
+        Ok(Some(BcmrPublication {
+            registry_hash,
+            uris,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BcmrPublication;
+    use crate::error::Result;
+    use crate::{ByteArray, Op, Opcode, Script, TxBuilder};
+
+    #[test]
+    fn test_bcmr_publication() -> Result<()> {
+        let registry = br#"{"version":{"major":0,"minor":1,"patch":0}}"#;
+        let publication = BcmrPublication::new(registry, vec!["example.com/bcmr.json".into()]);
+        let output = publication.to_output();
+        // OP_RETURN, push "BCMR", push 32 bytes
+        assert!(hex::encode(output.script.ser_ops()).starts_with("6a0442434d5220"));
+        let parsed = BcmrPublication::from_script(&output.script)?.expect("is BCMR");
+        assert_eq!(parsed, publication);
+        assert!(parsed.verify_registry(registry));
+        assert!(!parsed.verify_registry(b"{}"));
+
+        let mut tx_builder = TxBuilder::new_simple();
+        publication.add_to_tx_builder(&mut tx_builder)?;
+        assert_eq!(tx_builder.outputs()[0].script(), &output.script);
+
+        assert_eq!(BcmrPublication::from_script(&Script::default())?, None);
+        let truncated = Script::from_ops(vec![
+            Op::Code(Opcode::OP_RETURN),
+            Op::from_array(ByteArray::from_slice_unnamed(b"BCMR")),
+            Op::from_array(ByteArray::from_slice_unnamed(&[0; 31])),
+        ]);
+        assert!(BcmrPublication::from_script(&truncated).is_err());
+        Ok(())
+    }
+}
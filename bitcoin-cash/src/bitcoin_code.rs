@@ -1,8 +1,11 @@
 use bitcoin_cash_base::ByteArrayError;
+use std::cell::Cell;
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
 
 use crate::{
-    encoding_utils::{encode_var_int, read_var_int},
-    error::Result,
+    encoding_utils::{encode_var_int, read_var_int, write_var_int},
+    error::{Error, Result},
     ByteArray, FixedByteArray,
 };
 
@@ -16,16 +19,297 @@ pub trait BitcoinCode: Sized {
         }
         return Ok(item);
     }
+
+    fn deser_strict(data: ByteArray) -> Result<Self> {
+        with_strict_encoding(|| Self::deser(data))
+    }
+
+    // borrowed decoding for large payloads like blocks; the default copies `data` once,
+    // implementations override it to only copy the bytes they keep
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        deser_slice_with(data, Self::deser_rest)
+    }
+
+    // streaming encoding, e.g. for block files too large to hold in memory; the defaults go
+    // through `ser`/`deser_rest`. `decode_from` must only read the item's own bytes, as items
+    // are nested and followed by other data in the same reader
+    fn encode_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.ser())?;
+        Ok(())
+    }
+
+    // the default buffers the item and retries `deser_rest` on every byte read, so
+    // implementations for large items override it to stream their fields
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        decode_with(reader, Self::deser_rest)
+    }
+
+    // equal to `self.ser().len()`, implementations override it to avoid serializing
+    fn encoded_size(&self) -> usize {
+        self.ser().len()
+    }
+}
+
+// errors of nested fields are merged into one, with the path from the outermost struct
+// (e.g. `UnhashedTx.inputs[3].script`) and the offset of the innermost failing field
+pub fn deser_error_in_field(
+    err: Error,
+    type_name: &'static str,
+    field: &str,
+    offset: usize,
+) -> Error {
+    let (path, inner_offset, source) = split_deser_error(err);
+    Error::DeserializeFailed {
+        type_name,
+        path: format!(".{}{}", field, path),
+        offset: offset + inner_offset,
+        source,
+    }
+}
+
+pub fn deser_error_at_index(err: Error, idx: usize, offset: usize) -> Error {
+    let (path, inner_offset, source) = split_deser_error(err);
+    Error::DeserializeFailed {
+        type_name: "",
+        path: format!("[{}]{}", idx, path),
+        offset: offset + inner_offset,
+        source,
+    }
+}
+
+fn split_deser_error(err: Error) -> (String, usize, Box<Error>) {
+    match err {
+        Error::DeserializeFailed {
+            path,
+            offset,
+            source,
+            ..
+        } => (path, offset, source),
+        err => (String::new(), 0, Box::new(err)),
+    }
+}
+
+pub fn var_int_size(number: u64) -> usize {
+    match number {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+// a CompactSize-encoded number outside of a length prefix, e.g. the services of an addrv2
+// entry; always has to be canonical, but isn't bounded by MAX_COMPACT_SIZE
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarInt(pub u64);
+
+// Bitcoin ABC's MAX_SIZE, the largest CompactSize it deserializes
+pub const MAX_COMPACT_SIZE: u64 = 0x0200_0000;
+
+thread_local! {
+    static IS_STRICT_ENCODING: Cell<bool> = const { Cell::new(false) };
+}
+
+struct StrictEncodingGuard {
+    was_strict: bool,
+}
+
+impl Drop for StrictEncodingGuard {
+    fn drop(&mut self) {
+        IS_STRICT_ENCODING.with(|is_strict| is_strict.set(self.was_strict));
+    }
+}
+
+// rejects non-minimal CompactSizes and sizes above MAX_COMPACT_SIZE while deserializing in `f`,
+// for consensus-critical contexts where re-serialized data has to match the original exactly
+pub fn with_strict_encoding<T>(f: impl FnOnce() -> T) -> T {
+    let _guard = StrictEncodingGuard {
+        was_strict: IS_STRICT_ENCODING.with(|is_strict| is_strict.replace(true)),
+    };
+    f()
+}
+
+pub fn is_strict_encoding() -> bool {
+    IS_STRICT_ENCODING.with(|is_strict| is_strict.get())
+}
+
+fn check_size(size: u64, encoded_len: usize) -> Result<usize> {
+    if is_strict_encoding() {
+        check_canonical_var_int(size, encoded_len)?;
+        if size > MAX_COMPACT_SIZE {
+            return Err(Error::CompactSizeTooLarge {
+                size,
+                max_size: MAX_COMPACT_SIZE,
+            });
+        }
+    }
+    Ok(size as usize)
 }
 
 fn read_size(data: ByteArray) -> Result<(usize, ByteArray)> {
     let mut cursor = std::io::Cursor::new(data.as_slice());
-    let len = read_var_int(&mut cursor)? as usize;
+    let len = read_var_int(&mut cursor)?;
     let position = cursor.position() as usize;
+    let len = check_size(len, position)?;
     let (_, rest) = data.split(position)?;
     Ok((len, rest))
 }
 
+fn read_size_slice(data: &[u8]) -> Result<(usize, &[u8])> {
+    let mut cursor = std::io::Cursor::new(data);
+    let len = read_var_int(&mut cursor)?;
+    let position = cursor.position() as usize;
+    Ok((check_size(len, position)?, &data[position..]))
+}
+
+fn read_size_from<R: Read>(reader: &mut R) -> Result<usize> {
+    let (len, encoded_len) = read_var_int_from(reader)?;
+    check_size(len, encoded_len)
+}
+
+// returns the number and its encoded length, reading only the bytes of the var-int
+fn read_var_int_from<R: Read>(reader: &mut R) -> Result<(u64, usize)> {
+    let mut bytes = [0; 9];
+    reader.read_exact(&mut bytes[..1])?;
+    let encoded_len = match bytes[0] {
+        0xfd => 3,
+        0xfe => 5,
+        0xff => 9,
+        _ => 1,
+    };
+    reader.read_exact(&mut bytes[1..encoded_len])?;
+    let number = read_var_int(&mut &bytes[..encoded_len])?;
+    Ok((number, encoded_len))
+}
+
+fn read_canonical_var_int(data: &[u8]) -> Result<(u64, usize)> {
+    let mut cursor = std::io::Cursor::new(data);
+    let number = read_var_int(&mut cursor)?;
+    let encoded_len = cursor.position() as usize;
+    check_canonical_var_int(number, encoded_len)?;
+    Ok((number, encoded_len))
+}
+
+fn check_canonical_var_int(number: u64, encoded_len: usize) -> Result<()> {
+    if encoded_len != var_int_size(number) {
+        return Err(Error::NonCanonicalVarInt { value: number });
+    }
+    Ok(())
+}
+
+// a reader which can look at its next byte without consuming it, for `with` codecs which
+// decide on the first byte whether their item is present
+pub struct PeekReader<'a, R> {
+    reader: &'a mut R,
+    peeked: Option<u8>,
+}
+
+impl<'a, R: Read> PeekReader<'a, R> {
+    pub fn new(reader: &'a mut R) -> Self {
+        PeekReader {
+            reader,
+            peeked: None,
+        }
+    }
+
+    // None at the end of the reader
+    pub fn peek(&mut self) -> Result<Option<u8>> {
+        if self.peeked.is_none() {
+            let mut byte = [0];
+            loop {
+                match self.reader.read(&mut byte) {
+                    Ok(0) => return Ok(None),
+                    Ok(_) => break,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            self.peeked = Some(byte[0]);
+        }
+        Ok(self.peeked)
+    }
+
+    // a byte peeked at but not read can't be put back into the inner reader
+    pub fn finish(self) -> Result<()> {
+        match self.peeked {
+            Some(byte) => Err(ByteArrayError::LeftoverBytes {
+                bytes: ByteArray::from_slice_unnamed(&[byte]),
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<R: Read> Read for PeekReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.peeked {
+            Some(byte) if !buf.is_empty() => {
+                buf[0] = byte;
+                self.peeked = None;
+                Ok(1)
+            }
+            _ => self.reader.read(buf),
+        }
+    }
+}
+
+// reads one byte at a time until `deser_rest` has enough data, so `deser_rest` may only fail
+// with truncation errors (see `is_truncated`) while its item is incomplete
+pub fn decode_with<R: Read, T>(
+    reader: &mut R,
+    deser_rest: impl Fn(ByteArray) -> Result<(T, ByteArray)>,
+) -> Result<T> {
+    let mut reader = PeekReader::new(reader);
+    let mut data = Vec::new();
+    loop {
+        match deser_rest(ByteArray::from_slice_unnamed(&data)) {
+            Ok((item, _)) => return Ok(item),
+            Err(err) if is_truncated(&err) => match reader.peek()? {
+                Some(byte) => {
+                    data.push(byte);
+                    reader.read_exact(&mut [0])?;
+                }
+                None => return Err(err),
+            },
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn is_truncated(err: &Error) -> bool {
+    match err {
+        Error::DeserializeFailed { source, .. } => is_truncated(source),
+        Error::ByteArrayError(ByteArrayError::InvalidSplit { .. }) => true,
+        Error::ByteArrayError(ByteArrayError::InvalidSlice { expected, actual }) => {
+            actual < expected
+        }
+        Error::Io(err) => err.kind() == io::ErrorKind::UnexpectedEof,
+        _ => false,
+    }
+}
+
+// doesn't trust `len` for the allocation, a short reader is an UnexpectedEof error
+fn read_bytes<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(bytes)
+}
+
+pub fn split_slice(data: &[u8], split_idx: usize) -> Result<(&[u8], &[u8])> {
+    if data.len() < split_idx {
+        return Err(ByteArrayError::InvalidSplit {
+            split_idx,
+            len: data.len(),
+        }
+        .into());
+    }
+    Ok(data.split_at(split_idx))
+}
+
 impl BitcoinCode for ByteArray {
     fn ser(&self) -> ByteArray {
         ByteArray::new("size", encode_var_int(self.len() as u64)).concat(self.clone())
@@ -36,6 +320,27 @@ impl BitcoinCode for ByteArray {
         let (byte_array, rest) = rest.split(len)?;
         Ok((byte_array, rest))
     }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (len, rest) = read_size_slice(data)?;
+        let (bytes, rest) = split_slice(rest, len)?;
+        Ok((ByteArray::from_slice_unnamed(bytes), rest))
+    }
+
+    fn encode_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_var_int(writer, self.len() as u64)?;
+        writer.write_all(self)?;
+        Ok(())
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let len = read_size_from(reader)?;
+        Ok(read_bytes(reader, len)?.into())
+    }
+
+    fn encoded_size(&self) -> usize {
+        var_int_size(self.len() as u64) + self.len()
+    }
 }
 
 impl<T, H> BitcoinCode for FixedByteArray<T, H>
@@ -53,24 +358,398 @@ where
         let fixed_byte_array = Self::from_byte_array(left)?;
         Ok((fixed_byte_array, right))
     }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let array = T::default();
+        let (left, right) = split_slice(data, array.as_ref().len())?;
+        let fixed_byte_array = Self::from_byte_array(ByteArray::from_slice_unnamed(left))?;
+        Ok((fixed_byte_array, right))
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let array = T::default();
+        let bytes = read_bytes(reader, array.as_ref().len())?;
+        Ok(Self::from_byte_array(bytes.into())?)
+    }
+
+    fn encoded_size(&self) -> usize {
+        self.as_byte_array().len()
+    }
+}
+
+// CompactSize count followed by the items
+pub fn ser_slice<T: BitcoinCode>(items: &[T]) -> ByteArray {
+    ByteArray::new("size", encode_var_int(items.len() as u64)).concat(ser_fixed_count(items))
+}
+
+// the items without a count, for formats where the count is implied
+pub fn ser_fixed_count<T: BitcoinCode>(items: &[T]) -> ByteArray {
+    ByteArray::from_parts(items.iter().map(|item| item.ser()))
+}
+
+pub fn deser_fixed_count<T: BitcoinCode>(
+    data: ByteArray,
+    count: usize,
+) -> Result<(Vec<T>, ByteArray)> {
+    deser_items(data, count, 0)
+}
+
+// `prefix_size` is the size of the count before the items, for error offsets
+fn deser_items<T: BitcoinCode>(
+    mut data: ByteArray,
+    count: usize,
+    prefix_size: usize,
+) -> Result<(Vec<T>, ByteArray)> {
+    let data_len = data.len();
+    // `count` may come from an untrusted peer, so only trust it as far as the data goes
+    let mut vec = Vec::with_capacity(count.min(data_len));
+    for idx in 0..count {
+        let offset = prefix_size + data_len - data.len();
+        let (item, rest) =
+            T::deser_rest(data).map_err(|err| deser_error_at_index(err, idx, offset))?;
+        vec.push(item);
+        data = rest;
+    }
+    Ok((vec, data))
+}
+
+pub fn deser_fixed_count_slice<T: BitcoinCode>(
+    data: &[u8],
+    count: usize,
+) -> Result<(Vec<T>, &[u8])> {
+    deser_items_slice(data, count, 0)
+}
+
+fn deser_items_slice<T: BitcoinCode>(
+    mut data: &[u8],
+    count: usize,
+    prefix_size: usize,
+) -> Result<(Vec<T>, &[u8])> {
+    let data_len = data.len();
+    let mut vec = Vec::with_capacity(count.min(data_len));
+    for idx in 0..count {
+        let offset = prefix_size + data_len - data.len();
+        let (item, rest) =
+            T::deser_slice(data).map_err(|err| deser_error_at_index(err, idx, offset))?;
+        vec.push(item);
+        data = rest;
+    }
+    Ok((vec, data))
+}
+
+pub fn decode_fixed_count_from<T: BitcoinCode, R: Read>(
+    reader: &mut R,
+    count: usize,
+) -> Result<Vec<T>> {
+    let mut vec = Vec::new();
+    for _ in 0..count {
+        vec.push(T::decode_from(reader)?);
+    }
+    Ok(vec)
+}
+
+// how the length of a byte field is written; scripts and P2P messages use CompactSize,
+// other protocols (e.g. SLP) use fixed-width little-endian integers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LenPrefix {
+    Var,
+    U8,
+    U16,
+    U32,
+}
+
+impl LenPrefix {
+    pub fn max_len(self) -> usize {
+        match self {
+            LenPrefix::Var => usize::MAX,
+            LenPrefix::U8 => u8::MAX as usize,
+            LenPrefix::U16 => u16::MAX as usize,
+            LenPrefix::U32 => u32::MAX as usize,
+        }
+    }
+}
+
+// panics if the bytes don't fit the prefix, like other infallible `ser` overflows
+pub fn ser_len_prefixed(bytes: &[u8], prefix: LenPrefix) -> ByteArray {
+    let len = bytes.len();
+    assert!(
+        len <= prefix.max_len(),
+        "{} bytes don't fit a {:?} length prefix",
+        len,
+        prefix,
+    );
+    let size = match prefix {
+        LenPrefix::Var => encode_var_int(len as u64),
+        LenPrefix::U8 => vec![len as u8],
+        LenPrefix::U16 => (len as u16).to_le_bytes().to_vec(),
+        LenPrefix::U32 => (len as u32).to_le_bytes().to_vec(),
+    };
+    ByteArray::new("size", size).concat(ByteArray::from_slice("data", bytes))
+}
+
+pub fn len_prefixed_size(bytes: &[u8], prefix: LenPrefix) -> usize {
+    let prefix_size = match prefix {
+        LenPrefix::Var => var_int_size(bytes.len() as u64),
+        LenPrefix::U8 => 1,
+        LenPrefix::U16 => 2,
+        LenPrefix::U32 => 4,
+    };
+    prefix_size + bytes.len()
+}
+
+pub fn deser_len_prefixed<T: From<Vec<u8>>>(
+    data: ByteArray,
+    prefix: LenPrefix,
+) -> Result<(T, ByteArray)> {
+    let (len, rest) = match prefix {
+        LenPrefix::Var => read_size(data)?,
+        LenPrefix::U8 => {
+            let (len, rest) = u8::deser_rest(data)?;
+            (len as usize, rest)
+        }
+        LenPrefix::U16 => {
+            let (len, rest) = u16::deser_rest(data)?;
+            (len as usize, rest)
+        }
+        LenPrefix::U32 => {
+            let (len, rest) = u32::deser_rest(data)?;
+            (len as usize, rest)
+        }
+    };
+    let (bytes, rest) = rest.split(len)?;
+    Ok((bytes.to_vec().into(), rest))
+}
+
+pub fn deser_len_prefixed_slice<T: From<Vec<u8>>>(
+    data: &[u8],
+    prefix: LenPrefix,
+) -> Result<(T, &[u8])> {
+    let (len, rest) = match prefix {
+        LenPrefix::Var => read_size_slice(data)?,
+        LenPrefix::U8 => {
+            let (len, rest) = u8::deser_slice(data)?;
+            (len as usize, rest)
+        }
+        LenPrefix::U16 => {
+            let (len, rest) = u16::deser_slice(data)?;
+            (len as usize, rest)
+        }
+        LenPrefix::U32 => {
+            let (len, rest) = u32::deser_slice(data)?;
+            (len as usize, rest)
+        }
+    };
+    let (bytes, rest) = split_slice(rest, len)?;
+    Ok((bytes.to_vec().into(), rest))
+}
+
+pub fn decode_len_prefixed_from<T: From<Vec<u8>>, R: Read>(
+    reader: &mut R,
+    prefix: LenPrefix,
+) -> Result<T> {
+    let len = match prefix {
+        LenPrefix::Var => read_size_from(reader)?,
+        LenPrefix::U8 => u8::decode_from(reader)? as usize,
+        LenPrefix::U16 => u16::decode_from(reader)? as usize,
+        LenPrefix::U32 => u32::decode_from(reader)? as usize,
+    };
+    Ok(read_bytes(reader, len)?.into())
+}
+
+// for custom codecs which only deserialize from ByteArrays
+pub fn deser_slice_with<T>(
+    data: &[u8],
+    deser_rest: impl FnOnce(ByteArray) -> Result<(T, ByteArray)>,
+) -> Result<(T, &[u8])> {
+    let (item, rest) = deser_rest(ByteArray::from_slice_unnamed(data))?;
+    Ok((item, &data[data.len() - rest.len()..]))
+}
+
+// trailing extension fields, present iff any bytes remain
+pub fn deser_optional_trailing<T: BitcoinCode>(data: ByteArray) -> Result<(Option<T>, ByteArray)> {
+    if data.is_empty() {
+        return Ok((None, data));
+    }
+    let (item, rest) = T::deser_rest(data)?;
+    Ok((Some(item), rest))
+}
+
+pub fn deser_optional_trailing_slice<T: BitcoinCode>(data: &[u8]) -> Result<(Option<T>, &[u8])> {
+    if data.is_empty() {
+        return Ok((None, data));
+    }
+    let (item, rest) = T::deser_slice(data)?;
+    Ok((Some(item), rest))
+}
+
+pub fn decode_optional_trailing_from<T: BitcoinCode, R: Read>(reader: &mut R) -> Result<Option<T>> {
+    let mut first = [0];
+    loop {
+        match reader.read(&mut first) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    let mut reader = first.as_ref().chain(reader);
+    Ok(Some(T::decode_from(&mut reader)?))
+}
+
+// a 0/1 flag byte, followed by the item if the flag is 1
+pub fn ser_optional_flag<T: BitcoinCode>(item: Option<&T>) -> ByteArray {
+    match item {
+        Some(item) => ByteArray::new("flag", [1]).concat(item.ser()),
+        None => ByteArray::new("flag", [0]),
+    }
+}
+
+pub fn deser_optional_flag<T: BitcoinCode>(data: ByteArray) -> Result<(Option<T>, ByteArray)> {
+    let (flag, rest) = data.split(1)?;
+    match flag[0] {
+        0 => Ok((None, rest)),
+        1 => {
+            let (item, rest) = T::deser_rest(rest)?;
+            Ok((Some(item), rest))
+        }
+        flag => Err(Error::InvalidOptionalFlag { flag }),
+    }
+}
+
+pub fn deser_optional_flag_slice<T: BitcoinCode>(data: &[u8]) -> Result<(Option<T>, &[u8])> {
+    let (flag, rest) = split_slice(data, 1)?;
+    match flag[0] {
+        0 => Ok((None, rest)),
+        1 => {
+            let (item, rest) = T::deser_slice(rest)?;
+            Ok((Some(item), rest))
+        }
+        flag => Err(Error::InvalidOptionalFlag { flag }),
+    }
+}
+
+pub fn decode_optional_flag_from<T: BitcoinCode, R: Read>(reader: &mut R) -> Result<Option<T>> {
+    match u8::decode_from(reader)? {
+        0 => Ok(None),
+        1 => Ok(Some(T::decode_from(reader)?)),
+        flag => Err(Error::InvalidOptionalFlag { flag }),
+    }
 }
 
 impl<T: BitcoinCode> BitcoinCode for Vec<T> {
     fn ser(&self) -> ByteArray {
-        let parts = self.iter().map(|item| item.ser());
-        let data = ByteArray::from_parts(parts);
-        ByteArray::new("size", encode_var_int(self.len() as u64)).concat(data)
+        ser_slice(self)
     }
 
     fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
-        let (len, mut byte_array) = read_size(data)?;
-        let mut vec = Vec::with_capacity(len);
-        for _ in 0..len {
-            let (item, rest) = T::deser_rest(byte_array)?;
-            vec.push(item);
-            byte_array = rest;
+        let data_len = data.len();
+        let (len, rest) = read_size(data)?;
+        let prefix_size = data_len - rest.len();
+        deser_items(rest, len, prefix_size)
+    }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (len, rest) = read_size_slice(data)?;
+        deser_items_slice(rest, len, data.len() - rest.len())
+    }
+
+    fn encode_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_var_int(writer, self.len() as u64)?;
+        for item in self {
+            item.encode_to(writer)?;
         }
-        Ok((vec, byte_array))
+        Ok(())
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let len = read_size_from(reader)?;
+        decode_fixed_count_from(reader, len)
+    }
+
+    fn encoded_size(&self) -> usize {
+        var_int_size(self.len() as u64) + self.iter().map(T::encoded_size).sum::<usize>()
+    }
+}
+
+impl<T: BitcoinCode, const N: usize> BitcoinCode for [T; N] {
+    fn ser(&self) -> ByteArray {
+        ser_fixed_count(self)
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (vec, rest) = deser_fixed_count(data, N)?;
+        Ok((vec_to_array(vec)?, rest))
+    }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (vec, rest) = deser_fixed_count_slice(data, N)?;
+        Ok((vec_to_array(vec)?, rest))
+    }
+
+    fn encode_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for item in self {
+            item.encode_to(writer)?;
+        }
+        Ok(())
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        vec_to_array(decode_fixed_count_from(reader, N)?)
+    }
+
+    fn encoded_size(&self) -> usize {
+        self.iter().map(T::encoded_size).sum()
+    }
+}
+
+fn vec_to_array<T, const N: usize>(vec: Vec<T>) -> Result<[T; N]> {
+    vec.try_into().map_err(|vec: Vec<T>| Error::InvalidSize {
+        expected: N,
+        actual: vec.len(),
+    })
+}
+
+impl BitcoinCode for VarInt {
+    fn ser(&self) -> ByteArray {
+        encode_var_int(self.0).into()
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (number, encoded_len) = read_canonical_var_int(&data)?;
+        let (_, rest) = data.split(encoded_len)?;
+        Ok((VarInt(number), rest))
+    }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (number, encoded_len) = read_canonical_var_int(data)?;
+        Ok((VarInt(number), &data[encoded_len..]))
+    }
+
+    fn encode_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_var_int(writer, self.0)?;
+        Ok(())
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let (number, encoded_len) = read_var_int_from(reader)?;
+        check_canonical_var_int(number, encoded_len)?;
+        Ok(VarInt(number))
+    }
+
+    fn encoded_size(&self) -> usize {
+        var_int_size(self.0)
+    }
+}
+
+impl From<u64> for VarInt {
+    fn from(number: u64) -> Self {
+        VarInt(number)
+    }
+}
+
+impl From<VarInt> for u64 {
+    fn from(var_int: VarInt) -> Self {
+        var_int.0
     }
 }
 
@@ -83,11 +762,61 @@ impl BitcoinCode for bool {
         let (left, right) = data.split(1)?;
         Ok((left[0] != 0, right))
     }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (left, right) = split_slice(data, 1)?;
+        Ok((left[0] != 0, right))
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        Ok(u8::decode_from(reader)? != 0)
+    }
+
+    fn encoded_size(&self) -> usize {
+        1
+    }
+}
+
+// network byte order, for formats that don't use Bitcoin's little-endian default
+pub trait BigEndianCode: Sized {
+    fn ser_be(&self) -> ByteArray;
+    fn deser_be_rest(data: ByteArray) -> Result<(Self, ByteArray)>;
+    fn deser_be_slice(data: &[u8]) -> Result<(Self, &[u8])>;
+    fn decode_be_from<R: Read>(reader: &mut R) -> Result<Self>;
 }
 
-macro_rules! array_impls {
+macro_rules! int_impls {
     ($($T:ident)+) => {
         $(
+            impl BigEndianCode for $T {
+                fn ser_be(&self) -> ByteArray {
+                    self.to_be_bytes().into()
+                }
+
+                fn deser_be_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+                    let split_idx = std::mem::size_of::<$T>();
+                    let (left, right) = data.split(split_idx)?;
+                    let mut array = [0; std::mem::size_of::<$T>()];
+                    array.copy_from_slice(&left);
+                    let value = $T::from_be_bytes(array);
+                    Ok((value, right))
+                }
+
+                fn deser_be_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+                    let split_idx = std::mem::size_of::<$T>();
+                    let (left, right) = split_slice(data, split_idx)?;
+                    let mut array = [0; std::mem::size_of::<$T>()];
+                    array.copy_from_slice(left);
+                    Ok(($T::from_be_bytes(array), right))
+                }
+
+                fn decode_be_from<R: Read>(reader: &mut R) -> Result<Self> {
+                    let mut array = [0; std::mem::size_of::<$T>()];
+                    reader.read_exact(&mut array)?;
+                    Ok($T::from_be_bytes(array))
+                }
+            }
+
             impl BitcoinCode for $T {
                 fn ser(&self) -> ByteArray {
                     self.to_le_bytes().into()
@@ -101,11 +830,34 @@ macro_rules! array_impls {
                     let value = $T::from_le_bytes(array);
                     Ok((value, right))
                 }
+
+                fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+                    let split_idx = std::mem::size_of::<$T>();
+                    let (left, right) = split_slice(data, split_idx)?;
+                    let mut array = [0; std::mem::size_of::<$T>()];
+                    array.copy_from_slice(left);
+                    Ok(($T::from_le_bytes(array), right))
+                }
+
+                fn encode_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+                    writer.write_all(&self.to_le_bytes())?;
+                    Ok(())
+                }
+
+                fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+                    let mut array = [0; std::mem::size_of::<$T>()];
+                    reader.read_exact(&mut array)?;
+                    Ok($T::from_le_bytes(array))
+                }
+
+                fn encoded_size(&self) -> usize {
+                    std::mem::size_of::<$T>()
+                }
             }
         )+
     }
 }
 
-array_impls! {
+int_impls! {
     u8 i8 u16 i16 u32 i32 u64 i64 u128 i128
 }
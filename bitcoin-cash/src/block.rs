@@ -0,0 +1,225 @@
+use crate::error::{Error, Result};
+use crate::{compact_to_target, difficulty, target_work, BitcoinCode, Hashed, Sha256d, Tx};
+use num::bigint::BigUint;
+use serde::{Deserialize, Serialize};
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockHeader {
+    pub version: i32,
+    pub prev_block: Sha256d,
+    pub merkle_root: Sha256d,
+    pub timestamp: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    pub fn block_hash(&self) -> Sha256d {
+        Sha256d::digest(self.ser())
+    }
+
+    pub fn target(&self) -> Result<BigUint> {
+        compact_to_target(self.bits)
+    }
+
+    pub fn work(&self) -> Result<BigUint> {
+        Ok(target_work(&self.target()?))
+    }
+
+    pub fn difficulty(&self) -> Result<f64> {
+        difficulty(self.bits)
+    }
+
+    // only checks the hash against the header's own bits; whether the bits are
+    // correct for the chain is up to the caller
+    pub fn validate_pow(&self) -> Result<()> {
+        let target = self.target()?;
+        let block_hash = self.block_hash();
+        if BigUint::from_bytes_le(block_hash.as_slice()) > target {
+            return Err(Error::InsufficientProofOfWork { block_hash });
+        }
+        Ok(())
+    }
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub txs: Vec<Tx>,
+}
+
+impl Block {
+    pub fn block_hash(&self) -> Sha256d {
+        self.header.block_hash()
+    }
+
+    pub fn merkle_root(&self) -> Sha256d {
+        merkle_root(self.txs.iter().map(Tx::hash))
+    }
+
+    pub fn verify_merkle_root(&self) -> Result<()> {
+        let (merkle_root, is_mutated) = merkle_root_and_mutation(self.txs.iter().map(Tx::hash));
+        if is_mutated {
+            return Err(Error::MutatedMerkleTree {
+                block_hash: self.block_hash(),
+            });
+        }
+        if merkle_root != self.header.merkle_root {
+            return Err(Error::MerkleRootMismatch {
+                expected: self.header.merkle_root.clone(),
+                actual: merkle_root,
+            });
+        }
+        Ok(())
+    }
+}
+
+// pairs of hashes are concatenated and hashed, duplicating the last one on odd levels
+pub fn merkle_root<'a>(txids: impl IntoIterator<Item = &'a Sha256d>) -> Sha256d {
+    merkle_root_and_mutation(txids).0
+}
+
+// also returns whether any level has two identical siblings, which, like in
+// merkle_block.rs, means txs could have been duplicated without changing the root
+// (CVE-2012-2459)
+fn merkle_root_and_mutation<'a>(txids: impl IntoIterator<Item = &'a Sha256d>) -> (Sha256d, bool) {
+    let mut level = txids.into_iter().cloned().collect::<Vec<_>>();
+    if level.is_empty() {
+        return (Sha256d::default(), false);
+    }
+    let mut is_mutated = false;
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let right = pair.last().expect("chunks are never empty");
+                is_mutated |= pair.len() == 2 && pair[0] == pair[1];
+                Sha256d::digest([pair[0].as_slice(), right.as_slice()].concat())
+            })
+            .collect();
+    }
+    (level.remove(0), is_mutated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merkle_root, Block, BlockHeader};
+    use crate::error::{Error, Result};
+    use crate::{BitcoinCode, Hashed, Sha256d, TxInput, TxOutpoint, TxOutput, UnhashedTx};
+
+    fn genesis_header() -> Result<BlockHeader> {
+        Ok(BlockHeader {
+            version: 1,
+            prev_block: Sha256d::default(),
+            merkle_root: Sha256d::from_hex_le(
+                "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+            )?,
+            timestamp: 1_231_006_505,
+            bits: 0x1d00_ffff,
+            nonce: 2_083_236_893,
+        })
+    }
+
+    #[test]
+    fn test_genesis_header() -> Result<()> {
+        let header = genesis_header()?;
+        let ser = header.ser();
+        assert_eq!(ser.len(), 80);
+        assert_eq!(BlockHeader::deser(ser)?, header);
+        assert_eq!(
+            header.block_hash().to_hex_le(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+        );
+        header.validate_pow()?;
+
+        let mut header = header;
+        header.nonce += 1;
+        assert!(matches!(
+            header.validate_pow(),
+            Err(Error::InsufficientProofOfWork { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_genesis_block() -> Result<()> {
+        let coinbase = hex::decode(
+            "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04\
+             ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e\
+             206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f205\
+             2a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6\
+             bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000",
+        )?;
+        let mut raw = genesis_header()?.ser().to_vec();
+        raw.push(1);
+        raw.extend_from_slice(&coinbase);
+        let block = Block::deser(raw.clone().into())?;
+        assert_eq!(block.txs.len(), 1);
+        assert_eq!(block.ser().to_vec(), raw);
+        block.verify_merkle_root()?;
+        assert_eq!(block.block_hash(), genesis_header()?.block_hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merkle_root() -> Result<()> {
+        let txs = (0..3)
+            .map(|vout| {
+                UnhashedTx {
+                    version: 1,
+                    inputs: vec![TxInput::new(
+                        TxOutpoint {
+                            vout,
+                            ..Default::default()
+                        },
+                        Default::default(),
+                        0xffff_ffff,
+                    )],
+                    outputs: vec![TxOutput {
+                        value: 1000,
+                        script: Default::default(),
+                        token: None,
+                    }],
+                    lock_time: 0,
+                }
+                .hashed()
+            })
+            .collect::<Vec<_>>();
+        let hash_pair = |left: &Sha256d, right: &Sha256d| {
+            Sha256d::digest([left.as_slice(), right.as_slice()].concat())
+        };
+        let expected = hash_pair(
+            &hash_pair(txs[0].hash(), txs[1].hash()),
+            &hash_pair(txs[2].hash(), txs[2].hash()),
+        );
+        assert_eq!(merkle_root(txs.iter().map(|tx| tx.hash())), expected);
+
+        let mut block = Block {
+            header: BlockHeader {
+                merkle_root: expected,
+                ..Default::default()
+            },
+            txs,
+        };
+        block.verify_merkle_root()?;
+
+        // duplicating the last tx results in the same root
+        let mut mutated_block = block.clone();
+        mutated_block.txs.push(mutated_block.txs[2].clone());
+        assert_eq!(mutated_block.merkle_root(), block.header.merkle_root);
+        assert!(matches!(
+            mutated_block.verify_merkle_root(),
+            Err(Error::MutatedMerkleTree { .. })
+        ));
+
+        block.txs.pop();
+        assert!(matches!(
+            block.verify_merkle_root(),
+            Err(Error::MerkleRootMismatch { .. })
+        ));
+        Ok(())
+    }
+}
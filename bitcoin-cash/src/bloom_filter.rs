@@ -0,0 +1,350 @@
+use crate::error::{Error, Result};
+use crate::{
+    BitcoinCode, ByteArray, Hash160, Hashed, MessagePayload, Op, Opcode, Pubkey, Script,
+    TxOutpoint, UnhashedTx,
+};
+
+pub const MAX_BLOOM_FILTER_SIZE: usize = 36_000;
+pub const MAX_BLOOM_HASH_FUNCS: u32 = 50;
+pub const MAX_FILTER_ADD_SIZE: usize = 520;
+
+pub const BLOOM_UPDATE_NONE: u8 = 0;
+pub const BLOOM_UPDATE_ALL: u8 = 1;
+pub const BLOOM_UPDATE_P2PUBKEY_ONLY: u8 = 2;
+
+const HASH_SEED_STEP: u32 = 0xfba4_c795;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct BloomFilter {
+    data: Vec<u8>,
+    num_hash_funcs: u32,
+    tweak: u32,
+    flags: u8,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone)]
+pub struct FilterLoad {
+    pub filter: Vec<u8>,
+    pub num_hash_funcs: u32,
+    pub tweak: u32,
+    pub flags: u8,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone)]
+pub struct FilterAdd {
+    pub element: Vec<u8>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct FilterClear;
+
+impl BloomFilter {
+    // sized for `num_elements` at the given false positive rate, capped at the BIP37 limits
+    pub fn new(num_elements: usize, fp_rate: f64, tweak: u32, flags: u8) -> Result<Self> {
+        // also rejects NaN
+        if !(fp_rate > 0.0 && fp_rate < 1.0) {
+            return Err(Error::InvalidBloomFilter {
+                reason: "false positive rate must be between 0 and 1",
+            });
+        }
+        let ln2 = std::f64::consts::LN_2;
+        let num_elements = num_elements.max(1) as f64;
+        let size = (-1.0 / (ln2 * ln2) * num_elements * fp_rate.ln() / 8.0)
+            .clamp(1.0, MAX_BLOOM_FILTER_SIZE as f64) as usize;
+        let num_hash_funcs =
+            (size as f64 * 8.0 / num_elements * ln2).clamp(1.0, MAX_BLOOM_HASH_FUNCS as f64) as u32;
+        Ok(BloomFilter {
+            data: vec![0; size],
+            num_hash_funcs,
+            tweak,
+            flags,
+        })
+    }
+
+    pub fn from_filter_load(filter_load: FilterLoad) -> Result<Self> {
+        if filter_load.filter.is_empty()
+            || filter_load.filter.len() > MAX_BLOOM_FILTER_SIZE
+            || filter_load.num_hash_funcs > MAX_BLOOM_HASH_FUNCS
+        {
+            return Err(Error::InvalidBloomFilter {
+                reason: "filter exceeds the BIP37 limits",
+            });
+        }
+        Ok(BloomFilter {
+            data: filter_load.filter,
+            num_hash_funcs: filter_load.num_hash_funcs,
+            tweak: filter_load.tweak,
+            flags: filter_load.flags,
+        })
+    }
+
+    pub fn to_filter_load(&self) -> FilterLoad {
+        FilterLoad {
+            filter: self.data.clone(),
+            num_hash_funcs: self.num_hash_funcs,
+            tweak: self.tweak,
+            flags: self.flags,
+        }
+    }
+
+    pub fn insert(&mut self, data: &[u8]) {
+        for hash_idx in 0..self.num_hash_funcs {
+            let bit_idx = self.bit_idx(hash_idx, data);
+            self.data[bit_idx / 8] |= 1 << (bit_idx % 8);
+        }
+    }
+
+    pub fn contains(&self, data: &[u8]) -> bool {
+        (0..self.num_hash_funcs).all(|hash_idx| {
+            let bit_idx = self.bit_idx(hash_idx, data);
+            self.data[bit_idx / 8] & (1 << (bit_idx % 8)) != 0
+        })
+    }
+
+    pub fn insert_outpoint(&mut self, outpoint: &TxOutpoint) {
+        self.insert(&outpoint.ser());
+    }
+
+    // both the pubkey and its hash, to match P2PK, P2PKH outputs and their spends
+    pub fn insert_pubkey(&mut self, pubkey: &Pubkey) {
+        self.insert(pubkey.as_slice());
+        self.insert(Hash160::digest(pubkey.as_slice().to_vec()).as_slice());
+    }
+
+    // both the script and its hash, to match P2SH outputs and the redeem script in their spends
+    pub fn insert_script(&mut self, script: &Script) {
+        let script_code = script.ser_ops();
+        self.insert(&script_code);
+        self.insert(Hash160::digest(script_code).as_slice());
+    }
+
+    // BIP37 matching; matched outputs get their outpoint inserted as the filter's flags say,
+    // so later spends of them match too
+    pub fn matches_tx(&mut self, tx: &UnhashedTx) -> bool {
+        let txid = tx.txid();
+        let mut is_match = self.contains(txid.as_slice());
+        for (vout, output) in tx.outputs.iter().enumerate() {
+            let has_match = push_data(&output.script).any(|data| self.contains(data));
+            if !has_match {
+                continue;
+            }
+            is_match = true;
+            let update_outpoint = match self.flags & 3 {
+                BLOOM_UPDATE_ALL => true,
+                BLOOM_UPDATE_P2PUBKEY_ONLY => is_pubkey_script(&output.script),
+                _ => false,
+            };
+            if update_outpoint {
+                self.insert_outpoint(&TxOutpoint {
+                    tx_hash: txid.clone(),
+                    vout: vout as u32,
+                });
+            }
+        }
+        if is_match {
+            return true;
+        }
+        tx.inputs.iter().any(|input| {
+            self.contains(&input.prev_out.ser())
+                || push_data(&input.script).any(|data| self.contains(data))
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.data.iter_mut().for_each(|byte| *byte = 0);
+    }
+
+    fn bit_idx(&self, hash_idx: u32, data: &[u8]) -> usize {
+        let seed = hash_idx
+            .wrapping_mul(HASH_SEED_STEP)
+            .wrapping_add(self.tweak);
+        murmur3(seed, data) as usize % (self.data.len() * 8)
+    }
+}
+
+impl FilterAdd {
+    pub fn new(element: impl Into<Vec<u8>>) -> Result<Self> {
+        let element = element.into();
+        if element.len() > MAX_FILTER_ADD_SIZE {
+            return Err(Error::InvalidBloomFilter {
+                reason: "filteradd data exceeds 520 bytes",
+            });
+        }
+        Ok(FilterAdd { element })
+    }
+}
+
+impl BitcoinCode for FilterClear {
+    fn ser(&self) -> ByteArray {
+        ByteArray::default()
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        Ok((FilterClear, data))
+    }
+}
+
+impl MessagePayload for FilterLoad {
+    const COMMAND: &'static str = "filterload";
+}
+
+impl MessagePayload for FilterAdd {
+    const COMMAND: &'static str = "filteradd";
+}
+
+impl MessagePayload for FilterClear {
+    const COMMAND: &'static str = "filterclear";
+}
+
+fn push_data(script: &Script) -> impl Iterator<Item = &[u8]> {
+    script.ops_arc().iter().filter_map(|op| match &op.op {
+        Op::PushByteArray { array, .. } if !array.is_empty() => Some(array.as_slice()),
+        _ => None,
+    })
+}
+
+// P2PK or bare multisig
+fn is_pubkey_script(script: &Script) -> bool {
+    let is_pubkey = |op: &Op| match op {
+        Op::PushByteArray { array, .. } => array.len() == 33 || array.len() == 65,
+        _ => false,
+    };
+    match script.ops_arc().as_ref() {
+        [pubkey, checksig] => is_pubkey(&pubkey.op) && checksig.op == Op::Code(Opcode::OP_CHECKSIG),
+        [_, pubkeys @ .., _, checkmultisig] if !pubkeys.is_empty() => {
+            checkmultisig.op == Op::Code(Opcode::OP_CHECKMULTISIG)
+                && pubkeys.iter().all(|pubkey| is_pubkey(&pubkey.op))
+        }
+        _ => false,
+    }
+}
+
+fn murmur3(seed: u32, data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+    let mut hash = seed;
+    let chunks = data.chunks_exact(4);
+    let remainder = chunks.remainder();
+    for chunk in chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        hash ^= k;
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe654_6b64);
+    }
+    if !remainder.is_empty() {
+        let mut k = 0u32;
+        for (idx, &byte) in remainder.iter().enumerate() {
+            k |= (byte as u32) << (8 * idx);
+        }
+        hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        murmur3, BloomFilter, FilterClear, FilterLoad, BLOOM_UPDATE_ALL, MAX_BLOOM_FILTER_SIZE,
+    };
+    use crate::error::{Error, Result};
+    use crate::{
+        Address, BitcoinCode, Prefix, Pubkey, Script, TxInput, TxOutpoint, TxOutput, UnhashedTx,
+    };
+
+    #[test]
+    fn test_murmur3() {
+        assert_eq!(murmur3(0, &[]), 0);
+        assert_eq!(murmur3(0xfba4_c795, &[]), 0x6a39_6f08);
+        assert_eq!(murmur3(0, &[0x00]), 0x514e_28b7);
+        assert_eq!(murmur3(0, &[0x00, 0x11, 0x22, 0x33]), 0xb447_1bf8);
+        assert_eq!(murmur3(0, &[0x00, 0x11, 0x22, 0x33, 0x44]), 0xe230_1fa8);
+        assert_eq!(murmur3(0, &[0xff]), 0xfd6c_f10d);
+    }
+
+    #[test]
+    fn test_bloom_filter() -> Result<()> {
+        // vector from Bitcoin Core's bloom_tests
+        let mut filter = BloomFilter::new(3, 0.01, 0, BLOOM_UPDATE_ALL)?;
+        let data = hex::decode("99108ad8ed9bb6274d3980bab5a85c048f0950c8")?;
+        filter.insert(&data);
+        assert!(filter.contains(&data));
+        assert!(!filter.contains(&hex::decode("19108ad8ed9bb6274d3980bab5a85c048f0950c8")?));
+        filter.insert(&hex::decode("b5a2c786d9ef4658287ced5914b37a1b4aa32eee")?);
+        filter.insert(&hex::decode("b9300670b4c5366e95b2699e8b18bc75e5f729c5")?);
+        assert_eq!(
+            hex::encode(filter.to_filter_load().ser()),
+            "03614e9b050000000000000001"
+        );
+        let filter_load = FilterLoad::deser(filter.to_filter_load().ser())?;
+        assert_eq!(BloomFilter::from_filter_load(filter_load)?, filter);
+        let mut reader = std::io::Cursor::new(vec![2, 0xff]);
+        assert_eq!(Vec::<FilterClear>::decode_from(&mut reader)?.len(), 2);
+        assert_eq!(reader.position(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bloom_filter_params() -> Result<()> {
+        for &fp_rate in &[0.0, 1.0, -0.5, 2.0, f64::NAN] {
+            assert!(matches!(
+                BloomFilter::new(10, fp_rate, 0, BLOOM_UPDATE_ALL),
+                Err(Error::InvalidBloomFilter { .. })
+            ));
+        }
+        let filter = BloomFilter::new(0, 0.999, 0, BLOOM_UPDATE_ALL)?;
+        assert_eq!(filter.to_filter_load().filter.len(), 1);
+        let filter = BloomFilter::new(1_000_000, 1e-9, 0, BLOOM_UPDATE_ALL)?;
+        assert_eq!(filter.to_filter_load().filter.len(), MAX_BLOOM_FILTER_SIZE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_tx() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let mut filter = BloomFilter::new(10, 0.0001, 5, BLOOM_UPDATE_ALL)?;
+        filter.insert_pubkey(&pubkey);
+        let funding_tx = UnhashedTx {
+            version: 1,
+            inputs: vec![TxInput::new(TxOutpoint::default(), Script::default(), 0)],
+            outputs: vec![TxOutput {
+                value: 1000,
+                script: address.p2pkh_script()?.into(),
+                token: None,
+            }],
+            lock_time: 0,
+        };
+        assert!(filter.matches_tx(&funding_tx));
+        // the funding output was inserted, so spending it matches without any pubkey push
+        let spending_tx = UnhashedTx {
+            inputs: vec![TxInput::new(
+                TxOutpoint {
+                    tx_hash: funding_tx.txid(),
+                    vout: 0,
+                },
+                Script::default(),
+                0,
+            )],
+            outputs: vec![],
+            ..funding_tx.clone()
+        };
+        assert!(filter.matches_tx(&spending_tx));
+        let unrelated_tx = UnhashedTx {
+            lock_time: 1,
+            outputs: vec![],
+            ..funding_tx
+        };
+        assert!(!filter.matches_tx(&unrelated_tx));
+        Ok(())
+    }
+}
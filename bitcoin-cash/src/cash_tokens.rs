@@ -0,0 +1,375 @@
+use crate::error::{Error, Result};
+use crate::{BitcoinCode, ByteArray, Hashed, NftCommitment, Sha256d, TxOutpoint, VarInt};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Read;
+
+// the first byte of the locking bytecode of outputs carrying tokens
+pub const PREFIX_TOKEN: u8 = 0xef;
+pub const MAX_NFT_COMMITMENT_LENGTH: usize = 40;
+pub const MAX_FUNGIBLE_TOKEN_AMOUNT: u64 = i64::MAX as u64;
+
+const RESERVED_BIT: u8 = 0x80;
+const HAS_COMMITMENT_LENGTH: u8 = 0x40;
+const HAS_NFT: u8 = 0x20;
+const HAS_AMOUNT: u8 = 0x10;
+const CAPABILITY_MASK: u8 = 0x0f;
+
+// the txid of the tx whose input 0 spent the category's genesis outpoint
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct TokenCategory(Sha256d);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum NftCapability {
+    // immutable
+    None,
+    // the commitment can be changed when spending the NFT
+    Mutable,
+    // can create new NFTs of the category
+    Minting,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Nft {
+    pub capability: NftCapability,
+    pub commitment: NftCommitment,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct TokenData {
+    pub category: TokenCategory,
+    // fungible tokens, 0 if there are none
+    pub amount: u64,
+    pub nft: Option<Nft>,
+}
+
+impl TokenCategory {
+    pub fn from_hash(hash: Sha256d) -> Self {
+        TokenCategory(hash)
+    }
+
+    // categories can only be created by spending the output at index 0 of a tx, as input 0
+    pub fn from_genesis_outpoint(outpoint: &TxOutpoint) -> Result<Self> {
+        if outpoint.vout != 0 {
+            return Err(Error::InvalidTokenGenesis {
+                reason: "genesis outpoint must have index 0",
+            });
+        }
+        Ok(TokenCategory(outpoint.tx_hash.clone()))
+    }
+
+    pub fn hash(&self) -> &Sha256d {
+        &self.0
+    }
+}
+
+// shown like txids, i.e. byte-reversed
+impl fmt::Display for TokenCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.to_hex_le())
+    }
+}
+
+impl NftCapability {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            NftCapability::None => 0,
+            NftCapability::Mutable => 1,
+            NftCapability::Minting => 2,
+        }
+    }
+
+    pub fn from_u8(capability: u8) -> Option<Self> {
+        match capability {
+            0 => Some(NftCapability::None),
+            1 => Some(NftCapability::Mutable),
+            2 => Some(NftCapability::Minting),
+            _ => None,
+        }
+    }
+}
+
+impl TokenData {
+    pub fn fungible(category: TokenCategory, amount: u64) -> Self {
+        TokenData {
+            category,
+            amount,
+            nft: None,
+        }
+    }
+
+    pub fn nft(
+        category: TokenCategory,
+        capability: NftCapability,
+        commitment: NftCommitment,
+    ) -> Self {
+        TokenData {
+            category,
+            amount: 0,
+            nft: Some(Nft {
+                capability,
+                commitment,
+            }),
+        }
+    }
+
+    // what OP_UTXOTOKENCATEGORY and OP_OUTPUTTOKENCATEGORY push: the category in internal
+    // byte order, plus the capability of mutable and minting NFTs
+    pub fn introspection_category(&self) -> Vec<u8> {
+        let mut category = self.category.0.as_slice().to_vec();
+        match &self.nft {
+            Some(nft) if nft.capability != NftCapability::None => {
+                category.push(nft.capability.to_u8())
+            }
+            _ => {}
+        }
+        category
+    }
+
+    // what OP_UTXOTOKENCOMMITMENT and OP_OUTPUTTOKENCOMMITMENT push
+    pub fn introspection_commitment(&self) -> &[u8] {
+        match &self.nft {
+            Some(nft) => nft.commitment.as_slice(),
+            None => &[],
+        }
+    }
+
+    fn bitfield(&self) -> u8 {
+        let mut bitfield = 0;
+        if let Some(nft) = &self.nft {
+            bitfield |= HAS_NFT | nft.capability.to_u8();
+            if !nft.commitment.is_empty() {
+                bitfield |= HAS_COMMITMENT_LENGTH;
+            }
+        }
+        if self.amount > 0 {
+            bitfield |= HAS_AMOUNT;
+        }
+        bitfield
+    }
+
+    // the encoding rules; token data violating them can't be serialized faithfully
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason| Err(Error::InvalidTokenPrefix { reason });
+        if self.nft.is_none() && self.amount == 0 {
+            return invalid("token prefix must have an NFT or a fungible amount");
+        }
+        if self.amount > MAX_FUNGIBLE_TOKEN_AMOUNT {
+            return invalid("fungible amount exceeds the maximum");
+        }
+        Ok(())
+    }
+}
+
+impl BitcoinCode for TokenData {
+    fn ser(&self) -> ByteArray {
+        let mut bytes = Vec::with_capacity(self.encoded_size());
+        bytes.push(PREFIX_TOKEN);
+        bytes.extend_from_slice(self.category.0.as_slice());
+        bytes.push(self.bitfield());
+        if let Some(nft) = &self.nft {
+            if !nft.commitment.is_empty() {
+                bytes.extend_from_slice(&VarInt(nft.commitment.len() as u64).ser());
+                bytes.extend_from_slice(&nft.commitment);
+            }
+        }
+        if self.amount > 0 {
+            bytes.extend_from_slice(&VarInt(self.amount).ser());
+        }
+        ByteArray::new("token_prefix", bytes)
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (token, rest) = Self::deser_slice(&data)?;
+        let prefix_len = data.len() - rest.len();
+        let (_, rest) = data.split(prefix_len)?;
+        Ok((token, rest))
+    }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let invalid = |reason| Error::InvalidTokenPrefix { reason };
+        if data.first() != Some(&PREFIX_TOKEN) {
+            return Err(invalid("token prefix must start with 0xef"));
+        }
+        if data.len() < 34 {
+            return Err(invalid("token prefix is truncated"));
+        }
+        let category = TokenCategory(Sha256d::from_slice(&data[1..33])?);
+        let bitfield = data[33];
+        let mut rest = &data[34..];
+        if bitfield & RESERVED_BIT != 0 {
+            return Err(invalid("reserved token bit is set"));
+        }
+        let has_nft = bitfield & HAS_NFT != 0;
+        let capability = NftCapability::from_u8(bitfield & CAPABILITY_MASK)
+            .ok_or_else(|| invalid("unknown NFT capability"))?;
+        if !has_nft && (capability != NftCapability::None || bitfield & HAS_COMMITMENT_LENGTH != 0)
+        {
+            return Err(invalid("capability or commitment without an NFT"));
+        }
+        let mut commitment = NftCommitment::empty();
+        if bitfield & HAS_COMMITMENT_LENGTH != 0 {
+            let (VarInt(length), after_length) = VarInt::deser_slice(rest)?;
+            if length == 0 || length as usize > MAX_NFT_COMMITMENT_LENGTH {
+                return Err(invalid("NFT commitment must be 1 to 40 bytes"));
+            }
+            if after_length.len() < length as usize {
+                return Err(invalid("token prefix is truncated"));
+            }
+            commitment = NftCommitment::new(after_length[..length as usize].to_vec())?;
+            rest = &after_length[length as usize..];
+        }
+        let mut amount = 0;
+        if bitfield & HAS_AMOUNT != 0 {
+            let (VarInt(parsed_amount), after_amount) = VarInt::deser_slice(rest)?;
+            if parsed_amount == 0 || parsed_amount > MAX_FUNGIBLE_TOKEN_AMOUNT {
+                return Err(invalid("fungible amount out of range"));
+            }
+            amount = parsed_amount;
+            rest = after_amount;
+        }
+        if !has_nft && amount == 0 {
+            return Err(invalid(
+                "token prefix must have an NFT or a fungible amount",
+            ));
+        }
+        let nft = if has_nft {
+            Some(Nft {
+                capability,
+                commitment,
+            })
+        } else {
+            None
+        };
+        let token = TokenData {
+            category,
+            amount,
+            nft,
+        };
+        Ok((token, rest))
+    }
+
+    // reads the fields the bitfield announces, then parses them like `deser_slice`
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut bytes = vec![0; 34];
+        reader.read_exact(&mut bytes)?;
+        let bitfield = bytes[33];
+        if bitfield & HAS_COMMITMENT_LENGTH != 0 {
+            let length = VarInt::decode_from(reader)?;
+            if length.0 as usize > MAX_NFT_COMMITMENT_LENGTH {
+                return Err(Error::InvalidTokenPrefix {
+                    reason: "NFT commitment must be 1 to 40 bytes",
+                });
+            }
+            bytes.extend_from_slice(&length.ser());
+            let mut commitment = vec![0; length.0 as usize];
+            reader.read_exact(&mut commitment)?;
+            bytes.extend_from_slice(&commitment);
+        }
+        if bitfield & HAS_AMOUNT != 0 {
+            bytes.extend_from_slice(&VarInt::decode_from(reader)?.ser());
+        }
+        Self::deser_slice(&bytes).map(|(token, _)| token)
+    }
+
+    fn encoded_size(&self) -> usize {
+        let commitment_size = match &self.nft {
+            Some(nft) if !nft.commitment.is_empty() => {
+                VarInt(nft.commitment.len() as u64).encoded_size() + nft.commitment.len()
+            }
+            _ => 0,
+        };
+        let amount_size = if self.amount > 0 {
+            VarInt(self.amount).encoded_size()
+        } else {
+            0
+        };
+        1 + 32 + 1 + commitment_size + amount_size
+    }
+}
+
+// codec for the token prefix of the spent output in sig hash preimages, which is left out
+// if it has no tokens
+pub mod token_prefix_code {
+    use super::{TokenData, PREFIX_TOKEN};
+    use crate::error::Result;
+    use crate::{BitcoinCode, ByteArray, PeekReader};
+    use std::io::Read;
+
+    pub fn ser(token: &Option<TokenData>) -> ByteArray {
+        match token {
+            Some(token) => token.ser(),
+            None => ByteArray::from_slice_unnamed(&[]),
+        }
+    }
+
+    pub fn deser_rest(data: ByteArray) -> Result<(Option<TokenData>, ByteArray)> {
+        if data.first() != Some(&PREFIX_TOKEN) {
+            return Ok((None, data));
+        }
+        let (token, rest) = TokenData::deser_rest(data)?;
+        Ok((Some(token), rest))
+    }
+
+    pub fn decode_from<R: Read>(reader: &mut PeekReader<R>) -> Result<Option<TokenData>> {
+        if reader.peek()? != Some(PREFIX_TOKEN) {
+            return Ok(None);
+        }
+        Ok(Some(TokenData::decode_from(reader)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NftCapability, TokenCategory, TokenData};
+    use crate::error::Result;
+    use crate::{BitcoinCode, ByteArray, NftCommitment, Script, Sha256d, TxOutput};
+
+    #[test]
+    fn test_token_output() -> Result<()> {
+        let commitment = NftCommitment::new(vec![0xcc])?;
+        let category = TokenCategory::from_hash(Sha256d::new([0xbb; 32]));
+        let token = TokenData {
+            amount: 253,
+            ..TokenData::nft(category.clone(), NftCapability::Minting, commitment)
+        };
+        let output = TxOutput {
+            value: 1000,
+            script: Script::from_ops(vec![]),
+            token: Some(token),
+        };
+        let ser = output.ser();
+        // value, locking bytecode length, prefix, category, bitfield, commitment, amount
+        let expected = format!("e80300000000000027ef{}7201ccfdfd00", "bb".repeat(32));
+        assert_eq!(hex::encode(&ser), expected);
+        assert_eq!(output.encoded_size(), ser.len());
+        assert_eq!(TxOutput::deser(ser)?, output);
+
+        let token = output.token.as_ref().unwrap();
+        let category_bytes = [[0xbb; 32].as_ref(), &[0x02]].concat();
+        assert_eq!(token.introspection_category(), category_bytes);
+        assert_eq!(token.introspection_commitment(), &[0xcc]);
+
+        let fungible = TokenData::fungible(category, 1);
+        let bytes = [fungible.ser().to_vec(), vec![0x51]].concat();
+        let (parsed, script) = TokenData::deser_slice(&bytes)?;
+        assert_eq!((parsed, script), (fungible.clone(), &[0x51][..]));
+
+        let tokens = vec![token.clone(), fungible];
+        let mut reader = std::io::Cursor::new([tokens.ser().to_vec(), vec![0x51]].concat());
+        assert_eq!(Vec::<TokenData>::decode_from(&mut reader)?, tokens);
+        assert_eq!(reader.position() as usize, tokens.encoded_size());
+
+        for invalid in &[
+            // reserved bit, capability without NFT, empty commitment, zero amount
+            "80", "01", "6000", "1000",
+        ] {
+            let prefix = format!("ef{}{}", "bb".repeat(32), invalid);
+            let bytes = ByteArray::from_slice_unnamed(&hex::decode(prefix).unwrap());
+            assert!(TokenData::deser(bytes.clone()).is_err(), "{}", invalid);
+            let mut reader = std::io::Cursor::new(bytes.to_vec());
+            assert!(TokenData::decode_from(&mut reader).is_err(), "{}", invalid);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use crate::error::{Error, Result};
+use crate::{Address, FeeRate, Hashed, Script, Sha256, Sha256d, TokenData, Tx, TxOutpoint};
+use std::future::Future;
+use std::pin::Pin;
+
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Utxo {
+    pub outpoint: TxOutpoint,
+    pub value: u64,
+    // None while the tx is unconfirmed
+    pub height: Option<u32>,
+    // CashTokens on the output, which spending it without a token output would burn
+    pub token: Option<TokenData>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxHistoryEntry {
+    pub txid: Sha256d,
+    // None while the tx is unconfirmed
+    pub height: Option<u32>,
+}
+
+// a source of chain data for wallets, e.g. an Electrum server or a node's RPC interface
+pub trait ChainBackend: Send + Sync {
+    // unspent outputs locked by `lock_script`, including unconfirmed ones if the backend
+    // sees the mempool; RpcClient only sees confirmed outputs
+    fn utxos<'a>(&'a self, lock_script: &'a Script) -> BackendFuture<'a, Vec<Utxo>>;
+
+    fn tx<'a>(&'a self, txid: &'a Sha256d) -> BackendFuture<'a, Tx>;
+
+    // returns the txid the backend reports for the broadcast tx
+    fn broadcast_raw_tx<'a>(&'a self, raw_tx: &'a [u8]) -> BackendFuture<'a, Sha256d>;
+
+    // the fee rate needed for a tx to confirm within `target_blocks`
+    fn estimate_fee(&self, target_blocks: u32) -> BackendFuture<'_, FeeRate>;
+
+    // txs spending from or paying to `lock_script`; not every backend indexes these
+    fn history<'a>(&'a self, _lock_script: &'a Script) -> BackendFuture<'a, Vec<TxHistoryEntry>> {
+        Box::pin(async move { Err(Error::UnsupportedByBackend { method: "history" }) })
+    }
+
+    fn address_utxos<'a>(&'a self, address: &'a Address<'_>) -> BackendFuture<'a, Vec<Utxo>> {
+        let lock_script: Script = address.into();
+        Box::pin(async move { self.utxos(&lock_script).await })
+    }
+
+    fn broadcast_tx<'a>(&'a self, tx: &'a Tx) -> BackendFuture<'a, Sha256d> {
+        self.broadcast_raw_tx(tx.raw())
+    }
+}
+
+// Electrum indexes outputs by the SHA256 of their lock script; its hex form, as used by
+// the protocol, is `to_hex_le`
+pub fn script_hash(lock_script: &Script) -> Sha256 {
+    Sha256::digest(lock_script.ser_ops())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{script_hash, BackendFuture, ChainBackend, Utxo};
+    use crate::error::Result;
+    use crate::{
+        Address, ByteArray, FeeRate, Hashed, Script, Sha256d, Tx, TxOutpoint, TxOutput, UnhashedTx,
+    };
+    use std::future::Future;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    struct MockBackend {
+        tx: Tx,
+    }
+
+    impl ChainBackend for MockBackend {
+        fn utxos<'a>(&'a self, lock_script: &'a Script) -> BackendFuture<'a, Vec<Utxo>> {
+            let utxos = self
+                .tx
+                .outputs()
+                .iter()
+                .enumerate()
+                .filter(|(_, output)| &output.script == lock_script)
+                .map(|(vout, output)| Utxo {
+                    outpoint: TxOutpoint {
+                        tx_hash: self.tx.hash().clone(),
+                        vout: vout as u32,
+                    },
+                    value: output.value,
+                    height: None,
+                    token: output.token.clone(),
+                })
+                .collect();
+            Box::pin(async move { Ok(utxos) })
+        }
+
+        fn tx<'a>(&'a self, _txid: &'a Sha256d) -> BackendFuture<'a, Tx> {
+            Box::pin(async move { Ok(self.tx.clone()) })
+        }
+
+        fn broadcast_raw_tx<'a>(&'a self, raw_tx: &'a [u8]) -> BackendFuture<'a, Sha256d> {
+            Box::pin(async move { Ok(Sha256d::digest(raw_tx.to_vec())) })
+        }
+
+        fn estimate_fee(&self, _target_blocks: u32) -> BackendFuture<'_, FeeRate> {
+            Box::pin(async move { Ok(FeeRate::from_sats_per_byte(1)) })
+        }
+    }
+
+    // a waker which does nothing, as Waker::noop needs a recent toolchain
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        // the vtable functions ignore the data pointer, so a null pointer is sound
+        unsafe { Waker::from_raw(clone(std::ptr::null())) }
+    }
+
+    // the mock never waits, so a single poll always completes its futures
+    fn poll_ready<T>(future: impl Future<Output = T>) -> T {
+        let mut future = Box::pin(future);
+        let waker = noop_waker();
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("mock future is pending"),
+        }
+    }
+
+    #[test]
+    fn test_script_hash() -> Result<()> {
+        let lock_script = Script::deser_ops(ByteArray::from_slice_unnamed(&hex::decode(
+            "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac",
+        )?))?;
+        assert_eq!(
+            script_hash(&lock_script).to_hex_le(),
+            "8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_provided_methods() -> Result<()> {
+        let address =
+            Address::from_cash_addr("bitcoincash:qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6a")?;
+        let lock_script: Script = (&address).into();
+        let tx = UnhashedTx {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![TxOutput {
+                value: 5000,
+                script: lock_script,
+                token: None,
+            }],
+            lock_time: 0,
+        }
+        .hashed();
+        let backend = MockBackend { tx };
+        let utxos = poll_ready(backend.address_utxos(&address))?;
+        assert_eq!(utxos.len(), 1);
+        assert_eq!(&utxos[0].outpoint.tx_hash, backend.tx.hash());
+        let txid = poll_ready(backend.broadcast_tx(&backend.tx))?;
+        assert_eq!(&txid, backend.tx.hash());
+        Ok(())
+    }
+}
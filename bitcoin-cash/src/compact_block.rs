@@ -0,0 +1,487 @@
+use crate::encoding_utils::encode_var_int;
+use crate::error::{Error, Result};
+use crate::{
+    BitcoinCode, Block, BlockHeader, ByteArray, Hashed, MessagePayload, Sha256, Sha256d, Tx, VarInt,
+};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+
+pub type ShortTxId = [u8; 6];
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct SendCmpct {
+    pub announce: bool,
+    pub version: u64,
+}
+
+// BIP152 cmpctblock: short ids for the txs the peer likely has, the rest prefilled
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Debug, Clone)]
+pub struct CmpctBlock {
+    pub header: BlockHeader,
+    pub nonce: u64,
+    pub short_ids: Vec<ShortTxId>,
+    pub prefilled_txs: Vec<PrefilledTx>,
+}
+
+// `index` is differential: the distance to the previous prefilled tx's position, minus one
+#[derive(PartialEq, Debug, Clone)]
+pub struct PrefilledTx {
+    pub index: u64,
+    pub tx: Tx,
+}
+
+// `indexes` are differential, like PrefilledTx::index
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct GetBlockTxn {
+    pub block_hash: Sha256d,
+    pub indexes: Vec<u64>,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Debug, Clone)]
+pub struct BlockTxn {
+    pub block_hash: Sha256d,
+    pub txs: Vec<Tx>,
+}
+
+// a block being reconstructed from a CmpctBlock, with None for the txs still missing
+#[derive(PartialEq, Debug, Clone)]
+pub struct PartialBlock {
+    pub header: BlockHeader,
+    pub txs: Vec<Option<Tx>>,
+}
+
+impl CmpctBlock {
+    pub fn new(block: &Block, nonce: u64, prefilled_positions: &[usize]) -> Result<Self> {
+        let mut cmpct_block = CmpctBlock {
+            header: block.header.clone(),
+            nonce,
+            short_ids: Vec::new(),
+            prefilled_txs: Vec::new(),
+        };
+        let mut next_position = 0;
+        for &position in prefilled_positions {
+            if position < next_position || position >= block.txs.len() {
+                return Err(Error::InvalidCompactBlock {
+                    reason: "prefilled positions must be ascending and within the block",
+                });
+            }
+            cmpct_block.prefilled_txs.push(PrefilledTx {
+                index: (position - next_position) as u64,
+                tx: block.txs[position].clone(),
+            });
+            next_position = position + 1;
+        }
+        let prefilled = prefilled_positions.iter().collect::<HashSet<_>>();
+        let (k0, k1) = cmpct_block.short_id_keys();
+        cmpct_block.short_ids = block
+            .txs
+            .iter()
+            .enumerate()
+            .filter(|(position, _)| !prefilled.contains(position))
+            .map(|(_, tx)| short_tx_id(k0, k1, tx.hash()))
+            .collect();
+        Ok(cmpct_block)
+    }
+
+    // the SipHash keys are the first two words of SHA256(header || nonce)
+    pub fn short_id_keys(&self) -> (u64, u64) {
+        let hash = Sha256::digest(self.header.ser().concat(self.nonce.ser()));
+        let word = |idx: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&hash.as_slice()[idx * 8..idx * 8 + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        (word(0), word(1))
+    }
+
+    pub fn short_tx_id(&self, txid: &Sha256d) -> ShortTxId {
+        let (k0, k1) = self.short_id_keys();
+        short_tx_id(k0, k1, txid)
+    }
+
+    pub fn prefilled_positions(&self) -> Result<Vec<usize>> {
+        absolute_positions(self.prefilled_txs.iter().map(|prefilled| prefilled.index))
+    }
+
+    // fills what it can from the prefilled txs and `candidates`, e.g. the mempool;
+    // colliding short ids are left missing, so they get requested
+    pub fn to_partial_block<'a>(
+        &self,
+        candidates: impl IntoIterator<Item = &'a Tx>,
+    ) -> Result<PartialBlock> {
+        let num_txs = self.short_ids.len() + self.prefilled_txs.len();
+        let mut txs = vec![None; num_txs];
+        for (position, prefilled) in self
+            .prefilled_positions()?
+            .into_iter()
+            .zip(&self.prefilled_txs)
+        {
+            let slot = txs.get_mut(position).ok_or(Error::InvalidCompactBlock {
+                reason: "prefilled position out of range",
+            })?;
+            *slot = Some(prefilled.tx.clone());
+        }
+        let mut short_id_positions = HashMap::new();
+        let mut collisions = HashSet::new();
+        let open_positions = (0..num_txs).filter(|&position| txs[position].is_none());
+        for (short_id, position) in self.short_ids.iter().zip(open_positions) {
+            if short_id_positions.insert(*short_id, position).is_some() {
+                collisions.insert(*short_id);
+            }
+        }
+        let (k0, k1) = self.short_id_keys();
+        for tx in candidates {
+            let short_id = short_tx_id(k0, k1, tx.hash());
+            if collisions.contains(&short_id) {
+                continue;
+            }
+            if let Some(&position) = short_id_positions.get(&short_id) {
+                match &txs[position] {
+                    Some(filled) if filled.hash() != tx.hash() => {
+                        txs[position] = None;
+                        collisions.insert(short_id);
+                    }
+                    _ => txs[position] = Some(tx.clone()),
+                }
+            }
+        }
+        Ok(PartialBlock {
+            header: self.header.clone(),
+            txs,
+        })
+    }
+}
+
+impl GetBlockTxn {
+    // positions may be given in any order; duplicates are requested only once
+    pub fn new(block_hash: Sha256d, positions: &[usize]) -> Self {
+        let mut positions = positions.to_vec();
+        positions.sort_unstable();
+        positions.dedup();
+        let mut next_position = 0;
+        let indexes = positions
+            .iter()
+            .map(|&position| {
+                let index = position - next_position;
+                next_position = position + 1;
+                index as u64
+            })
+            .collect();
+        GetBlockTxn {
+            block_hash,
+            indexes,
+        }
+    }
+
+    pub fn positions(&self) -> Result<Vec<usize>> {
+        absolute_positions(self.indexes.iter().cloned())
+    }
+}
+
+impl BlockTxn {
+    // answers a getblocktxn request from the full block
+    pub fn from_block(block: &Block, request: &GetBlockTxn) -> Result<Self> {
+        let txs = request
+            .positions()?
+            .into_iter()
+            .map(|position| {
+                block
+                    .txs
+                    .get(position)
+                    .cloned()
+                    .ok_or(Error::InvalidCompactBlock {
+                        reason: "requested position out of range",
+                    })
+            })
+            .collect::<Result<_>>()?;
+        Ok(BlockTxn {
+            block_hash: request.block_hash.clone(),
+            txs,
+        })
+    }
+}
+
+impl PartialBlock {
+    pub fn missing_positions(&self) -> Vec<usize> {
+        (0..self.txs.len())
+            .filter(|&position| self.txs[position].is_none())
+            .collect()
+    }
+
+    pub fn get_block_txn(&self) -> GetBlockTxn {
+        GetBlockTxn::new(self.header.block_hash(), &self.missing_positions())
+    }
+
+    // also verifies the merkle root, which catches txs wrongly matched by short id
+    pub fn complete(self, block_txn: BlockTxn) -> Result<Block> {
+        if block_txn.block_hash != self.header.block_hash() {
+            return Err(Error::InvalidCompactBlock {
+                reason: "blocktxn is for a different block",
+            });
+        }
+        let mut missing_txs = block_txn.txs.into_iter();
+        let txs = self
+            .txs
+            .into_iter()
+            .map(|tx| tx.or_else(|| missing_txs.next()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::InvalidCompactBlock {
+                reason: "blocktxn has too few txs",
+            })?;
+        if missing_txs.next().is_some() {
+            return Err(Error::InvalidCompactBlock {
+                reason: "blocktxn has too many txs",
+            });
+        }
+        let block = Block {
+            header: self.header,
+            txs,
+        };
+        block.verify_merkle_root()?;
+        Ok(block)
+    }
+}
+
+impl BitcoinCode for PrefilledTx {
+    fn ser(&self) -> ByteArray {
+        ByteArray::new("index", encode_var_int(self.index)).concat(self.tx.ser())
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (index, rest) = read_index(data)?;
+        let (tx, rest) = Tx::deser_rest(rest)?;
+        Ok((PrefilledTx { index, tx }, rest))
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        Ok(PrefilledTx {
+            index: VarInt::decode_from(reader)?.0,
+            tx: Tx::decode_from(reader)?,
+        })
+    }
+}
+
+impl BitcoinCode for GetBlockTxn {
+    fn ser(&self) -> ByteArray {
+        let indexes = self
+            .indexes
+            .iter()
+            .map(|&index| ByteArray::new("index", encode_var_int(index)));
+        self.block_hash
+            .ser()
+            .concat(ByteArray::new(
+                "size",
+                encode_var_int(self.indexes.len() as u64),
+            ))
+            .concat(ByteArray::from_parts(indexes))
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (block_hash, rest) = Sha256d::deser_rest(data)?;
+        let (num_indexes, mut rest) = read_index(rest)?;
+        // every index takes at least a byte, which bounds the allocation
+        let mut indexes = Vec::with_capacity((num_indexes as usize).min(rest.len()));
+        for _ in 0..num_indexes {
+            let (index, next) = read_index(rest)?;
+            indexes.push(index);
+            rest = next;
+        }
+        Ok((
+            GetBlockTxn {
+                block_hash,
+                indexes,
+            },
+            rest,
+        ))
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let block_hash = Sha256d::decode_from(reader)?;
+        let VarInt(num_indexes) = VarInt::decode_from(reader)?;
+        // the count isn't backed by data yet, so the allocation grows as indexes are read
+        let mut indexes = Vec::new();
+        for _ in 0..num_indexes {
+            indexes.push(VarInt::decode_from(reader)?.0);
+        }
+        Ok(GetBlockTxn {
+            block_hash,
+            indexes,
+        })
+    }
+}
+
+impl MessagePayload for SendCmpct {
+    const COMMAND: &'static str = "sendcmpct";
+}
+
+impl MessagePayload for CmpctBlock {
+    const COMMAND: &'static str = "cmpctblock";
+}
+
+impl MessagePayload for GetBlockTxn {
+    const COMMAND: &'static str = "getblocktxn";
+}
+
+impl MessagePayload for BlockTxn {
+    const COMMAND: &'static str = "blocktxn";
+}
+
+fn read_index(data: ByteArray) -> Result<(u64, ByteArray)> {
+    let (VarInt(index), rest) = VarInt::deser_rest(data)?;
+    Ok((index, rest))
+}
+
+fn absolute_positions(indexes: impl Iterator<Item = u64>) -> Result<Vec<usize>> {
+    let mut next_position = 0u64;
+    indexes
+        .map(|index| {
+            let position = next_position
+                .checked_add(index)
+                .filter(|&position| position < u32::MAX as u64)
+                .ok_or(Error::InvalidCompactBlock {
+                    reason: "differential index overflows",
+                })?;
+            next_position = position + 1;
+            Ok(position as usize)
+        })
+        .collect()
+}
+
+fn short_tx_id(k0: u64, k1: u64, txid: &Sha256d) -> ShortTxId {
+    let hash = siphash24(k0, k1, txid.as_slice());
+    let mut short_id = [0; 6];
+    short_id.copy_from_slice(&hash.to_le_bytes()[..6]);
+    short_id
+}
+
+fn siphash24(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+    let round = |v: &mut [u64; 4]| {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    };
+    let chunks = data.chunks_exact(8);
+    let remainder = chunks.remainder();
+    for chunk in chunks {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(chunk);
+        let word = u64::from_le_bytes(bytes);
+        v[3] ^= word;
+        round(&mut v);
+        round(&mut v);
+        v[0] ^= word;
+    }
+    let mut last = [0; 8];
+    last[..remainder.len()].copy_from_slice(remainder);
+    last[7] = data.len() as u8;
+    let word = u64::from_le_bytes(last);
+    v[3] ^= word;
+    round(&mut v);
+    round(&mut v);
+    v[0] ^= word;
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{siphash24, BlockTxn, CmpctBlock, GetBlockTxn};
+    use crate::error::Result;
+    use crate::{
+        merkle_root, BitcoinCode, Block, BlockHeader, Sha256d, TxInput, TxOutpoint, TxOutput,
+        UnhashedTx,
+    };
+    use std::io::Cursor;
+
+    #[test]
+    fn test_siphash24() {
+        let (k0, k1) = (0x0706_0504_0302_0100, 0x0f0e_0d0c_0b0a_0908);
+        assert_eq!(siphash24(k0, k1, &[]), 0x726f_db47_dd0e_0e31);
+        let data = (0..15).collect::<Vec<u8>>();
+        assert_eq!(siphash24(k0, k1, &data), 0xa129_ca61_49be_45e5);
+    }
+
+    #[test]
+    fn test_reconstruct_block() -> Result<()> {
+        let txs = (0..5)
+            .map(|vout| {
+                UnhashedTx {
+                    version: 1,
+                    inputs: vec![TxInput::new(
+                        TxOutpoint {
+                            vout,
+                            ..Default::default()
+                        },
+                        Default::default(),
+                        0xffff_ffff,
+                    )],
+                    outputs: vec![TxOutput {
+                        value: 1000,
+                        script: Default::default(),
+                        token: None,
+                    }],
+                    lock_time: 0,
+                }
+                .hashed()
+            })
+            .collect::<Vec<_>>();
+        let block = Block {
+            header: BlockHeader {
+                merkle_root: merkle_root(txs.iter().map(|tx| tx.hash())),
+                ..Default::default()
+            },
+            txs,
+        };
+        let cmpct_block = CmpctBlock::new(&block, 7, &[0, 3])?;
+        assert_eq!(cmpct_block.prefilled_txs[1].index, 2);
+        let cmpct_block = CmpctBlock::deser(cmpct_block.ser())?;
+        assert_eq!(cmpct_block.prefilled_positions()?, vec![0, 3]);
+        let mut reader = Cursor::new(cmpct_block.ser().to_vec());
+        assert_eq!(CmpctBlock::decode_from(&mut reader)?, cmpct_block);
+
+        let mempool = [block.txs[1].clone(), block.txs[4].clone()];
+        let partial_block = cmpct_block.to_partial_block(&mempool)?;
+        assert_eq!(partial_block.missing_positions(), vec![2]);
+        let request = GetBlockTxn::deser(partial_block.get_block_txn().ser())?;
+        assert_eq!(request.positions()?, vec![2]);
+        let requests = vec![request.clone(), request.clone()];
+        let mut reader = Cursor::new(requests.ser().to_vec());
+        assert_eq!(Vec::<GetBlockTxn>::decode_from(&mut reader)?, requests);
+        let response = BlockTxn::from_block(&block, &request)?;
+        assert_eq!(partial_block.clone().complete(response)?, block);
+
+        let wrong_response =
+            BlockTxn::from_block(&block, &GetBlockTxn::new(request.block_hash, &[1]))?;
+        assert!(partial_block.complete(wrong_response).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_block_txn_positions() -> Result<()> {
+        let block_hash = Sha256d::new([1; 32]);
+        let request = GetBlockTxn::new(block_hash.clone(), &[5, 2, 5, 0, 2]);
+        assert_eq!(request.indexes, vec![0, 1, 2]);
+        assert_eq!(request.positions()?, vec![0, 2, 5]);
+        assert_eq!(GetBlockTxn::new(block_hash, &[]).indexes, Vec::<u64>::new());
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use crate::error::{Error, Result};
+use crate::{
+    encoding_utils::encode_var_int, FeeRate, InputReference, Script, Signatory, TaggedScript,
+    TxBuilder, UnsignedTx, UnsignedTxInput,
+};
+
+pub const MAX_STANDARD_TX_SIZE: usize = 100_000;
+
+pub struct ConsolidationInput<S: Signatory> {
+    pub input: UnsignedTxInput,
+    pub lock_script: TaggedScript<S::Script>,
+    pub signatory: S,
+}
+
+pub struct Consolidation<'b, S> {
+    pub unsigned_tx: UnsignedTx<'b>,
+    pub input_refs: Vec<InputReference<S>>,
+}
+
+pub struct ConsolidationBuilder {
+    destination: Script,
+    fee_rate: FeeRate,
+    max_tx_size: usize,
+}
+
+impl ConsolidationBuilder {
+    pub fn new(destination: Script, fee_rate: FeeRate) -> Self {
+        ConsolidationBuilder {
+            destination,
+            fee_rate,
+            max_tx_size: MAX_STANDARD_TX_SIZE,
+        }
+    }
+
+    pub fn set_max_tx_size(&mut self, max_tx_size: usize) {
+        self.max_tx_size = max_tx_size;
+    }
+
+    pub fn build<'b, S>(
+        &self,
+        utxos: Vec<ConsolidationInput<S>>,
+    ) -> Result<Vec<Consolidation<'b, S>>>
+    where
+        S: Signatory + Clone + Sync + Send + 'b,
+    {
+        let base_size = self.tx_builder()?.estimated_size();
+        let mut batches = Vec::new();
+        let mut batch = Vec::new();
+        let mut batch_inputs_size = 0;
+        for utxo in utxos {
+            let mut single = self.tx_builder()?;
+            single.add_input(
+                utxo.input.clone(),
+                utxo.lock_script.clone(),
+                utxo.signatory.clone(),
+            );
+            let input_size = single.estimated_size() - base_size;
+            if base_size + input_size > self.max_tx_size {
+                return Err(Error::TxTooLarge {
+                    size: base_size + input_size,
+                    max_size: self.max_tx_size,
+                });
+            }
+            if !batch.is_empty()
+                && self.tx_size(base_size, batch.len() + 1, batch_inputs_size + input_size)
+                    > self.max_tx_size
+            {
+                batches.push(std::mem::take(&mut batch));
+                batch_inputs_size = 0;
+            }
+            batch.push(utxo);
+            batch_inputs_size += input_size;
+        }
+        if !batch.is_empty() {
+            batches.push(batch);
+        }
+        batches
+            .into_iter()
+            .map(|batch| self.build_batch(batch))
+            .collect()
+    }
+
+    fn tx_builder<'b>(&self) -> Result<TxBuilder<'b>> {
+        let mut tx_builder = TxBuilder::new_simple();
+        tx_builder.set_fee_rate(self.fee_rate);
+        tx_builder.add_sweep_output(self.destination.clone())?;
+        Ok(tx_builder)
+    }
+
+    fn tx_size(&self, base_size: usize, num_inputs: usize, inputs_size: usize) -> usize {
+        // base_size already accounts for a one byte input count
+        base_size + inputs_size + encode_var_int(num_inputs as u64).len() - 1
+    }
+
+    fn build_batch<'b, S>(&self, batch: Vec<ConsolidationInput<S>>) -> Result<Consolidation<'b, S>>
+    where
+        S: Signatory + Clone + Sync + Send + 'b,
+    {
+        let mut tx_builder = self.tx_builder()?;
+        let input_refs = batch
+            .into_iter()
+            .map(|utxo| tx_builder.add_input(utxo.input, utxo.lock_script, utxo.signatory))
+            .collect();
+        Ok(Consolidation {
+            unsigned_tx: tx_builder.build()?,
+            input_refs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConsolidationBuilder, ConsolidationInput};
+    use crate::error::{Error, Result};
+    use crate::{
+        Address, P2PKHSignatory, Prefix, Pubkey, SigHashFlags, TxOutpoint, UnsignedTxInput,
+        DEFAULT_FEE_RATE,
+    };
+
+    #[test]
+    fn test_consolidation_batches() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let utxos = || -> Result<Vec<_>> {
+            (0..5)
+                .map(|vout| {
+                    Ok(ConsolidationInput {
+                        input: UnsignedTxInput {
+                            prev_out: TxOutpoint {
+                                vout,
+                                ..Default::default()
+                            },
+                            sequence: 0xffff_ffff,
+                            value: 10_000,
+                            token: None,
+                        },
+                        lock_script: address.p2pkh_script()?,
+                        signatory: P2PKHSignatory {
+                            pubkey,
+                            sig_hash_flags: SigHashFlags::DEFAULT,
+                        },
+                    })
+                })
+                .collect()
+        };
+        let mut builder = ConsolidationBuilder::new(address.clone().into(), DEFAULT_FEE_RATE);
+        let consolidations = builder.build(utxos()?)?;
+        assert_eq!(consolidations.len(), 1);
+        assert_eq!(consolidations[0].input_refs.len(), 5);
+
+        // 44 bytes without inputs plus two 149 byte P2PKH inputs
+        builder.set_max_tx_size(44 + 2 * 149);
+        let consolidations = builder.build(utxos()?)?;
+        let batch_sizes = consolidations
+            .iter()
+            .map(|consolidation| consolidation.input_refs.len())
+            .collect::<Vec<_>>();
+        assert_eq!(batch_sizes, vec![2, 2, 1]);
+        for consolidation in &consolidations {
+            assert!(consolidation.unsigned_tx.estimated_size() <= 44 + 2 * 149);
+        }
+
+        builder.set_max_tx_size(100);
+        assert!(matches!(
+            builder.build(utxos()?),
+            Err(Error::TxTooLarge { .. })
+        ));
+        Ok(())
+    }
+}
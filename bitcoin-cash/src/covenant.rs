@@ -0,0 +1,103 @@
+use crate::error::Result;
+use crate::{
+    Address, BitcoinCode, Hashed, Op, Opcode, Ops, Prefix, Script, Sha256d, TaggedScript, TxOutput,
+    TxPreimage,
+};
+
+// an output paying `value` back to the P2SH address of the covenant's own redeem script,
+// the single output of "recreate myself" covenants
+#[derive(Clone, Debug, PartialEq)]
+pub struct CovenantRecurrence {
+    output: TxOutput,
+}
+
+impl CovenantRecurrence {
+    pub fn new<T: Ops>(redeem_script: &TaggedScript<T>, value: u64) -> Result<Self> {
+        let redeem_script = Script::new(redeem_script.ops().to_vec());
+        let address = Address::from_redeem_script(Prefix::default(), redeem_script)?;
+        Ok(CovenantRecurrence {
+            output: TxOutput {
+                value,
+                script: address.into(),
+                token: None,
+            },
+        })
+    }
+
+    pub fn output(&self) -> &TxOutput {
+        &self.output
+    }
+
+    pub fn expected_hash_outputs(&self) -> Sha256d {
+        Sha256d::digest(self.output.ser())
+    }
+
+    pub fn matches(&self, preimage: &TxPreimage) -> bool {
+        preimage.hash_outputs == self.expected_hash_outputs()
+    }
+
+    // expects the preimage's hashOutputs on top of the stack
+    pub fn verify_ops(&self) -> Vec<Op> {
+        vec![
+            self.output.ser().into(),
+            Op::Code(Opcode::OP_HASH256),
+            Op::Code(Opcode::OP_EQUALVERIFY),
+        ]
+    }
+
+    // expects the preimage's hashOutputs, then the 8-byte output value on top of the stack,
+    // for covenants which compute the recreated value in script
+    pub fn verify_ops_with_value_on_stack(&self) -> Vec<Op> {
+        vec![
+            self.output.script.ser().into(),
+            Op::Code(Opcode::OP_CAT),
+            Op::Code(Opcode::OP_HASH256),
+            Op::Code(Opcode::OP_EQUALVERIFY),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CovenantRecurrence;
+    use crate::error::Result;
+    use crate::{
+        BitcoinCode, Hashed, Op, Opcode, Script, Sha256d, SigHashFlags, TaggedOp, TaggedScript,
+        TxInput, TxOutpoint, UnhashedTx,
+    };
+
+    #[test]
+    fn test_covenant_recurrence() -> Result<()> {
+        let redeem_script =
+            TaggedScript::<Script>::new(vec![TaggedOp::from_op(Op::Code(Opcode::OP_1))]);
+        let recurrence = CovenantRecurrence::new(&redeem_script, 9_000)?;
+        let mut input = TxInput::new(TxOutpoint::default(), Script::default(), 0xffff_ffff);
+        input.value = Some(10_000);
+        input.lock_script = Some(redeem_script.clone().into());
+        let mut tx = UnhashedTx {
+            inputs: vec![input],
+            outputs: vec![recurrence.output().clone()],
+            ..Default::default()
+        };
+        let preimage = tx.preimages(&[SigHashFlags::DEFAULT]).remove(0).remove(0);
+        assert!(recurrence.matches(&preimage));
+        match &recurrence.verify_ops()[0] {
+            Op::PushByteArray { array, .. } => {
+                assert_eq!(Sha256d::digest(array.clone()), preimage.hash_outputs)
+            }
+            _ => panic!("expected a push of the serialized output"),
+        }
+        match &recurrence.verify_ops_with_value_on_stack()[0] {
+            Op::PushByteArray { array, .. } => {
+                let output = 9_000u64.ser().concat(array.clone());
+                assert_eq!(Sha256d::digest(output), preimage.hash_outputs)
+            }
+            _ => panic!("expected a push of the serialized output script"),
+        }
+
+        tx.outputs[0].value = 8_000;
+        let preimage = tx.preimages(&[SigHashFlags::DEFAULT]).remove(0).remove(0);
+        assert!(!recurrence.matches(&preimage));
+        Ok(())
+    }
+}
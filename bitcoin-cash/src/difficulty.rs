@@ -0,0 +1,157 @@
+use crate::error::{Error, Result};
+use crate::BlockHeader;
+use num::bigint::BigUint;
+use num::{One, Zero};
+
+// expands nBits into the full 256-bit target, rejecting negative, zero and overflowing targets
+pub fn compact_to_target(bits: u32) -> Result<BigUint> {
+    let exponent = bits >> 24;
+    let mantissa = bits & 0x007f_ffff;
+    let is_negative = bits & 0x0080_0000 != 0;
+    let is_overflow = mantissa != 0
+        && (exponent > 34
+            || (mantissa > 0xff && exponent > 33)
+            || (mantissa > 0xffff && exponent > 32));
+    if is_negative || is_overflow {
+        return Err(Error::InvalidCompactTarget { bits });
+    }
+    let target = if exponent <= 3 {
+        BigUint::from(mantissa >> (8 * (3 - exponent)))
+    } else {
+        BigUint::from(mantissa) << (8 * (exponent - 3)) as usize
+    };
+    if target.is_zero() {
+        return Err(Error::InvalidCompactTarget { bits });
+    }
+    Ok(target)
+}
+
+// the inverse of compact_to_target, truncating the target to three significant bytes
+pub fn target_to_compact(target: &BigUint) -> u32 {
+    let bytes = target.to_bytes_be();
+    if target.is_zero() {
+        return 0;
+    }
+    let mut size = bytes.len() as u32;
+    let mut mantissa = bytes
+        .iter()
+        .chain(std::iter::repeat(&0))
+        .take(3)
+        .fold(0u32, |mantissa, &byte| mantissa << 8 | byte as u32);
+    // the mantissa's top bit is the sign bit, so such targets need an extra byte
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    mantissa | size << 24
+}
+
+// the expected number of hashes to meet the target, 2^256 / (target + 1)
+pub fn target_work(target: &BigUint) -> BigUint {
+    (BigUint::one() << 256usize) / (target + BigUint::one())
+}
+
+// multiples of the minimum difficulty (bits 0x1d00ffff), computed like bitcoind's
+// getdifficulty so explorers show the same numbers
+pub fn difficulty(bits: u32) -> Result<f64> {
+    compact_to_target(bits)?;
+    let mut exponent = bits >> 24;
+    let mut difficulty = 0xffff as f64 / (bits & 0x00ff_ffff) as f64;
+    while exponent < 29 {
+        difficulty *= 256.0;
+        exponent += 1;
+    }
+    while exponent > 29 {
+        difficulty /= 256.0;
+        exponent -= 1;
+    }
+    Ok(difficulty)
+}
+
+// total work of consecutive headers, checking that each one connects to the one before
+// and meets its own target; whether the targets follow the DAA is up to HeaderChain
+pub fn chain_work<'a>(headers: impl IntoIterator<Item = &'a BlockHeader>) -> Result<BigUint> {
+    let mut work = BigUint::zero();
+    let mut prev_hash = None;
+    for header in headers {
+        let block_hash = header.block_hash();
+        if let Some(prev_hash) = prev_hash {
+            if header.prev_block != prev_hash {
+                return Err(Error::InvalidHeader {
+                    block_hash,
+                    reason: "doesn't connect to the previous header",
+                });
+            }
+        }
+        header.validate_pow()?;
+        work += header.work()?;
+        prev_hash = Some(block_hash);
+    }
+    Ok(work)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chain_work, compact_to_target, difficulty, target_to_compact, target_work};
+    use crate::error::{Error, Result};
+    use crate::{BlockHeader, Sha256d};
+    use num::bigint::BigUint;
+
+    #[test]
+    fn test_compact_to_target() -> Result<()> {
+        assert_eq!(
+            compact_to_target(0x1d00_ffff)?,
+            BigUint::from(0xffffu32) << 208usize
+        );
+        assert_eq!(compact_to_target(0x0312_3456)?, BigUint::from(0x12_3456u32));
+        assert_eq!(compact_to_target(0x0212_3456)?, BigUint::from(0x1234u32));
+        assert!(compact_to_target(0x0480_0001).is_err());
+        assert!(compact_to_target(0x0100_00ff).is_err());
+        assert!(compact_to_target(0x2301_0000).is_err());
+
+        for &bits in &[0x1d00_ffffu32, 0x1804_dafe, 0x0312_3456, 0x2100_8000] {
+            assert_eq!(target_to_compact(&compact_to_target(bits)?), bits);
+        }
+        assert_eq!(target_to_compact(&BigUint::from(0x80u32)), 0x0200_8000);
+        assert_eq!(
+            target_work(&compact_to_target(0x1d00_ffff)?),
+            BigUint::from(0x1_0001_0001u64)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_difficulty() -> Result<()> {
+        assert_eq!(difficulty(0x1d00_ffff)?, 1.0);
+        assert!((difficulty(0x1b04_04cb)? - 16_307.420_938_523_983).abs() < 1e-9);
+        assert!((difficulty(0x1804_dafe)? - 226_446_058_610.774_66).abs() < 1e-3);
+        assert!(difficulty(0x1d80_ffff).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_work() -> Result<()> {
+        let mine = |prev_block: Sha256d| {
+            let mut header = BlockHeader {
+                prev_block,
+                bits: 0x207f_ffff,
+                ..Default::default()
+            };
+            while header.validate_pow().is_err() {
+                header.nonce += 1;
+            }
+            header
+        };
+        let first = mine(Sha256d::default());
+        let second = mine(first.block_hash());
+        assert_eq!(
+            chain_work(&[first.clone(), second.clone()])?,
+            BigUint::from(4u32)
+        );
+        assert!(matches!(
+            chain_work(&[second, first]),
+            Err(Error::InvalidHeader { .. })
+        ));
+        Ok(())
+    }
+}
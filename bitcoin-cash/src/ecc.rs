@@ -1,4 +1,7 @@
-use crate::{error::Result, ByteArray, Pubkey};
+use crate::{
+    error::{Error, Result},
+    ByteArray, Pubkey, SecretKey,
+};
 
 pub trait ECC: Default {
     fn sign(&self, secret_key: &[u8], msg_array: impl Into<ByteArray>) -> Result<ByteArray>;
@@ -8,4 +11,112 @@ pub trait ECC: Default {
     fn derive_pubkey(&self, secret_key: &[u8]) -> Result<Pubkey>;
 
     fn normalize_sig(&self, sig: &[u8]) -> Result<Vec<u8>>;
+
+    fn secret_key_tweak_add(&self, secret_key: &[u8], tweak: &[u8]) -> Result<SecretKey>;
+
+    fn secret_key_tweak_mul(&self, secret_key: &[u8], tweak: &[u8]) -> Result<SecretKey>;
+
+    fn pubkey_tweak_add(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey>;
+
+    fn pubkey_tweak_mul(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey>;
+
+    fn combine_pubkeys(&self, pubkeys: &[Pubkey]) -> Result<Pubkey>;
+
+    fn sign_recoverable(&self, secret_key: &[u8], msg_hash: &[u8]) -> Result<Vec<u8>>;
+
+    fn recover_pubkey(&self, msg_hash: &[u8], sig: &[u8]) -> Result<Pubkey>;
+}
+
+pub const RECOVERABLE_SIG_LEN: usize = 65;
+
+const COMPACT_HEADER_COMPRESSED: u8 = 31;
+
+pub fn encode_recoverable_sig(recovery_id: u8, sig: &[u8]) -> Vec<u8> {
+    let mut compact = Vec::with_capacity(RECOVERABLE_SIG_LEN);
+    compact.push(COMPACT_HEADER_COMPRESSED + recovery_id);
+    compact.extend_from_slice(sig);
+    compact
+}
+
+pub fn decode_recoverable_sig(sig: &[u8]) -> Result<(u8, &[u8])> {
+    if sig.len() != RECOVERABLE_SIG_LEN {
+        return Err(Error::InvalidSize {
+            expected: RECOVERABLE_SIG_LEN,
+            actual: sig.len(),
+        });
+    }
+    match sig[0] {
+        27..=34 => Ok(((sig[0] - 27) & 3, &sig[1..])),
+        _ => Err(Error::InvalidSignatureFormat),
+    }
+}
+
+pub trait ECCObject: Send + Sync {
+    fn dyn_sign(&self, secret_key: &[u8], msg_array: ByteArray) -> Result<ByteArray>;
+
+    fn dyn_verify(&self, pubkey: &[u8], msg_array: &[u8], sig: &[u8]) -> Result<bool>;
+
+    fn dyn_derive_pubkey(&self, secret_key: &[u8]) -> Result<Pubkey>;
+
+    fn dyn_normalize_sig(&self, sig: &[u8]) -> Result<Vec<u8>>;
+
+    fn dyn_secret_key_tweak_add(&self, secret_key: &[u8], tweak: &[u8]) -> Result<SecretKey>;
+
+    fn dyn_secret_key_tweak_mul(&self, secret_key: &[u8], tweak: &[u8]) -> Result<SecretKey>;
+
+    fn dyn_pubkey_tweak_add(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey>;
+
+    fn dyn_pubkey_tweak_mul(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey>;
+
+    fn dyn_combine_pubkeys(&self, pubkeys: &[Pubkey]) -> Result<Pubkey>;
+
+    fn dyn_sign_recoverable(&self, secret_key: &[u8], msg_hash: &[u8]) -> Result<Vec<u8>>;
+
+    fn dyn_recover_pubkey(&self, msg_hash: &[u8], sig: &[u8]) -> Result<Pubkey>;
+}
+
+impl<E: ECC + Send + Sync> ECCObject for E {
+    fn dyn_sign(&self, secret_key: &[u8], msg_array: ByteArray) -> Result<ByteArray> {
+        self.sign(secret_key, msg_array)
+    }
+
+    fn dyn_verify(&self, pubkey: &[u8], msg_array: &[u8], sig: &[u8]) -> Result<bool> {
+        self.verify(pubkey, msg_array, sig)
+    }
+
+    fn dyn_derive_pubkey(&self, secret_key: &[u8]) -> Result<Pubkey> {
+        self.derive_pubkey(secret_key)
+    }
+
+    fn dyn_normalize_sig(&self, sig: &[u8]) -> Result<Vec<u8>> {
+        self.normalize_sig(sig)
+    }
+
+    fn dyn_secret_key_tweak_add(&self, secret_key: &[u8], tweak: &[u8]) -> Result<SecretKey> {
+        self.secret_key_tweak_add(secret_key, tweak)
+    }
+
+    fn dyn_secret_key_tweak_mul(&self, secret_key: &[u8], tweak: &[u8]) -> Result<SecretKey> {
+        self.secret_key_tweak_mul(secret_key, tweak)
+    }
+
+    fn dyn_pubkey_tweak_add(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey> {
+        self.pubkey_tweak_add(pubkey, tweak)
+    }
+
+    fn dyn_pubkey_tweak_mul(&self, pubkey: &Pubkey, tweak: &[u8]) -> Result<Pubkey> {
+        self.pubkey_tweak_mul(pubkey, tweak)
+    }
+
+    fn dyn_combine_pubkeys(&self, pubkeys: &[Pubkey]) -> Result<Pubkey> {
+        self.combine_pubkeys(pubkeys)
+    }
+
+    fn dyn_sign_recoverable(&self, secret_key: &[u8], msg_hash: &[u8]) -> Result<Vec<u8>> {
+        self.sign_recoverable(secret_key, msg_hash)
+    }
+
+    fn dyn_recover_pubkey(&self, msg_hash: &[u8], sig: &[u8]) -> Result<Pubkey> {
+        self.recover_pubkey(msg_hash, sig)
+    }
 }
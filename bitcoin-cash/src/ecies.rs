@@ -0,0 +1,106 @@
+use crate::error::{Error, Result};
+use crate::{Pubkey, ECC};
+use aes::Aes128;
+use block_modes::block_padding::Pkcs7;
+use block_modes::{BlockMode, Cbc};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Digest;
+
+type Aes128Cbc = Cbc<Aes128, Pkcs7>;
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+pub const ECIES_MAGIC: &[u8] = b"BIE1";
+const PUBKEY_SIZE: usize = 33;
+const MAC_SIZE: usize = 32;
+
+#[derive(Error, Clone, Copy, Debug, PartialEq)]
+pub enum EciesError {
+    #[error("Invalid base64 encoding")]
+    InvalidBase64,
+    #[error("Message too short: {0} bytes")]
+    MessageTooShort(usize),
+    #[error("Invalid magic bytes")]
+    InvalidMagic,
+    #[error("Invalid MAC")]
+    InvalidMac,
+    #[error("Invalid padding")]
+    InvalidPadding,
+}
+
+struct EciesKeys {
+    iv: [u8; 16],
+    key_e: [u8; 16],
+    key_m: [u8; 32],
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for EciesKeys {
+    fn drop(&mut self) {
+        use zeroize::Zeroize;
+        self.iv.zeroize();
+        self.key_e.zeroize();
+        self.key_m.zeroize();
+    }
+}
+
+fn derive_keys(ecc: &impl ECC, pubkey: &Pubkey, secret_key: &[u8]) -> Result<EciesKeys> {
+    let ecdh_key = ecc.pubkey_tweak_mul(pubkey, secret_key)?;
+    let key = sha2::Sha512::digest(ecdh_key.as_slice());
+    let mut keys = EciesKeys {
+        iv: [0; 16],
+        key_e: [0; 16],
+        key_m: [0; 32],
+    };
+    keys.iv.copy_from_slice(&key[..16]);
+    keys.key_e.copy_from_slice(&key[16..32]);
+    keys.key_m.copy_from_slice(&key[32..]);
+    Ok(keys)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_varkey(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac
+}
+
+pub fn encrypt_to_pubkey(
+    ecc: &impl ECC,
+    pubkey: &Pubkey,
+    ephemeral_secret_key: &[u8],
+    message: &[u8],
+) -> Result<String> {
+    let ephemeral_pubkey = ecc.derive_pubkey(ephemeral_secret_key)?;
+    let keys = derive_keys(ecc, pubkey, ephemeral_secret_key)?;
+    let cipher = Aes128Cbc::new_var(&keys.key_e, &keys.iv).expect("Key sizes are valid");
+    let ciphertext = cipher.encrypt_vec(message);
+    let mut encrypted = Vec::with_capacity(
+        ECIES_MAGIC.len() + PUBKEY_SIZE + ciphertext.len() + MAC_SIZE,
+    );
+    encrypted.extend_from_slice(ECIES_MAGIC);
+    encrypted.extend_from_slice(ephemeral_pubkey.as_slice());
+    encrypted.extend_from_slice(&ciphertext);
+    let mac = hmac_sha256(&keys.key_m, &encrypted).finalize().into_bytes();
+    encrypted.extend_from_slice(&mac);
+    Ok(base64::encode(&encrypted))
+}
+
+pub fn decrypt_with_secret(ecc: &impl ECC, secret_key: &[u8], encrypted: &str) -> Result<Vec<u8>> {
+    let encrypted = base64::decode(encrypted).map_err(|_| EciesError::InvalidBase64)?;
+    let header_size = ECIES_MAGIC.len() + PUBKEY_SIZE;
+    if encrypted.len() < header_size + MAC_SIZE {
+        return Err(EciesError::MessageTooShort(encrypted.len()).into());
+    }
+    if &encrypted[..ECIES_MAGIC.len()] != ECIES_MAGIC {
+        return Err(EciesError::InvalidMagic.into());
+    }
+    let ephemeral_pubkey = Pubkey::from_slice(&encrypted[ECIES_MAGIC.len()..header_size]);
+    let keys = derive_keys(ecc, &ephemeral_pubkey, secret_key)?;
+    let (payload, mac) = encrypted.split_at(encrypted.len() - MAC_SIZE);
+    hmac_sha256(&keys.key_m, payload)
+        .verify(mac)
+        .map_err(|_| EciesError::InvalidMac)?;
+    let cipher = Aes128Cbc::new_var(&keys.key_e, &keys.iv).expect("Key sizes are valid");
+    cipher
+        .decrypt_vec(&payload[header_size..])
+        .map_err(|_| Error::Ecies(EciesError::InvalidPadding))
+}
@@ -0,0 +1,510 @@
+use crate::error::{Error, Result};
+use crate::{
+    script_hash, BackendFuture, BitcoinCode, BlockHeader, ByteArray, ChainBackend, FeeRate, Hashed,
+    Nft, NftCapability, NftCommitment, Script, Sha256, Sha256d, TokenCategory, TokenData, Tx,
+    TxHistoryEntry, TxOutpoint, Utxo, DEFAULT_FEE_RATE,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+pub const ELECTRUM_PROTOCOL_VERSION: &str = "1.4";
+
+type PendingRequests = Arc<std::sync::Mutex<HashMap<u64, oneshot::Sender<Result<Value>>>>>;
+
+#[derive(Debug)]
+pub enum ElectrumEvent {
+    // the history of a subscribed script changed; `status` is None if it has no history
+    ScriptHashStatus {
+        script_hash: Sha256,
+        status: Option<String>,
+    },
+    NewTip {
+        height: u32,
+        header: BlockHeader,
+    },
+    Disconnected(Error),
+}
+
+// a connection to an Electrum/Fulcrum server over plain TCP; notifications for subscriptions
+// arrive as ElectrumEvents on the receiver returned by `connect`
+pub struct ElectrumClient {
+    writer: Mutex<OwnedWriteHalf>,
+    pending: PendingRequests,
+    next_id: AtomicU64,
+    server_version: String,
+}
+
+#[derive(Deserialize)]
+struct UnspentEntry {
+    tx_hash: String,
+    tx_pos: u32,
+    // 0 or -1 for unconfirmed txs
+    height: i64,
+    value: u64,
+    // only sent by token aware servers like Fulcrum
+    #[serde(default)]
+    token_data: Option<TokenDataEntry>,
+}
+
+#[derive(Deserialize)]
+struct TokenDataEntry {
+    category: String,
+    // a decimal string, as amounts can exceed the range of JSON numbers
+    amount: String,
+    #[serde(default)]
+    nft: Option<NftEntry>,
+}
+
+#[derive(Deserialize)]
+struct NftEntry {
+    capability: String,
+    commitment: String,
+}
+
+#[derive(Deserialize)]
+struct HistoryEntry {
+    tx_hash: String,
+    height: i64,
+}
+
+#[derive(Deserialize)]
+struct HeaderEntry {
+    height: u32,
+    hex: String,
+}
+
+impl ElectrumClient {
+    pub async fn connect(
+        addr: impl ToSocketAddrs,
+        client_name: &str,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<ElectrumEvent>)> {
+        let stream = TcpStream::connect(addr).await?;
+        let (reader, writer) = stream.into_split();
+        let pending = PendingRequests::default();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(read_responses(reader, pending.clone(), sender));
+        let mut client = ElectrumClient {
+            writer: Mutex::new(writer),
+            pending,
+            next_id: AtomicU64::new(0),
+            server_version: String::new(),
+        };
+        let version = client
+            .request(
+                "server.version",
+                json!([client_name, ELECTRUM_PROTOCOL_VERSION]),
+            )
+            .await?;
+        client.server_version = version
+            .get(0)
+            .and_then(Value::as_str)
+            .ok_or(Error::InvalidElectrumResponse {
+                reason: "missing server version",
+            })?
+            .to_string();
+        Ok((client, receiver))
+    }
+
+    pub fn server_version(&self) -> &str {
+        &self.server_version
+    }
+
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        self.pending
+            .lock()
+            .expect("pending requests poisoned")
+            .insert(id, sender);
+        let request = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+        if let Err(err) = self.writer.lock().await.write_all(&line).await {
+            self.pending
+                .lock()
+                .expect("pending requests poisoned")
+                .remove(&id);
+            return Err(err.into());
+        }
+        receiver.await.map_err(|_| Error::InvalidElectrumResponse {
+            reason: "connection closed",
+        })?
+    }
+
+    // returns the current status of the script's history
+    pub async fn subscribe_script(&self, lock_script: &Script) -> Result<Option<String>> {
+        let status = self
+            .request(
+                "blockchain.scripthash.subscribe",
+                json!([script_hash(lock_script).to_hex_le()]),
+            )
+            .await?;
+        Ok(status.as_str().map(str::to_string))
+    }
+
+    // returns the current tip
+    pub async fn subscribe_headers(&self) -> Result<(u32, BlockHeader)> {
+        let tip = self
+            .request("blockchain.headers.subscribe", json!([]))
+            .await?;
+        parse_header(tip)
+    }
+}
+
+impl ChainBackend for ElectrumClient {
+    fn utxos<'a>(&'a self, lock_script: &'a Script) -> BackendFuture<'a, Vec<Utxo>> {
+        Box::pin(async move {
+            let utxos = self
+                .request(
+                    "blockchain.scripthash.listunspent",
+                    json!([script_hash(lock_script).to_hex_le()]),
+                )
+                .await?;
+            parse_utxos(utxos)
+        })
+    }
+
+    fn history<'a>(&'a self, lock_script: &'a Script) -> BackendFuture<'a, Vec<TxHistoryEntry>> {
+        Box::pin(async move {
+            let history = self
+                .request(
+                    "blockchain.scripthash.get_history",
+                    json!([script_hash(lock_script).to_hex_le()]),
+                )
+                .await?;
+            parse_history(history)
+        })
+    }
+
+    fn tx<'a>(&'a self, txid: &'a Sha256d) -> BackendFuture<'a, Tx> {
+        Box::pin(async move {
+            let raw_tx = self
+                .request(
+                    "blockchain.transaction.get",
+                    json!([txid.to_hex_le(), false]),
+                )
+                .await?;
+            let tx = Tx::deser(parse_hex(&raw_tx)?)?;
+            if tx.hash() != txid {
+                return Err(Error::InvalidElectrumResponse {
+                    reason: "server returned a different tx",
+                });
+            }
+            Ok(tx)
+        })
+    }
+
+    fn broadcast_raw_tx<'a>(&'a self, raw_tx: &'a [u8]) -> BackendFuture<'a, Sha256d> {
+        Box::pin(async move {
+            let txid = self
+                .request(
+                    "blockchain.transaction.broadcast",
+                    json!([hex::encode(raw_tx)]),
+                )
+                .await?;
+            match txid.as_str() {
+                Some(txid) => Sha256d::from_hex_le(txid),
+                None => Err(Error::InvalidElectrumResponse {
+                    reason: "broadcast didn't return a txid",
+                }),
+            }
+        })
+    }
+
+    fn estimate_fee(&self, target_blocks: u32) -> BackendFuture<'_, FeeRate> {
+        Box::pin(async move {
+            let fee = self
+                .request("blockchain.estimatefee", json!([target_blocks]))
+                .await?;
+            parse_fee_rate(fee)
+        })
+    }
+}
+
+async fn read_responses(
+    reader: OwnedReadHalf,
+    pending: PendingRequests,
+    sender: mpsc::UnboundedSender<ElectrumEvent>,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        let result = match lines.next_line().await {
+            Ok(Some(line)) => handle_line(&line, &pending, &sender),
+            Ok(None) => Err(Error::InvalidElectrumResponse {
+                reason: "connection closed",
+            }),
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = result {
+            // dropping the senders fails all outstanding requests
+            pending.lock().expect("pending requests poisoned").clear();
+            let _ = sender.send(ElectrumEvent::Disconnected(err));
+            return;
+        }
+    }
+}
+
+fn handle_line(
+    line: &str,
+    pending: &PendingRequests,
+    sender: &mpsc::UnboundedSender<ElectrumEvent>,
+) -> Result<()> {
+    let mut message: Value = serde_json::from_str(line)?;
+    if let Some(id) = message.get("id").and_then(Value::as_u64) {
+        let request = pending
+            .lock()
+            .expect("pending requests poisoned")
+            .remove(&id);
+        if let Some(request) = request {
+            let _ = request.send(parse_response(message));
+        }
+        return Ok(());
+    }
+    let params = message["params"].take();
+    let event = match message["method"].as_str() {
+        Some("blockchain.scripthash.subscribe") => ElectrumEvent::ScriptHashStatus {
+            script_hash: Sha256::from_hex_le(params[0].as_str().unwrap_or_default())?,
+            status: params[1].as_str().map(str::to_string),
+        },
+        Some("blockchain.headers.subscribe") => {
+            let (height, header) = parse_header(params[0].clone())?;
+            ElectrumEvent::NewTip { height, header }
+        }
+        _ => return Ok(()),
+    };
+    let _ = sender.send(event);
+    Ok(())
+}
+
+fn parse_response(mut response: Value) -> Result<Value> {
+    let error = response["error"].take();
+    if !error.is_null() {
+        return Err(Error::ElectrumServerError {
+            code: error["code"].as_i64().unwrap_or_default(),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+        });
+    }
+    Ok(response["result"].take())
+}
+
+fn parse_hex(value: &Value) -> Result<ByteArray> {
+    match value.as_str() {
+        Some(hex) => Ok(ByteArray::from_slice_unnamed(&hex::decode(hex)?)),
+        None => Err(Error::InvalidElectrumResponse {
+            reason: "expected a hex string",
+        }),
+    }
+}
+
+fn parse_header(tip: Value) -> Result<(u32, BlockHeader)> {
+    let tip: HeaderEntry = serde_json::from_value(tip)?;
+    let header = BlockHeader::deser(ByteArray::from_slice_unnamed(&hex::decode(&tip.hex)?))?;
+    Ok((tip.height, header))
+}
+
+fn confirmed_height(height: i64) -> Option<u32> {
+    if height > 0 {
+        Some(height as u32)
+    } else {
+        None
+    }
+}
+
+fn parse_utxos(utxos: Value) -> Result<Vec<Utxo>> {
+    let entries: Vec<UnspentEntry> = serde_json::from_value(utxos)?;
+    entries
+        .into_iter()
+        .map(|entry| {
+            Ok(Utxo {
+                outpoint: TxOutpoint {
+                    tx_hash: Sha256d::from_hex_le(&entry.tx_hash)?,
+                    vout: entry.tx_pos,
+                },
+                value: entry.value,
+                height: confirmed_height(entry.height),
+                token: entry.token_data.map(parse_token_data).transpose()?,
+            })
+        })
+        .collect()
+}
+
+fn parse_token_data(entry: TokenDataEntry) -> Result<TokenData> {
+    let invalid = |reason| Error::InvalidElectrumResponse { reason };
+    let amount = entry
+        .amount
+        .parse::<u64>()
+        .map_err(|_| invalid("token amount isn't a number"))?;
+    let nft = match entry.nft {
+        Some(nft) => {
+            let capability = match nft.capability.as_str() {
+                "none" => NftCapability::None,
+                "mutable" => NftCapability::Mutable,
+                "minting" => NftCapability::Minting,
+                _ => return Err(invalid("unknown NFT capability")),
+            };
+            let commitment = NftCommitment::new(hex::decode(&nft.commitment)?)?;
+            Some(Nft {
+                capability,
+                commitment,
+            })
+        }
+        None => None,
+    };
+    let token = TokenData {
+        category: TokenCategory::from_hash(Sha256d::from_hex_le(&entry.category)?),
+        amount,
+        nft,
+    };
+    token.validate()?;
+    Ok(token)
+}
+
+fn parse_history(history: Value) -> Result<Vec<TxHistoryEntry>> {
+    let entries: Vec<HistoryEntry> = serde_json::from_value(history)?;
+    entries
+        .into_iter()
+        .map(|entry| {
+            Ok(TxHistoryEntry {
+                txid: Sha256d::from_hex_le(&entry.tx_hash)?,
+                height: confirmed_height(entry.height),
+            })
+        })
+        .collect()
+}
+
+// servers report BCH per kB, or -1 if they have no estimate
+fn parse_fee_rate(fee: Value) -> Result<FeeRate> {
+    let bch_per_kb = fee.as_f64().ok_or(Error::InvalidElectrumResponse {
+        reason: "fee estimate isn't a number",
+    })?;
+    if bch_per_kb < 0.0 {
+        return Ok(DEFAULT_FEE_RATE);
+    }
+    Ok(FeeRate::from_sats_per_kb((bch_per_kb * 1e8).round() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_fee_rate, ElectrumClient, ElectrumEvent};
+    use crate::error::Result;
+    use crate::{
+        script_hash, Address, ChainBackend, FeeRate, Hashed, NftCapability, NftCommitment, Script,
+        Sha256d, TokenCategory, TokenData, DEFAULT_FEE_RATE,
+    };
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_fee_rate() -> Result<()> {
+        assert_eq!(
+            parse_fee_rate(json!(0.00001))?,
+            FeeRate::from_sats_per_kb(1000)
+        );
+        assert_eq!(parse_fee_rate(json!(-1))?, DEFAULT_FEE_RATE);
+        assert!(parse_fee_rate(json!("1")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_electrum_client() -> Result<()> {
+        let address =
+            Address::from_cash_addr("bitcoincash:qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6a")?;
+        let lock_script: Script = (&address).into();
+        let hash_hex = script_hash(&lock_script).to_hex_le();
+        let txid_hex = "11".repeat(32);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let addr = listener.local_addr()?;
+            let server_hash_hex = hash_hex.clone();
+            let server_txid_hex = txid_hex.clone();
+            let server = tokio::spawn(async move {
+                let (stream, _) = listener.accept().await?;
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                while let Some(line) = lines.next_line().await? {
+                    let request: Value = serde_json::from_str(&line)?;
+                    let result = match request["method"].as_str() {
+                        Some("server.version") => json!(["Fulcrum 1.9.0", "1.4"]),
+                        Some("blockchain.scripthash.listunspent") => {
+                            assert_eq!(request["params"][0], json!(server_hash_hex));
+                            json!([
+                                {"tx_hash": server_txid_hex, "tx_pos": 1, "height": 800000,
+                                 "value": 5000},
+                                {"tx_hash": server_txid_hex, "tx_pos": 2, "height": 0,
+                                 "value": 7000},
+                                {"tx_hash": server_txid_hex, "tx_pos": 3, "height": 0,
+                                 "value": 800, "token_data": {
+                                     "category": "22".repeat(32), "amount": "1000",
+                                     "nft": {"capability": "minting", "commitment": "cafe"},
+                                 }},
+                            ])
+                        }
+                        Some("blockchain.scripthash.subscribe") => json!(null),
+                        Some("blockchain.estimatefee") => json!(0.00002),
+                        _ => json!(null),
+                    };
+                    let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+                    writer
+                        .write_all(format!("{}\n", response).as_bytes())
+                        .await?;
+                    if request["method"] == "blockchain.scripthash.subscribe" {
+                        let notification = json!({
+                            "jsonrpc": "2.0",
+                            "method": "blockchain.scripthash.subscribe",
+                            "params": [server_hash_hex, "abcd"],
+                        });
+                        writer
+                            .write_all(format!("{}\n", notification).as_bytes())
+                            .await?;
+                    }
+                }
+                Result::Ok(())
+            });
+
+            let (client, mut events) = ElectrumClient::connect(addr, "test").await?;
+            assert_eq!(client.server_version(), "Fulcrum 1.9.0");
+            let utxos = client.address_utxos(&address).await?;
+            assert_eq!(utxos.len(), 3);
+            assert_eq!(utxos[0].outpoint.tx_hash, Sha256d::from_hex_le(&txid_hex)?);
+            assert_eq!(utxos[0].outpoint.vout, 1);
+            assert_eq!(utxos[0].height, Some(800_000));
+            assert_eq!(utxos[1].height, None);
+            assert_eq!(utxos[1].token, None);
+            let category = TokenCategory::from_hash(Sha256d::new([0x22; 32]));
+            let commitment = NftCommitment::new(vec![0xca, 0xfe])?;
+            let token = TokenData {
+                amount: 1000,
+                ..TokenData::nft(category, NftCapability::Minting, commitment)
+            };
+            assert_eq!(utxos[2].token, Some(token));
+            assert_eq!(
+                client.estimate_fee(6).await?,
+                FeeRate::from_sats_per_kb(2000)
+            );
+            assert_eq!(client.subscribe_script(&lock_script).await?, None);
+            match events.recv().await {
+                Some(ElectrumEvent::ScriptHashStatus {
+                    script_hash: hash,
+                    status,
+                }) => {
+                    assert_eq!(hash, script_hash(&lock_script));
+                    assert_eq!(status.as_deref(), Some("abcd"));
+                }
+                event => panic!("unexpected event {:?}", event),
+            }
+            drop(client);
+            server.await.expect("server task panicked")
+        })
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{address::CashAddrError, ByteArrayError, IntegerError, JsonError};
+use crate::{
+    address::CashAddrError, ByteArrayError, EciesError, IntegerError, JsonError, Sha256d,
+    TokenCategory,
+};
 
 #[derive(Error, Clone, Debug, PartialEq)]
 pub enum ScriptSerializeError {
@@ -39,30 +42,239 @@ pub enum Error {
     #[error("Invalid size: expected {expected}, got {actual}")]
     InvalidSize { expected: usize, actual: usize },
 
+    #[error("Non-canonical CompactSize encoding of {value}")]
+    NonCanonicalVarInt { value: u64 },
+
+    #[error("CompactSize {size} exceeds the maximum of {max_size}")]
+    CompactSizeTooLarge { size: u64, max_size: u64 },
+
+    #[error("Invalid optional flag: expected 0 or 1, got {flag}")]
+    InvalidOptionalFlag { flag: u8 },
+
+    #[error("Deserialization failed at byte {offset} in {type_name}{path}: {source}")]
+    DeserializeFailed {
+        type_name: &'static str,
+        path: String,
+        offset: usize,
+        source: Box<Error>,
+    },
+
+    #[error("Invalid tag in {field}")]
+    InvalidTag { field: &'static str },
+
+    #[error("Validation of {field} failed: {source}")]
+    ValidationFailed {
+        field: &'static str,
+        source: Box<Error>,
+    },
+
     #[error("Script serialize error: {0}")]
     ScriptSerialize(#[from] ScriptSerializeError),
 
     #[error("Script serialize error: {amount}")]
     InsufficientInputAmount { amount: u64 },
 
+    #[error("Invalid fee rate: {fee_rate} sats/byte")]
+    InvalidFeeRate { fee_rate: f64 },
+
+    #[error("Absurd fee: {fee} sats ({fee_per_kb} sats/kB)")]
+    AbsurdFee { fee: u64, fee_per_kb: u64 },
+
+    #[error("Output of {value} sats is below the dust limit of {dust_limit} sats")]
+    DustOutput { value: u64, dust_limit: u64 },
+
+    #[error("Invalid sweep: {reason}")]
+    InvalidSweep { reason: &'static str },
+
+    #[error("Invalid batch payment: {reason}")]
+    InvalidBatchPayment { reason: &'static str },
+
+    #[error("Invalid CoinJoin: {reason}")]
+    InvalidCoinJoin { reason: &'static str },
+
+    #[error("Invalid change output: {reason}")]
+    InvalidChangeOutput { reason: &'static str },
+
+    #[error("Tx size {size} exceeds the maximum of {max_size}")]
+    TxTooLarge { size: usize, max_size: usize },
+
+    #[error("Invalid compact target {bits:#010x}")]
+    InvalidCompactTarget { bits: u32 },
+
+    #[error("Block {block_hash} doesn't meet its proof-of-work target")]
+    InsufficientProofOfWork { block_hash: Sha256d },
+
+    #[error("Merkle root mismatch: header commits to {expected}, txs hash to {actual}")]
+    MerkleRootMismatch { expected: Sha256d, actual: Sha256d },
+
+    #[error("Block {block_hash} has identical sibling hashes in its merkle tree")]
+    MutatedMerkleTree { block_hash: Sha256d },
+
+    #[error("Invalid header {block_hash}: {reason}")]
+    InvalidHeader {
+        block_hash: Sha256d,
+        reason: &'static str,
+    },
+
+    #[error("Unknown parent header {prev_block}")]
+    UnknownParentHeader { prev_block: Sha256d },
+
+    #[error("Invalid partial merkle tree: {reason}")]
+    InvalidPartialMerkleTree { reason: &'static str },
+
+    #[error("Tx {txid} is not matched by the merkle block")]
+    TxNotInMerkleBlock { txid: Sha256d },
+
+    #[error("Invalid bloom filter: {reason}")]
+    InvalidBloomFilter { reason: &'static str },
+
+    #[error("Invalid compact block: {reason}")]
+    InvalidCompactBlock { reason: &'static str },
+
+    #[error("Invalid network message: {reason}")]
+    InvalidNetworkMessage { reason: &'static str },
+
+    #[error("The chain backend doesn't support {method}")]
+    UnsupportedByBackend { method: &'static str },
+
+    #[error("Electrum server error {code}: {message}")]
+    ElectrumServerError { code: i64, message: String },
+
+    #[error("Invalid Electrum response: {reason}")]
+    InvalidElectrumResponse { reason: &'static str },
+
+    #[error("RPC error {code}: {message}")]
+    RpcError { code: i64, message: String },
+
+    #[error("RPC request failed with HTTP status {status}")]
+    RpcHttpError { status: u16 },
+
+    #[error("Invalid RPC response: {reason}")]
+    InvalidRpcResponse { reason: &'static str },
+
+    #[error("Expected a {expected} message, got {actual}")]
+    UnexpectedCommand {
+        expected: &'static str,
+        actual: String,
+    },
+
+    #[error("SHA256 midstate length {length} isn't a multiple of the block size")]
+    InvalidMidstateLength { length: u64 },
+
     #[error("Invalid signature format")]
     InvalidSignatureFormat,
 
     #[error("Invalid invalid pubkey")]
     InvalidPubkey,
 
+    #[error("Invalid tweak")]
+    InvalidTweak,
+
+    #[error("Nonce of signer {signer_idx} doesn't match its commitment")]
+    InvalidNonceCommitment { signer_idx: usize },
+
+    #[error("Invalid signer index {signer_idx}, there are {num_signers} signers")]
+    InvalidSignerIndex {
+        signer_idx: usize,
+        num_signers: usize,
+    },
+
+    #[error("Stale signatures for preimage generation {generation}, now {current_generation}")]
+    StalePreimages {
+        generation: u64,
+        current_generation: u64,
+    },
+
+    #[error("Inputs {input_indices:?} are missing signatures")]
+    MissingSignatures { input_indices: Vec<usize> },
+
     #[error("Input {input_idx} already spent")]
     InputAlreadySigned { input_idx: usize },
 
+    #[error("Input {input_idx} has no signatory, attach one with attach_signatory")]
+    MissingSignatory { input_idx: usize },
+
+    #[error("Input index {input_idx} out of bounds for {num_inputs} inputs")]
+    InputIndexOutOfBounds { input_idx: usize, num_inputs: usize },
+
+    #[error("Output index {output_idx} out of bounds for {num_outputs} outputs")]
+    OutputIndexOutOfBounds {
+        output_idx: usize,
+        num_outputs: usize,
+    },
+
+    #[error("Transactions differ in {field}")]
+    TxMismatch { field: &'static str },
+
+    #[error("Input {input_idx} has conflicting signatures")]
+    ConflictingSignatures { input_idx: usize },
+
+    #[error("Data carrier of {size} bytes exceeds {max_size} bytes")]
+    DataCarrierTooLarge { size: usize, max_size: usize },
+
+    #[error("Transaction already has a data carrier output")]
+    MultipleDataCarriers,
+
+    #[error("Invalid timelock: {reason}")]
+    InvalidTimelock { reason: &'static str },
+
+    #[error("Timelock of input {input_idx} is inconsistent with the tx: {reason}")]
+    TimelockMismatch {
+        input_idx: usize,
+        reason: &'static str,
+    },
+
+    #[error("Input {input_idx} signs with SIGHASH_SINGLE but has no output at its index")]
+    UnpairedSingleInput { input_idx: usize },
+
+    #[error("Invalid sig hash flags: {reason}")]
+    InvalidSigHashFlags { reason: &'static str },
+
+    #[error("Payment requirement not met: {reason}")]
+    PaymentRequirementNotMet { reason: &'static str },
+
+    #[error("Invalid BCMR output: {reason}")]
+    InvalidBcmrOutput { reason: &'static str },
+
+    #[error("Invalid token prefix: {reason}")]
+    InvalidTokenPrefix { reason: &'static str },
+
+    #[error("NFT commitment of {len} bytes exceeds 40 bytes")]
+    NftCommitmentTooLong { len: usize },
+
+    #[error("Invalid token genesis: {reason}")]
+    InvalidTokenGenesis { reason: &'static str },
+
+    #[error("Non-standard token output: {reason}")]
+    NonStandardTokenOutput { reason: &'static str },
+
+    #[error("Token rule violated for category {category}: {reason}")]
+    TokenRuleViolation {
+        category: TokenCategory,
+        reason: &'static str,
+    },
+
+    #[error("Invalid pledge: {reason}")]
+    InvalidPledge { reason: &'static str },
+
     #[error("Invalid address type")]
     InvalidAddressType,
 
+    #[error("Address {cash_addr} doesn't signal token support")]
+    AddressNotTokenAware { cash_addr: String },
+
+    #[error("Invalid {m}-of-{n} multisig")]
+    InvalidMultisig { m: usize, n: usize },
+
     #[error("Integer error: {0}")]
     IntegerError(#[from] IntegerError),
 
     #[error("Byte array error: {0}")]
     ByteArrayError(#[from] ByteArrayError),
 
+    #[error("ECIES error: {0}")]
+    Ecies(#[from] EciesError),
+
     #[error("{0}")]
     Msg(String),
 }
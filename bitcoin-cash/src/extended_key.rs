@@ -0,0 +1,95 @@
+use crate::error::Result;
+use crate::{KeyPair, SecretKey, ECC};
+use hmac::{Hmac, Mac, NewMac};
+
+type HmacSha512 = Hmac<sha2::Sha512>;
+
+pub const HARDENED_INDEX: u32 = 0x8000_0000;
+
+// a BIP32 extended secret key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtendedSecretKey {
+    secret_key: SecretKey,
+    chain_code: [u8; 32],
+}
+
+impl ExtendedSecretKey {
+    pub fn new(secret_key: SecretKey, chain_code: [u8; 32]) -> Self {
+        ExtendedSecretKey {
+            secret_key,
+            chain_code,
+        }
+    }
+
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let (secret_key, chain_code) = hmac_sha512(b"Bitcoin seed", seed);
+        ExtendedSecretKey::new(SecretKey::new(secret_key), chain_code)
+    }
+
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn keypair(&self, ecc: &impl ECC) -> Result<KeyPair> {
+        KeyPair::from_secret_key(ecc, self.secret_key.clone())
+    }
+
+    // indices from HARDENED_INDEX on derive hardened children
+    pub fn derive_child(&self, ecc: &impl ECC, index: u32) -> Result<Self> {
+        let mut data = Vec::with_capacity(37);
+        if index >= HARDENED_INDEX {
+            data.push(0);
+            data.extend_from_slice(self.secret_key.as_slice());
+        } else {
+            data.extend_from_slice(self.secret_key.pubkey(ecc)?.as_slice());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+        let (tweak, chain_code) = hmac_sha512(&self.chain_code, &data);
+        let secret_key = ecc.secret_key_tweak_add(self.secret_key.as_slice(), &tweak)?;
+        Ok(ExtendedSecretKey::new(secret_key, chain_code))
+    }
+
+    pub fn derive_path(&self, ecc: &impl ECC, path: &[u32]) -> Result<Self> {
+        let mut key = self.clone();
+        for &index in path {
+            key = key.derive_child(ecc, index)?;
+        }
+        Ok(key)
+    }
+}
+
+// returns the left and right halves of the HMAC
+fn hmac_sha512(key: &[u8], data: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = HmacSha512::new_varkey(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    let hash = mac.finalize().into_bytes();
+    let mut left = [0; 32];
+    let mut right = [0; 32];
+    left.copy_from_slice(&hash[..32]);
+    right.copy_from_slice(&hash[32..]);
+    (left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExtendedSecretKey;
+    use hex_literal::hex;
+
+    #[test]
+    fn test_from_seed() {
+        // BIP32 test vector 1
+        let master = ExtendedSecretKey::from_seed(&hex!("000102030405060708090a0b0c0d0e0f"));
+        assert_eq!(
+            master.secret_key().as_slice(),
+            hex!("e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"),
+        );
+        assert_eq!(
+            master.chain_code(),
+            &hex!("873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508"),
+        );
+    }
+}
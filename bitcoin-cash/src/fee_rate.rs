@@ -0,0 +1,89 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const MIN_RELAY_FEE_RATE: FeeRate = FeeRate::from_sats_per_kb(1000);
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+#[serde(transparent)]
+pub struct FeeRate {
+    sats_per_kb: u64,
+}
+
+impl FeeRate {
+    pub const fn from_sats_per_kb(sats_per_kb: u64) -> Self {
+        FeeRate { sats_per_kb }
+    }
+
+    pub const fn from_sats_per_byte(sats_per_byte: u64) -> Self {
+        FeeRate {
+            sats_per_kb: sats_per_byte * 1000,
+        }
+    }
+
+    // rounds up, so the resulting rate never pays less than requested
+    pub fn from_sats_per_byte_f64(sats_per_byte: f64) -> Result<Self> {
+        if !sats_per_byte.is_finite() || sats_per_byte < 0.0 {
+            return Err(Error::InvalidFeeRate {
+                fee_rate: sats_per_byte,
+            });
+        }
+        Ok(FeeRate {
+            sats_per_kb: (sats_per_byte * 1000.0).ceil() as u64,
+        })
+    }
+
+    // rounds down, so a tx is never judged to pay more than it actually does
+    pub fn from_fee_and_size(fee: u64, size: usize) -> Self {
+        FeeRate {
+            sats_per_kb: (fee as u128 * 1000 / size.max(1) as u128) as u64,
+        }
+    }
+
+    pub fn sats_per_kb(self) -> u64 {
+        self.sats_per_kb
+    }
+
+    pub fn sats_per_byte(self) -> f64 {
+        self.sats_per_kb as f64 / 1000.0
+    }
+
+    // rounds up, so the fee never under-pays the rate
+    pub fn fee_for_size(self, size: usize) -> u64 {
+        ((size as u128 * self.sats_per_kb as u128 + 999) / 1000) as u64
+    }
+
+    pub fn meets_relay_minimum(self) -> bool {
+        self >= MIN_RELAY_FEE_RATE
+    }
+}
+
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} sats/kB", self.sats_per_kb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FeeRate, MIN_RELAY_FEE_RATE};
+
+    #[test]
+    fn test_fee_rate_rounding() -> crate::error::Result<()> {
+        let fee_rate = FeeRate::from_sats_per_byte_f64(1.2345)?;
+        assert_eq!(fee_rate.sats_per_kb(), 1235);
+        // 226 * 1.235 = 279.11, which truncating math would under-pay by a sat
+        assert_eq!(fee_rate.fee_for_size(226), 280);
+        assert_eq!(FeeRate::from_sats_per_byte(1).fee_for_size(226), 226);
+        assert_eq!(FeeRate::from_fee_and_size(279, 226).sats_per_kb(), 1234);
+        assert!(FeeRate::from_sats_per_byte_f64(-1.0).is_err());
+        assert!(FeeRate::from_sats_per_byte_f64(f64::NAN).is_err());
+        assert!(FeeRate::from_sats_per_kb(1000).meets_relay_minimum());
+        assert!(!FeeRate::from_sats_per_kb(999).meets_relay_minimum());
+        assert!(FeeRate::from_sats_per_byte(2) > MIN_RELAY_FEE_RATE);
+        assert_eq!(fee_rate.to_string(), "1235 sats/kB");
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{
+    error::{Error, Result},
+    BitcoinCode, FeeRate, Hashed, InputReference, Op, Ops, Pubkey, Sha256d, SigHashFlags, Signatory,
+    SignatoryKind, TaggedScript, TxBuilder, TxInput, TxOutput, TxPreimage, UnhashedTx, UnsignedTx,
+    UnsignedTxInput, ECC,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+pub const PLEDGE_SIG_HASH_FLAGS: SigHashFlags =
+    SigHashFlags::DEFAULT.union(SigHashFlags::ANYONECANPAY);
+pub const FLIPSTARTER_TX_VERSION: i32 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlipstarterCampaign {
+    outputs: Vec<TxOutput>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Pledge {
+    input: TxInput,
+}
+
+impl FlipstarterCampaign {
+    pub fn new(outputs: Vec<TxOutput>) -> Self {
+        FlipstarterCampaign { outputs }
+    }
+
+    pub fn outputs(&self) -> &[TxOutput] {
+        &self.outputs
+    }
+
+    pub fn requested_amount(&self) -> u64 {
+        self.outputs.iter().map(|output| output.value).sum()
+    }
+
+    pub fn pledge_tx<'b, S: Signatory + 'b + Sync + Send>(
+        &self,
+        input: impl Into<UnsignedTxInput>,
+        lock_script: TaggedScript<S::Script>,
+        signatory: S,
+    ) -> Result<(UnsignedTx<'b>, InputReference<S>)> {
+        let sig_hash_flags =
+            <S::Kind as SignatoryKind>::sig_hash_flags_vec(signatory.sig_hash_flags());
+        if sig_hash_flags.is_empty()
+            || sig_hash_flags
+                .iter()
+                .any(|&flags| flags != PLEDGE_SIG_HASH_FLAGS)
+        {
+            return Err(Error::InvalidPledge {
+                reason: "pledges must sign with ALL|FORKID|ANYONECANPAY",
+            });
+        }
+        let mut builder = TxBuilder::new(FLIPSTARTER_TX_VERSION, 0);
+        let input_ref = builder.add_input(input, lock_script, signatory);
+        builder.add_outputs(self.outputs.iter().cloned());
+        Ok((builder.build_without_estimation()?, input_ref))
+    }
+
+    pub fn pledge_from_tx(&self, unsigned_tx: UnsignedTx<'_>) -> Result<Pledge> {
+        if unsigned_tx.is_input_signed(0) != Some(true) {
+            return Err(Error::InvalidPledge {
+                reason: "pledge input is not signed",
+            });
+        }
+        let mut tx = unsigned_tx.partial_tx();
+        if tx.inputs.len() != 1 {
+            return Err(Error::InvalidPledge {
+                reason: "pledge tx must have exactly one input",
+            });
+        }
+        let pledge = Pledge {
+            input: tx.inputs.remove(0),
+        };
+        self.pledge_preimages(&pledge)?;
+        Ok(pledge)
+    }
+
+    pub fn pledge_preimages(&self, pledge: &Pledge) -> Result<Vec<TxPreimage>> {
+        let input = &pledge.input;
+        let sig_hash_flags = input.sig_hash_flags.as_ref().ok_or(Error::InvalidPledge {
+            reason: "pledge has no sig hash flags",
+        })?;
+        if sig_hash_flags.is_empty()
+            || sig_hash_flags
+                .iter()
+                .any(|&flags| flags != PLEDGE_SIG_HASH_FLAGS)
+        {
+            return Err(Error::InvalidPledge {
+                reason: "pledges must sign with ALL|FORKID|ANYONECANPAY",
+            });
+        }
+        if input.value.is_none() || input.lock_script.is_none() {
+            return Err(Error::InvalidPledge {
+                reason: "pledge is missing its value or lock script",
+            });
+        }
+        if input.script.ops().is_empty() {
+            return Err(Error::InvalidPledge {
+                reason: "pledge input is not signed",
+            });
+        }
+        let tx = UnhashedTx {
+            version: FLIPSTARTER_TX_VERSION,
+            inputs: vec![input.clone()],
+            outputs: self.outputs.clone(),
+            lock_time: 0,
+        };
+        Ok(tx.preimages(sig_hash_flags).remove(0))
+    }
+
+    pub fn verify_p2pkh_pledge(&self, ecc: &impl ECC, pledge: &Pledge) -> Result<bool> {
+        let preimages = self.pledge_preimages(pledge)?;
+        let ops = pledge.input.script.ops();
+        let (sig, pubkey) = match ops.as_ref() {
+            [sig, pubkey] => match (&sig.op, &pubkey.op) {
+                (Op::PushByteArray { array: sig, .. }, Op::PushByteArray { array: pubkey, .. }) => {
+                    (sig.clone(), pubkey.clone())
+                }
+                _ => return Ok(false),
+            },
+            _ => return Ok(false),
+        };
+        let (sig, sig_hash_type) = match sig.split_last() {
+            Some((&sig_hash_type, sig)) => (sig, sig_hash_type),
+            None => return Ok(false),
+        };
+        if u32::from(sig_hash_type) != PLEDGE_SIG_HASH_FLAGS.bits() {
+            return Ok(false);
+        }
+        let pubkey = match Pubkey::from_slice_checked(&pubkey) {
+            Some(pubkey) => pubkey,
+            None => return Ok(false),
+        };
+        let sig_hash = Sha256d::digest(preimages[0].ser());
+        ecc.verify(pubkey.as_slice(), sig_hash.as_slice(), sig)
+    }
+
+    // pledges come from untrusted contributors, so each signature is checked against the
+    // campaign preimage, which also commits to the pledged value
+    pub fn assemble(
+        &self,
+        ecc: &impl ECC,
+        pledges: Vec<Pledge>,
+        fee_rate: FeeRate,
+    ) -> Result<UnhashedTx> {
+        let mut outpoints = HashSet::new();
+        let mut pledged_amount = 0u64;
+        for pledge in &pledges {
+            if !self.verify_p2pkh_pledge(ecc, pledge)? {
+                return Err(Error::InvalidPledge {
+                    reason: "pledge signature is invalid",
+                });
+            }
+            let outpoint = &pledge.input.prev_out;
+            if !outpoints.insert((outpoint.tx_hash.to_vec_le(), outpoint.vout)) {
+                return Err(Error::InvalidPledge {
+                    reason: "duplicate pledge outpoint",
+                });
+            }
+            pledged_amount = pledged_amount
+                .checked_add(pledge.amount())
+                .ok_or(Error::InvalidPledge {
+                    reason: "pledged amount overflows",
+                })?;
+        }
+        let tx = UnhashedTx {
+            version: FLIPSTARTER_TX_VERSION,
+            inputs: pledges.into_iter().map(|pledge| pledge.input).collect(),
+            outputs: self.outputs.clone(),
+            lock_time: 0,
+        };
+        let fee = fee_rate.fee_for_size(tx.ser().len());
+        let required_amount = self.requested_amount().saturating_add(fee);
+        if pledged_amount < required_amount {
+            return Err(Error::InsufficientInputAmount {
+                amount: required_amount - pledged_amount,
+            });
+        }
+        Ok(tx)
+    }
+}
+
+impl Pledge {
+    pub fn input(&self) -> &TxInput {
+        &self.input
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.input.value.unwrap_or(0)
+    }
+}
@@ -1,13 +1,15 @@
 use crate::{
-    error::Result, BitcoinByteArray, BitcoinCode, BitcoinDataType, ByteArray, DataType,
-    FixedByteArray, Function, Op,
+    error::Result, split_slice, BitcoinByteArray, BitcoinCode, BitcoinDataType, ByteArray,
+    ByteArrayError, DataType, FixedByteArray, Function, Op,
 };
 
 use bitcoin_cash_base::FixedByteArrayLE;
 use serde::{Deserialize, Serialize};
 use sha1::Digest;
 use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::fmt::{Debug, Display};
+use std::marker::PhantomData;
 use std::sync::Arc;
 
 pub trait Hashed: Display + Debug + Sized + Eq + PartialEq {
@@ -58,6 +60,21 @@ pub struct Sha256(FixedByteArrayLE<[u8; 32]>);
 pub struct Sha256d(FixedByteArrayLE<[u8; 32]>);
 #[derive(Clone, Eq, PartialEq, Default, Hash, Deserialize, Serialize)]
 pub struct Hash160(FixedByteArrayLE<[u8; 20]>);
+#[derive(Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "ByteArray", into = "ByteArray")]
+pub struct Sha512(ByteArray);
+#[derive(Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct DoubleHash<Outer, Inner> {
+    hash: Outer,
+    #[serde(skip)]
+    phantom: PhantomData<Inner>,
+}
+
+pub type Ripemd160Sha512 = DoubleHash<Ripemd160, Sha512>;
+pub type Sha512d = DoubleHash<Sha512, Sha512>;
+
+const SHA512_LEN: usize = 64;
 
 impl Sha1 {
     pub fn new(hash: [u8; 20]) -> Self {
@@ -84,6 +101,25 @@ impl Hash160 {
         Hash160(FixedByteArray::new_unnamed(hash))
     }
 }
+impl Sha512 {
+    pub fn new(hash: [u8; SHA512_LEN]) -> Self {
+        Sha512(ByteArray::from_slice_unnamed(&hash))
+    }
+}
+impl<Outer: Hashed, Inner: Hashed> DoubleHash<Outer, Inner> {
+    pub fn new(hash: Outer) -> Self {
+        DoubleHash {
+            hash,
+            phantom: PhantomData,
+        }
+    }
+    pub fn outer(&self) -> &Outer {
+        &self.hash
+    }
+    pub fn into_outer(self) -> Outer {
+        self.hash
+    }
+}
 
 impl Debug for Sha1 {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
@@ -110,6 +146,16 @@ impl Debug for Hash160 {
         write!(fmt, "Hash160({})", self.to_hex_le())
     }
 }
+impl Debug for Sha512 {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(fmt, "Sha512({})", self.to_hex_be())
+    }
+}
+impl<Outer: Hashed, Inner: Hashed> Debug for DoubleHash<Outer, Inner> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(fmt, "DoubleHash({:?})", self.hash)
+    }
+}
 
 impl Display for Sha1 {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
@@ -136,6 +182,16 @@ impl Display for Hash160 {
         write!(fmt, "{}", self.to_hex_le())
     }
 }
+impl Display for Sha512 {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(fmt, "{}", self.to_hex_be())
+    }
+}
+impl<Outer: Hashed, Inner: Hashed> Display for DoubleHash<Outer, Inner> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        Display::fmt(&self.hash, fmt)
+    }
+}
 
 impl Hashed for Sha1 {
     fn function() -> Function {
@@ -276,6 +332,70 @@ impl Hashed for Hash160 {
     }
 }
 
+impl Hashed for Sha512 {
+    fn function() -> Function {
+        Function::Sha512
+    }
+    fn digest_slice(msg: &[u8]) -> Arc<[u8]> {
+        sha2::Sha512::digest(msg)[..].into()
+    }
+    fn as_slice(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+    fn from_slice(hash: &[u8]) -> Result<Self> {
+        Self::from_byte_array(ByteArray::from_slice_unnamed(hash))
+    }
+    fn from_byte_array(hash: ByteArray) -> Result<Self> {
+        if hash.len() != SHA512_LEN {
+            return Err(ByteArrayError::InvalidSlice {
+                expected: SHA512_LEN,
+                actual: hash.len(),
+            }
+            .into());
+        }
+        Ok(Sha512(hash))
+    }
+    fn named(self, name: impl Into<Cow<'static, str>>) -> Self {
+        Sha512(self.0.named(name))
+    }
+    fn as_byte_array(&self) -> &ByteArray {
+        &self.0
+    }
+    fn into_byte_array(self) -> ByteArray {
+        self.0
+    }
+}
+
+impl<Outer: Hashed, Inner: Hashed> Hashed for DoubleHash<Outer, Inner> {
+    fn function() -> Function {
+        Outer::function()
+    }
+    fn digest_slice(msg: &[u8]) -> Arc<[u8]> {
+        Outer::digest_slice(&Inner::digest_slice(msg))
+    }
+    fn digest(msg: impl Into<ByteArray>) -> Self {
+        DoubleHash::new(Outer::digest(Inner::digest(msg).into_byte_array()))
+    }
+    fn as_slice(&self) -> &[u8] {
+        self.hash.as_slice()
+    }
+    fn from_slice(hash: &[u8]) -> Result<Self> {
+        Ok(DoubleHash::new(Outer::from_slice(hash)?))
+    }
+    fn from_byte_array(hash: ByteArray) -> Result<Self> {
+        Ok(DoubleHash::new(Outer::from_byte_array(hash)?))
+    }
+    fn named(self, name: impl Into<Cow<'static, str>>) -> Self {
+        DoubleHash::new(self.hash.named(name))
+    }
+    fn as_byte_array(&self) -> &ByteArray {
+        self.hash.as_byte_array()
+    }
+    fn into_byte_array(self) -> ByteArray {
+        self.hash.into_byte_array()
+    }
+}
+
 impl BitcoinCode for Sha1 {
     fn ser(&self) -> ByteArray {
         self.0.ser()
@@ -285,6 +405,15 @@ impl BitcoinCode for Sha1 {
         let (array, leftover) = FixedByteArrayLE::<[u8; 20]>::deser_rest(data)?;
         Ok((Sha1(array), leftover))
     }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (array, leftover) = FixedByteArrayLE::<[u8; 20]>::deser_slice(data)?;
+        Ok((Sha1(array), leftover))
+    }
+
+    fn decode_from<R: std::io::Read>(reader: &mut R) -> Result<Self> {
+        Ok(Sha1(FixedByteArrayLE::<[u8; 20]>::decode_from(reader)?))
+    }
 }
 
 impl BitcoinCode for Ripemd160 {
@@ -296,6 +425,17 @@ impl BitcoinCode for Ripemd160 {
         let (array, leftover) = FixedByteArrayLE::<[u8; 20]>::deser_rest(data)?;
         Ok((Ripemd160(array), leftover))
     }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (array, leftover) = FixedByteArrayLE::<[u8; 20]>::deser_slice(data)?;
+        Ok((Ripemd160(array), leftover))
+    }
+
+    fn decode_from<R: std::io::Read>(reader: &mut R) -> Result<Self> {
+        Ok(Ripemd160(FixedByteArrayLE::<[u8; 20]>::decode_from(
+            reader,
+        )?))
+    }
 }
 
 impl BitcoinCode for Sha256 {
@@ -307,6 +447,15 @@ impl BitcoinCode for Sha256 {
         let (array, leftover) = FixedByteArrayLE::<[u8; 32]>::deser_rest(data)?;
         Ok((Sha256(array), leftover))
     }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (array, leftover) = FixedByteArrayLE::<[u8; 32]>::deser_slice(data)?;
+        Ok((Sha256(array), leftover))
+    }
+
+    fn decode_from<R: std::io::Read>(reader: &mut R) -> Result<Self> {
+        Ok(Sha256(FixedByteArrayLE::<[u8; 32]>::decode_from(reader)?))
+    }
 }
 
 impl BitcoinCode for Sha256d {
@@ -318,6 +467,15 @@ impl BitcoinCode for Sha256d {
         let (array, leftover) = FixedByteArrayLE::<[u8; 32]>::deser_rest(data)?;
         Ok((Sha256d(array), leftover))
     }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (array, leftover) = FixedByteArrayLE::<[u8; 32]>::deser_slice(data)?;
+        Ok((Sha256d(array), leftover))
+    }
+
+    fn decode_from<R: std::io::Read>(reader: &mut R) -> Result<Self> {
+        Ok(Sha256d(FixedByteArrayLE::<[u8; 32]>::decode_from(reader)?))
+    }
 }
 
 impl BitcoinCode for Hash160 {
@@ -329,6 +487,57 @@ impl BitcoinCode for Hash160 {
         let (array, leftover) = FixedByteArrayLE::<[u8; 20]>::deser_rest(data)?;
         Ok((Hash160(array), leftover))
     }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (array, leftover) = FixedByteArrayLE::<[u8; 20]>::deser_slice(data)?;
+        Ok((Hash160(array), leftover))
+    }
+
+    fn decode_from<R: std::io::Read>(reader: &mut R) -> Result<Self> {
+        Ok(Hash160(FixedByteArrayLE::<[u8; 20]>::decode_from(reader)?))
+    }
+}
+
+impl BitcoinCode for Sha512 {
+    fn ser(&self) -> ByteArray {
+        self.0.clone()
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (hash, leftover) = data.split(SHA512_LEN)?;
+        Ok((Sha512(hash), leftover))
+    }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (hash, leftover) = split_slice(data, SHA512_LEN)?;
+        Ok((Sha512(ByteArray::from_slice_unnamed(hash)), leftover))
+    }
+
+    fn decode_from<R: std::io::Read>(reader: &mut R) -> Result<Self> {
+        let mut hash = [0; SHA512_LEN];
+        reader.read_exact(&mut hash)?;
+        Ok(Sha512(ByteArray::from_slice_unnamed(&hash)))
+    }
+}
+
+impl<Outer: Hashed + BitcoinCode, Inner: Hashed> BitcoinCode for DoubleHash<Outer, Inner> {
+    fn ser(&self) -> ByteArray {
+        self.hash.ser()
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (hash, leftover) = Outer::deser_rest(data)?;
+        Ok((DoubleHash::new(hash), leftover))
+    }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (hash, leftover) = Outer::deser_slice(data)?;
+        Ok((DoubleHash::new(hash), leftover))
+    }
+
+    fn decode_from<R: std::io::Read>(reader: &mut R) -> Result<Self> {
+        Ok(DoubleHash::new(Outer::decode_from(reader)?))
+    }
 }
 
 impl BitcoinDataType for Sha1 {
@@ -396,6 +605,32 @@ impl BitcoinDataType for Hash160 {
     }
 }
 
+impl BitcoinDataType for Sha512 {
+    type Type = BitcoinByteArray;
+    fn to_data(&self) -> Self::Type {
+        BitcoinByteArray(self.0.clone())
+    }
+    fn to_pushop(&self) -> Op {
+        self.0.clone().into()
+    }
+    fn to_data_type(&self) -> DataType {
+        DataType::ByteArray(Some(SHA512_LEN))
+    }
+}
+
+impl<Outer: Hashed + BitcoinDataType, Inner: Hashed> BitcoinDataType for DoubleHash<Outer, Inner> {
+    type Type = Outer::Type;
+    fn to_data(&self) -> Self::Type {
+        self.hash.to_data()
+    }
+    fn to_pushop(&self) -> Op {
+        self.hash.to_pushop()
+    }
+    fn to_data_type(&self) -> DataType {
+        self.hash.to_data_type()
+    }
+}
+
 impl From<Sha1> for ByteArray {
     fn from(hash: Sha1) -> Self {
         hash.into_byte_array()
@@ -421,10 +656,28 @@ impl From<Hash160> for ByteArray {
         hash.into_byte_array()
     }
 }
+impl From<Sha512> for ByteArray {
+    fn from(hash: Sha512) -> Self {
+        hash.into_byte_array()
+    }
+}
+impl<Outer: Hashed, Inner: Hashed> From<DoubleHash<Outer, Inner>> for ByteArray {
+    fn from(hash: DoubleHash<Outer, Inner>) -> Self {
+        hash.into_byte_array()
+    }
+}
+impl TryFrom<ByteArray> for Sha512 {
+    type Error = crate::error::Error;
+    fn try_from(hash: ByteArray) -> Result<Self> {
+        Sha512::from_byte_array(hash)
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{Hash160, Hashed, Result, Ripemd160, Sha1, Sha256, Sha256d};
+    use super::{
+        Hash160, Hashed, Result, Ripemd160, Ripemd160Sha512, Sha1, Sha256, Sha256d, Sha512,
+    };
     use crate::{error::Error, ByteArrayError};
     use hex_literal::hex;
 
@@ -608,4 +861,37 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_sha512() -> Result<()> {
+        const ABC_SHA512: [u8; 64] = hex!(
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a"
+            "2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+        assert_eq!(Sha512::digest(b"abc").as_slice(), &ABC_SHA512[..]);
+        assert_eq!(Sha512::digest(b"abc"), Sha512::from_slice(&ABC_SHA512)?);
+        assert_eq!(Sha512::digest(b"abc"), Sha512::new(ABC_SHA512));
+        assert_eq!(
+            Error::ByteArrayError(ByteArrayError::InvalidSlice {
+                expected: 64,
+                actual: 2,
+            })
+            .to_string(),
+            Sha512::from_slice(&[0, 0]).unwrap_err().to_string(),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ripemd160_sha512() -> Result<()> {
+        let hash = Ripemd160Sha512::digest(b"abc");
+        let expected = Ripemd160::digest(Sha512::digest(b"abc").as_slice());
+        assert_eq!(hash.as_slice(), expected.as_slice());
+        assert_eq!(hash.outer(), &expected);
+        assert_eq!(
+            Ripemd160Sha512::digest_slice(b"abc").as_ref(),
+            expected.as_slice()
+        );
+        Ok(())
+    }
 }
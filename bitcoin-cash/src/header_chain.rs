@@ -0,0 +1,483 @@
+use crate::error::{Error, Result};
+use crate::{compact_to_target, target_to_compact, BlockHeader, Sha256d};
+use num::bigint::BigUint;
+use num::Zero;
+use std::collections::{BTreeMap, HashMap};
+
+const MEDIAN_TIME_SPAN: usize = 11;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredHeader {
+    pub header: BlockHeader,
+    pub height: u32,
+    // total work of the chain up to and including this header
+    pub chain_work: BigUint,
+}
+
+// storage backing a HeaderChain; the best chain is kept as a height -> hash index
+pub trait HeaderStore {
+    fn header(&self, hash: &Sha256d) -> Result<Option<StoredHeader>>;
+    fn insert_header(&mut self, hash: Sha256d, header: StoredHeader) -> Result<()>;
+    fn best_hash_at(&self, height: u32) -> Result<Option<Sha256d>>;
+    fn best_height(&self) -> Result<Option<u32>>;
+    // drops the best chain from `from_height` upwards and replaces it with `hashes`
+    fn set_best_chain(&mut self, from_height: u32, hashes: Vec<Sha256d>) -> Result<()>;
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryHeaderStore {
+    headers: HashMap<Sha256d, StoredHeader>,
+    best_chain: BTreeMap<u32, Sha256d>,
+}
+
+// the block ASERT difficulty is computed relative to
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsertAnchor {
+    pub height: u32,
+    pub bits: u32,
+    pub parent_time: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainParams {
+    pub pow_limit: BigUint,
+    // None disables retargeting, every header has to repeat its parent's bits
+    pub asert_anchor: Option<AsertAnchor>,
+    pub target_spacing: i64,
+    pub asert_half_life: i64,
+    pub checkpoints: Vec<(u32, Sha256d)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HeaderStatus {
+    AlreadyKnown,
+    Extended,
+    Reorganized { fork_height: u32 },
+    SideChain,
+}
+
+pub struct HeaderChain<S: HeaderStore = MemoryHeaderStore> {
+    params: ChainParams,
+    store: S,
+    start_height: u32,
+}
+
+impl HeaderStore for MemoryHeaderStore {
+    fn header(&self, hash: &Sha256d) -> Result<Option<StoredHeader>> {
+        Ok(self.headers.get(hash).cloned())
+    }
+
+    fn insert_header(&mut self, hash: Sha256d, header: StoredHeader) -> Result<()> {
+        self.headers.insert(hash, header);
+        Ok(())
+    }
+
+    fn best_hash_at(&self, height: u32) -> Result<Option<Sha256d>> {
+        Ok(self.best_chain.get(&height).cloned())
+    }
+
+    fn best_height(&self) -> Result<Option<u32>> {
+        Ok(self.best_chain.keys().next_back().cloned())
+    }
+
+    fn set_best_chain(&mut self, from_height: u32, hashes: Vec<Sha256d>) -> Result<()> {
+        self.best_chain.split_off(&from_height);
+        self.best_chain.extend((from_height..).zip(hashes));
+        Ok(())
+    }
+}
+
+impl ChainParams {
+    pub fn mainnet() -> Self {
+        ChainParams {
+            pow_limit: (BigUint::from(1u32) << 224usize) - 1u32,
+            // the November 2020 upgrade, when ASERT (aserti3-2d) activated
+            asert_anchor: Some(AsertAnchor {
+                height: 661_647,
+                bits: 0x1804_dafe,
+                parent_time: 1_605_447_844,
+            }),
+            target_spacing: 600,
+            asert_half_life: 2 * 24 * 60 * 60,
+            checkpoints: vec![],
+        }
+    }
+
+    pub fn regtest() -> Self {
+        ChainParams {
+            pow_limit: (BigUint::from(1u32) << 255usize) - 1u32,
+            asert_anchor: None,
+            ..ChainParams::mainnet()
+        }
+    }
+
+    pub fn next_bits(&self, parent: &StoredHeader) -> Result<u32> {
+        let anchor = match &self.asert_anchor {
+            Some(anchor) => anchor,
+            None => return Ok(parent.header.bits),
+        };
+        let time_diff = parent.header.timestamp as i64 - anchor.parent_time;
+        let height_diff = parent.height as i64 - anchor.height as i64;
+        let exponent =
+            (time_diff - self.target_spacing * (height_diff + 1)) * 65536 / self.asert_half_life;
+        // the shift rounds towards negative infinity, keeping `frac` positive
+        let shifts = exponent >> 16;
+        let frac = (exponent & 0xffff) as u128;
+        let factor = 65536
+            + ((195_766_423_245_049 * frac
+                + 971_821_376 * frac * frac
+                + 5127 * frac * frac * frac
+                + (1 << 47))
+                >> 48);
+        let target = compact_to_target(anchor.bits)? * BigUint::from(factor);
+        let shifts = shifts - 16;
+        let target = if shifts < 0 {
+            target >> (-shifts) as usize
+        } else {
+            target << shifts as usize
+        };
+        let target = if target.is_zero() {
+            BigUint::from(1u32)
+        } else if target > self.pow_limit {
+            self.pow_limit.clone()
+        } else {
+            target
+        };
+        Ok(target_to_compact(&target))
+    }
+}
+
+impl HeaderChain<MemoryHeaderStore> {
+    pub fn in_memory(
+        params: ChainParams,
+        start_height: u32,
+        start_header: BlockHeader,
+    ) -> Result<Self> {
+        HeaderChain::new(
+            params,
+            MemoryHeaderStore::default(),
+            start_height,
+            start_header,
+        )
+    }
+}
+
+impl<S: HeaderStore> HeaderChain<S> {
+    // `start_header` is trusted as-is, like a checkpoint; headers below it can't be added.
+    // If the store already has a best chain, it's continued instead.
+    pub fn new(
+        params: ChainParams,
+        mut store: S,
+        start_height: u32,
+        start_header: BlockHeader,
+    ) -> Result<Self> {
+        let start_hash = start_header.block_hash();
+        if let Some(anchor) = &params.asert_anchor {
+            // the difficulty algorithms before ASERT aren't implemented
+            if start_height < anchor.height {
+                return Err(Error::InvalidHeader {
+                    block_hash: start_hash,
+                    reason: "chain has to start at or after the ASERT anchor",
+                });
+            }
+        }
+        if store.best_height()?.is_none() {
+            let chain_work = start_header.work()?;
+            let stored = StoredHeader {
+                header: start_header,
+                height: start_height,
+                chain_work,
+            };
+            store.insert_header(start_hash.clone(), stored)?;
+            store.set_best_chain(start_height, vec![start_hash])?;
+        }
+        Ok(HeaderChain {
+            params,
+            store,
+            start_height,
+        })
+    }
+
+    pub fn params(&self) -> &ChainParams {
+        &self.params
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn header(&self, hash: &Sha256d) -> Result<Option<StoredHeader>> {
+        self.store.header(hash)
+    }
+
+    pub fn tip(&self) -> Result<(Sha256d, StoredHeader)> {
+        let height = self.best_height()?;
+        let hash = self.best_hash_at(height)?;
+        let header = self.stored_header(&hash)?;
+        Ok((hash, header))
+    }
+
+    pub fn is_in_best_chain(&self, hash: &Sha256d) -> Result<bool> {
+        let header = match self.store.header(hash)? {
+            Some(header) => header,
+            None => return Ok(false),
+        };
+        Ok(self.store.best_hash_at(header.height)?.as_ref() == Some(hash))
+    }
+
+    // best chain hashes for a getheaders message: the last ten, then exponentially sparser
+    pub fn block_locator(&self) -> Result<Vec<Sha256d>> {
+        let mut height = self.best_height()?;
+        let mut step = 1;
+        let mut locator = Vec::new();
+        loop {
+            locator.push(self.best_hash_at(height)?);
+            if height == self.start_height {
+                return Ok(locator);
+            }
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            height = height.saturating_sub(step).max(self.start_height);
+        }
+    }
+
+    pub fn add_header(&mut self, header: BlockHeader) -> Result<HeaderStatus> {
+        let block_hash = header.block_hash();
+        if self.store.header(&block_hash)?.is_some() {
+            return Ok(HeaderStatus::AlreadyKnown);
+        }
+        let parent =
+            self.store
+                .header(&header.prev_block)?
+                .ok_or_else(|| Error::UnknownParentHeader {
+                    prev_block: header.prev_block.clone(),
+                })?;
+        let height = parent.height + 1;
+        let invalid = |reason| {
+            Err(Error::InvalidHeader {
+                block_hash: block_hash.clone(),
+                reason,
+            })
+        };
+        if height <= self.last_checkpoint_height()? {
+            return invalid("forks off the best chain below a checkpoint");
+        }
+        let checkpoint = self
+            .params
+            .checkpoints
+            .iter()
+            .find(|(checkpoint_height, _)| *checkpoint_height == height);
+        if let Some((_, checkpoint_hash)) = checkpoint {
+            if checkpoint_hash != &block_hash {
+                return invalid("doesn't match the checkpoint at its height");
+            }
+        }
+        if header.bits != self.params.next_bits(&parent)? {
+            return invalid("incorrect difficulty bits");
+        }
+        header.validate_pow()?;
+        if header.timestamp <= self.median_time_past(&parent)? {
+            return invalid("timestamp isn't after the median time of the last 11 blocks");
+        }
+
+        let chain_work = parent.chain_work.clone() + header.work()?;
+        let (_, tip) = self.tip()?;
+        let is_new_best = chain_work > tip.chain_work;
+        let mut ancestor_hash = header.prev_block.clone();
+        let stored = StoredHeader {
+            header,
+            height,
+            chain_work,
+        };
+        self.store.insert_header(block_hash.clone(), stored)?;
+        if !is_new_best {
+            return Ok(HeaderStatus::SideChain);
+        }
+        // walk back to the best chain, collecting the headers of the new branch
+        let mut branch = vec![block_hash];
+        let mut ancestor = parent;
+        while self.store.best_hash_at(ancestor.height)?.as_ref() != Some(&ancestor_hash) {
+            branch.push(ancestor_hash);
+            ancestor_hash = ancestor.header.prev_block.clone();
+            ancestor = self.stored_header(&ancestor_hash)?;
+        }
+        branch.reverse();
+        self.store.set_best_chain(ancestor.height + 1, branch)?;
+        if ancestor.height == tip.height {
+            return Ok(HeaderStatus::Extended);
+        }
+        Ok(HeaderStatus::Reorganized {
+            fork_height: ancestor.height,
+        })
+    }
+
+    fn median_time_past(&self, parent: &StoredHeader) -> Result<u32> {
+        let mut timestamps = vec![parent.header.timestamp];
+        let mut header = parent.header.clone();
+        while timestamps.len() < MEDIAN_TIME_SPAN {
+            match self.store.header(&header.prev_block)? {
+                Some(stored) => header = stored.header,
+                None => break,
+            }
+            timestamps.push(header.timestamp);
+        }
+        timestamps.sort_unstable();
+        Ok(timestamps[timestamps.len() / 2])
+    }
+
+    // the chain can't be reorganized below its start or a checkpoint it already passed
+    fn last_checkpoint_height(&self) -> Result<u32> {
+        let best_height = self.best_height()?;
+        Ok(self
+            .params
+            .checkpoints
+            .iter()
+            .map(|&(height, _)| height)
+            .filter(|&height| height <= best_height)
+            .fold(self.start_height, u32::max))
+    }
+
+    fn best_height(&self) -> Result<u32> {
+        self.store.best_height()?.ok_or(Error::InvalidHeader {
+            block_hash: Sha256d::default(),
+            reason: "header store has no best chain",
+        })
+    }
+
+    fn best_hash_at(&self, height: u32) -> Result<Sha256d> {
+        self.store
+            .best_hash_at(height)?
+            .ok_or(Error::InvalidHeader {
+                block_hash: Sha256d::default(),
+                reason: "header store is missing a best chain height",
+            })
+    }
+
+    fn stored_header(&self, hash: &Sha256d) -> Result<StoredHeader> {
+        self.store
+            .header(hash)?
+            .ok_or_else(|| Error::UnknownParentHeader {
+                prev_block: hash.clone(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChainParams, HeaderChain, HeaderStatus, StoredHeader};
+    use crate::error::{Error, Result};
+    use crate::{BlockHeader, Sha256d};
+
+    const REGTEST_BITS: u32 = 0x207f_ffff;
+
+    fn mine(prev_block: Sha256d, timestamp: u32, tag: u8) -> Result<BlockHeader> {
+        let mut header = BlockHeader {
+            version: 4,
+            prev_block,
+            merkle_root: Sha256d::new([tag; 32]),
+            timestamp,
+            bits: REGTEST_BITS,
+            nonce: 0,
+        };
+        while header.validate_pow().is_err() {
+            header.nonce += 1;
+        }
+        Ok(header)
+    }
+
+    fn mine_branch(
+        chain: &mut HeaderChain,
+        mut prev_block: Sha256d,
+        len: u32,
+        tag: u8,
+    ) -> Result<Vec<(Sha256d, HeaderStatus)>> {
+        let mut added = Vec::new();
+        for idx in 0..len {
+            let header = mine(prev_block, tag as u32 * 1_000 + idx * 600, tag)?;
+            prev_block = header.block_hash();
+            added.push((prev_block.clone(), chain.add_header(header)?));
+        }
+        Ok(added)
+    }
+
+    #[test]
+    fn test_asert_bits() -> Result<()> {
+        let params = ChainParams::mainnet();
+        let anchor = params.asert_anchor.clone().unwrap();
+        let parent = |height_diff: u32, time_diff: i64| StoredHeader {
+            header: BlockHeader {
+                timestamp: (anchor.parent_time + time_diff) as u32,
+                ..Default::default()
+            },
+            height: anchor.height + height_diff,
+            chain_work: Default::default(),
+        };
+        assert_eq!(params.next_bits(&parent(0, 600))?, 0x1804_dafe);
+        assert_eq!(params.next_bits(&parent(99, 60_000))?, 0x1804_dafe);
+        // a half-life behind schedule doubles the target, ahead of schedule halves it
+        assert_eq!(params.next_bits(&parent(0, 600 + 172_800))?, 0x1809_b5fc);
+        assert_eq!(
+            params.next_bits(&parent(288, 289 * 600 - 172_800))?,
+            0x1802_6d7f
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reorg() -> Result<()> {
+        let genesis = mine(Sha256d::default(), 0, 0)?;
+        let genesis_hash = genesis.block_hash();
+        let mut chain = HeaderChain::in_memory(ChainParams::regtest(), 0, genesis)?;
+        let branch_a = mine_branch(&mut chain, genesis_hash.clone(), 3, 1)?;
+        assert!(branch_a
+            .iter()
+            .all(|(_, status)| status == &HeaderStatus::Extended));
+
+        let branch_b = mine_branch(&mut chain, branch_a[0].0.clone(), 3, 2)?;
+        assert_eq!(branch_b[0].1, HeaderStatus::SideChain);
+        assert_eq!(branch_b[1].1, HeaderStatus::SideChain);
+        assert_eq!(branch_b[2].1, HeaderStatus::Reorganized { fork_height: 1 });
+        let (tip_hash, tip) = chain.tip()?;
+        assert_eq!(tip_hash, branch_b[2].0);
+        assert_eq!(tip.height, 4);
+        assert!(chain.is_in_best_chain(&branch_a[0].0)?);
+        assert!(!chain.is_in_best_chain(&branch_a[2].0)?);
+        assert!(chain.is_in_best_chain(&branch_b[1].0)?);
+        assert_eq!(chain.block_locator()?.len(), 5);
+        assert_eq!(chain.block_locator()?.last(), Some(&genesis_hash));
+
+        let header = mine(branch_b[2].0.clone(), 0, 3)?;
+        assert!(matches!(
+            chain.add_header(header),
+            Err(Error::InvalidHeader { .. })
+        ));
+        let mut header = mine(branch_b[2].0.clone(), 10_000, 3)?;
+        header.bits = 0x1d00_ffff;
+        assert!(chain.add_header(header).is_err());
+        let header = mine(Sha256d::new([9; 32]), 10_000, 3)?;
+        assert!(matches!(
+            chain.add_header(header),
+            Err(Error::UnknownParentHeader { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint() -> Result<()> {
+        let genesis = mine(Sha256d::default(), 0, 0)?;
+        let genesis_hash = genesis.block_hash();
+        let checkpointed = mine(genesis_hash.clone(), 1_000, 1)?;
+        let params = ChainParams {
+            checkpoints: vec![(1, checkpointed.block_hash())],
+            ..ChainParams::regtest()
+        };
+        let mut chain = HeaderChain::in_memory(params, 0, genesis)?;
+        let other = mine(genesis_hash, 1_000, 2)?;
+        assert!(chain.add_header(other.clone()).is_err());
+        assert_eq!(chain.add_header(checkpointed)?, HeaderStatus::Extended);
+        // once passed, the chain can't fork below the checkpoint
+        assert!(chain.add_header(other).is_err());
+        Ok(())
+    }
+}
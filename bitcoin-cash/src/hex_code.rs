@@ -0,0 +1,83 @@
+use crate::BitcoinCode;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::Deref;
+
+// serializes any BitcoinCode type as the hex of its consensus encoding,
+// or as raw bytes for non-human-readable formats
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Hex<T>(pub T);
+
+impl<T> Hex<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Hex<T> {
+    fn from(value: T) -> Self {
+        Hex(value)
+    }
+}
+
+impl<T> Deref for Hex<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: BitcoinCode> Serialize for Hex<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let data = self.0.ser();
+        if serializer.is_human_readable() {
+            data.hex().serialize(serializer)
+        } else {
+            data.to_vec().serialize(serializer)
+        }
+    }
+}
+
+impl<'de, T: BitcoinCode> Deserialize<'de> for Hex<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = if deserializer.is_human_readable() {
+            let hex = String::deserialize(deserializer)?;
+            hex::decode(&hex).map_err(serde::de::Error::custom)?
+        } else {
+            Vec::<u8>::deserialize(deserializer)?
+        };
+        T::deser(data.into())
+            .map(Hex)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Hex;
+    use crate::{TxOutpoint, TxOutput};
+
+    #[test]
+    fn test_hex_roundtrip() -> Result<(), serde_json::Error> {
+        let outputs = vec![Hex(TxOutput {
+            value: 1000,
+            script: Default::default(),
+            token: None,
+        })];
+        let json = serde_json::to_string(&outputs)?;
+        assert_eq!(json, r#"["e80300000000000000"]"#);
+        assert_eq!(serde_json::from_str::<Vec<Hex<TxOutput>>>(&json)?, outputs);
+        // trailing bytes are rejected
+        assert!(
+            serde_json::from_str::<Hex<TxOutpoint>>(&format!("\"{}00\"", "00".repeat(36))).is_err()
+        );
+        assert!(serde_json::from_str::<Hex<TxOutpoint>>("\"zz\"").is_err());
+        Ok(())
+    }
+}
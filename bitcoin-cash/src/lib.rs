@@ -2,41 +2,115 @@
 extern crate thiserror;
 
 mod address;
+mod async_signatory;
+mod batch_payment;
+mod bcmr;
 mod bitcoin_code;
+mod block;
+mod bloom_filter;
+mod cash_tokens;
+mod chain_backend;
+mod compact_block;
+mod consolidation;
+mod covenant;
 mod deserializer;
 mod destination;
+mod difficulty;
 mod ecc;
+mod ecies;
+#[cfg(feature = "electrum-client")]
+mod electrum_client;
+mod extended_key;
+mod fee_rate;
+mod flipstarter;
 pub mod error;
 mod hash;
+mod header_chain;
+mod hex_code;
+mod malleability;
+mod merkle_block;
+mod musig;
+mod nft_commitment;
 mod ops;
+mod p2p;
+#[cfg(feature = "p2p-client")]
+mod p2p_client;
+#[cfg(feature = "payment-protocol")]
+mod payment_protocol;
+mod peer_address;
 mod pubkey;
+#[cfg(feature = "rpc-client")]
+mod rpc_client;
 mod script;
 mod scripts;
+mod secret_key;
 mod serialize_json;
 mod serializer;
+mod sha256d_hasher;
 mod tagged_op;
+mod token_genesis;
+mod token_validation;
 mod tx;
 mod tx_builder;
 mod tx_preimage;
 mod sequence;
+mod unlock_size;
+mod wallet;
 
 pub use address::{Address, AddressType, Prefix};
+pub use async_signatory::*;
+pub use batch_payment::*;
+pub use bcmr::*;
 pub use bitcoin_code::*;
+pub use block::*;
+pub use bloom_filter::*;
+pub use cash_tokens::*;
+pub use chain_backend::*;
+pub use compact_block::*;
+pub use consolidation::*;
+pub use covenant::*;
 pub use deserializer::*;
 pub use destination::*;
+pub use difficulty::*;
 pub use ecc::*;
+pub use ecies::*;
+#[cfg(feature = "electrum-client")]
+pub use electrum_client::*;
+pub use extended_key::*;
+pub use fee_rate::*;
+pub use flipstarter::*;
 pub use hash::*;
+pub use header_chain::*;
+pub use hex_code::*;
+pub use malleability::*;
+pub use merkle_block::*;
+pub use musig::*;
+pub use nft_commitment::*;
 pub use ops::*;
+pub use p2p::*;
+#[cfg(feature = "p2p-client")]
+pub use p2p_client::*;
+#[cfg(feature = "payment-protocol")]
+pub use payment_protocol::*;
+pub use peer_address::*;
 pub use pubkey::*;
+#[cfg(feature = "rpc-client")]
+pub use rpc_client::*;
 pub use script::*;
 pub use scripts::*;
+pub use secret_key::*;
 pub use serialize_json::*;
 pub use serializer::*;
+pub use sha256d_hasher::*;
 pub use tagged_op::*;
+pub use token_genesis::*;
+pub use token_validation::*;
 pub use tx::*;
 pub use tx_builder::*;
 pub use tx_preimage::*;
 pub use sequence::*;
+pub use unlock_size::*;
+pub use wallet::*;
 
 pub use bitcoin_cash_base::*;
 pub use bitcoin_cash_code::*;
@@ -0,0 +1,169 @@
+use crate::error::Result;
+use crate::{deserialize_ops, Op, Opcode, UnhashedTx};
+
+// (n - 1) / 2 of secp256k1, the largest s value allowed by the low-S rule
+const HALF_CURVE_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+const SCHNORR_TX_SIGNATURE_SIZE: usize = 65;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MalleabilityKind {
+    NonPushOp,
+    NonMinimalPush,
+    NonDerSignature,
+    HighSSignature,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MalleabilityIssue {
+    pub input_idx: usize,
+    pub op_idx: usize,
+    pub kind: MalleabilityKind,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MalleabilityReport {
+    pub issues: Vec<MalleabilityIssue>,
+}
+
+impl MalleabilityReport {
+    pub fn is_malleable(&self) -> bool {
+        !self.issues.is_empty()
+    }
+}
+
+impl UnhashedTx {
+    pub fn malleability_report(&self) -> Result<MalleabilityReport> {
+        let mut report = MalleabilityReport::default();
+        for (input_idx, input) in self.inputs.iter().enumerate() {
+            let ops = deserialize_ops(&input.script.ser_ops())?;
+            for (op_idx, op) in ops.iter().enumerate() {
+                let mut add_issue = |kind| {
+                    report.issues.push(MalleabilityIssue {
+                        input_idx,
+                        op_idx,
+                        kind,
+                    })
+                };
+                match op {
+                    Op::Code(opcode) if *opcode as u8 <= Opcode::OP_16 as u8 => {}
+                    Op::Code(_) | Op::Invalid(_) => add_issue(MalleabilityKind::NonPushOp),
+                    Op::PushBoolean(_) | Op::PushInteger(_) => {}
+                    Op::PushByteArray { array, is_minimal } => {
+                        let is_small_int =
+                            array.len() == 1 && (1..=16).contains(&array[0]) || array[..] == [0x81];
+                        if !is_minimal || is_small_int {
+                            add_issue(MalleabilityKind::NonMinimalPush);
+                        }
+                        if array.len() != SCHNORR_TX_SIGNATURE_SIZE && array.first() == Some(&0x30)
+                        {
+                            let sig = &array[..array.len() - 1];
+                            if !is_strict_der(sig) {
+                                add_issue(MalleabilityKind::NonDerSignature);
+                            } else if !is_low_s(sig) {
+                                add_issue(MalleabilityKind::HighSSignature);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+// BIP66 strict DER encoding of an ECDSA signature without the sig hash byte
+fn is_strict_der(sig: &[u8]) -> bool {
+    if sig.len() < 8 || sig.len() > 72 || sig[0] != 0x30 || sig[1] as usize != sig.len() - 2 {
+        return false;
+    }
+    let r_len = sig[3] as usize;
+    if sig[2] != 0x02 || r_len == 0 || 5 + r_len >= sig.len() {
+        return false;
+    }
+    let s_len = sig[5 + r_len] as usize;
+    if sig[4 + r_len] != 0x02 || s_len == 0 || r_len + s_len + 6 != sig.len() {
+        return false;
+    }
+    let is_valid_int =
+        |int: &[u8]| int[0] & 0x80 == 0 && !(int.len() > 1 && int[0] == 0 && int[1] & 0x80 == 0);
+    is_valid_int(&sig[4..4 + r_len]) && is_valid_int(&sig[6 + r_len..])
+}
+
+fn is_low_s(sig: &[u8]) -> bool {
+    let r_len = sig[3] as usize;
+    let s = &sig[6 + r_len..];
+    let s = &s[s.iter().take_while(|&&byte| byte == 0).count()..];
+    if s.len() != HALF_CURVE_ORDER.len() {
+        return s.len() < HALF_CURVE_ORDER.len();
+    }
+    s <= &HALF_CURVE_ORDER[..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MalleabilityKind;
+    use crate::error::Result;
+    use crate::{ByteArray, Op, Script, TxInput, TxOutpoint, UnhashedTx};
+    use hex_literal::hex;
+
+    fn push(data: &[u8], is_minimal: bool) -> Op {
+        Op::PushByteArray {
+            array: ByteArray::from_slice_unnamed(data),
+            is_minimal,
+        }
+    }
+
+    fn audit(ops: Vec<Op>) -> Result<Vec<MalleabilityKind>> {
+        let tx = UnhashedTx {
+            inputs: vec![TxInput::new(
+                TxOutpoint::default(),
+                Script::from_ops(ops),
+                0xffff_ffff,
+            )],
+            ..Default::default()
+        };
+        let report = tx.malleability_report()?;
+        Ok(report.issues.iter().map(|issue| issue.kind).collect())
+    }
+
+    #[test]
+    fn test_malleability_report() -> Result<()> {
+        let low_s = hex!(
+            "3044022057292e2d4dfe775becdd0a9e6547997c728cdf35390f6a017da56d654d374e4902206b643be2fc
+             53763b4e284845bfea2c597d2dc7759941dce937636c9d341b71ed41"
+        );
+        let high_s = hex!(
+            "3045022057292e2d4dfe775becdd0a9e6547997c728cdf35390f6a017da56d654d374e49022100949bc41d
+             03ac89c4b1d7b7ba4015d3a53d8115711606c352886ef1ef9c1acf5441"
+        );
+        let pubkey = [2; 33];
+        assert!(audit(vec![push(&low_s, true), push(&pubkey, true)])?.is_empty());
+        assert!(audit(vec![push(&[3; 65], true), push(&pubkey, true)])?.is_empty());
+        assert_eq!(
+            audit(vec![push(&high_s, true), push(&pubkey, true)])?,
+            vec![MalleabilityKind::HighSSignature],
+        );
+        let mut non_der = low_s;
+        non_der[1] += 1;
+        assert_eq!(
+            audit(vec![push(&non_der, true)])?,
+            vec![MalleabilityKind::NonDerSignature],
+        );
+        assert!(audit(vec![push(&[5], true), push(&[], true)])?.is_empty());
+        assert_eq!(
+            audit(vec![push(&[5], false), push(&[], false)])?,
+            vec![
+                MalleabilityKind::NonMinimalPush,
+                MalleabilityKind::NonMinimalPush
+            ],
+        );
+        assert_eq!(
+            audit(vec![Op::Code(crate::Opcode::OP_DUP)])?,
+            vec![MalleabilityKind::NonPushOp],
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+use crate::error::{Error, Result};
+use crate::{BitcoinCode, BlockHeader, Hashed, MessagePayload, Sha256d};
+use serde::{Deserialize, Serialize};
+
+// BIP37 merkleblock: a header plus the partial merkle tree proving which txs it commits to
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, Deserialize, Serialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MerkleBlock {
+    pub header: BlockHeader,
+    pub partial_tree: PartialMerkleTree,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, Deserialize, Serialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialMerkleTree {
+    pub num_txs: u32,
+    pub hashes: Vec<Sha256d>,
+    pub flags: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerkleMatch {
+    pub txid: Sha256d,
+    pub position: u32,
+}
+
+impl MerkleBlock {
+    // checks the tree against the header and returns the position of `txid` in the block;
+    // the header's proof-of-work has to be checked separately
+    pub fn verify_tx(&self, txid: &Sha256d) -> Result<u32> {
+        let (merkle_root, matches) = self.partial_tree.extract_matches()?;
+        if merkle_root != self.header.merkle_root {
+            return Err(Error::MerkleRootMismatch {
+                expected: self.header.merkle_root.clone(),
+                actual: merkle_root,
+            });
+        }
+        matches
+            .into_iter()
+            .find(|merkle_match| &merkle_match.txid == txid)
+            .map(|merkle_match| merkle_match.position)
+            .ok_or_else(|| Error::TxNotInMerkleBlock { txid: txid.clone() })
+    }
+}
+
+impl PartialMerkleTree {
+    pub fn from_txids(txids: &[Sha256d], is_match: &[bool]) -> Result<Self> {
+        if txids.is_empty() || txids.len() != is_match.len() {
+            return Err(Error::InvalidPartialMerkleTree {
+                reason: "needs one match flag for each of at least one txid",
+            });
+        }
+        let mut builder = TreeBuilder {
+            txids,
+            is_match,
+            hashes: Vec::new(),
+            bits: Vec::new(),
+        };
+        builder.build(tree_height(txids.len()), 0);
+        let mut flags = vec![0; (builder.bits.len() + 7) / 8];
+        for (bit_idx, &bit) in builder.bits.iter().enumerate() {
+            flags[bit_idx / 8] |= (bit as u8) << (bit_idx % 8);
+        }
+        Ok(PartialMerkleTree {
+            num_txs: txids.len() as u32,
+            hashes: builder.hashes,
+            flags,
+        })
+    }
+
+    // returns the merkle root the tree commits to and the matched txids with their positions
+    pub fn extract_matches(&self) -> Result<(Sha256d, Vec<MerkleMatch>)> {
+        let invalid = |reason| Err(Error::InvalidPartialMerkleTree { reason });
+        let num_txs = self.num_txs as usize;
+        if num_txs == 0 {
+            return invalid("tree has no txs");
+        }
+        if self.hashes.len() > num_txs {
+            return invalid("more hashes than txs");
+        }
+        if self.flags.len() * 8 < self.hashes.len() {
+            return invalid("fewer flag bits than hashes");
+        }
+        let mut extractor = TreeExtractor {
+            num_txs,
+            hashes: &self.hashes,
+            flags: &self.flags,
+            hash_idx: 0,
+            bit_idx: 0,
+            matches: Vec::new(),
+        };
+        let merkle_root = extractor.extract(tree_height(num_txs), 0)?;
+        if extractor.hash_idx != self.hashes.len() {
+            return invalid("not all hashes were consumed");
+        }
+        if (extractor.bit_idx + 7) / 8 != self.flags.len() {
+            return invalid("not all flag bytes were consumed");
+        }
+        Ok((merkle_root, extractor.matches))
+    }
+}
+
+impl MessagePayload for MerkleBlock {
+    const COMMAND: &'static str = "merkleblock";
+}
+
+struct TreeBuilder<'a> {
+    txids: &'a [Sha256d],
+    is_match: &'a [bool],
+    hashes: Vec<Sha256d>,
+    bits: Vec<bool>,
+}
+
+impl TreeBuilder<'_> {
+    fn build(&mut self, height: u32, pos: usize) {
+        let first = pos << height;
+        let last = ((pos + 1) << height).min(self.txids.len());
+        let is_parent_of_match = self.is_match[first..last].iter().any(|&is_match| is_match);
+        self.bits.push(is_parent_of_match);
+        if height == 0 || !is_parent_of_match {
+            let hash = self.subtree_hash(height, pos);
+            self.hashes.push(hash);
+            return;
+        }
+        self.build(height - 1, pos * 2);
+        if pos * 2 + 1 < tree_width(self.txids.len(), height - 1) {
+            self.build(height - 1, pos * 2 + 1);
+        }
+    }
+
+    fn subtree_hash(&self, height: u32, pos: usize) -> Sha256d {
+        if height == 0 {
+            return self.txids[pos].clone();
+        }
+        let left = self.subtree_hash(height - 1, pos * 2);
+        if pos * 2 + 1 < tree_width(self.txids.len(), height - 1) {
+            hash_pair(&left, &self.subtree_hash(height - 1, pos * 2 + 1))
+        } else {
+            hash_pair(&left, &left)
+        }
+    }
+}
+
+struct TreeExtractor<'a> {
+    num_txs: usize,
+    hashes: &'a [Sha256d],
+    flags: &'a [u8],
+    hash_idx: usize,
+    bit_idx: usize,
+    matches: Vec<MerkleMatch>,
+}
+
+impl TreeExtractor<'_> {
+    fn extract(&mut self, height: u32, pos: usize) -> Result<Sha256d> {
+        let byte = self
+            .flags
+            .get(self.bit_idx / 8)
+            .ok_or(Error::InvalidPartialMerkleTree {
+                reason: "ran out of flag bits",
+            })?;
+        let is_parent_of_match = byte & (1 << (self.bit_idx % 8)) != 0;
+        self.bit_idx += 1;
+        if height == 0 || !is_parent_of_match {
+            let hash = self
+                .hashes
+                .get(self.hash_idx)
+                .ok_or(Error::InvalidPartialMerkleTree {
+                    reason: "ran out of hashes",
+                })?
+                .clone();
+            self.hash_idx += 1;
+            if height == 0 && is_parent_of_match {
+                self.matches.push(MerkleMatch {
+                    txid: hash.clone(),
+                    position: pos as u32,
+                });
+            }
+            return Ok(hash);
+        }
+        let left = self.extract(height - 1, pos * 2)?;
+        if pos * 2 + 1 < tree_width(self.num_txs, height - 1) {
+            let right = self.extract(height - 1, pos * 2 + 1)?;
+            // identical siblings let a mutated tree with duplicated txs hash to the same root
+            if right == left {
+                return Err(Error::InvalidPartialMerkleTree {
+                    reason: "identical sibling hashes",
+                });
+            }
+            Ok(hash_pair(&left, &right))
+        } else {
+            Ok(hash_pair(&left, &left))
+        }
+    }
+}
+
+fn tree_width(num_txs: usize, height: u32) -> usize {
+    (num_txs + (1 << height) - 1) >> height
+}
+
+fn tree_height(num_txs: usize) -> u32 {
+    let mut height = 0;
+    while tree_width(num_txs, height) > 1 {
+        height += 1;
+    }
+    height
+}
+
+fn hash_pair(left: &Sha256d, right: &Sha256d) -> Sha256d {
+    Sha256d::digest([left.as_slice(), right.as_slice()].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MerkleBlock, PartialMerkleTree};
+    use crate::error::{Error, Result};
+    use crate::{merkle_root, BitcoinCode, BlockHeader, Hashed, Sha256d};
+
+    #[test]
+    fn test_partial_merkle_tree() -> Result<()> {
+        let txids = (0..7u8)
+            .map(|idx| Sha256d::digest(vec![idx]))
+            .collect::<Vec<_>>();
+        let is_match = [false, false, true, false, false, true, false];
+        let partial_tree = PartialMerkleTree::from_txids(&txids, &is_match)?;
+        let merkle_block = MerkleBlock {
+            header: BlockHeader {
+                merkle_root: merkle_root(&txids),
+                ..Default::default()
+            },
+            partial_tree,
+        };
+        let merkle_block = MerkleBlock::deser(merkle_block.ser())?;
+        assert_eq!(merkle_block.verify_tx(&txids[2])?, 2);
+        assert_eq!(merkle_block.verify_tx(&txids[5])?, 5);
+        assert!(matches!(
+            merkle_block.verify_tx(&txids[3]),
+            Err(Error::TxNotInMerkleBlock { .. })
+        ));
+
+        let mut tampered = merkle_block.clone();
+        tampered.partial_tree.hashes[0] = Sha256d::default();
+        assert!(matches!(
+            tampered.verify_tx(&txids[2]),
+            Err(Error::MerkleRootMismatch { .. })
+        ));
+        let mut tampered = merkle_block;
+        tampered.partial_tree.flags.push(0);
+        assert!(tampered.partial_tree.extract_matches().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_tx_tree() -> Result<()> {
+        let txid = Sha256d::digest(b"coinbase".to_vec());
+        let partial_tree = PartialMerkleTree::from_txids(std::slice::from_ref(&txid), &[true])?;
+        assert_eq!(partial_tree.flags, vec![1]);
+        let (merkle_root, matches) = partial_tree.extract_matches()?;
+        assert_eq!(merkle_root, txid);
+        assert_eq!(matches[0].position, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+use crate::error::{Error, Result};
+use crate::{ByteArray, Function, Hashed, Pubkey, SecretKey, Sha256, ECC};
+use hmac::{Hmac, Mac, NewMac};
+use lazy_static::lazy_static;
+use num::bigint::BigUint;
+use num::{One, Zero};
+
+lazy_static! {
+    static ref CURVE_ORDER: BigUint = BigUint::parse_bytes(
+        b"fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+        16,
+    )
+    .unwrap();
+    static ref FIELD_PRIME: BigUint = BigUint::parse_bytes(
+        b"fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+        16,
+    )
+    .unwrap();
+}
+
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+// the RFC6979 algorithm tag of BCH Schnorr signatures, as used by Bitcoin ABC and BCHN
+const SCHNORR_NONCE_ALGO: &[u8; 16] = b"Schnorr+SHA256  ";
+
+#[derive(Clone, Debug)]
+pub struct MuSigKeyAgg {
+    pubkeys: Vec<Pubkey>,
+    coefficients: Vec<BigUint>,
+    agg_pubkey: Pubkey,
+}
+
+// a nonce for a single signing session; partial_sign consumes it, as signing two messages
+// with the same nonce leaks the secret key
+pub struct MuSigNonce {
+    secret_nonce: [u8; 32],
+    public_nonce: Pubkey,
+}
+
+#[derive(Clone, Debug)]
+pub struct MuSigSession<'a> {
+    key_agg: &'a MuSigKeyAgg,
+    public_nonces: Vec<Pubkey>,
+    agg_nonce_x: [u8; 32],
+    negate_nonces: bool,
+    challenge: BigUint,
+}
+
+impl MuSigKeyAgg {
+    pub fn new(ecc: &impl ECC, pubkeys: Vec<Pubkey>) -> Result<Self> {
+        let key_list_hash = Sha256::digest(
+            pubkeys
+                .iter()
+                .flat_map(|pubkey| pubkey.as_slice().iter().cloned())
+                .collect::<Vec<_>>(),
+        );
+        let mut coefficients = Vec::with_capacity(pubkeys.len());
+        let mut weighted_pubkeys = Vec::with_capacity(pubkeys.len());
+        for pubkey in &pubkeys {
+            let coefficient = hash_to_scalar(&[key_list_hash.as_slice(), pubkey.as_slice()]);
+            weighted_pubkeys.push(ecc.pubkey_tweak_mul(pubkey, &scalar_bytes(&coefficient))?);
+            coefficients.push(coefficient);
+        }
+        let agg_pubkey = ecc.combine_pubkeys(&weighted_pubkeys)?;
+        Ok(MuSigKeyAgg {
+            pubkeys,
+            coefficients,
+            agg_pubkey,
+        })
+    }
+
+    pub fn agg_pubkey(&self) -> &Pubkey {
+        &self.agg_pubkey
+    }
+
+    pub fn pubkeys(&self) -> &[Pubkey] {
+        &self.pubkeys
+    }
+
+    pub fn coefficient(&self, signer_idx: usize) -> Result<[u8; 32]> {
+        Ok(scalar_bytes(self.signer_coefficient(signer_idx)?))
+    }
+
+    fn signer_coefficient(&self, signer_idx: usize) -> Result<&BigUint> {
+        self.coefficients
+            .get(signer_idx)
+            .ok_or(Error::InvalidSignerIndex {
+                signer_idx,
+                num_signers: self.pubkeys.len(),
+            })
+    }
+}
+
+impl MuSigNonce {
+    // `secret_nonce` must be fresh randomness, never reused for another session
+    pub fn new(ecc: &impl ECC, secret_nonce: &[u8]) -> Result<Self> {
+        let public_nonce = ecc.derive_pubkey(secret_nonce)?;
+        let mut nonce = MuSigNonce {
+            secret_nonce: [0; 32],
+            public_nonce,
+        };
+        nonce.secret_nonce.copy_from_slice(secret_nonce);
+        Ok(nonce)
+    }
+
+    pub fn public_nonce(&self) -> &Pubkey {
+        &self.public_nonce
+    }
+
+    pub fn commitment(&self) -> Sha256 {
+        Sha256::digest(self.public_nonce.as_slice())
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for MuSigNonce {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.secret_nonce);
+    }
+}
+
+pub fn musig_verify_nonce_commitment(commitment: &Sha256, public_nonce: &Pubkey) -> bool {
+    &Sha256::digest(public_nonce.as_slice()) == commitment
+}
+
+impl<'a> MuSigSession<'a> {
+    pub fn new(
+        ecc: &impl ECC,
+        key_agg: &'a MuSigKeyAgg,
+        nonce_commitments: &[Sha256],
+        public_nonces: Vec<Pubkey>,
+        msg: &[u8],
+    ) -> Result<Self> {
+        if public_nonces.len() != key_agg.pubkeys.len()
+            || nonce_commitments.len() != key_agg.pubkeys.len()
+        {
+            return Err(Error::InvalidSize {
+                expected: key_agg.pubkeys.len(),
+                actual: public_nonces.len().min(nonce_commitments.len()),
+            });
+        }
+        for (signer_idx, (commitment, public_nonce)) in
+            nonce_commitments.iter().zip(&public_nonces).enumerate()
+        {
+            if !musig_verify_nonce_commitment(commitment, public_nonce) {
+                return Err(Error::InvalidNonceCommitment { signer_idx });
+            }
+        }
+        let agg_nonce = ecc.combine_pubkeys(&public_nonces)?;
+        let negate_nonces = !has_square_y(&agg_nonce)?;
+        let mut agg_nonce_x = [0; 32];
+        agg_nonce_x.copy_from_slice(&agg_nonce.as_slice()[1..]);
+        let challenge = schnorr_challenge(&agg_nonce_x, &key_agg.agg_pubkey, msg);
+        Ok(MuSigSession {
+            key_agg,
+            public_nonces,
+            agg_nonce_x,
+            negate_nonces,
+            challenge,
+        })
+    }
+
+    // the secret key and nonce only go through the backend's constant-time scalar ops
+    pub fn partial_sign(
+        &self,
+        ecc: &impl ECC,
+        signer_idx: usize,
+        secret_key: &[u8],
+        nonce: MuSigNonce,
+    ) -> Result<[u8; 32]> {
+        let order = &*CURVE_ORDER;
+        let coefficient = self.key_agg.signer_coefficient(signer_idx)?;
+        let weighted_challenge = (&self.challenge * coefficient) % order;
+        let weighted_key =
+            ecc.secret_key_tweak_mul(secret_key, &scalar_bytes(&weighted_challenge))?;
+        let s = if self.negate_nonces {
+            let negated_nonce = negate_secret(ecc, &nonce.secret_nonce)?;
+            ecc.secret_key_tweak_add(weighted_key.as_slice(), negated_nonce.as_slice())?
+        } else {
+            ecc.secret_key_tweak_add(weighted_key.as_slice(), &nonce.secret_nonce)?
+        };
+        let mut partial_sig = [0; 32];
+        partial_sig.copy_from_slice(s.as_slice());
+        Ok(partial_sig)
+    }
+
+    pub fn verify_partial(
+        &self,
+        ecc: &impl ECC,
+        signer_idx: usize,
+        partial_sig: &[u8; 32],
+    ) -> Result<bool> {
+        let order = &*CURVE_ORDER;
+        let coefficient = self.key_agg.signer_coefficient(signer_idx)?;
+        let s = BigUint::from_bytes_be(partial_sig);
+        if s.is_zero() || &s >= order {
+            return Ok(false);
+        }
+        let e = (&self.challenge * coefficient) % order;
+        let s_g = ecc.derive_pubkey(partial_sig)?;
+        let neg_e_p = ecc.pubkey_tweak_mul(
+            &self.key_agg.pubkeys[signer_idx],
+            &scalar_bytes(&((order - e) % order)),
+        )?;
+        let expected_nonce = ecc.combine_pubkeys(&[s_g, neg_e_p])?;
+        let public_nonce = &self.public_nonces[signer_idx];
+        Ok(if self.negate_nonces {
+            expected_nonce == negate_point(public_nonce)
+        } else {
+            &expected_nonce == public_nonce
+        })
+    }
+
+    pub fn aggregate(&self, partial_sigs: &[[u8; 32]], msg: impl Into<ByteArray>) -> ByteArray {
+        let order = &*CURVE_ORDER;
+        let s = partial_sigs
+            .iter()
+            .fold(BigUint::zero(), |acc, sig| {
+                (acc + BigUint::from_bytes_be(sig)) % order
+            });
+        let mut sig = self.agg_nonce_x.to_vec();
+        sig.extend_from_slice(&scalar_bytes(&s));
+        msg.into().apply_function(sig, Function::SchnorrSign)
+    }
+}
+
+// a BCH Schnorr signature with the deterministic nonce of Bitcoin ABC and BCHN, so the same
+// key and message give the same signature as there
+pub fn schnorr_sign(
+    ecc: &impl ECC,
+    secret_key: &[u8],
+    msg: impl Into<ByteArray>,
+) -> Result<ByteArray> {
+    let msg = msg.into();
+    let pubkey = ecc.derive_pubkey(secret_key)?;
+    let mut nonce_rng = Rfc6979::new(&[secret_key, &msg, SCHNORR_NONCE_ALGO]);
+    // nonces that aren't valid scalars are skipped, like libsecp256k1 does
+    let (k, nonce) = loop {
+        let k = nonce_rng.generate();
+        if let Ok(nonce) = ecc.derive_pubkey(&k) {
+            break (k, nonce);
+        }
+    };
+    let mut nonce_x = [0; 32];
+    nonce_x.copy_from_slice(&nonce.as_slice()[1..]);
+    let e = schnorr_challenge(&nonce_x, &pubkey, &msg);
+    let weighted_key = ecc.secret_key_tweak_mul(secret_key, &scalar_bytes(&e))?;
+    let s = if has_square_y(&nonce)? {
+        ecc.secret_key_tweak_add(weighted_key.as_slice(), &k)?
+    } else {
+        ecc.secret_key_tweak_add(weighted_key.as_slice(), negate_secret(ecc, &k)?.as_slice())?
+    };
+    let mut sig = nonce_x.to_vec();
+    sig.extend_from_slice(s.as_slice());
+    Ok(msg.apply_function(sig, Function::SchnorrSign))
+}
+
+pub fn schnorr_verify(ecc: &impl ECC, pubkey: &Pubkey, msg: &[u8], sig: &[u8]) -> Result<bool> {
+    if sig.len() != 64 {
+        return Err(Error::InvalidSignatureFormat);
+    }
+    let order = &*CURVE_ORDER;
+    let mut r = [0; 32];
+    r.copy_from_slice(&sig[..32]);
+    let s = BigUint::from_bytes_be(&sig[32..]);
+    if s.is_zero() || &s >= order || BigUint::from_bytes_be(&r) >= *FIELD_PRIME {
+        return Ok(false);
+    }
+    let e = schnorr_challenge(&r, pubkey, msg);
+    let s_g = ecc.derive_pubkey(&sig[32..])?;
+    let neg_e = (order - e) % order;
+    let nonce = if neg_e.is_zero() {
+        s_g
+    } else {
+        let neg_e_p = ecc.pubkey_tweak_mul(pubkey, &scalar_bytes(&neg_e))?;
+        match ecc.combine_pubkeys(&[s_g, neg_e_p]) {
+            Ok(nonce) => nonce,
+            Err(_) => return Ok(false),
+        }
+    };
+    Ok(nonce.as_slice()[1..] == r[..] && has_square_y(&nonce)?)
+}
+
+fn schnorr_challenge(nonce_x: &[u8; 32], pubkey: &Pubkey, msg: &[u8]) -> BigUint {
+    hash_to_scalar(&[nonce_x, pubkey.as_slice(), msg])
+}
+
+fn hash_to_scalar(parts: &[&[u8]]) -> BigUint {
+    let hash = Sha256::digest(parts.concat());
+    BigUint::from_bytes_be(hash.as_slice()) % &*CURVE_ORDER
+}
+
+// the HMAC-SHA256 DRBG of RFC6979 section 3.2, seeded like libsecp256k1's
+// nonce_function_rfc6979
+struct Rfc6979 {
+    k: [u8; 32],
+    v: [u8; 32],
+    retry: bool,
+}
+
+impl Rfc6979 {
+    fn new(seed: &[&[u8]]) -> Self {
+        let mut rng = Rfc6979 {
+            k: [0; 32],
+            v: [1; 32],
+            retry: false,
+        };
+        for &separator in &[0u8, 1] {
+            rng.k = rng.hmac(&[&rng.v, &[separator], &seed.concat()]);
+            rng.v = rng.hmac(&[&rng.v]);
+        }
+        rng
+    }
+
+    fn generate(&mut self) -> [u8; 32] {
+        if self.retry {
+            self.k = self.hmac(&[&self.v, &[0]]);
+            self.v = self.hmac(&[&self.v]);
+        }
+        self.v = self.hmac(&[&self.v]);
+        self.retry = true;
+        self.v
+    }
+
+    fn hmac(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut mac = HmacSha256::new_varkey(&self.k).expect("HMAC accepts keys of any size");
+        for part in parts {
+            mac.update(part);
+        }
+        let mut hash = [0; 32];
+        hash.copy_from_slice(&mac.finalize().into_bytes());
+        hash
+    }
+}
+
+// n - secret, computed by the backend so it runs in constant time
+fn negate_secret(ecc: &impl ECC, secret: &[u8]) -> Result<SecretKey> {
+    let minus_one = scalar_bytes(&(&*CURVE_ORDER - BigUint::one()));
+    ecc.secret_key_tweak_mul(secret, &minus_one)
+}
+
+fn scalar_bytes(scalar: &BigUint) -> [u8; 32] {
+    let bytes = scalar.to_bytes_be();
+    let mut array = [0; 32];
+    array[32 - bytes.len()..].copy_from_slice(&bytes);
+    array
+}
+
+fn negate_point(pubkey: &Pubkey) -> Pubkey {
+    let mut bytes = [0; 33];
+    bytes.copy_from_slice(pubkey.as_slice());
+    bytes[0] ^= 1;
+    Pubkey::new(bytes)
+}
+
+fn has_square_y(pubkey: &Pubkey) -> Result<bool> {
+    let p = &*FIELD_PRIME;
+    let slice = pubkey.as_slice();
+    if slice[0] != 2 && slice[0] != 3 {
+        return Err(Error::InvalidPubkey);
+    }
+    let x = BigUint::from_bytes_be(&slice[1..]);
+    let y_squared = (x.modpow(&BigUint::from(3u32), p) + BigUint::from(7u32)) % p;
+    let mut y = y_squared.modpow(&((p + BigUint::one()) >> 2), p);
+    if (&y % 2u32 == BigUint::one()) != (slice[0] == 3) {
+        y = p - y;
+    }
+    let legendre = y.modpow(&((p - BigUint::one()) >> 1), p);
+    Ok(legendre.is_one())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rfc6979;
+    use crate::{Hashed, Sha256};
+    use hex_literal::hex;
+
+    #[test]
+    fn test_rfc6979() {
+        // the well-known secp256k1 vector, for secret key 1
+        let mut secret_key = [0; 32];
+        secret_key[31] = 1;
+        let msg = Sha256::digest(b"Satoshi Nakamoto".to_vec());
+        let mut rng = Rfc6979::new(&[&secret_key, msg.as_slice()]);
+        assert_eq!(
+            rng.generate(),
+            hex!("8f8a276c19f4149656b280621e358cce24f5f52542772691ee69063b74f15d15"),
+        );
+        assert_ne!(
+            rng.generate(),
+            hex!("8f8a276c19f4149656b280621e358cce24f5f52542772691ee69063b74f15d15"),
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use crate::error::{Error, Result};
+use crate::MAX_NFT_COMMITMENT_LENGTH;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::ops::Deref;
+
+// the 0 to 40 bytes an NFT commits to
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "Vec<u8>", into = "Vec<u8>")]
+pub struct NftCommitment(Vec<u8>);
+
+impl NftCommitment {
+    pub fn new(commitment: Vec<u8>) -> Result<Self> {
+        if commitment.len() > MAX_NFT_COMMITMENT_LENGTH {
+            return Err(Error::NftCommitmentTooLong {
+                len: commitment.len(),
+            });
+        }
+        Ok(NftCommitment(commitment))
+    }
+
+    pub fn empty() -> Self {
+        NftCommitment(Vec::new())
+    }
+
+    // encoded like a VM number, so covenants can do arithmetic on it after OP_BIN2NUM
+    pub fn from_sequence_number(number: u64) -> Self {
+        let mut bytes = number.to_le_bytes().to_vec();
+        while bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        if bytes.last().is_some_and(|&last| last & 0x80 != 0) {
+            bytes.push(0);
+        }
+        NftCommitment(bytes)
+    }
+
+    // None if the commitment isn't a minimally encoded, non-negative VM number
+    pub fn sequence_number(&self) -> Option<u64> {
+        let (&last, rest) = match self.0.split_last() {
+            Some(split) => split,
+            None => return Some(0),
+        };
+        let is_minimal = last != 0 || rest.last().is_some_and(|&byte| byte & 0x80 != 0);
+        if last & 0x80 != 0 || !is_minimal || self.0.len() > 9 {
+            return None;
+        }
+        let mut bytes = [0; 9];
+        bytes[..self.0.len()].copy_from_slice(&self.0);
+        if bytes[8] != 0 {
+            return None;
+        }
+        let mut number_bytes = [0; 8];
+        number_bytes.copy_from_slice(&bytes[..8]);
+        Some(u64::from_le_bytes(number_bytes))
+    }
+
+    pub fn from_text(text: &str) -> Result<Self> {
+        Self::new(text.as_bytes().to_vec())
+    }
+
+    // None if the commitment isn't UTF-8
+    pub fn as_text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Deref for NftCommitment {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl TryFrom<Vec<u8>> for NftCommitment {
+    type Error = Error;
+
+    fn try_from(commitment: Vec<u8>) -> Result<Self> {
+        Self::new(commitment)
+    }
+}
+
+impl From<NftCommitment> for Vec<u8> {
+    fn from(commitment: NftCommitment) -> Self {
+        commitment.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NftCommitment;
+    use crate::error::{Error, Result};
+
+    #[test]
+    fn test_nft_commitment() -> Result<()> {
+        for &(number, bytes) in &[
+            (0, &[][..]),
+            (1, &[1][..]),
+            (0x80, &[0x80, 0][..]),
+            (0x1234, &[0x34, 0x12][..]),
+            (
+                u64::MAX,
+                &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0][..],
+            ),
+        ] {
+            let commitment = NftCommitment::from_sequence_number(number);
+            assert_eq!(commitment.as_slice(), bytes);
+            assert_eq!(commitment.sequence_number(), Some(number));
+        }
+        // negative, not minimal
+        for bytes in &[vec![0x81], vec![1, 0]] {
+            assert_eq!(NftCommitment::new(bytes.clone())?.sequence_number(), None);
+        }
+
+        let ticket = NftCommitment::from_text("ticket #1")?;
+        assert_eq!(ticket.as_text(), Some("ticket #1"));
+        assert!(matches!(
+            NftCommitment::new(vec![0; 41]),
+            Err(Error::NftCommitmentTooLong { len: 41 })
+        ));
+        let json = serde_json::to_string(&ticket).unwrap();
+        assert_eq!(
+            serde_json::from_str::<NftCommitment>(&json).unwrap(),
+            ticket
+        );
+        assert!(serde_json::from_str::<NftCommitment>(&format!("{:?}", [0; 41])).is_err());
+        Ok(())
+    }
+}
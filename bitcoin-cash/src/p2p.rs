@@ -0,0 +1,349 @@
+use crate::error::{Error, Result};
+use crate::{BitcoinCode, Block, BlockHeader, ByteArray, Hashed, Sha256d, Tx, MAX_COMPACT_SIZE};
+use std::io::{Read, Write};
+
+pub const MAINNET_MAGIC: [u8; 4] = [0xe3, 0xe1, 0xf3, 0xe8];
+pub const TESTNET_MAGIC: [u8; 4] = [0xf4, 0xe5, 0xf3, 0xf4];
+pub const REGTEST_MAGIC: [u8; 4] = [0xda, 0xb5, 0xbf, 0xfa];
+
+pub const PROTOCOL_VERSION: i32 = 70016;
+pub const MAX_MESSAGE_PAYLOAD_SIZE: u32 = MAX_COMPACT_SIZE as u32;
+
+pub const NODE_NETWORK: u64 = 1;
+pub const NODE_BLOOM: u64 = 1 << 2;
+pub const NODE_BITCOIN_CASH: u64 = 1 << 5;
+
+pub const INV_TX: u32 = 1;
+pub const INV_BLOCK: u32 = 2;
+pub const INV_FILTERED_BLOCK: u32 = 3;
+pub const INV_CMPCT_BLOCK: u32 = 4;
+
+const COMMAND_SIZE: usize = 12;
+
+// a payload that can be sent in a NetworkMessage under its command name
+pub trait MessagePayload: BitcoinCode {
+    const COMMAND: &'static str;
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone)]
+pub struct MessageHeader {
+    pub magic: [u8; 4],
+    pub command: [u8; COMMAND_SIZE],
+    pub payload_size: u32,
+    pub checksum: [u8; 4],
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct NetworkMessage {
+    pub header: MessageHeader,
+    pub payload: ByteArray,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone, Default)]
+pub struct NetworkAddress {
+    pub services: u64,
+    // IPv4 addresses are mapped into IPv6, as ::ffff:a.b.c.d
+    pub ip: [u8; 16],
+    #[bitcoin_code(big_endian)]
+    pub port: u16,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone)]
+pub struct VersionMessage {
+    pub version: i32,
+    pub services: u64,
+    pub timestamp: i64,
+    pub addr_recv: NetworkAddress,
+    pub addr_from: NetworkAddress,
+    pub nonce: u64,
+    pub user_agent: ByteArray,
+    pub start_height: i32,
+    #[bitcoin_code(optional = "trailing")]
+    pub relay: Option<bool>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Verack;
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Ping {
+    pub nonce: u64,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Pong {
+    pub nonce: u64,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone)]
+pub struct InvVector {
+    pub inv_type: u32,
+    pub hash: Sha256d,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone, Default)]
+pub struct Inv {
+    pub items: Vec<InvVector>,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone, Default)]
+pub struct GetData {
+    pub items: Vec<InvVector>,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone)]
+pub struct GetHeaders {
+    pub version: u32,
+    // newest first, thinning out exponentially towards the genesis block
+    pub locator_hashes: Vec<Sha256d>,
+    // all zeros to get as many headers as possible
+    pub hash_stop: Sha256d,
+}
+
+// headers messages reuse the block format, with the tx count always zero
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone)]
+pub struct HeadersEntry {
+    pub header: BlockHeader,
+    #[bitcoin_code(tag = 0)]
+    pub num_txs: u8,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone, Default)]
+pub struct Headers {
+    pub headers: Vec<HeadersEntry>,
+}
+
+impl MessageHeader {
+    pub fn command(&self) -> Result<&str> {
+        let len = self
+            .command
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(COMMAND_SIZE);
+        if self.command[len..].iter().any(|&byte| byte != 0) {
+            return Err(Error::InvalidNetworkMessage {
+                reason: "command has bytes after its NUL padding",
+            });
+        }
+        Ok(std::str::from_utf8(&self.command[..len])?)
+    }
+
+    fn check_payload(&self, payload: &[u8]) -> Result<()> {
+        if self.checksum != payload_checksum(payload) {
+            return Err(Error::InvalidNetworkMessage {
+                reason: "payload checksum mismatch",
+            });
+        }
+        Ok(())
+    }
+
+    fn check_payload_size(&self) -> Result<usize> {
+        if self.payload_size > MAX_MESSAGE_PAYLOAD_SIZE {
+            return Err(Error::InvalidNetworkMessage {
+                reason: "payload too large",
+            });
+        }
+        Ok(self.payload_size as usize)
+    }
+}
+
+impl NetworkMessage {
+    pub fn new<T: MessagePayload>(magic: [u8; 4], payload: &T) -> Self {
+        let payload = payload.ser();
+        let mut command = [0; COMMAND_SIZE];
+        command[..T::COMMAND.len()].copy_from_slice(T::COMMAND.as_bytes());
+        NetworkMessage {
+            header: MessageHeader {
+                magic,
+                command,
+                payload_size: payload.len() as u32,
+                checksum: payload_checksum(&payload),
+            },
+            payload,
+        }
+    }
+
+    pub fn command(&self) -> Result<&str> {
+        self.header.command()
+    }
+
+    pub fn parse_payload<T: MessagePayload>(&self) -> Result<T> {
+        let command = self.command()?;
+        if command != T::COMMAND {
+            return Err(Error::UnexpectedCommand {
+                expected: T::COMMAND,
+                actual: command.to_string(),
+            });
+        }
+        T::deser_strict(self.payload.clone())
+    }
+}
+
+impl BitcoinCode for NetworkMessage {
+    fn ser(&self) -> ByteArray {
+        self.header.ser().concat(self.payload.clone())
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (header, rest) = MessageHeader::deser_rest(data)?;
+        let (payload, rest) = rest.split(header.check_payload_size()?)?;
+        header.check_payload(&payload)?;
+        Ok((NetworkMessage { header, payload }, rest))
+    }
+
+    fn encode_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.header.encode_to(writer)?;
+        writer.write_all(&self.payload)?;
+        Ok(())
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let header = MessageHeader::decode_from(reader)?;
+        let mut payload = vec![0; header.check_payload_size()?];
+        reader.read_exact(&mut payload)?;
+        header.check_payload(&payload)?;
+        Ok(NetworkMessage {
+            header,
+            payload: payload.into(),
+        })
+    }
+
+    fn encoded_size(&self) -> usize {
+        self.header.encoded_size() + self.payload.len()
+    }
+}
+
+impl BitcoinCode for Verack {
+    fn ser(&self) -> ByteArray {
+        ByteArray::default()
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        Ok((Verack, data))
+    }
+}
+
+fn payload_checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = Sha256d::digest(payload.to_vec());
+    let mut checksum = [0; 4];
+    checksum.copy_from_slice(&hash.as_slice()[..4]);
+    checksum
+}
+
+macro_rules! message_payloads {
+    ($($T:ident => $command:literal),+ $(,)?) => {
+        $(
+            impl MessagePayload for $T {
+                const COMMAND: &'static str = $command;
+            }
+        )+
+    }
+}
+
+message_payloads! {
+    VersionMessage => "version",
+    Verack => "verack",
+    Ping => "ping",
+    Pong => "pong",
+    Inv => "inv",
+    GetData => "getdata",
+    GetHeaders => "getheaders",
+    Headers => "headers",
+    Tx => "tx",
+    Block => "block",
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Headers, HeadersEntry, Inv, NetworkAddress, NetworkMessage, Ping, Verack, VersionMessage,
+        MAINNET_MAGIC, NODE_NETWORK, PROTOCOL_VERSION,
+    };
+    use crate::error::{Error, Result};
+    use crate::{BitcoinCode, BlockHeader};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_verack_message() -> Result<()> {
+        let message = NetworkMessage::new(MAINNET_MAGIC, &Verack);
+        assert_eq!(
+            hex::encode(message.ser()),
+            "e3e1f3e876657261636b000000000000000000005df6e0e2",
+        );
+        assert_eq!(message.command()?, "verack");
+        assert_eq!(message.parse_payload::<Verack>()?, Verack);
+        let mut reader = Cursor::new(vec![2, 0xff]);
+        assert_eq!(
+            Vec::<Verack>::decode_from(&mut reader)?,
+            vec![Verack, Verack]
+        );
+        assert_eq!(reader.position(), 1);
+        assert!(matches!(
+            message.parse_payload::<Ping>(),
+            Err(Error::UnexpectedCommand { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_roundtrip() -> Result<()> {
+        let version = VersionMessage {
+            version: PROTOCOL_VERSION,
+            services: NODE_NETWORK,
+            timestamp: 1_600_000_000,
+            addr_recv: NetworkAddress {
+                services: NODE_NETWORK,
+                ip: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 127, 0, 0, 1],
+                port: 8333,
+            },
+            addr_from: NetworkAddress::default(),
+            nonce: 42,
+            user_agent: b"/bitcoin-cash:1.0.0/".to_vec().into(),
+            start_height: 650_000,
+            relay: Some(true),
+        };
+        let message = NetworkMessage::new(MAINNET_MAGIC, &version);
+        let ser = message.ser();
+        assert_eq!(ser.len(), message.encoded_size());
+        let decoded = NetworkMessage::decode_from(&mut Cursor::new(ser.to_vec()))?;
+        assert_eq!(decoded.parse_payload::<VersionMessage>()?, version);
+        assert_eq!(&decoded.payload[44..46], &[0x20, 0x8d]);
+
+        let headers = Headers {
+            headers: vec![HeadersEntry {
+                header: BlockHeader::default(),
+                num_txs: 0,
+            }],
+        };
+        let message = NetworkMessage::new(MAINNET_MAGIC, &headers);
+        assert_eq!(message.payload.len(), 1 + 81);
+        let mut corrupted = message.ser().to_vec();
+        *corrupted.last_mut().unwrap() = 1;
+        assert!(NetworkMessage::deser(corrupted.into()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_huge_count() -> Result<()> {
+        let payload = hex::decode("ffffffffffffffff0f")?;
+        assert!(Inv::deser(payload.clone().into()).is_err());
+        assert!(Inv::deser_slice(&payload).is_err());
+        let message = NetworkMessage {
+            payload: payload.into(),
+            ..NetworkMessage::new(MAINNET_MAGIC, &Inv { items: vec![] })
+        };
+        assert!(message.parse_payload::<Inv>().is_err());
+        Ok(())
+    }
+}
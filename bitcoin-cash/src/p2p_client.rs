@@ -0,0 +1,351 @@
+use crate::error::{Error, Result};
+use crate::{
+    Addr, AddrV2, AddrV2Entry, BitcoinCode, Block, BlockHeader, GetAddr, GetData, GetHeaders,
+    Headers, Inv, InvVector, MerkleBlock, MessageHeader, MessagePayload, NetworkAddress,
+    NetworkMessage, Ping, Pong, SendAddrV2, Sha256d, Tx, Verack, VersionMessage, INV_BLOCK, INV_TX,
+    MAINNET_MAGIC, MAX_MESSAGE_PAYLOAD_SIZE, PROTOCOL_VERSION,
+};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, Mutex};
+
+const MESSAGE_HEADER_SIZE: usize = 24;
+
+#[derive(Clone, Debug)]
+pub struct P2PClientConfig {
+    pub magic: [u8; 4],
+    pub services: u64,
+    pub user_agent: String,
+    pub start_height: i32,
+    // whether the peer should announce txs before a filter is loaded
+    pub relay: bool,
+    pub handshake_timeout: Duration,
+}
+
+#[derive(Debug)]
+pub enum P2PEvent {
+    Inv(Vec<InvVector>),
+    Headers(Vec<BlockHeader>),
+    Tx(Tx),
+    Block(Block),
+    MerkleBlock(MerkleBlock),
+    // from both addr and addrv2 messages
+    Addresses(Vec<AddrV2Entry>),
+    Other(NetworkMessage),
+    Disconnected(Error),
+}
+
+// a single peer connection; messages from the peer arrive as P2PEvents on the receiver
+// returned by `connect`, pings are answered in the background
+pub struct P2PClient {
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    magic: [u8; 4],
+    peer_version: VersionMessage,
+}
+
+impl Default for P2PClientConfig {
+    fn default() -> Self {
+        P2PClientConfig {
+            magic: MAINNET_MAGIC,
+            services: 0,
+            user_agent: "/bitcoin-cash:1.0.0/".to_string(),
+            start_height: 0,
+            relay: true,
+            handshake_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl P2PClient {
+    pub async fn connect(
+        addr: impl ToSocketAddrs,
+        config: P2PClientConfig,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<P2PEvent>)> {
+        let stream = TcpStream::connect(addr).await?;
+        let (mut reader, writer) = stream.into_split();
+        let writer = Arc::new(Mutex::new(writer));
+        let handshake = handshake(&writer, &mut reader, &config);
+        let peer_version = tokio::time::timeout(config.handshake_timeout, handshake)
+            .await
+            .map_err(|_| Error::InvalidNetworkMessage {
+                reason: "handshake timed out",
+            })??;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(read_events(reader, writer.clone(), config.magic, sender));
+        let client = P2PClient {
+            writer,
+            magic: config.magic,
+            peer_version,
+        };
+        Ok((client, receiver))
+    }
+
+    pub fn peer_version(&self) -> &VersionMessage {
+        &self.peer_version
+    }
+
+    pub async fn send<T: MessagePayload>(&self, payload: &T) -> Result<()> {
+        send_message(&self.writer, self.magic, payload).await
+    }
+
+    // `locator_hashes` are known block hashes, newest first
+    pub async fn request_headers(&self, locator_hashes: Vec<Sha256d>) -> Result<()> {
+        self.send(&GetHeaders {
+            version: PROTOCOL_VERSION as u32,
+            locator_hashes,
+            hash_stop: Sha256d::default(),
+        })
+        .await
+    }
+
+    pub async fn request_txs(&self, txids: &[Sha256d]) -> Result<()> {
+        self.request(INV_TX, txids).await
+    }
+
+    pub async fn request_blocks(&self, block_hashes: &[Sha256d]) -> Result<()> {
+        self.request(INV_BLOCK, block_hashes).await
+    }
+
+    pub async fn request_addresses(&self) -> Result<()> {
+        self.send(&GetAddr).await
+    }
+
+    pub async fn broadcast_tx(&self, tx: &Tx) -> Result<()> {
+        self.send(tx).await
+    }
+
+    async fn request(&self, inv_type: u32, hashes: &[Sha256d]) -> Result<()> {
+        let items = hashes
+            .iter()
+            .map(|hash| InvVector {
+                inv_type,
+                hash: hash.clone(),
+            })
+            .collect();
+        self.send(&GetData { items }).await
+    }
+}
+
+async fn handshake(
+    writer: &Mutex<OwnedWriteHalf>,
+    reader: &mut OwnedReadHalf,
+    config: &P2PClientConfig,
+) -> Result<VersionMessage> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let version = VersionMessage {
+        version: PROTOCOL_VERSION,
+        services: config.services,
+        timestamp: now.as_secs() as i64,
+        addr_recv: NetworkAddress::default(),
+        addr_from: NetworkAddress::default(),
+        // only used to detect connections to ourselves
+        nonce: now.as_nanos() as u64,
+        user_agent: config.user_agent.as_bytes().to_vec().into(),
+        start_height: config.start_height,
+        relay: Some(config.relay),
+    };
+    send_message(writer, config.magic, &version).await?;
+    let mut peer_version = None;
+    let mut has_verack = false;
+    while peer_version.is_none() || !has_verack {
+        let message = read_message(reader, config.magic).await?;
+        match message.command()? {
+            VersionMessage::COMMAND => {
+                peer_version = Some(message.parse_payload::<VersionMessage>()?);
+                // BIP155 only allows signalling addrv2 support before the verack
+                send_message(writer, config.magic, &SendAddrV2).await?;
+                send_message(writer, config.magic, &Verack).await?;
+            }
+            Verack::COMMAND => has_verack = true,
+            _ => {}
+        }
+    }
+    Ok(peer_version.expect("loop only ends with a version"))
+}
+
+async fn send_message<T: MessagePayload>(
+    writer: &Mutex<OwnedWriteHalf>,
+    magic: [u8; 4],
+    payload: &T,
+) -> Result<()> {
+    let message = NetworkMessage::new(magic, payload).ser();
+    writer.lock().await.write_all(&message).await?;
+    Ok(())
+}
+
+async fn read_message(reader: &mut OwnedReadHalf, magic: [u8; 4]) -> Result<NetworkMessage> {
+    let mut message = vec![0; MESSAGE_HEADER_SIZE];
+    reader.read_exact(&mut message).await?;
+    let (header, _) = MessageHeader::deser_slice(&message)?;
+    if header.magic != magic {
+        return Err(Error::InvalidNetworkMessage {
+            reason: "wrong network magic",
+        });
+    }
+    if header.payload_size > MAX_MESSAGE_PAYLOAD_SIZE {
+        return Err(Error::InvalidNetworkMessage {
+            reason: "payload too large",
+        });
+    }
+    message.resize(MESSAGE_HEADER_SIZE + header.payload_size as usize, 0);
+    reader
+        .read_exact(&mut message[MESSAGE_HEADER_SIZE..])
+        .await?;
+    NetworkMessage::deser(message.into())
+}
+
+async fn read_events(
+    mut reader: OwnedReadHalf,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    magic: [u8; 4],
+    sender: mpsc::UnboundedSender<P2PEvent>,
+) {
+    loop {
+        let event = match read_message(&mut reader, magic).await {
+            Ok(message) => handle_message(&writer, magic, message).await,
+            Err(err) => Err(err),
+        };
+        match event {
+            Ok(None) => {}
+            Ok(Some(event)) => {
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+            Err(err) => {
+                let _ = sender.send(P2PEvent::Disconnected(err));
+                return;
+            }
+        }
+    }
+}
+
+async fn handle_message(
+    writer: &Mutex<OwnedWriteHalf>,
+    magic: [u8; 4],
+    message: NetworkMessage,
+) -> Result<Option<P2PEvent>> {
+    let event = match message.command()? {
+        Ping::COMMAND => {
+            let ping = message.parse_payload::<Ping>()?;
+            send_message(writer, magic, &Pong { nonce: ping.nonce }).await?;
+            return Ok(None);
+        }
+        Inv::COMMAND => P2PEvent::Inv(message.parse_payload::<Inv>()?.items),
+        Headers::COMMAND => {
+            let headers = message.parse_payload::<Headers>()?.headers;
+            P2PEvent::Headers(headers.into_iter().map(|entry| entry.header).collect())
+        }
+        Tx::COMMAND => P2PEvent::Tx(message.parse_payload()?),
+        Block::COMMAND => P2PEvent::Block(message.parse_payload()?),
+        MerkleBlock::COMMAND => P2PEvent::MerkleBlock(message.parse_payload()?),
+        Addr::COMMAND => {
+            let addresses = message.parse_payload::<Addr>()?.addresses;
+            P2PEvent::Addresses(addresses.into_iter().map(AddrV2Entry::from).collect())
+        }
+        AddrV2::COMMAND => P2PEvent::Addresses(message.parse_payload::<AddrV2>()?.entries),
+        _ => P2PEvent::Other(message),
+    };
+    Ok(Some(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_message, send_message, P2PClient, P2PClientConfig, P2PEvent};
+    use crate::error::Result;
+    use crate::{
+        AddrV2, AddrV2Entry, GetAddr, Inv, InvVector, MessagePayload, NetworkAddress, PeerAddr,
+        Ping, Pong, SendAddrV2, Sha256d, Tx, UnhashedTx, Verack, VersionMessage, INV_TX,
+        MAINNET_MAGIC,
+    };
+    use tokio::net::TcpListener;
+    use tokio::sync::Mutex;
+
+    #[test]
+    fn test_p2p_client() -> Result<()> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let addr = listener.local_addr()?;
+            let peer = tokio::spawn(async move {
+                let (stream, _) = listener.accept().await?;
+                let (mut reader, writer) = stream.into_split();
+                let writer = Mutex::new(writer);
+                let version = read_message(&mut reader, MAINNET_MAGIC).await?;
+                assert_eq!(version.command()?, VersionMessage::COMMAND);
+                let peer_version = VersionMessage {
+                    start_height: 700_000,
+                    addr_recv: NetworkAddress::default(),
+                    addr_from: NetworkAddress::default(),
+                    ..version.parse_payload::<VersionMessage>()?
+                };
+                send_message(&writer, MAINNET_MAGIC, &peer_version).await?;
+                send_message(&writer, MAINNET_MAGIC, &Verack).await?;
+                let send_addrv2 = read_message(&mut reader, MAINNET_MAGIC).await?;
+                assert_eq!(send_addrv2.command()?, SendAddrV2::COMMAND);
+                let verack = read_message(&mut reader, MAINNET_MAGIC).await?;
+                assert_eq!(verack.command()?, Verack::COMMAND);
+
+                send_message(&writer, MAINNET_MAGIC, &Ping { nonce: 9 }).await?;
+                let pong = read_message(&mut reader, MAINNET_MAGIC).await?;
+                assert_eq!(pong.parse_payload::<Pong>()?.nonce, 9);
+                let inv = Inv {
+                    items: vec![InvVector {
+                        inv_type: INV_TX,
+                        hash: Sha256d::default(),
+                    }],
+                };
+                send_message(&writer, MAINNET_MAGIC, &inv).await?;
+                let get_addr = read_message(&mut reader, MAINNET_MAGIC).await?;
+                assert_eq!(get_addr.command()?, GetAddr::COMMAND);
+                let addrv2 = AddrV2 {
+                    entries: vec![AddrV2Entry {
+                        time: 0,
+                        services: 0,
+                        addr: PeerAddr::TorV3([1; 32]),
+                        port: 8333,
+                    }],
+                };
+                send_message(&writer, MAINNET_MAGIC, &addrv2).await?;
+                let tx = read_message(&mut reader, MAINNET_MAGIC).await?;
+                tx.parse_payload::<Tx>()
+            });
+
+            let (client, mut events) = P2PClient::connect(addr, P2PClientConfig::default()).await?;
+            assert_eq!(client.peer_version().start_height, 700_000);
+            match events.recv().await {
+                Some(P2PEvent::Inv(items)) => assert_eq!(items[0].inv_type, INV_TX),
+                event => panic!("expected an inv, got {:?}", event),
+            }
+            client.request_addresses().await?;
+            match events.recv().await {
+                Some(P2PEvent::Addresses(entries)) => {
+                    assert_eq!(entries[0].addr, PeerAddr::TorV3([1; 32]))
+                }
+                event => panic!("expected addresses, got {:?}", event),
+            }
+            let tx = UnhashedTx {
+                version: 1,
+                inputs: vec![],
+                outputs: vec![],
+                lock_time: 0,
+            }
+            .hashed();
+            client.broadcast_tx(&tx).await?;
+            let received = peer.await.expect("peer task panicked")?;
+            assert_eq!(received.hash(), tx.hash());
+            assert!(matches!(
+                events.recv().await,
+                Some(P2PEvent::Disconnected(_))
+            ));
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::{Error, Result};
+use crate::{Address, BitcoinCode, FeeRate, Script, TxBuilder, TxOutput, UnhashedTx};
+use serde::{Deserialize, Serialize};
+
+pub const PAYMENT_REQUEST_CONTENT_TYPE: &str = "application/payment-request";
+pub const PAYMENT_CONTENT_TYPE: &str = "application/payment";
+pub const PAYMENT_ACK_CONTENT_TYPE: &str = "application/payment-ack";
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequest {
+    pub network: String,
+    pub currency: String,
+    pub required_fee_rate: f64,
+    pub outputs: Vec<PaymentRequestOutput>,
+    pub time: String,
+    pub expires: String,
+    #[serde(default)]
+    pub memo: String,
+    pub payment_url: String,
+    pub payment_id: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequestOutput {
+    pub amount: u64,
+    pub address: Address<'static>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payment {
+    pub currency: String,
+    pub transactions: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentAck {
+    pub payment: Payment,
+    #[serde(default)]
+    pub memo: String,
+}
+
+impl PaymentRequest {
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn requested_amount(&self) -> u64 {
+        self.outputs.iter().map(|output| output.amount).sum()
+    }
+
+    pub fn tx_outputs(&self) -> Vec<TxOutput> {
+        self.outputs
+            .iter()
+            .map(|output| TxOutput {
+                value: output.amount,
+                script: (&output.address).into(),
+                token: None,
+            })
+            .collect()
+    }
+
+    pub fn fee_rate(&self) -> Result<FeeRate> {
+        FeeRate::from_sats_per_byte_f64(self.required_fee_rate)
+    }
+
+    pub fn apply_to_builder(&self, tx_builder: &mut TxBuilder<'_>) -> Result<()> {
+        let fee_rate = self.fee_rate()?;
+        tx_builder.add_outputs(self.tx_outputs());
+        tx_builder.set_fee_rate(tx_builder.fee_rate().max(fee_rate));
+        Ok(())
+    }
+
+    // the fee is only known if every input has its value set, which deserialized txs lack;
+    // receivers have to fill them in from the spent outputs first
+    pub fn verify_tx(&self, tx: &UnhashedTx) -> Result<()> {
+        let mut unmatched_outputs = tx.outputs.iter().collect::<Vec<_>>();
+        for requested in self.tx_outputs() {
+            let matched_idx = unmatched_outputs
+                .iter()
+                .position(|&output| output == &requested)
+                .ok_or(Error::PaymentRequirementNotMet {
+                    reason: "tx is missing a requested output",
+                })?;
+            unmatched_outputs.remove(matched_idx);
+        }
+        let fee = tx.fee().ok_or(Error::PaymentRequirementNotMet {
+            reason: "tx fee is unknown, as inputs are missing their values",
+        })?;
+        if FeeRate::from_fee_and_size(fee, tx.ser().len()) < self.fee_rate()? {
+            return Err(Error::PaymentRequirementNotMet {
+                reason: "tx fee rate is below the required fee rate",
+            });
+        }
+        Ok(())
+    }
+
+    pub fn payment(&self, txs: &[UnhashedTx]) -> Result<Payment> {
+        for tx in txs {
+            self.verify_tx(tx)?;
+        }
+        Ok(Payment::new(self.currency.clone(), txs))
+    }
+}
+
+impl PaymentRequestOutput {
+    pub fn script(&self) -> Script {
+        (&self.address).into()
+    }
+}
+
+impl Payment {
+    pub fn new(currency: String, txs: &[UnhashedTx]) -> Self {
+        Payment {
+            currency,
+            transactions: txs.iter().map(|tx| hex::encode(tx.ser())).collect(),
+        }
+    }
+
+    pub fn txs(&self) -> Result<Vec<UnhashedTx>> {
+        self.transactions
+            .iter()
+            .map(|tx| UnhashedTx::deser(hex::decode(tx)?.into()))
+            .collect()
+    }
+}
+
+impl PaymentAck {
+    pub fn new(payment: Payment, memo: String) -> Self {
+        PaymentAck { payment, memo }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Payment, PaymentRequest};
+    use crate::error::{Error, Result};
+    use crate::{Address, Prefix, Pubkey, Script, TxInput, TxOutpoint, UnhashedTx};
+
+    #[test]
+    fn test_payment_request() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let json = format!(
+            r#"{{
+                "network": "main",
+                "currency": "BCH",
+                "requiredFeeRate": 1.0,
+                "outputs": [{{"amount": 39365, "address": "{}"}}],
+                "time": "2019-03-01T00:00:00.000Z",
+                "expires": "2019-03-01T00:15:00.000Z",
+                "memo": "Payment request",
+                "paymentUrl": "https://example.com/i/abc",
+                "paymentId": "abc"
+            }}"#,
+            address.cash_addr(),
+        );
+        let request = PaymentRequest::from_json(&json)?;
+        assert_eq!(request.requested_amount(), 39365);
+        assert_eq!(request.fee_rate()?.sats_per_kb(), 1000);
+
+        let mut input = TxInput::new(TxOutpoint::default(), Script::new(vec![]), 0xffff_ffff);
+        input.value = Some(50_000);
+        let mut tx = UnhashedTx {
+            inputs: vec![input],
+            outputs: request.tx_outputs(),
+            ..Default::default()
+        };
+        request.verify_tx(&tx)?;
+        let payment = request.payment(&[tx.clone()])?;
+        let payment_json = serde_json::to_string(&payment)?;
+        let payment = serde_json::from_str::<Payment>(&payment_json)?;
+        let mut txs = payment.txs()?;
+        assert_eq!(txs[0].outputs, tx.outputs);
+        assert!(matches!(
+            request.verify_tx(&txs[0]),
+            Err(Error::PaymentRequirementNotMet { .. })
+        ));
+        txs[0].inputs[0].value = Some(50_000);
+        request.verify_tx(&txs[0])?;
+
+        tx.outputs[0].value -= 1;
+        assert!(matches!(
+            request.verify_tx(&tx),
+            Err(Error::PaymentRequirementNotMet { .. })
+        ));
+        tx.outputs[0].value += 1;
+        tx.inputs[0].value = Some(39_400);
+        assert!(matches!(
+            request.verify_tx(&tx),
+            Err(Error::PaymentRequirementNotMet { .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+use crate::error::{Error, Result};
+use crate::{BigEndianCode, BitcoinCode, ByteArray, MessagePayload, NetworkAddress, VarInt};
+use std::convert::{TryFrom, TryInto};
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+// BIP155 network ids
+pub const NET_IPV4: u8 = 1;
+pub const NET_IPV6: u8 = 2;
+pub const NET_TOR_V2: u8 = 3;
+pub const NET_TOR_V3: u8 = 4;
+pub const NET_I2P: u8 = 5;
+pub const NET_CJDNS: u8 = 6;
+
+pub const MAX_ADDRV2_ADDR_SIZE: usize = 512;
+
+// Tor v2 addresses are mapped into IPv6 in addr messages, using the OnionCat prefix
+const ONION_CAT_PREFIX: [u8; 6] = [0xfd, 0x87, 0xd8, 0x7e, 0xeb, 0x43];
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PeerAddr {
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+    // deprecated, only ever relayed by old nodes
+    TorV2([u8; 10]),
+    // the ed25519 public key of the hidden service
+    TorV3([u8; 32]),
+    // the SHA256 of the I2P destination
+    I2p([u8; 32]),
+    Cjdns(Ipv6Addr),
+    // networks this crate doesn't know are kept as-is, to be ignored or relayed
+    Unknown { network_id: u8, addr: ByteArray },
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone)]
+pub struct TimestampedAddress {
+    pub time: u32,
+    pub address: NetworkAddress,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone, Default)]
+pub struct Addr {
+    pub addresses: Vec<TimestampedAddress>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct AddrV2Entry {
+    pub time: u32,
+    // var-int encoded in addrv2
+    pub services: u64,
+    pub addr: PeerAddr,
+    pub port: u16,
+}
+
+#[bitcoin_code(crate = "crate")]
+#[derive(BitcoinCode, PartialEq, Eq, Debug, Clone, Default)]
+pub struct AddrV2 {
+    pub entries: Vec<AddrV2Entry>,
+}
+
+// sent between version and verack to ask the peer for addrv2 instead of addr
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct SendAddrV2;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct GetAddr;
+
+impl PeerAddr {
+    pub fn network_id(&self) -> u8 {
+        match self {
+            PeerAddr::Ipv4(_) => NET_IPV4,
+            PeerAddr::Ipv6(_) => NET_IPV6,
+            PeerAddr::TorV2(_) => NET_TOR_V2,
+            PeerAddr::TorV3(_) => NET_TOR_V3,
+            PeerAddr::I2p(_) => NET_I2P,
+            PeerAddr::Cjdns(_) => NET_CJDNS,
+            PeerAddr::Unknown { network_id, .. } => *network_id,
+        }
+    }
+
+    pub fn addr_bytes(&self) -> Vec<u8> {
+        match self {
+            PeerAddr::Ipv4(ip) => ip.octets().to_vec(),
+            PeerAddr::Ipv6(ip) | PeerAddr::Cjdns(ip) => ip.octets().to_vec(),
+            PeerAddr::TorV2(addr) => addr.to_vec(),
+            PeerAddr::TorV3(addr) | PeerAddr::I2p(addr) => addr.to_vec(),
+            PeerAddr::Unknown { addr, .. } => addr.to_vec(),
+        }
+    }
+
+    pub fn from_parts(network_id: u8, addr: &[u8]) -> Result<Self> {
+        if addr.len() > MAX_ADDRV2_ADDR_SIZE {
+            return Err(Error::InvalidNetworkMessage {
+                reason: "addrv2 address too long",
+            });
+        }
+        let wrong_size = |_| Error::InvalidNetworkMessage {
+            reason: "addrv2 address size doesn't match its network",
+        };
+        Ok(match network_id {
+            NET_IPV4 => PeerAddr::Ipv4(<[u8; 4]>::try_from(addr).map_err(wrong_size)?.into()),
+            NET_IPV6 => PeerAddr::Ipv6(<[u8; 16]>::try_from(addr).map_err(wrong_size)?.into()),
+            NET_TOR_V2 => PeerAddr::TorV2(addr.try_into().map_err(wrong_size)?),
+            NET_TOR_V3 => PeerAddr::TorV3(addr.try_into().map_err(wrong_size)?),
+            NET_I2P => PeerAddr::I2p(addr.try_into().map_err(wrong_size)?),
+            NET_CJDNS => PeerAddr::Cjdns(<[u8; 16]>::try_from(addr).map_err(wrong_size)?.into()),
+            _ => PeerAddr::Unknown {
+                network_id,
+                addr: ByteArray::from_slice_unnamed(addr),
+            },
+        })
+    }
+
+    // the IPv6 (or IPv4-mapped) encoding used by addr and version messages, if it has one
+    pub fn to_legacy_ip(&self) -> Option<[u8; 16]> {
+        match self {
+            PeerAddr::Ipv4(ip) => Some(ip.to_ipv6_mapped().octets()),
+            PeerAddr::Ipv6(ip) => Some(ip.octets()),
+            PeerAddr::TorV2(addr) => {
+                let mut ip = [0; 16];
+                ip[..6].copy_from_slice(&ONION_CAT_PREFIX);
+                ip[6..].copy_from_slice(addr);
+                Some(ip)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            PeerAddr::Ipv4(ip) => Some(IpAddr::V4(*ip)),
+            PeerAddr::Ipv6(ip) => Some(IpAddr::V6(*ip)),
+            _ => None,
+        }
+    }
+}
+
+impl AddrV2Entry {
+    // only IPv4 and IPv6 peers can be connected to directly
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        Some(SocketAddr::new(self.addr.ip()?, self.port))
+    }
+}
+
+impl From<[u8; 16]> for PeerAddr {
+    fn from(ip: [u8; 16]) -> Self {
+        if ip[..6] == ONION_CAT_PREFIX {
+            let mut addr = [0; 10];
+            addr.copy_from_slice(&ip[6..]);
+            return PeerAddr::TorV2(addr);
+        }
+        let ip = Ipv6Addr::from(ip);
+        match ip.to_ipv4_mapped() {
+            Some(ipv4) => PeerAddr::Ipv4(ipv4),
+            None => PeerAddr::Ipv6(ip),
+        }
+    }
+}
+
+impl From<TimestampedAddress> for AddrV2Entry {
+    fn from(address: TimestampedAddress) -> Self {
+        AddrV2Entry {
+            time: address.time,
+            services: address.address.services,
+            addr: address.address.ip.into(),
+            port: address.address.port,
+        }
+    }
+}
+
+impl BitcoinCode for PeerAddr {
+    fn ser(&self) -> ByteArray {
+        ByteArray::new("network_id", vec![self.network_id()])
+            .concat(ByteArray::new_unnamed(self.addr_bytes()).ser())
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (network_id, rest) = u8::deser_rest(data)?;
+        let (addr, rest) = ByteArray::deser_rest(rest)?;
+        Ok((PeerAddr::from_parts(network_id, &addr)?, rest))
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let network_id = u8::decode_from(reader)?;
+        let addr = ByteArray::decode_from(reader)?;
+        PeerAddr::from_parts(network_id, &addr)
+    }
+}
+
+impl BitcoinCode for AddrV2Entry {
+    fn ser(&self) -> ByteArray {
+        self.time
+            .ser()
+            .concat(VarInt(self.services).ser())
+            .concat(self.addr.ser())
+            .concat(self.port.ser_be())
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (time, rest) = u32::deser_rest(data)?;
+        let (VarInt(services), rest) = VarInt::deser_rest(rest)?;
+        let (addr, rest) = PeerAddr::deser_rest(rest)?;
+        let (port, rest) = u16::deser_be_rest(rest)?;
+        let entry = AddrV2Entry {
+            time,
+            services,
+            addr,
+            port,
+        };
+        Ok((entry, rest))
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        Ok(AddrV2Entry {
+            time: u32::decode_from(reader)?,
+            services: VarInt::decode_from(reader)?.0,
+            addr: PeerAddr::decode_from(reader)?,
+            port: u16::decode_be_from(reader)?,
+        })
+    }
+}
+
+impl BitcoinCode for SendAddrV2 {
+    fn ser(&self) -> ByteArray {
+        ByteArray::default()
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        Ok((SendAddrV2, data))
+    }
+}
+
+impl BitcoinCode for GetAddr {
+    fn ser(&self) -> ByteArray {
+        ByteArray::default()
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        Ok((GetAddr, data))
+    }
+}
+
+impl MessagePayload for Addr {
+    const COMMAND: &'static str = "addr";
+}
+
+impl MessagePayload for AddrV2 {
+    const COMMAND: &'static str = "addrv2";
+}
+
+impl MessagePayload for SendAddrV2 {
+    const COMMAND: &'static str = "sendaddrv2";
+}
+
+impl MessagePayload for GetAddr {
+    const COMMAND: &'static str = "getaddr";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddrV2, AddrV2Entry, GetAddr, PeerAddr, SendAddrV2, TimestampedAddress, NET_I2P};
+    use crate::error::{Error, Result};
+    use crate::{BitcoinCode, ByteArray, NetworkAddress, NODE_NETWORK};
+    use std::io::Cursor;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_addrv2() -> Result<()> {
+        let entries = vec![
+            AddrV2Entry {
+                time: 1_600_000_000,
+                services: NODE_NETWORK,
+                addr: PeerAddr::Ipv4(Ipv4Addr::new(1, 2, 3, 4)),
+                port: 8333,
+            },
+            AddrV2Entry {
+                time: 1_600_000_000,
+                services: 0x1_0000_0000,
+                addr: PeerAddr::TorV3([0xab; 32]),
+                port: 8333,
+            },
+            AddrV2Entry {
+                time: 0,
+                services: 0,
+                addr: PeerAddr::Unknown {
+                    network_id: 0x42,
+                    addr: ByteArray::from_slice_unnamed(b"future"),
+                },
+                port: 1,
+            },
+        ];
+        let ser = entries[0].ser();
+        assert_eq!(hex::encode(&ser), "00105e5f01010401020304208d");
+        let addrv2 = AddrV2 { entries };
+        assert_eq!(AddrV2::deser(addrv2.ser())?, addrv2);
+        let mut reader = Cursor::new(addrv2.ser().to_vec());
+        assert_eq!(AddrV2::decode_from(&mut reader)?, addrv2);
+        let addrs = addrv2.entries.iter().map(|entry| entry.addr.clone());
+        let addrs = addrs.collect::<Vec<_>>();
+        let mut reader = Cursor::new(addrs.ser().to_vec());
+        assert_eq!(Vec::<PeerAddr>::decode_from(&mut reader)?, addrs);
+        assert_eq!(
+            addrv2.entries[0].socket_addr(),
+            Some("1.2.3.4:8333".parse().unwrap())
+        );
+        assert_eq!(addrv2.entries[1].socket_addr(), None);
+
+        let mut wrong_size = PeerAddr::TorV3([0; 32]).ser().to_vec();
+        wrong_size[0] = NET_I2P;
+        wrong_size[1] = 31;
+        wrong_size.pop();
+        assert!(matches!(
+            PeerAddr::deser(wrong_size.into()),
+            Err(Error::InvalidNetworkMessage { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_empty_payloads() -> Result<()> {
+        let mut reader = Cursor::new(vec![2, 2, 0xff]);
+        assert_eq!(Vec::<SendAddrV2>::decode_from(&mut reader)?.len(), 2);
+        assert_eq!(Vec::<GetAddr>::decode_from(&mut reader)?.len(), 2);
+        assert_eq!(reader.position(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_addr() {
+        let ip = Ipv4Addr::new(10, 0, 0, 1).to_ipv6_mapped().octets();
+        let entry = AddrV2Entry::from(TimestampedAddress {
+            time: 5,
+            address: NetworkAddress {
+                services: NODE_NETWORK,
+                ip,
+                port: 8333,
+            },
+        });
+        assert_eq!(entry.addr, PeerAddr::Ipv4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(entry.addr.to_legacy_ip(), Some(ip));
+
+        let tor_v2 = PeerAddr::TorV2([7; 10]);
+        let legacy_ip = tor_v2.to_legacy_ip().unwrap();
+        assert_eq!(PeerAddr::from(legacy_ip), tor_v2);
+        assert_eq!(PeerAddr::I2p([1; 32]).to_legacy_ip(), None);
+    }
+}
@@ -0,0 +1,450 @@
+use crate::error::{Error, Result};
+use crate::{
+    BackendFuture, BitcoinCode, Block, ByteArray, ChainBackend, FeeRate, Hashed, Script, Sha256d,
+    Tx, TxOutpoint, Utxo, DEFAULT_FEE_RATE,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Take};
+use tokio::net::TcpStream;
+
+// leaves room for the hex of a 32 MB block returned by getblock
+pub const MAX_RPC_RESPONSE_SIZE: usize = 128 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockchainInfo {
+    pub chain: String,
+    pub blocks: u32,
+    pub headers: u32,
+    pub best_block_hash: Sha256d,
+    pub median_time: u32,
+    pub initial_block_download: bool,
+}
+
+// a JSON-RPC client for bitcoind/BCHN over plain HTTP, using one connection per call
+pub struct RpcClient {
+    addr: String,
+    auth: String,
+    next_id: AtomicU64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+struct BlockchainInfoEntry {
+    chain: String,
+    blocks: u32,
+    headers: u32,
+    bestblockhash: String,
+    mediantime: u32,
+    initialblockdownload: bool,
+}
+
+#[derive(Deserialize)]
+struct UnspentEntry {
+    txid: String,
+    vout: u32,
+    // in BCH
+    amount: f64,
+    height: u32,
+}
+
+impl RpcClient {
+    // `addr` is the node's host:port, e.g. 127.0.0.1:8332
+    pub fn new(addr: impl Into<String>, user: &str, password: &str) -> Self {
+        RpcClient {
+            addr: addr.into(),
+            auth: base64::encode(format!("{}:{}", user, password)),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let body = serde_json::to_string(&json!({
+            "jsonrpc": "1.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))?;
+        let request = format!(
+            "POST / HTTP/1.1\r\nHost: {}\r\nAuthorization: Basic {}\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.addr,
+            self.auth,
+            body.len(),
+            body,
+        );
+        let mut stream = TcpStream::connect(self.addr.as_str()).await?;
+        stream.write_all(request.as_bytes()).await?;
+        let (status, body) = read_http_response(BufReader::new(stream)).await?;
+        parse_rpc_response(status, &body)
+    }
+
+    pub async fn get_raw_transaction(&self, txid: &Sha256d) -> Result<Tx> {
+        let raw_tx = self
+            .call("getrawtransaction", json!([txid.to_hex_le(), false]))
+            .await?;
+        let tx = Tx::deser(parse_hex(&raw_tx)?)?;
+        if tx.hash() != txid {
+            return Err(Error::InvalidRpcResponse {
+                reason: "node returned a different tx",
+            });
+        }
+        Ok(tx)
+    }
+
+    pub async fn send_raw_transaction(&self, raw_tx: &[u8]) -> Result<Sha256d> {
+        let txid = self
+            .call("sendrawtransaction", json!([hex::encode(raw_tx)]))
+            .await?;
+        match txid.as_str() {
+            Some(txid) => Sha256d::from_hex_le(txid),
+            None => Err(Error::InvalidRpcResponse {
+                reason: "sendrawtransaction didn't return a txid",
+            }),
+        }
+    }
+
+    pub async fn get_block(&self, block_hash: &Sha256d) -> Result<Block> {
+        let raw_block = self
+            .call("getblock", json!([block_hash.to_hex_le(), 0]))
+            .await?;
+        let block = Block::deser(parse_hex(&raw_block)?)?;
+        if &block.header.block_hash() != block_hash {
+            return Err(Error::InvalidRpcResponse {
+                reason: "node returned a different block",
+            });
+        }
+        Ok(block)
+    }
+
+    pub async fn get_blockchain_info(&self) -> Result<BlockchainInfo> {
+        let info = self.call("getblockchaininfo", json!([])).await?;
+        let info: BlockchainInfoEntry = serde_json::from_value(info)?;
+        Ok(BlockchainInfo {
+            chain: info.chain,
+            blocks: info.blocks,
+            headers: info.headers,
+            best_block_hash: Sha256d::from_hex_le(&info.bestblockhash)?,
+            median_time: info.mediantime,
+            initial_block_download: info.initialblockdownload,
+        })
+    }
+}
+
+impl ChainBackend for RpcClient {
+    // nodes don't index outputs by script, so this scans the UTXO set, which can take a
+    // while. The UTXO set only has confirmed outputs: unconfirmed ones are missing, and
+    // outputs already spent by unconfirmed txs are still returned.
+    fn utxos<'a>(&'a self, lock_script: &'a Script) -> BackendFuture<'a, Vec<Utxo>> {
+        Box::pin(async move {
+            let descriptor = format!("raw({})", lock_script.ser_ops().hex());
+            let scan = self
+                .call("scantxoutset", json!(["start", [descriptor]]))
+                .await?;
+            let entries: Vec<UnspentEntry> = serde_json::from_value(scan["unspents"].clone())?;
+            entries
+                .into_iter()
+                .map(|entry| {
+                    Ok(Utxo {
+                        outpoint: TxOutpoint {
+                            tx_hash: Sha256d::from_hex_le(&entry.txid)?,
+                            vout: entry.vout,
+                        },
+                        value: (entry.amount * 1e8).round() as u64,
+                        height: Some(entry.height),
+                        token: None,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    fn tx<'a>(&'a self, txid: &'a Sha256d) -> BackendFuture<'a, Tx> {
+        Box::pin(self.get_raw_transaction(txid))
+    }
+
+    fn broadcast_raw_tx<'a>(&'a self, raw_tx: &'a [u8]) -> BackendFuture<'a, Sha256d> {
+        Box::pin(self.send_raw_transaction(raw_tx))
+    }
+
+    // BCHN's estimatefee takes no target, as BCH blocks are rarely full
+    fn estimate_fee(&self, _target_blocks: u32) -> BackendFuture<'_, FeeRate> {
+        Box::pin(async move {
+            let fee = self.call("estimatefee", json!([])).await?;
+            match fee.as_f64() {
+                Some(bch_per_kb) if bch_per_kb > 0.0 => {
+                    Ok(FeeRate::from_sats_per_kb((bch_per_kb * 1e8).round() as u64))
+                }
+                Some(_) => Ok(DEFAULT_FEE_RATE),
+                None => Err(Error::InvalidRpcResponse {
+                    reason: "fee estimate isn't a number",
+                }),
+            }
+        })
+    }
+}
+
+// returns the status and body; bitcoind sends a Content-Length, proxies in front of it may
+// use chunked encoding instead. The whole response, headers included, may be at most
+// MAX_RPC_RESPONSE_SIZE bytes.
+async fn read_http_response(reader: impl AsyncBufRead + Unpin) -> Result<(u16, Vec<u8>)> {
+    let mut reader = reader.take(MAX_RPC_RESPONSE_SIZE as u64);
+    let status = read_line(&mut reader)
+        .await?
+        .split(' ')
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(Error::InvalidRpcResponse {
+            reason: "invalid HTTP status line",
+        })?;
+    let mut content_length = None;
+    let mut is_chunked = false;
+    loop {
+        let line = read_line(&mut reader).await?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => continue,
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            let length = value
+                .parse::<usize>()
+                .map_err(|_| Error::InvalidRpcResponse {
+                    reason: "invalid Content-Length",
+                })?;
+            content_length = Some(length);
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            // chunked is always the last encoding applied
+            is_chunked = value
+                .rsplit(',')
+                .next()
+                .is_some_and(|encoding| encoding.trim().eq_ignore_ascii_case("chunked"));
+        }
+    }
+    let mut body = Vec::new();
+    if is_chunked {
+        loop {
+            let line = read_line(&mut reader).await?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| Error::InvalidRpcResponse {
+                reason: "invalid chunk size",
+            })?;
+            if size == 0 {
+                // skip the trailers
+                while !read_line(&mut reader).await?.is_empty() {}
+                break;
+            }
+            read_body(&mut reader, &mut body, size).await?;
+            if !read_line(&mut reader).await?.is_empty() {
+                return Err(Error::InvalidRpcResponse {
+                    reason: "chunk longer than its size",
+                });
+            }
+        }
+    } else if let Some(content_length) = content_length {
+        read_body(&mut reader, &mut body, content_length).await?;
+    } else {
+        reader.read_to_end(&mut body).await?;
+        if reader.limit() == 0 {
+            return Err(Error::InvalidRpcResponse {
+                reason: "response too large",
+            });
+        }
+    }
+    Ok((status, body))
+}
+
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut Take<R>) -> Result<String> {
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line).await?;
+    if line.pop() != Some(b'\n') {
+        return Err(Error::InvalidRpcResponse {
+            reason: if reader.limit() == 0 {
+                "response too large"
+            } else {
+                "incomplete HTTP response"
+            },
+        });
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(std::str::from_utf8(&line)?.to_string())
+}
+
+async fn read_body<R: AsyncBufRead + Unpin>(
+    reader: &mut Take<R>,
+    body: &mut Vec<u8>,
+    size: usize,
+) -> Result<()> {
+    if size as u64 > reader.limit() {
+        return Err(Error::InvalidRpcResponse {
+            reason: "response too large",
+        });
+    }
+    let start = body.len();
+    body.resize(start + size, 0);
+    reader.read_exact(&mut body[start..]).await?;
+    Ok(())
+}
+
+// bitcoind answers RPC errors with a non-200 status and the error in the JSON body
+fn parse_rpc_response(status: u16, body: &[u8]) -> Result<Value> {
+    let mut body: Value = match serde_json::from_slice(body) {
+        Ok(body) => body,
+        Err(_) if status != 200 => return Err(Error::RpcHttpError { status }),
+        Err(err) => return Err(err.into()),
+    };
+    let error = body["error"].take();
+    if !error.is_null() {
+        return Err(Error::RpcError {
+            code: error["code"].as_i64().unwrap_or_default(),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+        });
+    }
+    Ok(body["result"].take())
+}
+
+fn parse_hex(value: &Value) -> Result<ByteArray> {
+    match value.as_str() {
+        Some(hex) => Ok(ByteArray::from_slice_unnamed(&hex::decode(hex)?)),
+        None => Err(Error::InvalidRpcResponse {
+            reason: "expected a hex string",
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_rpc_response, read_http_response, RpcClient, MAX_RPC_RESPONSE_SIZE};
+    use crate::error::{Error, Result};
+    use crate::{Address, ChainBackend, Hashed, Script, Sha256d};
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn read_response(response: &[u8]) -> Result<Value> {
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        let (status, body) = runtime.block_on(read_http_response(response))?;
+        parse_rpc_response(status, &body)
+    }
+
+    #[test]
+    fn test_read_http_response() -> Result<()> {
+        let body = "{\"result\":5,\"error\":null,\"id\":0}";
+        // bytes after the Content-Length aren't part of the body
+        let ok = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}}}",
+            body.len(),
+            body
+        );
+        assert_eq!(read_response(ok.as_bytes())?, json!(5));
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                        b\r\n{\"result\":5\r\n15;ext=1\r\n,\"error\":null,\"id\":0}\r\n\
+                        0\r\n\r\n";
+        assert_eq!(read_response(chunked)?, json!(5));
+        let rpc_error = b"HTTP/1.1 500 Internal Server Error\r\n\r\n\
+                          {\"result\":null,\"error\":\
+                          {\"code\":-25,\"message\":\"Missing inputs\"}}";
+        match read_response(rpc_error) {
+            Err(Error::RpcError { code, message }) => {
+                assert_eq!(code, -25);
+                assert_eq!(message, "Missing inputs");
+            }
+            result => panic!("expected an RPC error, got {:?}", result),
+        }
+        let unauthorized = b"HTTP/1.1 401 Unauthorized\r\n\r\n";
+        assert!(matches!(
+            read_response(unauthorized),
+            Err(Error::RpcHttpError { status: 401 })
+        ));
+        let too_large = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+            MAX_RPC_RESPONSE_SIZE,
+        );
+        assert!(matches!(
+            read_response(too_large.as_bytes()),
+            Err(Error::InvalidRpcResponse {
+                reason: "response too large",
+            })
+        ));
+        let truncated = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n{}";
+        assert!(matches!(read_response(truncated), Err(Error::Io(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rpc_client() -> Result<()> {
+        let address =
+            Address::from_cash_addr("bitcoincash:qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6a")?;
+        let lock_script: Script = (&address).into();
+        let txid_hex = "11".repeat(32);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let addr = listener.local_addr()?;
+            let node_txid_hex = txid_hex.clone();
+            let descriptor = format!("raw({})", lock_script.ser_ops().hex());
+            let node = tokio::spawn(async move {
+                for _ in 0..2 {
+                    let (mut stream, _) = listener.accept().await?;
+                    let mut request = vec![0; 4096];
+                    let size = stream.read(&mut request).await?;
+                    let request = String::from_utf8_lossy(&request[..size]).to_string();
+                    assert!(request.contains("Authorization: Basic dXNlcjpwYXNz\r\n"));
+                    let body: Value =
+                        serde_json::from_str(request.split("\r\n\r\n").nth(1).unwrap())?;
+                    let result = match body["method"].as_str() {
+                        Some("getblockchaininfo") => json!({
+                            "chain": "main",
+                            "blocks": 800000,
+                            "headers": 800001,
+                            "bestblockhash": node_txid_hex,
+                            "mediantime": 1690000000,
+                            "initialblockdownload": false,
+                        }),
+                        Some("scantxoutset") => {
+                            assert_eq!(body["params"], json!(["start", [descriptor]]));
+                            json!({
+                                "success": true,
+                                "unspents": [{
+                                    "txid": node_txid_hex,
+                                    "vout": 3,
+                                    "amount": 0.00012345,
+                                    "height": 799000,
+                                }],
+                            })
+                        }
+                        _ => json!(null),
+                    };
+                    let body = json!({"result": result, "error": null, "id": body["id"]});
+                    let body = body.to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body,
+                    );
+                    stream.write_all(response.as_bytes()).await?;
+                }
+                Result::Ok(())
+            });
+
+            let client = RpcClient::new(addr.to_string(), "user", "pass");
+            let info = client.get_blockchain_info().await?;
+            assert_eq!(info.blocks, 800_000);
+            assert_eq!(info.best_block_hash, Sha256d::from_hex_le(&txid_hex)?);
+            let utxos = client.utxos(&lock_script).await?;
+            assert_eq!(utxos.len(), 1);
+            assert_eq!(utxos[0].outpoint.vout, 3);
+            assert_eq!(utxos[0].value, 12_345);
+            assert_eq!(utxos[0].height, Some(799_000));
+            node.await.expect("node task panicked")
+        })
+    }
+}
@@ -1,5 +1,7 @@
 use crate::error::{self, ScriptSerializeError};
-use crate::{encoding_utils::encode_int, BitcoinCode, ByteArray, Op, Opcode, Ops, TaggedOp};
+use crate::{
+    encoding_utils::encode_int, var_int_size, BitcoinCode, ByteArray, Op, Opcode, Ops, TaggedOp,
+};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::borrow::Cow;
@@ -38,6 +40,13 @@ impl Script {
         &self.ops
     }
 
+    pub fn is_data_carrier(&self) -> bool {
+        match self.ops.first() {
+            Some(op) => matches!(op.op, Op::Code(Opcode::OP_RETURN)),
+            None => false,
+        }
+    }
+
     pub fn to_script_code(&self, n_codesep: Option<usize>) -> Script {
         let idx = if let Some(n_codesep) = n_codesep {
             let mut n_codeseps_found = 0;
@@ -140,6 +149,20 @@ fn serialize_push_bytes(bytes: ByteArray, is_minimal_push: bool) -> error::Resul
     }
 }
 
+fn op_size(op: &Op) -> usize {
+    match op {
+        Op::PushByteArray { array, is_minimal } => {
+            let mut prefix = Vec::new();
+            match serialize_push_prefix(&mut prefix, array, *is_minimal).expect("Serialize failed")
+            {
+                PushPrefixTail::NoTail => prefix.len(),
+                PushPrefixTail::PushedData => prefix.len() + array.len(),
+            }
+        }
+        _ => serialize_op(op).expect("Serialize failed").len(),
+    }
+}
+
 pub fn serialize_op(op: &Op) -> error::Result<ByteArray> {
     use Opcode::*;
     Ok(match *op {
@@ -289,6 +312,11 @@ impl Script {
         serialize_ops(self.ops.iter().map(|op| &op.op)).expect("Serialize failed")
     }
 
+    // the length of `ser_ops`, without copying pushed data
+    pub fn ops_size(&self) -> usize {
+        self.ops.iter().map(|op| op_size(&op.op)).sum()
+    }
+
     pub fn deser_ops(byte_array: ByteArray) -> error::Result<Self> {
         let ops = deserialize_ops_byte_array(byte_array)?;
         Ok(Self::from_ops(ops))
@@ -305,6 +333,22 @@ impl BitcoinCode for Script {
         let ops = deserialize_ops_byte_array(script_code)?;
         Ok((Self::from_ops(ops), rest))
     }
+
+    fn deser_slice(data: &[u8]) -> error::Result<(Self, &[u8])> {
+        let (script_code, rest) = ByteArray::deser_slice(data)?;
+        let ops = deserialize_ops_byte_array(script_code)?;
+        Ok((Self::from_ops(ops), rest))
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> error::Result<Self> {
+        let script_code = ByteArray::decode_from(reader)?;
+        Ok(Self::from_ops(deserialize_ops_byte_array(script_code)?))
+    }
+
+    fn encoded_size(&self) -> usize {
+        let ops_size = self.ops_size();
+        var_int_size(ops_size as u64) + ops_size
+    }
 }
 
 impl Serialize for Script {
@@ -347,4 +391,29 @@ impl std::fmt::Display for Script {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::Script;
+    use crate::{BitcoinCode, ByteArray, Integer, Op, Opcode};
+
+    #[test]
+    fn test_encoded_size() {
+        let push = |len: usize, is_minimal| Op::PushByteArray {
+            array: ByteArray::from_slice_unnamed(&vec![0x42; len]),
+            is_minimal,
+        };
+        let script = Script::from_ops(vec![
+            Op::Code(Opcode::OP_DUP),
+            Op::PushBoolean(true),
+            Op::PushInteger(Integer::from(16u8)),
+            Op::PushInteger(Integer::from(1000i16)),
+            push(0, true),
+            push(0, false),
+            push(1, true),
+            push(75, true),
+            push(76, true),
+            push(300, true),
+        ]);
+        assert_eq!(script.ops_size(), script.ser_ops().len());
+        assert_eq!(script.encoded_size(), script.ser().len());
+    }
+}
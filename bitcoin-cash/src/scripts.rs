@@ -1,9 +1,13 @@
 use crate::{
     error::{Error, Result},
-    Address, AddressType, ByteArray,
+    address::AddressPrefix,
+    schnorr_sign, Address, BitcoinCode, ByteArray, Hashed, InputReference, Integer,
+    KeyPair, Op,
     Opcode::*,
-    Pubkey, Script, SigHashFlags, Signatory, SignatoryKindOne, TaggedScript, TxOutput, TxPreimage,
-    MAX_SIGNATURE_SIZE,
+    Ops, Prefix, Pubkey, Script, Sha256d, SigHashFlags, Signatory, SignatoryKindMultiple,
+    SignatoryKindOne, TaggedScript,
+    TxOutput, TxPreimage, UnlockScriptKind, UnsignedTx, ECC, MAX_SIGNATURE_SIZE,
+    SCHNORR_SIGNATURE_SIZE,
 };
 
 #[derive(Clone, Debug)]
@@ -12,6 +16,30 @@ pub struct P2PKHSignatory {
     pub sig_hash_flags: SigHashFlags,
 }
 
+#[derive(Clone, Debug)]
+pub struct MultisigSignatory {
+    m: usize,
+    pubkeys: Vec<Pubkey>,
+    sig_hash_flags: SigHashFlags,
+    is_p2sh: bool,
+}
+
+pub const MAX_MULTISIG_PUBKEYS: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SigAlgorithm {
+    Ecdsa,
+    Schnorr,
+}
+
+// holds the key and signs itself, unlike P2PKHSignatory, which only knows the pubkey
+#[derive(Clone, Debug)]
+pub struct P2pkhSignatory {
+    pub keypair: KeyPair,
+    pub sig_hash_flags: SigHashFlags,
+    pub sig_algorithm: SigAlgorithm,
+}
+
 struct ParamsAddress<'a>(&'a Address<'a>);
 
 #[crate::script(P2PKHInputs, crate = "crate")]
@@ -32,31 +60,66 @@ pub fn p2sh_script(params: ParamsAddress<'_>, redeem_script: ByteArray) {
 
 impl Into<Script> for &'_ Address<'_> {
     fn into(self) -> Script {
-        match self.addr_type() {
-            AddressType::P2SH => ParamsAddress(self).p2sh_script().into(),
-            AddressType::P2PKH => ParamsAddress(self).p2pkh_script().into(),
+        if self.addr_type().is_p2sh() {
+            ParamsAddress(self).p2sh_script().into()
+        } else {
+            ParamsAddress(self).p2pkh_script().into()
         }
     }
 }
 
 impl Into<Script> for Address<'_> {
     fn into(self) -> Script {
-        match self.addr_type() {
-            AddressType::P2SH => ParamsAddress(&self).p2sh_script().into(),
-            AddressType::P2PKH => ParamsAddress(&self).p2pkh_script().into(),
+        if self.addr_type().is_p2sh() {
+            ParamsAddress(&self).p2sh_script().into()
+        } else {
+            ParamsAddress(&self).p2pkh_script().into()
         }
     }
 }
 
 impl Address<'_> {
     pub fn p2pkh_script(&self) -> Result<TaggedScript<P2PKHInputs>> {
-        if self.addr_type() != AddressType::P2PKH {
+        if self.addr_type().is_p2sh() {
             return Err(Error::InvalidAddressType);
         }
         Ok(ParamsAddress(self).p2pkh_script())
     }
 }
 
+impl<'a> Address<'a> {
+    pub fn from_multisig<P: Into<AddressPrefix<'a>>>(
+        prefix: P,
+        m: usize,
+        pubkeys: &[Pubkey],
+    ) -> Result<Address<'a>> {
+        let mut pubkeys = pubkeys.to_vec();
+        sort_pubkeys_bip67(&mut pubkeys);
+        Address::from_redeem_script(prefix, multisig_script(m, &pubkeys)?)
+    }
+}
+
+pub fn sort_pubkeys_bip67(pubkeys: &mut [Pubkey]) {
+    pubkeys.sort_by(|a, b| a.as_slice().cmp(b.as_slice()));
+}
+
+pub fn multisig_script(m: usize, pubkeys: &[Pubkey]) -> Result<Script> {
+    let n = pubkeys.len();
+    if m == 0 || m > n || n > MAX_MULTISIG_PUBKEYS {
+        return Err(Error::InvalidMultisig { m, n });
+    }
+    let mut ops = vec![Op::PushInteger(Integer::from(m as u8))];
+    for pubkey in pubkeys {
+        ops.push(Op::PushByteArray {
+            array: pubkey.as_byte_array(),
+            is_minimal: true,
+        });
+    }
+    ops.push(Op::PushInteger(Integer::from(n as u8)));
+    ops.push(Op::Code(OP_CHECKMULTISIG));
+    Ok(Script::from_ops(ops))
+}
+
 impl<'b> Signatory for P2PKHSignatory {
     type Script = P2PKHInputs;
     type Signatures = ByteArray;
@@ -86,4 +149,221 @@ impl<'b> Signatory for P2PKHSignatory {
     fn is_p2sh(&self) -> bool {
         false
     }
+    fn estimated_unlock_size(&self) -> Option<usize> {
+        Some(UnlockScriptKind::P2PKHEcdsa.unlock_script_size())
+    }
+}
+
+impl P2pkhSignatory {
+    pub fn new(keypair: KeyPair, sig_hash_flags: SigHashFlags) -> Self {
+        P2pkhSignatory {
+            keypair,
+            sig_hash_flags,
+            sig_algorithm: SigAlgorithm::Ecdsa,
+        }
+    }
+
+    pub fn new_schnorr(keypair: KeyPair, sig_hash_flags: SigHashFlags) -> Self {
+        P2pkhSignatory {
+            keypair,
+            sig_hash_flags,
+            sig_algorithm: SigAlgorithm::Schnorr,
+        }
+    }
+
+    pub fn address(&self) -> Address<'static> {
+        Address::from_pk(Prefix::default(), self.keypair.pubkey())
+    }
+
+    pub fn lock_script(&self) -> TaggedScript<P2PKHInputs> {
+        ParamsAddress(&self.address()).p2pkh_script()
+    }
+
+    pub fn sign(&self, ecc: &impl ECC, preimage: &TxPreimage) -> Result<ByteArray> {
+        let sig_hash = Sha256d::digest(preimage.ser());
+        let secret_key = self.keypair.secret_key().as_slice();
+        match self.sig_algorithm {
+            SigAlgorithm::Ecdsa => ecc.sign(secret_key, sig_hash),
+            SigAlgorithm::Schnorr => schnorr_sign(ecc, secret_key, sig_hash),
+        }
+    }
+
+    pub fn sign_input(
+        &self,
+        ecc: &impl ECC,
+        unsigned_tx: &mut UnsignedTx<'_>,
+        input_ref: InputReference<Self>,
+    ) -> Result<()> {
+        let sig = self.sign(ecc, unsigned_tx.input_preimages(input_ref))?;
+        unsigned_tx.sign_input(input_ref, sig)
+    }
+
+    fn p2pkh_signatory(&self) -> P2PKHSignatory {
+        P2PKHSignatory {
+            pubkey: *self.keypair.pubkey(),
+            sig_hash_flags: self.sig_hash_flags,
+        }
+    }
+
+    fn unlock_script_kind(&self) -> UnlockScriptKind {
+        match self.sig_algorithm {
+            SigAlgorithm::Ecdsa => UnlockScriptKind::P2PKHEcdsa,
+            SigAlgorithm::Schnorr => UnlockScriptKind::P2PKHSchnorr,
+        }
+    }
+}
+
+impl Signatory for P2pkhSignatory {
+    type Script = P2PKHInputs;
+    type Signatures = ByteArray;
+    type Kind = SignatoryKindOne;
+    fn sig_hash_flags(&self) -> SigHashFlags {
+        self.sig_hash_flags
+    }
+    fn placeholder_signatures(&self) -> Self::Signatures {
+        match self.sig_algorithm {
+            SigAlgorithm::Ecdsa => ByteArray::new_unnamed(vec![0; MAX_SIGNATURE_SIZE]),
+            SigAlgorithm::Schnorr => ByteArray::new_unnamed(vec![0; SCHNORR_SIGNATURE_SIZE]),
+        }
+    }
+    fn build_script(
+        &self,
+        tx_preimage: &TxPreimage,
+        estimated_size: Option<usize>,
+        sigs: Self::Signatures,
+        lock_script: &Script,
+        tx_outputs: &[TxOutput],
+    ) -> Self::Script {
+        self.p2pkh_signatory()
+            .build_script(tx_preimage, estimated_size, sigs, lock_script, tx_outputs)
+    }
+    fn is_p2sh(&self) -> bool {
+        false
+    }
+    fn estimated_unlock_size(&self) -> Option<usize> {
+        Some(self.unlock_script_kind().unlock_script_size())
+    }
+}
+
+impl MultisigSignatory {
+    pub fn new(m: usize, mut pubkeys: Vec<Pubkey>, sig_hash_flags: SigHashFlags) -> Result<Self> {
+        sort_pubkeys_bip67(&mut pubkeys);
+        multisig_script(m, &pubkeys)?;
+        Ok(MultisigSignatory {
+            m,
+            pubkeys,
+            sig_hash_flags,
+            is_p2sh: true,
+        })
+    }
+
+    pub fn new_bare(
+        m: usize,
+        pubkeys: Vec<Pubkey>,
+        sig_hash_flags: SigHashFlags,
+    ) -> Result<Self> {
+        multisig_script(m, &pubkeys)?;
+        Ok(MultisigSignatory {
+            m,
+            pubkeys,
+            sig_hash_flags,
+            is_p2sh: false,
+        })
+    }
+
+    pub fn m(&self) -> usize {
+        self.m
+    }
+
+    pub fn pubkeys(&self) -> &[Pubkey] {
+        &self.pubkeys
+    }
+
+    pub fn script(&self) -> Script {
+        multisig_script(self.m, &self.pubkeys).expect("Validated in constructor")
+    }
+
+    pub fn lock_script(&self) -> TaggedScript<Script> {
+        TaggedScript::new(self.script().ops().into_owned())
+    }
+
+    pub fn address<'a>(&self, prefix: impl Into<AddressPrefix<'a>>) -> Result<Address<'a>> {
+        if !self.is_p2sh {
+            return Err(Error::InvalidAddressType);
+        }
+        Address::from_redeem_script(prefix, self.script())
+    }
+
+    pub fn sign(
+        &self,
+        ecc: &impl ECC,
+        secret_key: &[u8],
+        preimage: &TxPreimage,
+    ) -> Result<(usize, ByteArray)> {
+        let pubkey = ecc.derive_pubkey(secret_key)?;
+        let pubkey_idx = self
+            .pubkeys
+            .iter()
+            .position(|multisig_pubkey| multisig_pubkey == &pubkey)
+            .ok_or(Error::InvalidPubkey)?;
+        let sig_hash = Sha256d::digest(preimage.ser());
+        Ok((pubkey_idx, ecc.sign(secret_key, sig_hash)?))
+    }
+}
+
+impl Signatory for MultisigSignatory {
+    type Script = Script;
+    type Signatures = Vec<(usize, ByteArray)>;
+    type Kind = SignatoryKindMultiple;
+    fn sig_hash_flags(&self) -> Vec<SigHashFlags> {
+        vec![self.sig_hash_flags; self.m]
+    }
+    fn placeholder_signatures(&self) -> Self::Signatures {
+        (0..self.m)
+            .map(|pubkey_idx| {
+                (
+                    pubkey_idx,
+                    ByteArray::new_unnamed(vec![0; MAX_SIGNATURE_SIZE]),
+                )
+            })
+            .collect()
+    }
+    fn build_script(
+        &self,
+        _tx_preimages: &[TxPreimage],
+        _estimated_size: Option<usize>,
+        mut sigs: Self::Signatures,
+        _lock_script: &Script,
+        _tx_outputs: &[TxOutput],
+    ) -> Self::Script {
+        sigs.sort_by_key(|&(pubkey_idx, _)| pubkey_idx);
+        // OP_CHECKMULTISIG pops one extra element
+        let mut ops = vec![Op::Code(OP_0)];
+        for (_, sig) in sigs {
+            ops.push(Op::PushByteArray {
+                array: sig.concat(ByteArray::new(
+                    "sig_hash",
+                    [self.sig_hash_flags.bits() as u8].as_ref(),
+                )),
+                is_minimal: false,
+            });
+        }
+        Script::from_ops(ops)
+    }
+    fn is_p2sh(&self) -> bool {
+        self.is_p2sh
+    }
+    fn estimated_unlock_size(&self) -> Option<usize> {
+        if self.is_p2sh {
+            Some(
+                UnlockScriptKind::P2SHMultisigEcdsa {
+                    m: self.m,
+                    n: self.pubkeys.len(),
+                }
+                .unlock_script_size(),
+            )
+        } else {
+            None
+        }
+    }
 }
@@ -0,0 +1,65 @@
+use crate::error::{Error, Result};
+use crate::{Pubkey, ECC};
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretKey([u8; 32]);
+
+#[derive(Clone, Debug)]
+pub struct KeyPair {
+    secret_key: SecretKey,
+    pubkey: Pubkey,
+}
+
+impl SecretKey {
+    pub fn new(secret_key: [u8; 32]) -> Self {
+        SecretKey(secret_key)
+    }
+
+    pub fn from_slice(slice: &[u8]) -> Result<Self> {
+        if slice.len() != 32 {
+            return Err(Error::InvalidSize {
+                expected: 32,
+                actual: slice.len(),
+            });
+        }
+        let mut secret_key = [0; 32];
+        secret_key.copy_from_slice(slice);
+        Ok(SecretKey(secret_key))
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn pubkey(&self, ecc: &impl ECC) -> Result<Pubkey> {
+        ecc.derive_pubkey(&self.0)
+    }
+}
+
+impl KeyPair {
+    pub fn from_secret_key(ecc: &impl ECC, secret_key: SecretKey) -> Result<Self> {
+        let pubkey = secret_key.pubkey(ecc)?;
+        Ok(KeyPair { secret_key, pubkey })
+    }
+
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+
+    pub fn pubkey(&self) -> &Pubkey {
+        &self.pubkey
+    }
+}
+
+impl std::fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SecretKey(<redacted>)")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.0);
+    }
+}
@@ -1,4 +1,18 @@
+use crate::error::{Error, Result};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 pub const CSV_TYPE_FLAG: u32 = 1 << 22;
+pub const CSV_DISABLE_FLAG: u32 = 1 << 31;
+pub const CSV_VALUE_MASK: u32 = 0xffff;
+pub const CSV_GRANULARITY_SECONDS: u64 = 512;
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+pub const BIP68_MIN_TX_VERSION: i32 = 2;
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum LockTime {
+    BlockHeight(u32),
+    Timestamp(u32),
+}
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub enum CsvTimedelta {
@@ -6,6 +20,10 @@ pub enum CsvTimedelta {
     Seconds512(u16),
 }
 
+// an input's nSequence, decoded according to BIP68
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Sequence(pub u32);
+
 impl CsvTimedelta {
     pub fn sequence(self) -> u32 {
         match self {
@@ -17,4 +35,184 @@ impl CsvTimedelta {
             }
         }
     }
+
+    // None if the disable flag is set
+    pub fn from_sequence(sequence: u32) -> Option<Self> {
+        if sequence & CSV_DISABLE_FLAG != 0 {
+            return None;
+        }
+        let value = (sequence & CSV_VALUE_MASK) as u16;
+        if sequence & CSV_TYPE_FLAG != 0 {
+            Some(CsvTimedelta::Seconds512(value))
+        } else {
+            Some(CsvTimedelta::Blockheight(value))
+        }
+    }
+
+    pub fn from_duration(duration: Duration) -> Result<Self> {
+        let secs = duration.as_secs();
+        let units = secs / CSV_GRANULARITY_SECONDS + u64::from(secs % CSV_GRANULARITY_SECONDS != 0);
+        if units > CSV_VALUE_MASK as u64 {
+            return Err(Error::InvalidTimelock {
+                reason: "relative lock exceeds 0xffff * 512 seconds",
+            });
+        }
+        Ok(CsvTimedelta::Seconds512(units as u16))
+    }
+
+    pub fn is_time_based(self) -> bool {
+        match self {
+            CsvTimedelta::Blockheight(_) => false,
+            CsvTimedelta::Seconds512(_) => true,
+        }
+    }
+
+    pub fn value(self) -> u16 {
+        match self {
+            CsvTimedelta::Blockheight(value) | CsvTimedelta::Seconds512(value) => value,
+        }
+    }
+}
+
+impl LockTime {
+    pub fn from_block_height(block_height: u32) -> Result<Self> {
+        if block_height >= LOCKTIME_THRESHOLD {
+            return Err(Error::InvalidTimelock {
+                reason: "block height must be below 500000000",
+            });
+        }
+        Ok(LockTime::BlockHeight(block_height))
+    }
+
+    pub fn from_timestamp(timestamp: u32) -> Result<Self> {
+        if timestamp < LOCKTIME_THRESHOLD {
+            return Err(Error::InvalidTimelock {
+                reason: "timestamp must be at least 500000000",
+            });
+        }
+        Ok(LockTime::Timestamp(timestamp))
+    }
+
+    pub fn from_system_time(time: SystemTime) -> Result<Self> {
+        let timestamp = time
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| Error::InvalidTimelock {
+                reason: "time is before the UNIX epoch",
+            })?
+            .as_secs();
+        if timestamp > u32::MAX as u64 {
+            return Err(Error::InvalidTimelock {
+                reason: "timestamp doesn't fit into 32 bits",
+            });
+        }
+        LockTime::from_timestamp(timestamp as u32)
+    }
+
+    pub fn from_value(value: u32) -> Self {
+        if value < LOCKTIME_THRESHOLD {
+            LockTime::BlockHeight(value)
+        } else {
+            LockTime::Timestamp(value)
+        }
+    }
+
+    pub fn value(self) -> u32 {
+        match self {
+            LockTime::BlockHeight(value) | LockTime::Timestamp(value) => value,
+        }
+    }
+
+    pub fn is_time_based(self) -> bool {
+        match self {
+            LockTime::BlockHeight(_) => false,
+            LockTime::Timestamp(_) => true,
+        }
+    }
+}
+
+impl Sequence {
+    pub fn is_final(self) -> bool {
+        self.0 == u32::MAX
+    }
+
+    pub fn is_relative_lock_disabled(self) -> bool {
+        self.0 & CSV_DISABLE_FLAG != 0
+    }
+
+    pub fn is_time_based(self) -> bool {
+        self.0 & CSV_TYPE_FLAG != 0
+    }
+
+    pub fn value(self) -> u16 {
+        (self.0 & CSV_VALUE_MASK) as u16
+    }
+
+    pub fn relative_lock(self) -> Option<CsvTimedelta> {
+        CsvTimedelta::from_sequence(self.0)
+    }
+
+    // BIP68 is only enforced for tx versions 2 and up; earlier txs have no relative lock
+    pub fn relative_lock_for_version(self, tx_version: i32) -> Option<CsvTimedelta> {
+        if tx_version < BIP68_MIN_TX_VERSION {
+            return None;
+        }
+        self.relative_lock()
+    }
+}
+
+impl From<u32> for Sequence {
+    fn from(sequence: u32) -> Self {
+        Sequence(sequence)
+    }
+}
+
+impl From<Sequence> for u32 {
+    fn from(sequence: Sequence) -> Self {
+        sequence.0
+    }
+}
+
+impl From<CsvTimedelta> for Sequence {
+    fn from(timedelta: CsvTimedelta) -> Self {
+        Sequence(timedelta.sequence())
+    }
+}
+
+pub fn validate_relative_lock_version(tx_version: i32) -> Result<()> {
+    if tx_version < BIP68_MIN_TX_VERSION {
+        return Err(Error::InvalidTimelock {
+            reason: "relative locks require tx version 2",
+        });
+    }
+    Ok(())
+}
+
+impl From<LockTime> for u32 {
+    fn from(lock_time: LockTime) -> Self {
+        lock_time.value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CsvTimedelta, Sequence};
+
+    #[test]
+    fn test_bip68_sequence() {
+        let sequence = Sequence::from(CsvTimedelta::Seconds512(300));
+        assert_eq!(sequence.0, (1 << 22) | 300);
+        assert!(sequence.is_time_based());
+        assert_eq!(sequence.value(), 300);
+        assert_eq!(sequence.relative_lock(), Some(CsvTimedelta::Seconds512(300)));
+        assert_eq!(sequence.relative_lock_for_version(1), None);
+        assert_eq!(sequence.relative_lock_for_version(2), Some(CsvTimedelta::Seconds512(300)));
+        assert_eq!(
+            Sequence(0x00ab_0010).relative_lock(),
+            Some(CsvTimedelta::Blockheight(0x10)),
+        );
+        let final_sequence = Sequence(0xffff_ffff);
+        assert!(final_sequence.is_final());
+        assert!(final_sequence.is_relative_lock_disabled());
+        assert_eq!(final_sequence.relative_lock(), None);
+    }
 }
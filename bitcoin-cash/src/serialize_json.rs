@@ -204,6 +204,7 @@ impl JsonTx {
                         .collect::<Result<Vec<_>, _>>()?,
                 ),
                 value: output.value,
+                token: None,
             };
             tx.outputs.push(output);
         }
@@ -0,0 +1,204 @@
+use crate::error::{Error, Result};
+use crate::Sha256d;
+use sha2::Digest;
+use std::io::{self, Write};
+
+const BLOCK_SIZE: usize = 64;
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09_e667,
+    0xbb67_ae85,
+    0x3c6e_f372,
+    0xa54f_f53a,
+    0x510e_527f,
+    0x9b05_688c,
+    0x1f83_d9ab,
+    0x5be0_cd19,
+];
+
+// SHA256 state after a whole number of 64-byte blocks, e.g. of a common preimage prefix
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Sha256Midstate {
+    pub state: [u32; 8],
+    // bytes hashed so far, always a multiple of 64
+    pub length: u64,
+}
+
+// incremental double SHA256; also implements Write, so `encode_to` can stream into it
+#[derive(Clone, Debug)]
+pub struct Sha256dHasher {
+    state: [u32; 8],
+    length: u64,
+    buffer: [u8; BLOCK_SIZE],
+}
+
+impl Sha256Midstate {
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        for (chunk, word) in bytes.chunks_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: [u8; 32], length: u64) -> Self {
+        let mut state = [0; 8];
+        for (word, chunk) in state.iter_mut().zip(bytes.chunks(4)) {
+            *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        Sha256Midstate { state, length }
+    }
+}
+
+impl Sha256dHasher {
+    pub fn new() -> Self {
+        Sha256dHasher {
+            state: INITIAL_STATE,
+            length: 0,
+            buffer: [0; BLOCK_SIZE],
+        }
+    }
+
+    pub fn from_midstate(midstate: Sha256Midstate) -> Result<Self> {
+        if midstate.length % BLOCK_SIZE as u64 != 0 {
+            return Err(Error::InvalidMidstateLength {
+                length: midstate.length,
+            });
+        }
+        Ok(Sha256dHasher {
+            state: midstate.state,
+            length: midstate.length,
+            buffer: [0; BLOCK_SIZE],
+        })
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        let buffered = self.buffered_len();
+        self.length += data.len() as u64;
+        if buffered > 0 {
+            let fill = (BLOCK_SIZE - buffered).min(data.len());
+            self.buffer[buffered..buffered + fill].copy_from_slice(&data[..fill]);
+            data = &data[fill..];
+            if buffered + fill < BLOCK_SIZE {
+                return;
+            }
+            let block = self.buffer;
+            self.compress(&block);
+        }
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+    }
+
+    // only available between whole blocks, when no input is buffered
+    pub fn midstate(&self) -> Option<Sha256Midstate> {
+        if self.buffered_len() != 0 {
+            return None;
+        }
+        Some(Sha256Midstate {
+            state: self.state,
+            length: self.length,
+        })
+    }
+
+    pub fn finalize(mut self) -> Sha256d {
+        let bit_length = self.length * 8;
+        self.update(&[0x80]);
+        while self.buffered_len() != BLOCK_SIZE - 8 {
+            self.update(&[0]);
+        }
+        self.update(&bit_length.to_be_bytes());
+        let first_hash = Sha256Midstate {
+            state: self.state,
+            length: self.length,
+        }
+        .to_bytes();
+        let mut hash = [0; 32];
+        hash.copy_from_slice(&sha2::Sha256::digest(&first_hash));
+        Sha256d::new(hash)
+    }
+
+    fn buffered_len(&self) -> usize {
+        (self.length % BLOCK_SIZE as u64) as usize
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        sha2::compress256(&mut self.state, std::slice::from_ref(block.into()));
+    }
+}
+
+impl Default for Sha256dHasher {
+    fn default() -> Self {
+        Sha256dHasher::new()
+    }
+}
+
+impl Write for Sha256dHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sha256Midstate, Sha256dHasher};
+    use crate::error::Result;
+    use crate::{BitcoinCode, BlockHeader, Hashed, Sha256d};
+
+    #[test]
+    fn test_incremental_hash() {
+        let data = (0..300u32).map(|idx| idx as u8).collect::<Vec<_>>();
+        for &len in &[0, 1, 55, 56, 63, 64, 65, 128, 300] {
+            for &chunk_size in &[1, 7, 64, 100] {
+                let mut hasher = Sha256dHasher::new();
+                for chunk in data[..len].chunks(chunk_size) {
+                    hasher.update(chunk);
+                }
+                assert_eq!(hasher.finalize(), Sha256d::digest(data[..len].to_vec()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_midstate() -> Result<()> {
+        let prefix = [0xab; 128];
+        let mut hasher = Sha256dHasher::new();
+        hasher.update(&prefix[..100]);
+        assert_eq!(hasher.midstate(), None);
+        hasher.update(&prefix[100..]);
+        let midstate = hasher.midstate().expect("prefix is two whole blocks");
+        let midstate = Sha256Midstate::from_bytes(midstate.to_bytes(), midstate.length);
+
+        let mut hasher = Sha256dHasher::from_midstate(midstate)?;
+        hasher.update(b"suffix");
+        let expected = Sha256d::digest([&prefix[..], b"suffix"].concat());
+        assert_eq!(hasher.finalize(), expected);
+        assert!(Sha256dHasher::from_midstate(Sha256Midstate {
+            length: 100,
+            ..midstate
+        })
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_into_hasher() -> Result<()> {
+        let header = BlockHeader {
+            version: 1,
+            timestamp: 1_231_006_505,
+            ..Default::default()
+        };
+        let mut hasher = Sha256dHasher::new();
+        header.encode_to(&mut hasher)?;
+        assert_eq!(hasher.finalize(), header.block_hash());
+        Ok(())
+    }
+}
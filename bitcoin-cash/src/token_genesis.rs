@@ -0,0 +1,100 @@
+use crate::error::{Error, Result};
+use crate::{NftCapability, NftCommitment, Script, TokenCategory, TokenData, TxOutpoint, TxOutput};
+
+#[derive(Clone, Debug)]
+pub struct TokenGenesisParams {
+    // spent by input 0 of the genesis tx, the category id is its tx hash
+    pub genesis_outpoint: TxOutpoint,
+    // the whole fungible supply, 0 for NFT-only categories
+    pub fungible_amount: u64,
+    // commitment of a minting NFT to create, None to create no NFT
+    pub minting_nft_commitment: Option<NftCommitment>,
+    // the satoshis of each token output, has to cover the token output dust limit
+    pub value: u64,
+    pub lock_script: Script,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenGenesis {
+    pub category: TokenCategory,
+    // the fungible supply output first, if any, then the minting NFT output, if any
+    pub outputs: Vec<TxOutput>,
+}
+
+pub fn token_genesis(params: TokenGenesisParams) -> Result<TokenGenesis> {
+    let category = TokenCategory::from_genesis_outpoint(&params.genesis_outpoint)?;
+    if params.fungible_amount == 0 && params.minting_nft_commitment.is_none() {
+        return Err(Error::InvalidTokenGenesis {
+            reason: "genesis must create fungible tokens or a minting NFT",
+        });
+    }
+    let mut tokens = Vec::new();
+    if params.fungible_amount > 0 {
+        tokens.push(TokenData::fungible(
+            category.clone(),
+            params.fungible_amount,
+        ));
+    }
+    if let Some(commitment) = params.minting_nft_commitment {
+        let nft = TokenData::nft(category.clone(), NftCapability::Minting, commitment);
+        tokens.push(nft);
+    }
+    let mut outputs = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        token.validate()?;
+        outputs.push(TxOutput {
+            value: params.value,
+            script: params.lock_script.clone(),
+            token: Some(token),
+        });
+    }
+    Ok(TokenGenesis { category, outputs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{token_genesis, TokenGenesisParams};
+    use crate::error::{Error, Result};
+    use crate::{NftCapability, NftCommitment, Script, Sha256d, TxOutpoint};
+
+    #[test]
+    fn test_token_genesis() -> Result<()> {
+        let params = TokenGenesisParams {
+            genesis_outpoint: TxOutpoint {
+                tx_hash: Sha256d::new([7; 32]),
+                vout: 0,
+            },
+            fungible_amount: 1_000_000,
+            minting_nft_commitment: Some(NftCommitment::empty()),
+            value: 1000,
+            lock_script: Script::default(),
+        };
+        let genesis = token_genesis(params.clone())?;
+        assert_eq!(genesis.category.hash(), &Sha256d::new([7; 32]));
+        assert_eq!(genesis.outputs.len(), 2);
+        let fungible = genesis.outputs[0].token.as_ref().unwrap();
+        assert_eq!((fungible.amount, &fungible.nft), (1_000_000, &None));
+        let nft = genesis.outputs[1]
+            .token
+            .as_ref()
+            .unwrap()
+            .nft
+            .as_ref()
+            .unwrap();
+        assert_eq!(nft.capability, NftCapability::Minting);
+
+        let mut wrong_index = params.clone();
+        wrong_index.genesis_outpoint.vout = 1;
+        assert!(matches!(
+            token_genesis(wrong_index),
+            Err(Error::InvalidTokenGenesis { .. })
+        ));
+        let empty = TokenGenesisParams {
+            fungible_amount: 0,
+            minting_nft_commitment: None,
+            ..params
+        };
+        assert!(token_genesis(empty).is_err());
+        Ok(())
+    }
+}
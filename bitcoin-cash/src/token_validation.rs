@@ -0,0 +1,196 @@
+use crate::error::{Error, Result};
+use crate::{
+    NftCapability, TokenCategory, TokenData, TxOutpoint, TxOutput, UnhashedTx,
+    MAX_FUNGIBLE_TOKEN_AMOUNT,
+};
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct CategoryInputs {
+    fungible_amount: u128,
+    has_minting_nft: bool,
+    num_mutable_nfts: usize,
+    immutable_commitments: Vec<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct CategoryOutputs<'a> {
+    fungible_amount: u128,
+    nfts: Vec<(NftCapability, &'a [u8])>,
+}
+
+// checks the CashTokens consensus rules of `tx`, `spent_tokens` holds the tokens of the
+// outputs spent by its inputs
+pub fn validate_token_tx(tx: &UnhashedTx, spent_tokens: &[Option<TokenData>]) -> Result<()> {
+    if spent_tokens.len() != tx.inputs.len() {
+        return Err(Error::Msg(format!(
+            "Got {} spent tokens for {} inputs",
+            spent_tokens.len(),
+            tx.inputs.len(),
+        )));
+    }
+    let inputs = tx.inputs.iter().zip(spent_tokens);
+    validate_token_spends(
+        inputs.map(|(input, token)| (&input.prev_out, token.as_ref())),
+        &tx.outputs,
+    )
+}
+
+// the rules on the spent outpoints and their tokens, in input order, and the outputs
+pub fn validate_token_spends<'a>(
+    inputs: impl IntoIterator<Item = (&'a TxOutpoint, Option<&'a TokenData>)>,
+    outputs: &[TxOutput],
+) -> Result<()> {
+    let mut genesis_category = None;
+    let mut category_inputs = HashMap::<&TokenCategory, CategoryInputs>::new();
+    for (input_idx, (prev_out, token)) in inputs.into_iter().enumerate() {
+        if input_idx == 0 && prev_out.vout == 0 {
+            genesis_category = Some(TokenCategory::from_hash(prev_out.tx_hash.clone()));
+        }
+        let token = match token {
+            Some(token) => token,
+            None => continue,
+        };
+        let spent = category_inputs.entry(&token.category).or_default();
+        spent.fungible_amount += u128::from(token.amount);
+        if let Some(nft) = &token.nft {
+            match nft.capability {
+                NftCapability::Minting => spent.has_minting_nft = true,
+                NftCapability::Mutable => spent.num_mutable_nfts += 1,
+                NftCapability::None => spent.immutable_commitments.push(nft.commitment.to_vec()),
+            }
+        }
+    }
+    let mut category_outputs = HashMap::<&TokenCategory, CategoryOutputs>::new();
+    for token in outputs.iter().filter_map(|output| output.token.as_ref()) {
+        token.validate()?;
+        let created = category_outputs.entry(&token.category).or_default();
+        created.fungible_amount += u128::from(token.amount);
+        if let Some(nft) = &token.nft {
+            created
+                .nfts
+                .push((nft.capability, nft.commitment.as_slice()));
+        }
+    }
+    for (category, created) in category_outputs {
+        let violation = |reason| {
+            Err(Error::TokenRuleViolation {
+                category: category.clone(),
+                reason,
+            })
+        };
+        if created.fungible_amount > u128::from(MAX_FUNGIBLE_TOKEN_AMOUNT) {
+            return violation("fungible output amounts exceed the maximum");
+        }
+        if genesis_category.as_ref() == Some(category) {
+            continue;
+        }
+        let mut spent = match category_inputs.remove(category) {
+            Some(spent) => spent,
+            None => return violation("category is neither spent nor created by the tx"),
+        };
+        if created.fungible_amount > spent.fungible_amount {
+            return violation("fungible output amounts exceed the spent amounts");
+        }
+        if spent.has_minting_nft {
+            continue;
+        }
+        // immutable NFTs are passed on as they are, each mutable NFT can become any one
+        // non-minting NFT
+        for (capability, commitment) in created.nfts {
+            if capability == NftCapability::Minting {
+                return violation("minting NFT created without spending one");
+            }
+            let immutable_idx = spent
+                .immutable_commitments
+                .iter()
+                .position(|spent_commitment| spent_commitment.as_slice() == commitment);
+            match immutable_idx {
+                Some(idx) if capability == NftCapability::None => {
+                    spent.immutable_commitments.swap_remove(idx);
+                }
+                _ if spent.num_mutable_nfts > 0 => spent.num_mutable_nfts -= 1,
+                _ => return violation("NFT output isn't backed by a spent NFT"),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_token_tx;
+    use crate::error::{Error, Result};
+    use crate::{
+        NftCapability, NftCommitment, Script, Sha256d, TokenCategory, TokenData, TxInput,
+        TxOutpoint, TxOutput, UnhashedTx,
+    };
+
+    fn tx(prev_vouts: &[u32], tokens: Vec<TokenData>) -> UnhashedTx {
+        UnhashedTx {
+            inputs: prev_vouts
+                .iter()
+                .map(|&vout| {
+                    let prev_out = TxOutpoint {
+                        tx_hash: Sha256d::new([1; 32]),
+                        vout,
+                    };
+                    TxInput::new(prev_out, Script::default(), 0)
+                })
+                .collect(),
+            outputs: tokens
+                .into_iter()
+                .map(|token| TxOutput {
+                    value: 1000,
+                    script: Script::default(),
+                    token: Some(token),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_token_tx() -> Result<()> {
+        let genesis = TokenCategory::from_hash(Sha256d::new([1; 32]));
+        let other = TokenCategory::from_hash(Sha256d::new([2; 32]));
+        let nft = |capability, commitment: &[u8]| {
+            let commitment = NftCommitment::new(commitment.to_vec()).unwrap();
+            TokenData::nft(other.clone(), capability, commitment)
+        };
+        let is_violation = |result| matches!(result, Err(Error::TokenRuleViolation { .. }));
+
+        // categories can only be created from an index 0 outpoint spent by input 0
+        let created = vec![TokenData::fungible(genesis.clone(), 100)];
+        validate_token_tx(&tx(&[0], created.clone()), &[None])?;
+        assert!(is_violation(validate_token_tx(&tx(&[1], created), &[None])));
+
+        // fungible amounts can decrease but never increase
+        let spent = [Some(TokenData::fungible(other.clone(), 100))];
+        let send = |amount| tx(&[1], vec![TokenData::fungible(other.clone(), amount)]);
+        validate_token_tx(&send(60), &spent)?;
+        assert!(is_violation(validate_token_tx(&send(101), &spent)));
+
+        // a mutable NFT can become one immutable NFT, immutable ones are only passed on
+        let mutable = [Some(nft(NftCapability::Mutable, b"a"))];
+        let immutable = [Some(nft(NftCapability::None, b"a"))];
+        let one = tx(&[1], vec![nft(NftCapability::None, b"b")]);
+        let two = tx(
+            &[1],
+            vec![
+                nft(NftCapability::None, b"a"),
+                nft(NftCapability::None, b"a"),
+            ],
+        );
+        validate_token_tx(&one, &mutable)?;
+        assert!(is_violation(validate_token_tx(&one, &immutable)));
+        assert!(is_violation(validate_token_tx(&two, &mutable)));
+
+        // minting NFTs can create any NFTs, but only minting NFTs create minting ones
+        let minting = [Some(nft(NftCapability::Minting, b""))];
+        validate_token_tx(&two, &minting)?;
+        let mint = tx(&[1], vec![nft(NftCapability::Minting, b"")]);
+        assert!(is_violation(validate_token_tx(&mint, &mutable)));
+        Ok(())
+    }
+}
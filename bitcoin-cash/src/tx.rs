@@ -1,12 +1,19 @@
 use crate::{
-    BitcoinCode, ByteArray, Hashed, Script, Sha256d, SigHashFlags, ToPreimages, TxPreimage,
+    deser_error_in_field,
+    error::{Error, Result},
+    var_int_size, BitcoinCode, ByteArray, CsvTimedelta, Hashed, Script, Sequence, Sha256d,
+    SigHashFlags, ToPreimages, TokenData, TxPreimage, DEFAULT_FORK_ID, PREFIX_TOKEN,
 };
 use serde::{Deserialize, Serialize};
+use std::io::Read;
 
 pub const DEFAULT_SEQUENCE: u32 = 0xffff_ffff;
+pub const NON_FINAL_SEQUENCE: u32 = 0xffff_fffe;
 // Mark Lundeberg: "71 bytes for the DER, but then +1 for the hashtype,
 // so 72 bytes for the full tx signature."
 pub const MAX_SIGNATURE_SIZE: usize = 72;
+// size of a typical P2PKH input spending the output, used for the dust limit
+pub const DUST_INPUT_SIZE: u64 = 148;
 
 #[bitcoin_code(crate = "crate")]
 #[derive(BitcoinCode, Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Default)]
@@ -37,11 +44,12 @@ pub struct TxInput {
     pub sig_hash_flags: Option<Vec<SigHashFlags>>,
 }
 
-#[bitcoin_code(crate = "crate")]
-#[derive(BitcoinCode, Deserialize, Serialize, PartialEq, Debug, Clone)]
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
 pub struct TxOutput {
     pub value: u64,
     pub script: Script,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<TokenData>,
 }
 
 #[bitcoin_code(crate = "crate")]
@@ -54,16 +62,11 @@ pub struct UnhashedTx {
     pub lock_time: u32,
 }
 
-#[bitcoin_code(crate = "crate")]
-#[derive(BitcoinCode, Deserialize, Serialize, PartialEq, Debug, Clone)]
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Tx {
-    #[bitcoin_code(skip)]
     unhashed_tx: UnhashedTx,
-
-    #[bitcoin_code(skip)]
     hash: Sha256d,
-
     raw: ByteArray,
 }
 
@@ -83,12 +86,42 @@ impl TxInput {
 
 impl UnhashedTx {
     pub fn preimages(&self, sig_hash_flags: &[SigHashFlags]) -> Vec<Vec<TxPreimage>> {
+        self.preimages_with_fork_id(sig_hash_flags, DEFAULT_FORK_ID)
+    }
+
+    // BIP143 preimages with `fork_id` in the sig hash type, see DEFAULT_FORK_ID
+    pub fn preimages_with_fork_id(
+        &self,
+        sig_hash_flags: &[SigHashFlags],
+        fork_id: u32,
+    ) -> Vec<Vec<TxPreimage>> {
         TxPreimage::build_preimages(&SigTxPreimage {
             tx: self,
             sig_hash_flags,
+            fork_id,
         })
     }
 
+    // the relative lock BIP68 enforces for the input, taking the tx version into account
+    pub fn input_relative_lock(&self, input_idx: usize) -> Option<CsvTimedelta> {
+        let sequence = Sequence(self.inputs.get(input_idx)?.sequence);
+        sequence.relative_lock_for_version(self.version)
+    }
+
+    pub fn fee(&self) -> Option<u64> {
+        let input_sum = self
+            .inputs
+            .iter()
+            .map(|input| input.value)
+            .sum::<Option<u64>>()?;
+        let output_sum = self.outputs.iter().map(|output| output.value).sum::<u64>();
+        input_sum.checked_sub(output_sum)
+    }
+
+    pub fn txid(&self) -> Sha256d {
+        Sha256d::digest(self.ser())
+    }
+
     pub fn hashed(self) -> Tx {
         let raw = self.ser();
         let hash = Sha256d::digest(raw.clone());
@@ -138,6 +171,169 @@ impl Tx {
     }
 }
 
+impl From<UnhashedTx> for Tx {
+    fn from(unhashed_tx: UnhashedTx) -> Self {
+        unhashed_tx.hashed()
+    }
+}
+
+impl BitcoinCode for Tx {
+    fn ser(&self) -> ByteArray {
+        self.raw.clone()
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (unhashed_tx, rest) = UnhashedTx::deser_rest(data.clone())?;
+        let tx_len = data.len() - rest.len();
+        let (raw, rest) = data.split(tx_len)?;
+        let hash = Sha256d::digest(raw.clone());
+        Ok((
+            Tx {
+                unhashed_tx,
+                hash,
+                raw,
+            },
+            rest,
+        ))
+    }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (unhashed_tx, rest) = UnhashedTx::deser_slice(data)?;
+        let raw = ByteArray::from_slice_unnamed(&data[..data.len() - rest.len()]);
+        let hash = Sha256d::digest(raw.clone());
+        Ok((
+            Tx {
+                unhashed_tx,
+                hash,
+                raw,
+            },
+            rest,
+        ))
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut reader = RecordingReader {
+            reader,
+            recorded: Vec::new(),
+        };
+        let unhashed_tx = UnhashedTx::decode_from(&mut reader)?;
+        let raw = ByteArray::from(reader.recorded);
+        let hash = Sha256d::digest(raw.clone());
+        Ok(Tx {
+            unhashed_tx,
+            hash,
+            raw,
+        })
+    }
+
+    fn encoded_size(&self) -> usize {
+        self.raw.len()
+    }
+}
+
+impl TxOutput {
+    // the token prefix, if any, followed by the script, as the output's length-prefixed field
+    pub fn locking_bytecode(&self) -> ByteArray {
+        match &self.token {
+            Some(token) => token.ser().concat(self.script.ser_ops()),
+            None => self.script.ser_ops(),
+        }
+    }
+
+    // 3 * (size + 148), i.e. 546 for P2PKH; token prefixes raise the limit accordingly
+    pub fn dust_limit(&self) -> u64 {
+        3 * (self.encoded_size() as u64 + DUST_INPUT_SIZE)
+    }
+
+    // relay rules for token outputs; outputs without tokens always pass
+    pub fn check_token_standardness(&self) -> Result<()> {
+        let token = match &self.token {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+        token.validate()?;
+        if self.script.is_data_carrier() {
+            return Err(Error::NonStandardTokenOutput {
+                reason: "Tokens cannot be sent to data carrier outputs",
+            });
+        }
+        let dust_limit = self.dust_limit();
+        if self.value < dust_limit {
+            return Err(Error::DustOutput {
+                value: self.value,
+                dust_limit,
+            });
+        }
+        Ok(())
+    }
+
+    fn from_locking_bytecode(value: u64, bytecode: ByteArray, offset: usize) -> Result<Self> {
+        let field_err = |err| deser_error_in_field(err, "TxOutput", "script", offset);
+        let (token, script_code) = if bytecode.first() == Some(&PREFIX_TOKEN) {
+            let (token, script_code) = TokenData::deser_rest(bytecode).map_err(field_err)?;
+            (Some(token), script_code)
+        } else {
+            (None, bytecode)
+        };
+        let script = Script::deser_ops(script_code).map_err(field_err)?;
+        Ok(TxOutput {
+            value,
+            script,
+            token,
+        })
+    }
+}
+
+impl BitcoinCode for TxOutput {
+    fn ser(&self) -> ByteArray {
+        self.value.ser().concat(self.locking_bytecode().ser())
+    }
+
+    fn deser_rest(data: ByteArray) -> Result<(Self, ByteArray)> {
+        let (value, rest) = u64::deser_rest(data)
+            .map_err(|err| deser_error_in_field(err, "TxOutput", "value", 0))?;
+        let (bytecode, rest) = ByteArray::deser_rest(rest)
+            .map_err(|err| deser_error_in_field(err, "TxOutput", "script", 8))?;
+        Ok((Self::from_locking_bytecode(value, bytecode, 8)?, rest))
+    }
+
+    fn deser_slice(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (value, rest) = u64::deser_slice(data)
+            .map_err(|err| deser_error_in_field(err, "TxOutput", "value", 0))?;
+        let (bytecode, rest) = ByteArray::deser_slice(rest)
+            .map_err(|err| deser_error_in_field(err, "TxOutput", "script", 8))?;
+        Ok((Self::from_locking_bytecode(value, bytecode, 8)?, rest))
+    }
+
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let value = u64::decode_from(reader)
+            .map_err(|err| deser_error_in_field(err, "TxOutput", "value", 0))?;
+        let bytecode = ByteArray::decode_from(reader)
+            .map_err(|err| deser_error_in_field(err, "TxOutput", "script", 8))?;
+        Self::from_locking_bytecode(value, bytecode, 8)
+    }
+
+    fn encoded_size(&self) -> usize {
+        let bytecode_size =
+            self.token.as_ref().map_or(0, |token| token.encoded_size()) + self.script.ops_size();
+        8 + var_int_size(bytecode_size as u64) + bytecode_size
+    }
+}
+
+// keeps the bytes a tx was decoded from, so its raw form doesn't have to be re-serialized
+struct RecordingReader<'r, R> {
+    reader: &'r mut R,
+    recorded: Vec<u8>,
+}
+
+impl<R: Read> Read for RecordingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let num_read = self.reader.read(buf)?;
+        self.recorded.extend_from_slice(&buf[..num_read]);
+        Ok(num_read)
+    }
+}
+
 impl Default for UnhashedTx {
     fn default() -> Self {
         UnhashedTx {
@@ -152,6 +348,7 @@ impl Default for UnhashedTx {
 struct SigTxPreimage<'b> {
     tx: &'b UnhashedTx,
     sig_hash_flags: &'b [SigHashFlags],
+    fork_id: u32,
 }
 
 impl ToPreimages for SigTxPreimage<'_> {
@@ -190,4 +387,67 @@ impl ToPreimages for SigTxPreimage<'_> {
     fn lock_time(&self) -> u32 {
         self.tx.lock_time
     }
+    fn fork_id(&self) -> u32 {
+        self.fork_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tx, TxInput, TxOutpoint, TxOutput, UnhashedTx, NON_FINAL_SEQUENCE};
+    use crate::error::Result;
+    use crate::{BitcoinCode, ByteArray, Hashed, Script, Sha256d, SigHashFlags};
+
+    #[test]
+    fn test_tx_txid() -> Result<()> {
+        let unhashed_tx = UnhashedTx {
+            version: 2,
+            inputs: vec![TxInput::new(
+                TxOutpoint {
+                    tx_hash: Sha256d::digest(b"prev".as_ref()),
+                    vout: 1,
+                },
+                Script::new(vec![]),
+                NON_FINAL_SEQUENCE,
+            )],
+            outputs: vec![TxOutput {
+                value: 5_000,
+                script: Script::new(vec![]),
+                token: None,
+            }],
+            lock_time: 700_000,
+        };
+        let raw = unhashed_tx.ser();
+        let txid = unhashed_tx.txid();
+        assert_eq!(txid, Sha256d::digest(raw.clone()));
+        let tx = Tx::from(unhashed_tx.clone());
+        assert_eq!(tx.hash(), &txid);
+        assert_eq!(tx.ser().as_ref(), raw.as_ref());
+
+        let data = ByteArray::from_slice_unnamed(&[raw.as_ref(), b"rest"].concat());
+        let (tx, rest) = Tx::deser_rest(data)?;
+        assert_eq!(rest.as_ref(), b"rest");
+        assert_eq!(tx.hash(), &txid);
+        assert_eq!(tx.raw().as_ref(), raw.as_ref());
+        assert_eq!(tx.unhashed_tx(), &unhashed_tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_preimages_with_fork_id() {
+        let mut input = TxInput::new(TxOutpoint::default(), Script::new(vec![]), 0xffff_ffff);
+        input.value = Some(10_000);
+        input.lock_script = Some(Script::new(vec![]));
+        let tx = UnhashedTx {
+            inputs: vec![input],
+            ..Default::default()
+        };
+        let flags = [SigHashFlags::DEFAULT];
+        let preimage = tx.preimages(&flags).remove(0).remove(0);
+        let fork_preimage = tx.preimages_with_fork_id(&flags, 0xff).remove(0).remove(0);
+        assert_eq!(preimage.sig_hash_type, 0x41);
+        assert_eq!(fork_preimage.sig_hash_type, 0xff41);
+        assert_eq!(preimage.hash_prevouts, fork_preimage.hash_prevouts);
+        assert_ne!(preimage.ser().as_ref(), fork_preimage.ser().as_ref());
+    }
 }
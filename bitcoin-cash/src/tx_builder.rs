@@ -1,7 +1,12 @@
 use crate::error::Result;
 use crate::{
-    error::Error, BitcoinCode, Ops, Script, SigHashFlags, TaggedOp, TaggedScript, TxInput,
-    TxOutpoint, TxOutput, TxPreimage, UnhashedTx,
+    encoding_utils::vec_to_int,
+    error::Error,
+    Address, BitcoinCode, ByteArray, CsvTimedelta, FeeRate, Hashed, LockTime, Op, Opcode, Ops,
+    Script, Sequence, Sha256d, SigHashFlags, TaggedOp, TaggedScript, TokenData, TxInput,
+    TxOutpoint, TxOutput, TxPreimage, UnhashedTx, BIP68_MIN_TX_VERSION, DEFAULT_SEQUENCE,
+    NON_FINAL_SEQUENCE,
+    validate_relative_lock_version, validate_token_spends, var_int_size,
 };
 use std::any::Any;
 use std::collections::HashMap;
@@ -10,6 +15,14 @@ use serde::{Deserialize, Serialize};
 
 pub const DEFAULT_FEE_PER_KB: u64 = 1000;
 pub const DUST_AMOUNT: u64 = 546;
+// BCH's fork id; preimages always follow BIP143 with SIGHASH_FORKID, as on BCH, eCash and
+// their testnets, only the fork id in the sig hash type differs between those chains
+pub const DEFAULT_FORK_ID: u32 = 0;
+// BCHN's -datacarriersize, which limits the whole OP_RETURN script
+pub const MAX_DATA_CARRIER_SIZE: usize = 223;
+pub const DEFAULT_MAX_FEE_PER_KB: u64 = 100_000;
+pub const DEFAULT_FEE_RATE: FeeRate = FeeRate::from_sats_per_kb(DEFAULT_FEE_PER_KB);
+pub const DEFAULT_MAX_FEE_RATE: FeeRate = FeeRate::from_sats_per_kb(DEFAULT_MAX_FEE_PER_KB);
 
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -17,24 +30,40 @@ pub struct UnsignedTxInput {
     pub prev_out: TxOutpoint,
     pub sequence: u32,
     pub value: u64,
+    // the tokens of the spent output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<TokenData>,
 }
 
+type FuncScript<'b> = Box<
+    dyn Fn(&[TxPreimage], Option<usize>, Option<Box<dyn Any>>, &Script, &[TxOutput]) -> Script
+        + 'b
+        + Sync
+        + Send,
+>;
+
 struct TxBuilderInput<'b> {
     input: UnsignedTxInput,
-    func_script: Box<
-        dyn Fn(&[TxPreimage], Option<usize>, Option<Box<dyn Any>>, &Script, &[TxOutput]) -> Script
-            + 'b
-            + Sync
-            + Send,
-    >,
+    func_script: FuncScript<'b>,
     sig_hash_flags: Vec<SigHashFlags>,
     lock_script: Option<Script>,
     is_p2sh: Option<bool>,
+    estimated_unlock_size: Option<usize>,
     signed_input: Option<TxInput>,
+    has_signatory: bool,
+    derivation_hint: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputSigningStatus {
+    Signed,
+    MissingSignatures { num_present: usize, num_required: usize },
+    // no signatory attached, e.g. after TxBuilder::from_tx or UnsignedTx::from_state
+    Placeholder,
 }
 
 #[derive(PartialEq, Debug, Clone)]
-enum TxBuilderOutput {
+pub enum TxBuilderOutput {
     KnownValue(TxOutput),
     Leftover {
         lower_bound: u64,
@@ -42,6 +71,14 @@ enum TxBuilderOutput {
         precedence: i32,
         script: Script,
     },
+    Sweep(Script),
+    // shares the leftover with other weighted outputs in proportion to its weight
+    Weighted {
+        weight: u32,
+        script: Script,
+    },
+    // like a leftover with the lowest precedence; dropped into fees if it would be dust
+    Change(Script),
 }
 
 #[derive(Default)]
@@ -50,13 +87,28 @@ pub struct TxBuilder<'b> {
     inputs: Vec<TxBuilderInput<'b>>,
     outputs: Vec<TxBuilderOutput>,
     lock_time: u32,
-    fee_per_kb: u64,
+    fee_rate: FeeRate,
+    max_fee: Option<u64>,
+    max_fee_rate: Option<FeeRate>,
+    cpfp_parents: Vec<CpfpParent>,
+    fork_id: u32,
+    generation: u64,
+    preimages_digest: Option<Sha256d>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpfpParent {
+    size: usize,
+    fee: u64,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 pub struct InputReference<T> {
     phantom: PhantomData<T>,
     input_idx: usize,
+    // the preimage generation when the reference was made, checked by `sign_input`
+    #[serde(default)]
+    generation: u64,
 }
 
 impl<T: Clone> Copy for InputReference<T> {}
@@ -68,6 +120,68 @@ pub struct UnsignedTx<'b> {
     tx_preimages: Vec<Vec<TxPreimage>>,
     inputs: Vec<Option<TxInput>>,
     estimated_size: usize,
+    change_output_idx: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeReport {
+    pub estimated_size: usize,
+    pub actual_size: Option<usize>,
+    pub fee: u64,
+    pub fee_per_byte: f64,
+    pub overhead_size: usize,
+    pub input_sizes: Vec<usize>,
+    pub output_sizes: Vec<usize>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsignedTxState {
+    pub version: i32,
+    pub lock_time: u32,
+    pub fee_per_kb: u64,
+    #[serde(default)]
+    pub fork_id: u32,
+    #[serde(default)]
+    pub generation: u64,
+    pub inputs: Vec<UnsignedTxInputState>,
+    pub outputs: Vec<TxOutput>,
+    pub estimated_size: usize,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsignedTxInputState {
+    pub input: UnsignedTxInput,
+    pub lock_script: Option<Script>,
+    pub sig_hash_flags: Vec<SigHashFlags>,
+    pub is_p2sh: Option<bool>,
+    pub signed_input: Option<TxInput>,
+    #[serde(default)]
+    pub derivation_hint: Option<String>,
+}
+
+// everything an offline signer needs to produce one signature, without the builder
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningRequest {
+    pub input_idx: usize,
+    pub sig_idx: usize,
+    pub sig_hash: Sha256d,
+    pub sig_hash_flags: SigHashFlags,
+    pub lock_script: Option<Script>,
+    pub value: u64,
+    pub derivation_hint: Option<String>,
+}
+
+// several parties' TxBuilders merged into one tx; each party pays for its own inputs and
+// outputs plus an equal share of the tx overhead
+pub struct CoinJoin<'b> {
+    pub unsigned_tx: UnsignedTx<'b>,
+    // [party_idx][input_idx in the party's builder] -> input_idx in the merged tx
+    input_indices: Vec<Vec<usize>>,
+    party_fees: Vec<u64>,
 }
 
 struct TxBuilderPreimages<'b> {
@@ -83,9 +197,16 @@ pub trait ToPreimages {
     fn input_sig_hash_flags_at(&self, input_idx: usize) -> &[SigHashFlags];
     fn input_value_at(&self, input_idx: usize) -> u64;
     fn input_lock_script_at(&self, input_idx: usize) -> Script;
+    // the tokens of the spent output, included in the preimage
+    fn input_token_at(&self, _input_idx: usize) -> Option<&TokenData> {
+        None
+    }
     fn num_outputs(&self) -> usize;
     fn output_at(&self, output_idx: usize) -> &TxOutput;
     fn lock_time(&self) -> u32;
+    fn fork_id(&self) -> u32 {
+        DEFAULT_FORK_ID
+    }
 }
 
 pub trait SignatoryKind {
@@ -116,6 +237,9 @@ pub trait Signatory {
     fn is_p2sh(&self) -> bool {
         true
     }
+    fn estimated_unlock_size(&self) -> Option<usize> {
+        None
+    }
 }
 
 impl<'b> TxBuilder<'b> {
@@ -125,7 +249,13 @@ impl<'b> TxBuilder<'b> {
             inputs: Vec::new(),
             outputs: Vec::new(),
             lock_time: 0,
-            fee_per_kb: DEFAULT_FEE_PER_KB,
+            fee_rate: DEFAULT_FEE_RATE,
+            max_fee: None,
+            max_fee_rate: Some(DEFAULT_MAX_FEE_RATE),
+            cpfp_parents: Vec::new(),
+            fork_id: DEFAULT_FORK_ID,
+            generation: 0,
+            preimages_digest: None,
         }
     }
 
@@ -135,7 +265,13 @@ impl<'b> TxBuilder<'b> {
             inputs: Vec::new(),
             outputs: Vec::new(),
             lock_time,
-            fee_per_kb: DEFAULT_FEE_PER_KB,
+            fee_rate: DEFAULT_FEE_RATE,
+            max_fee: None,
+            max_fee_rate: Some(DEFAULT_MAX_FEE_RATE),
+            cpfp_parents: Vec::new(),
+            fork_id: DEFAULT_FORK_ID,
+            generation: 0,
+            preimages_digest: None,
         }
     }
 
@@ -145,7 +281,13 @@ impl<'b> TxBuilder<'b> {
             inputs: Vec::new(),
             outputs: Vec::new(),
             lock_time,
-            fee_per_kb,
+            fee_rate: FeeRate::from_sats_per_kb(fee_per_kb),
+            max_fee: None,
+            max_fee_rate: Some(DEFAULT_MAX_FEE_RATE),
+            cpfp_parents: Vec::new(),
+            fork_id: DEFAULT_FORK_ID,
+            generation: 0,
+            preimages_digest: None,
         }
     }
 
@@ -166,22 +308,208 @@ impl<'b> TxBuilder<'b> {
                         prev_out: input.prev_out.clone(),
                         sequence: input.sequence,
                         value: input.value.unwrap_or(0),
+                        token: None,
                     },
                     is_p2sh: input.is_p2sh,
                     lock_script: input.lock_script.clone(),
                     sig_hash_flags: input.clone().sig_hash_flags.unwrap_or(vec![]),
                     func_script: Box::new(func),
+                    estimated_unlock_size: None,
                     signed_input: Some(input),
+                    has_signatory: false,
+                    derivation_hint: None,
                 }
             }).collect(),
             outputs: tx.outputs.into_iter().map(TxBuilderOutput::KnownValue).collect(),
             lock_time: tx.lock_time,
-            fee_per_kb: DEFAULT_FEE_PER_KB,
+            fee_rate: DEFAULT_FEE_RATE,
+            max_fee: None,
+            max_fee_rate: Some(DEFAULT_MAX_FEE_RATE),
+            cpfp_parents: Vec::new(),
+            fork_id: DEFAULT_FORK_ID,
+            generation: 0,
+            preimages_digest: None,
         }
     }
 
     pub fn set_fee_per_kb(&mut self, fee_per_kb: u64) {
-        self.fee_per_kb = fee_per_kb;
+        self.fee_rate = FeeRate::from_sats_per_kb(fee_per_kb);
+    }
+
+    pub fn fee_per_kb(&self) -> u64 {
+        self.fee_rate.sats_per_kb()
+    }
+
+    pub fn set_fee_rate(&mut self, fee_rate: FeeRate) {
+        self.fee_rate = fee_rate;
+    }
+
+    pub fn fee_rate(&self) -> FeeRate {
+        self.fee_rate
+    }
+
+    // only selects the fork id, not the sighash algorithm, see DEFAULT_FORK_ID
+    pub fn set_fork_id(&mut self, fork_id: u32) {
+        self.fork_id = fork_id;
+    }
+
+    pub fn fork_id(&self) -> u32 {
+        self.fork_id
+    }
+
+    pub fn set_lock_time(&mut self, lock_time: impl Into<u32>) {
+        self.lock_time = lock_time.into();
+    }
+
+    pub fn set_input_sequence(&mut self, input_idx: usize, sequence: u32) -> Result<()> {
+        self.input_mut(input_idx)?.input.sequence = sequence;
+        Ok(())
+    }
+
+    // e.g. a BIP32 path, passed through to offline signers in SigningRequest
+    pub fn set_derivation_hint(&mut self, input_idx: usize, hint: impl Into<String>) -> Result<()> {
+        self.input_mut(input_idx)?.derivation_hint = Some(hint.into());
+        Ok(())
+    }
+
+    pub fn set_final_sequence(&mut self, input_idx: usize) -> Result<()> {
+        self.set_input_sequence(input_idx, DEFAULT_SEQUENCE)
+    }
+
+    pub fn set_non_final_sequence(&mut self, input_idx: usize) -> Result<()> {
+        self.set_input_sequence(input_idx, NON_FINAL_SEQUENCE)
+    }
+
+    pub fn set_relative_lock(&mut self, input_idx: usize, timedelta: CsvTimedelta) -> Result<()> {
+        validate_relative_lock_version(self.version)?;
+        self.set_input_sequence(input_idx, timedelta.sequence())
+    }
+
+    pub fn validate_timelocks(&self) -> Result<()> {
+        for (input_idx, input) in self.inputs.iter().enumerate() {
+            let lock_script = match &input.lock_script {
+                Some(lock_script) => lock_script,
+                None => continue,
+            };
+            let sequence = input.input.sequence;
+            let ops = lock_script.ops();
+            for window in ops.windows(2) {
+                let value = match script_num(&window[0].op) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                let mismatch = |reason| Err(Error::TimelockMismatch { input_idx, reason });
+                match window[1].op {
+                    Op::Code(Opcode::OP_CHECKLOCKTIMEVERIFY) => {
+                        let required = LockTime::from_value(value);
+                        let lock_time = LockTime::from_value(self.lock_time);
+                        if required.is_time_based() != lock_time.is_time_based() {
+                            return mismatch("CLTV and tx lock time use different units");
+                        }
+                        if required.value() > lock_time.value() {
+                            return mismatch("tx lock time is below the CLTV value");
+                        }
+                        if sequence == DEFAULT_SEQUENCE {
+                            return mismatch("final sequence disables the tx lock time");
+                        }
+                    }
+                    Op::Code(Opcode::OP_CHECKSEQUENCEVERIFY) => {
+                        let required = match CsvTimedelta::from_sequence(value) {
+                            Some(required) => required,
+                            None => continue,
+                        };
+                        if self.version < BIP68_MIN_TX_VERSION {
+                            return mismatch("CSV requires tx version 2");
+                        }
+                        let relative_lock = match Sequence(sequence).relative_lock() {
+                            Some(relative_lock) => relative_lock,
+                            None => return mismatch("input sequence has the disable flag set"),
+                        };
+                        if required.is_time_based() != relative_lock.is_time_based() {
+                            return mismatch("CSV and input sequence use different units");
+                        }
+                        if required.value() > relative_lock.value() {
+                            return mismatch("input sequence is below the CSV value");
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let all_final = self.inputs.iter().all(|input| input.input.sequence == DEFAULT_SEQUENCE);
+        if self.lock_time != 0 && !self.inputs.is_empty() && all_final {
+            return Err(Error::InvalidTimelock {
+                reason: "lock time is ignored because all input sequences are final",
+            });
+        }
+        Ok(())
+    }
+
+    // the CashTokens rules for the known outputs, using the spent tokens of the inputs
+    pub fn validate_tokens(&self) -> Result<()> {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| (&input.input.prev_out, input.input.token.as_ref()));
+        let outputs = self
+            .outputs
+            .iter()
+            .filter_map(|output| match output {
+                TxBuilderOutput::KnownValue(output) => Some(output.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for output in &outputs {
+            output.check_token_standardness()?;
+        }
+        validate_token_spends(inputs, &outputs)
+    }
+
+    pub fn set_max_fee(&mut self, max_fee: Option<u64>) {
+        self.max_fee = max_fee;
+    }
+
+    pub fn set_max_fee_per_kb(&mut self, max_fee_per_kb: Option<u64>) {
+        self.max_fee_rate = max_fee_per_kb.map(FeeRate::from_sats_per_kb);
+    }
+
+    pub fn set_max_fee_rate(&mut self, max_fee_rate: Option<FeeRate>) {
+        self.max_fee_rate = max_fee_rate;
+    }
+
+    pub fn add_cpfp_parent(&mut self, parent: CpfpParent) {
+        self.cpfp_parents.push(parent);
+    }
+
+    pub fn add_parent_output_input<S: Signatory + 'b + Sync + Send>(
+        &mut self,
+        parent: &UnhashedTx,
+        vout: u32,
+        lock_script: TaggedScript<S::Script>,
+        input_signatory: S,
+    ) -> Result<InputReference<S>> {
+        let output = parent.outputs.get(vout as usize).ok_or_else(|| {
+            Error::Msg(format!("Parent tx has no output {}", vout))
+        })?;
+        let input = UnsignedTxInput {
+            prev_out: TxOutpoint {
+                tx_hash: Sha256d::digest(parent.ser()),
+                vout,
+            },
+            sequence: DEFAULT_SEQUENCE,
+            value: output.value,
+            token: output.token.clone(),
+        };
+        Ok(self.add_input(input, lock_script, input_signatory))
+    }
+
+    // the tx has to pay for its own size, even if its parents overpay for the package
+    pub fn required_fee(&self, size: usize) -> u64 {
+        let parents_size = self.cpfp_parents.iter().map(|parent| parent.size).sum::<usize>();
+        let parents_fee = self.cpfp_parents.iter().map(|parent| parent.fee).sum::<u64>();
+        let package_fee = self.fee_rate.fee_for_size(size + parents_size);
+        let own_fee = self.fee_rate.fee_for_size(size);
+        own_fee.max(package_fee.saturating_sub(parents_fee))
     }
 
     pub fn add_input<S: Signatory + 'b + Sync + Send>(
@@ -193,38 +521,49 @@ impl<'b> TxBuilder<'b> {
         let sig_hash_flags = input_signatory.sig_hash_flags();
         let sig_hash_flags = <S::Kind as SignatoryKind>::sig_hash_flags_vec(sig_hash_flags);
         let is_p2sh = input_signatory.is_p2sh();
-        let func = move |tx_preimages: &[TxPreimage],
-                         estimated_size: Option<usize>,
-                         sigs: Option<Box<dyn Any>>,
-                         lock_script: &Script,
-                         tx_outputs: &[TxOutput]| {
-            let sigs = match sigs {
-                Some(sigs) => *sigs.downcast::<S::Signatures>().expect("Incompatible sigs"),
-                None => input_signatory.placeholder_signatures(),
-            };
-            let tx_preimages = <S::Kind as SignatoryKind>::make_tx_preimages(tx_preimages);
-            let mut ops: Vec<_> = input_signatory
-                .build_script(tx_preimages, estimated_size, sigs, lock_script, tx_outputs)
-                .ops()
-                .into();
-            if input_signatory.is_p2sh() {
-                ops.push(TaggedOp::from_op(lock_script.ser_ops().into()));
-            }
-            Script::new(ops)
-        };
+        let estimated_unlock_size = input_signatory.estimated_unlock_size();
         let input_idx = self.inputs.len();
         self.inputs.push(TxBuilderInput {
             input: input.into(),
-            func_script: Box::new(func),
+            func_script: signatory_func_script(input_signatory),
             sig_hash_flags,
             lock_script: Some(lock_script.into()),
             is_p2sh: Some(is_p2sh),
+            estimated_unlock_size,
             signed_input: None,
+            has_signatory: true,
+            derivation_hint: None,
         });
         InputReference {
             phantom: PhantomData,
             input_idx,
+            generation: self.generation,
+        }
+    }
+
+    // adds an input signed with SIGHASH_SINGLE together with the output it commits to,
+    // both at the same index
+    pub fn add_paired_input<S: Signatory + 'b + Sync + Send>(
+        &mut self,
+        input: impl Into<UnsignedTxInput>,
+        lock_script: TaggedScript<S::Script>,
+        input_signatory: S,
+        output: impl Into<TxOutput>,
+    ) -> Result<InputReference<S>> {
+        let input_idx = self.inputs.len();
+        if self.outputs.len() != input_idx {
+            return Err(Error::UnpairedSingleInput { input_idx });
+        }
+        let sig_hash_flags = input_signatory.sig_hash_flags();
+        let sig_hash_flags = <S::Kind as SignatoryKind>::sig_hash_flags_vec(sig_hash_flags);
+        if sig_hash_flags.is_empty() || !sig_hash_flags.iter().all(|flags| flags.is_single()) {
+            return Err(Error::InvalidSigHashFlags {
+                reason: "paired inputs must sign with SIGHASH_SINGLE",
+            });
         }
+        let input_ref = self.add_input(input, lock_script, input_signatory);
+        self.add_output(output);
+        Ok(input_ref)
     }
 
     pub fn add_output(&mut self, output: impl Into<TxOutput>) {
@@ -232,12 +571,86 @@ impl<'b> TxBuilder<'b> {
             .push(TxBuilderOutput::KnownValue(output.into()));
     }
 
+    // sends `token` to `script` with exactly the output's dust limit as value
+    pub fn add_token_output(&mut self, script: Script, token: TokenData) {
+        let mut output = TxOutput {
+            value: 0,
+            script,
+            token: Some(token),
+        };
+        output.value = output.dust_limit();
+        self.add_output(output);
+    }
+
+    // pays `address`, refusing to send tokens to addresses that don't signal token support
+    pub fn add_address_output(
+        &mut self,
+        address: &Address<'_>,
+        value: u64,
+        token: Option<TokenData>,
+    ) -> Result<()> {
+        if token.is_some() && !address.is_token_aware() {
+            return Err(Error::AddressNotTokenAware {
+                cash_addr: address.cash_addr().to_string(),
+            });
+        }
+        self.add_address_output_unchecked(address, value, token);
+        Ok(())
+    }
+
+    // like `add_address_output`, but sends tokens to any address, e.g. ones of a wallet known
+    // to support tokens
+    pub fn add_address_output_unchecked(
+        &mut self,
+        address: &Address<'_>,
+        value: u64,
+        token: Option<TokenData>,
+    ) {
+        self.add_output(TxOutput {
+            value,
+            script: address.into(),
+            token,
+        });
+    }
+
     pub fn add_outputs(&mut self, outputs: impl IntoIterator<Item = impl Into<TxOutput>>) {
         for output in outputs {
             self.add_output(output);
         }
     }
 
+    pub fn add_data_output(
+        &mut self,
+        lokad_prefix: impl Into<ByteArray>,
+        pushes: impl IntoIterator<Item = impl Into<ByteArray>>,
+    ) -> Result<()> {
+        if self.outputs.iter().any(|output| output.script().is_data_carrier()) {
+            return Err(Error::MultipleDataCarriers);
+        }
+        let mut ops = vec![Op::Code(Opcode::OP_RETURN)];
+        let arrays = std::iter::once(lokad_prefix.into()).chain(pushes.into_iter().map(Into::into));
+        for array in arrays {
+            ops.push(Op::PushByteArray {
+                array,
+                is_minimal: false,
+            });
+        }
+        let script = Script::from_ops(ops);
+        let size = script.ser_ops().len();
+        if size > MAX_DATA_CARRIER_SIZE {
+            return Err(Error::DataCarrierTooLarge {
+                size,
+                max_size: MAX_DATA_CARRIER_SIZE,
+            });
+        }
+        self.add_output(TxOutput {
+            value: 0,
+            script,
+            token: None,
+        });
+        Ok(())
+    }
+
     pub fn add_leftover_output(&mut self, script: Script) {
         self.outputs.push(TxBuilderOutput::Leftover {
             lower_bound: DUST_AMOUNT,
@@ -262,6 +675,83 @@ impl<'b> TxBuilder<'b> {
         });
     }
 
+    pub fn add_leftover_output_weighted(&mut self, weight: u32, script: Script) {
+        self.outputs.push(TxBuilderOutput::Weighted { weight, script });
+    }
+
+    pub fn add_sweep_output(&mut self, script: Script) -> Result<()> {
+        if self.outputs.iter().any(|output| !output.is_known_value()) {
+            return Err(Error::InvalidSweep {
+                reason: "sweep output cannot be combined with other leftover outputs",
+            });
+        }
+        self.outputs.push(TxBuilderOutput::Sweep(script));
+        Ok(())
+    }
+
+    pub fn add_change_output(&mut self, script: impl Into<Script>) -> Result<()> {
+        if self.outputs.iter().any(|output| output.is_sweep() || output.is_change()) {
+            return Err(Error::InvalidChangeOutput {
+                reason: "only one change output is allowed and it cannot be combined with a sweep",
+            });
+        }
+        self.outputs.push(TxBuilderOutput::Change(script.into()));
+        Ok(())
+    }
+
+    // shifts the index of all later inputs; InputReference::after_removal updates the
+    // references to them
+    pub fn remove_input(&mut self, input_idx: usize) -> Result<UnsignedTxInput> {
+        self.input_mut(input_idx)?;
+        Ok(self.inputs.remove(input_idx).input)
+    }
+
+    pub fn remove_output(&mut self, output_idx: usize) -> Result<TxBuilderOutput> {
+        self.output_mut(output_idx)?;
+        Ok(self.outputs.remove(output_idx))
+    }
+
+    pub fn replace_output(
+        &mut self,
+        output_idx: usize,
+        output: impl Into<TxOutput>,
+    ) -> Result<TxBuilderOutput> {
+        let output = TxBuilderOutput::KnownValue(output.into());
+        Ok(std::mem::replace(self.output_mut(output_idx)?, output))
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = &UnsignedTxInput> {
+        self.inputs.iter().map(|input| &input.input)
+    }
+
+    pub fn input(&self, input_idx: usize) -> Option<&UnsignedTxInput> {
+        self.inputs.get(input_idx).map(|input| &input.input)
+    }
+
+    pub fn input_lock_script(&self, input_idx: usize) -> Option<&Script> {
+        self.inputs.get(input_idx)?.lock_script.as_ref()
+    }
+
+    pub fn outputs(&self) -> &[TxBuilderOutput] {
+        &self.outputs
+    }
+
+    fn input_mut(&mut self, input_idx: usize) -> Result<&mut TxBuilderInput<'b>> {
+        let num_inputs = self.inputs.len();
+        self.inputs.get_mut(input_idx).ok_or(Error::InputIndexOutOfBounds {
+            input_idx,
+            num_inputs,
+        })
+    }
+
+    fn output_mut(&mut self, output_idx: usize) -> Result<&mut TxBuilderOutput> {
+        let num_outputs = self.outputs.len();
+        self.outputs.get_mut(output_idx).ok_or(Error::OutputIndexOutOfBounds {
+            output_idx,
+            num_outputs,
+        })
+    }
+
     pub fn version(&self) -> i32 {
         self.version
     }
@@ -272,24 +762,11 @@ impl<'b> TxBuilder<'b> {
 
     fn estimate_size(&self, outputs: Vec<TxOutput>) -> usize {
         let mut inputs = Vec::with_capacity(self.inputs.len());
+        let mut known_unlock_size = 0;
         for input in &self.inputs {
-            let n_sigs = input.sig_hash_flags.len();
-            let (preimages, lock_script) = match &input.lock_script {
-                Some(lock_script) => {
-                    let lock_script = Script::new(lock_script.ops().to_vec());
-                    (vec![TxPreimage::empty_with_script(&lock_script); n_sigs], lock_script)
-                }
-                None => (vec![], Script::new(vec![])),
-            };
-            inputs.push(TxInput {
-                prev_out: input.input.prev_out.clone(),
-                script: (input.func_script)(&preimages, None, None, &lock_script, &outputs),
-                sequence: input.input.sequence,
-                lock_script: None,
-                value: None,
-                is_p2sh: None,
-                sig_hash_flags: None,
-            });
+            let (tx_input, extra_size) = self.estimate_input(input, &outputs);
+            inputs.push(tx_input);
+            known_unlock_size += extra_size;
         }
         let tx = UnhashedTx {
             version: self.version,
@@ -297,7 +774,56 @@ impl<'b> TxBuilder<'b> {
             outputs,
             lock_time: self.lock_time,
         };
-        tx.ser().len()
+        tx.encoded_size() + known_unlock_size
+    }
+
+    // caches the size of inputs with a known unlock size, so the leftover loop in build()
+    // only reruns the func_scripts of the others, which may depend on the outputs
+    fn size_estimator(&self) -> SizeEstimator<'_, 'b> {
+        let mut base_size = 4 + var_int_size(self.inputs.len() as u64) + 4;
+        let mut output_dependent_inputs = Vec::new();
+        for input in &self.inputs {
+            if input.estimated_unlock_size.is_some() {
+                let (tx_input, extra_size) = self.estimate_input(input, &[]);
+                base_size += tx_input.encoded_size() + extra_size;
+            } else {
+                output_dependent_inputs.push(input);
+            }
+        }
+        SizeEstimator {
+            builder: self,
+            base_size,
+            output_dependent_inputs,
+        }
+    }
+
+    // returns the input with a placeholder script, and the number of bytes to add for
+    // inputs with a known unlock size, whose script is left empty
+    fn estimate_input(&self, input: &TxBuilderInput<'b>, outputs: &[TxOutput]) -> (TxInput, usize) {
+        if let Some(unlock_size) = input.estimated_unlock_size {
+            // replaces the empty script (1 byte length prefix)
+            let extra_size = var_int_size(unlock_size as u64) + unlock_size - 1;
+            let tx_input = TxInput::new(
+                input.input.prev_out.clone(),
+                Script::new(vec![]),
+                input.input.sequence,
+            );
+            return (tx_input, extra_size);
+        }
+        let n_sigs = input.sig_hash_flags.len();
+        let (preimages, lock_script) = match &input.lock_script {
+            Some(lock_script) => {
+                let lock_script = Script::new(lock_script.ops().to_vec());
+                (vec![TxPreimage::empty_with_script(&lock_script); n_sigs], lock_script)
+            }
+            None => (vec![], Script::new(vec![])),
+        };
+        let tx_input = TxInput::new(
+            input.input.prev_out.clone(),
+            (input.func_script)(&preimages, None, None, &lock_script, outputs),
+            input.input.sequence,
+        );
+        (tx_input, 0)
     }
 
     fn make_outputs(&self, leftover_amounts: &HashMap<usize, u64>) -> Vec<TxOutput> {
@@ -305,23 +831,34 @@ impl<'b> TxBuilder<'b> {
         for (idx, output) in self.outputs.iter().enumerate() {
             match *output {
                 TxBuilderOutput::KnownValue(ref output) => outputs.push(output.clone()),
-                TxBuilderOutput::Leftover { ref script, .. } => outputs.push(TxOutput {
+                TxBuilderOutput::Leftover { ref script, .. }
+                | TxBuilderOutput::Weighted { ref script, .. }
+                | TxBuilderOutput::Sweep(ref script)
+                | TxBuilderOutput::Change(ref script) => outputs.push(TxOutput {
                     value: match leftover_amounts.get(&idx) {
                         Some(&value) => value,
                         None => continue,
                     },
                     script: script.clone(),
+                    token: None,
                 }),
             }
         }
         outputs
     }
 
-    pub fn build_without_estimation(self) -> Result<UnsignedTx<'b>> {
+    pub fn build_without_estimation(mut self) -> Result<UnsignedTx<'b>> {
         let outputs = self.outputs.iter().map(|output| match output {
             TxBuilderOutput::KnownValue(output) => Ok(output.clone()),
-            TxBuilderOutput::Leftover { .. } => Err(Error::Msg("Found a leftover output".to_string())),
+            TxBuilderOutput::Leftover { .. }
+            | TxBuilderOutput::Weighted { .. }
+            | TxBuilderOutput::Sweep(_)
+            | TxBuilderOutput::Change(_) => {
+                Err(Error::Msg("Found a leftover output".to_string()))
+            }
         }).collect::<Result<Vec<_>>>()?;
+        self.check_single_pairing(outputs.len())?;
+        self.check_signatories()?;
         let estimated_size = self.estimate_size(outputs.clone());
         let mut inputs = vec![None; self.inputs.len()];
         for (idx, input) in self.inputs.iter().enumerate() {
@@ -335,10 +872,14 @@ impl<'b> TxBuilder<'b> {
             builder: &self,
             outputs: &outputs,
         });
+        self.track_preimages(&tx_preimages, &inputs)?;
         return Ok(UnsignedTx::new(outputs, self, tx_preimages, estimated_size, inputs));
     }
 
     pub fn build(self) -> Result<UnsignedTx<'b>> {
+        self.check_signatories()?;
+        self.validate_timelocks()?;
+        self.validate_tokens()?;
         let known_output_amount = self.known_output_sum();
         let total_input_amount = self.input_sum();
         if known_output_amount > total_input_amount {
@@ -347,102 +888,613 @@ impl<'b> TxBuilder<'b> {
             });
         }
         let mut total_leftover = total_input_amount - known_output_amount;
+        if let Some(sweep_idx) = self.outputs.iter().position(TxBuilderOutput::is_sweep) {
+            return self.build_sweep(sweep_idx, total_leftover);
+        }
         let mut leftover_amounts = HashMap::new();
+        let size_estimator = self.size_estimator();
         let mut leftover_precedence = self
             .outputs
             .iter()
             .enumerate()
             .filter_map(|(idx, output)| match output {
-                TxBuilderOutput::Leftover { precedence, .. } => Some((idx, precedence)),
+                TxBuilderOutput::Leftover {
+                    lower_bound,
+                    upper_bound,
+                    precedence,
+                    ..
+                } => Some((idx, *precedence, *lower_bound, *upper_bound)),
                 _ => None,
             })
             .collect::<Vec<_>>();
-        leftover_precedence.sort_by(|(_, a), (_, b)| a.cmp(b));
+        leftover_precedence.sort_by_key(|&(_, precedence, ..)| precedence);
         let mut estimated_size = None;
-        for (idx, _) in leftover_precedence {
-            if let TxBuilderOutput::Leftover {
+        for (idx, _, lower_bound, upper_bound) in leftover_precedence {
+            if let Some(new_size) = self.fund_leftover(
+                &size_estimator,
+                idx,
                 lower_bound,
                 upper_bound,
-                ..
-            } = self.outputs[idx]
-            {
-                if total_leftover <= lower_bound {
-                    continue;
-                }
-                let max_leftover = total_leftover.min(upper_bound);
-                leftover_amounts.insert(idx, max_leftover);
-                let new_size = self.estimate_size(self.make_outputs(&leftover_amounts));
-                let fee = new_size as u64 * self.fee_per_kb / 1000;
-                if fee <= total_leftover {
-                    let leftover = (total_leftover - fee).min(upper_bound);
-                    if leftover <= lower_bound {
-                        leftover_amounts.remove(&idx);
-                        continue;
-                    }
-                    leftover_amounts.insert(idx, leftover);
-                    total_leftover -= leftover;
-                    estimated_size = Some(new_size);
-                } else {
-                    leftover_amounts.remove(&idx);
-                }
+                &mut leftover_amounts,
+                &mut total_leftover,
+            ) {
+                estimated_size = Some(new_size);
+            }
+        }
+        if let Some(new_size) =
+            self.fund_weighted(&size_estimator, &mut leftover_amounts, &mut total_leftover)
+        {
+            estimated_size = Some(new_size);
+        }
+        if let Some(change_idx) = self.outputs.iter().position(TxBuilderOutput::is_change) {
+            if let Some(new_size) = self.fund_leftover(
+                &size_estimator,
+                change_idx,
+                DUST_AMOUNT,
+                u64::MAX,
+                &mut leftover_amounts,
+                &mut total_leftover,
+            ) {
+                estimated_size = Some(new_size);
             }
         }
         let estimated_size = match estimated_size {
             Some(estimated_size) => estimated_size,
-            None => self.estimate_size(self.make_outputs(&leftover_amounts)),
+            None => size_estimator.estimate(&self.make_outputs(&leftover_amounts)),
         };
         let outputs = self.make_outputs(&leftover_amounts);
-        let tx_preimages = TxPreimage::build_preimages(&TxBuilderPreimages {
-            builder: &self,
-            outputs: &outputs,
-        });
-        let mut inputs = vec![None; self.inputs.len()];
-        for (idx, input) in self.inputs.iter().enumerate() {
-            if let Some(signed_input) = &input.signed_input {
-                if !signed_input.script.ops().is_empty() {
-                    inputs[idx] = Some(signed_input.clone());
-                }
-            }
+        let change_output_idx = self.change_output_idx(&leftover_amounts);
+        let mut unsigned_tx = self.finish_build(outputs, estimated_size)?;
+        unsigned_tx.change_output_idx = change_output_idx;
+        Ok(unsigned_tx)
+    }
+
+    // returns the new estimated size if the output could be funded
+    fn fund_leftover(
+        &self,
+        size_estimator: &SizeEstimator,
+        idx: usize,
+        lower_bound: u64,
+        upper_bound: u64,
+        leftover_amounts: &mut HashMap<usize, u64>,
+        total_leftover: &mut u64,
+    ) -> Option<usize> {
+        if *total_leftover <= lower_bound {
+            return None;
         }
-        Ok(UnsignedTx::new(outputs, self, tx_preimages, estimated_size, inputs))
+        leftover_amounts.insert(idx, (*total_leftover).min(upper_bound));
+        let new_size = size_estimator.estimate(&self.make_outputs(leftover_amounts));
+        let fee = self.required_fee(new_size);
+        if fee > *total_leftover {
+            leftover_amounts.remove(&idx);
+            return None;
+        }
+        let leftover = (*total_leftover - fee).min(upper_bound);
+        if leftover <= lower_bound {
+            leftover_amounts.remove(&idx);
+            return None;
+        }
+        leftover_amounts.insert(idx, leftover);
+        *total_leftover -= leftover;
+        Some(new_size)
     }
 
-    pub fn known_output_sum(&self) -> u64 {
-        self
+    // splits the leftover (minus fee) across all weighted outputs by their weight,
+    // dropping the lowest-weighted output while any share would be dust
+    fn fund_weighted(
+        &self,
+        size_estimator: &SizeEstimator,
+        leftover_amounts: &mut HashMap<usize, u64>,
+        total_leftover: &mut u64,
+    ) -> Option<usize> {
+        let mut weighted = self
             .outputs
             .iter()
-            .map(|output| output.get_value())
-            .sum::<u64>()
+            .enumerate()
+            .filter_map(|(idx, output)| match output {
+                TxBuilderOutput::Weighted { weight, .. } => Some((idx, *weight)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        while !weighted.is_empty() {
+            for &(idx, _) in &weighted {
+                leftover_amounts.insert(idx, *total_leftover);
+            }
+            let new_size = size_estimator.estimate(&self.make_outputs(leftover_amounts));
+            let fee = self.required_fee(new_size);
+            let total_weight = weighted.iter().map(|&(_, weight)| weight as u128).sum::<u128>();
+            if fee > *total_leftover || total_weight == 0 {
+                break;
+            }
+            let distributable = *total_leftover - fee;
+            let mut remaining = distributable;
+            let shares = weighted
+                .iter()
+                .enumerate()
+                .map(|(i, &(idx, weight))| {
+                    let share = if i == weighted.len() - 1 {
+                        remaining
+                    } else {
+                        (distributable as u128 * weight as u128 / total_weight) as u64
+                    };
+                    remaining -= share;
+                    (idx, share)
+                })
+                .collect::<Vec<_>>();
+            if shares.iter().any(|&(_, share)| share < DUST_AMOUNT) {
+                let (pos, _) = weighted
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, &(_, weight))| weight)?;
+                let (idx, _) = weighted.remove(pos);
+                leftover_amounts.remove(&idx);
+                continue;
+            }
+            leftover_amounts.extend(shares);
+            *total_leftover -= distributable;
+            return Some(new_size);
+        }
+        for (idx, _) in weighted {
+            leftover_amounts.remove(&idx);
+        }
+        None
     }
 
-    pub fn input_sum(&self) -> u64 {
-        self
-            .inputs
-            .iter()
-            .map(|input| input.input.value)
-            .sum::<u64>()
+    // index of the change output in the built tx, if it was funded
+    fn change_output_idx(&self, leftover_amounts: &HashMap<usize, u64>) -> Option<usize> {
+        let change_idx = self.outputs.iter().position(TxBuilderOutput::is_change)?;
+        leftover_amounts.get(&change_idx)?;
+        Some(
+            self.outputs[..change_idx]
+                .iter()
+                .enumerate()
+                .filter(|(idx, output)| {
+                    output.is_known_value() || leftover_amounts.contains_key(idx)
+                })
+                .count(),
+        )
     }
-}
 
-impl TxBuilderOutput {
-    fn get_value(&self) -> u64 {
-        match self {
-            TxBuilderOutput::Leftover { .. } => 0,
-            TxBuilderOutput::KnownValue(output) => output.value,
+    // inputs are sorted by outpoint and outputs by value and script, so every party
+    // arrives at the same tx; each party may have at most one leftover or change output.
+    // Every party pays for its own share of the tx at `fee_rate`, plus its CPFP parents,
+    // within its own max fee and max fee rate.
+    pub fn coinjoin(parties: Vec<TxBuilder<'b>>, fee_rate: FeeRate) -> Result<CoinJoin<'b>> {
+        let first = parties.first().ok_or(Error::InvalidCoinJoin {
+            reason: "no parties to merge",
+        })?;
+        let (version, lock_time, fork_id) = (first.version, first.lock_time, first.fork_id);
+        if parties.iter().any(|party| {
+            (party.version, party.lock_time, party.fork_id) != (version, lock_time, fork_id)
+        }) {
+            return Err(Error::InvalidCoinJoin {
+                reason: "parties disagree on version, lock time or fork id",
+            });
         }
-    }
-}
-
-impl ToPreimages for TxBuilderPreimages<'_> {
-    fn version(&self) -> i32 {
-        self.builder.version()
-    }
-    fn num_inputs(&self) -> usize {
-        self.builder.inputs.len()
-    }
-    fn input_outpoint_at(&self, input_idx: usize) -> &TxOutpoint {
-        &self.builder.inputs[input_idx].input.prev_out
-    }
+        let num_inputs = parties.iter().map(|party| party.inputs.len()).sum::<usize>();
+        let num_outputs = parties.iter().map(|party| party.outputs.len()).sum::<usize>();
+        let overhead_size =
+            4 + var_int_size(num_inputs as u64) + var_int_size(num_outputs as u64) + 4;
+        let overhead_share = (overhead_size + parties.len() - 1) / parties.len();
+        let outputs_size =
+            |outputs: &[TxOutput]| outputs.iter().map(TxOutput::encoded_size).sum::<usize>();
+        let mut merged_inputs = Vec::with_capacity(num_inputs);
+        let mut merged_outputs = Vec::with_capacity(num_outputs);
+        let mut party_fees = Vec::with_capacity(parties.len());
+        let mut cpfp_parents = Vec::new();
+        // the merged tx may only pay as much as all parties allow together
+        let max_fee = parties
+            .iter()
+            .map(|party| party.max_fee)
+            .sum::<Option<u64>>();
+        let max_fee_rate = parties
+            .iter()
+            .map(|party| party.max_fee_rate)
+            .collect::<Option<Vec<_>>>()
+            .and_then(|max_fee_rates| max_fee_rates.into_iter().max());
+        for (party_idx, mut party) in parties.into_iter().enumerate() {
+            party.fee_rate = fee_rate;
+            let mut known_outputs = Vec::new();
+            let mut leftover = None;
+            for output in &party.outputs {
+                match output {
+                    TxBuilderOutput::KnownValue(output) => known_outputs.push(output.clone()),
+                    TxBuilderOutput::Leftover {
+                        lower_bound,
+                        upper_bound,
+                        script,
+                        ..
+                    } if leftover.is_none() => {
+                        leftover = Some((*lower_bound, *upper_bound, script.clone()))
+                    }
+                    TxBuilderOutput::Change(script) if leftover.is_none() => {
+                        leftover = Some((DUST_AMOUNT, u64::MAX, script.clone()))
+                    }
+                    _ => {
+                        return Err(Error::InvalidCoinJoin {
+                            reason: "a party may only have one leftover or change output",
+                        })
+                    }
+                }
+            }
+            let inputs_size = party
+                .inputs
+                .iter()
+                .map(|input| match &input.signed_input {
+                    Some(signed_input) => signed_input.encoded_size(),
+                    None => {
+                        let (tx_input, extra_size) = party.estimate_input(input, &known_outputs);
+                        tx_input.encoded_size() + extra_size
+                    }
+                })
+                .sum::<usize>();
+            let base_size = overhead_share + inputs_size + outputs_size(&known_outputs);
+            let input_sum = party.input_sum();
+            let known_output_sum = party.known_output_sum();
+            let mut outputs = known_outputs;
+            if let Some((lower_bound, upper_bound, script)) = leftover {
+                let output = TxOutput {
+                    value: 0,
+                    script,
+                    token: None,
+                };
+                let fee = party.required_fee(base_size + output.encoded_size());
+                let value = input_sum
+                    .saturating_sub(known_output_sum + fee)
+                    .min(upper_bound);
+                if value > lower_bound {
+                    outputs.push(TxOutput { value, ..output });
+                }
+            }
+            let output_sum = outputs.iter().map(|output| output.value).sum::<u64>();
+            let party_size = overhead_share + inputs_size + outputs_size(&outputs);
+            let fee = party.required_fee(party_size);
+            if input_sum < output_sum + fee {
+                return Err(Error::InsufficientInputAmount {
+                    amount: output_sum + fee - input_sum,
+                });
+            }
+            party.check_fee(&outputs, party_size)?;
+            party_fees.push(input_sum - output_sum);
+            cpfp_parents.extend(party.cpfp_parents);
+            merged_outputs.extend(outputs);
+            merged_inputs.extend(
+                party
+                    .inputs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(input_idx, input)| (party_idx, input_idx, input)),
+            );
+        }
+        merged_inputs.sort_by(|(_, _, a), (_, _, b)| {
+            let a = &a.input.prev_out;
+            let b = &b.input.prev_out;
+            (a.tx_hash.as_slice(), a.vout).cmp(&(b.tx_hash.as_slice(), b.vout))
+        });
+        if merged_inputs
+            .windows(2)
+            .any(|pair| pair[0].2.input.prev_out == pair[1].2.input.prev_out)
+        {
+            return Err(Error::InvalidCoinJoin {
+                reason: "parties spend the same output",
+            });
+        }
+        merged_outputs.sort_by(|a, b| {
+            (a.value, a.script.ser_ops().as_slice()).cmp(&(b.value, b.script.ser_ops().as_slice()))
+        });
+        let mut input_indices = vec![Vec::new(); party_fees.len()];
+        let mut builder = TxBuilder::new(version, lock_time);
+        builder.fork_id = fork_id;
+        builder.fee_rate = fee_rate;
+        builder.max_fee = max_fee;
+        builder.max_fee_rate = max_fee_rate;
+        builder.cpfp_parents = cpfp_parents;
+        for (merged_idx, (party_idx, input_idx, input)) in merged_inputs.into_iter().enumerate() {
+            let indices = &mut input_indices[party_idx];
+            if indices.len() <= input_idx {
+                indices.resize(input_idx + 1, 0);
+            }
+            indices[input_idx] = merged_idx;
+            builder.inputs.push(input);
+        }
+        builder.add_outputs(merged_outputs.clone());
+        builder.check_fee(&merged_outputs, builder.estimated_size())?;
+        Ok(CoinJoin {
+            unsigned_tx: builder.build_without_estimation()?,
+            input_indices,
+            party_fees,
+        })
+    }
+
+    fn build_sweep(self, sweep_idx: usize, total_leftover: u64) -> Result<UnsignedTx<'b>> {
+        // leftover outputs may have been added after the sweep output
+        if self.outputs.iter().filter(|output| !output.is_known_value()).count() > 1 {
+            return Err(Error::InvalidSweep {
+                reason: "sweep output cannot be combined with other leftover outputs",
+            });
+        }
+        let mut leftover_amounts = HashMap::new();
+        leftover_amounts.insert(sweep_idx, total_leftover);
+        let estimated_size = self.estimate_size(self.make_outputs(&leftover_amounts));
+        let fee = self.required_fee(estimated_size);
+        if fee > total_leftover {
+            return Err(Error::InsufficientInputAmount {
+                amount: fee - total_leftover,
+            });
+        }
+        let value = total_leftover - fee;
+        if value < DUST_AMOUNT {
+            return Err(Error::DustOutput {
+                value,
+                dust_limit: DUST_AMOUNT,
+            });
+        }
+        leftover_amounts.insert(sweep_idx, value);
+        let outputs = self.make_outputs(&leftover_amounts);
+        self.finish_build(outputs, estimated_size)
+    }
+
+    fn finish_build(
+        mut self,
+        outputs: Vec<TxOutput>,
+        estimated_size: usize,
+    ) -> Result<UnsignedTx<'b>> {
+        self.check_fee(&outputs, estimated_size)?;
+        self.check_single_pairing(outputs.len())?;
+        let tx_preimages = TxPreimage::build_preimages(&TxBuilderPreimages {
+            builder: &self,
+            outputs: &outputs,
+        });
+        let mut inputs = vec![None; self.inputs.len()];
+        for (idx, input) in self.inputs.iter().enumerate() {
+            if let Some(signed_input) = &input.signed_input {
+                if !signed_input.script.ops().is_empty() {
+                    inputs[idx] = Some(signed_input.clone());
+                }
+            }
+        }
+        self.track_preimages(&tx_preimages, &inputs)?;
+        Ok(UnsignedTx::new(outputs, self, tx_preimages, estimated_size, inputs))
+    }
+
+    // rebuilding with different preimages invalidates signatures made for the old ones, so
+    // signed inputs carried over from `from_tx` or `from_state` can't be kept
+    fn track_preimages(
+        &mut self,
+        tx_preimages: &[Vec<TxPreimage>],
+        signed_inputs: &[Option<TxInput>],
+    ) -> Result<()> {
+        let preimages_digest = preimages_digest(tx_preimages);
+        if self
+            .preimages_digest
+            .as_ref()
+            .is_some_and(|digest| digest != &preimages_digest)
+        {
+            self.generation += 1;
+            if signed_inputs.iter().any(Option::is_some) {
+                return Err(Error::StalePreimages {
+                    generation: self.generation - 1,
+                    current_generation: self.generation,
+                });
+            }
+        }
+        self.preimages_digest = Some(preimages_digest);
+        Ok(())
+    }
+
+    // inputs resumed without their signatory can neither be estimated nor signed
+    fn check_signatories(&self) -> Result<()> {
+        for (input_idx, input) in self.inputs.iter().enumerate() {
+            let is_signed = input
+                .signed_input
+                .as_ref()
+                .is_some_and(|signed_input| !signed_input.script.ops().is_empty());
+            if !input.has_signatory && !is_signed {
+                return Err(Error::MissingSignatory { input_idx });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_single_pairing(&self, num_outputs: usize) -> Result<()> {
+        for (input_idx, input) in self.inputs.iter().enumerate() {
+            let is_single = input.sig_hash_flags.iter().any(|flags| flags.is_single());
+            if is_single && input_idx >= num_outputs {
+                return Err(Error::UnpairedSingleInput { input_idx });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_fee(&self, outputs: &[TxOutput], estimated_size: usize) -> Result<()> {
+        let output_sum = outputs.iter().map(|output| output.value).sum::<u64>();
+        let fee = self.input_sum().saturating_sub(output_sum);
+        // CPFP children are judged by the fee rate of the whole package
+        let package_size = estimated_size
+            + self.cpfp_parents.iter().map(|parent| parent.size).sum::<usize>();
+        let package_fee = fee + self.cpfp_parents.iter().map(|parent| parent.fee).sum::<u64>();
+        let fee_rate = FeeRate::from_fee_and_size(package_fee, package_size);
+        let exceeds_max_fee = self.max_fee.is_some_and(|max_fee| fee > max_fee);
+        let exceeds_max_fee_rate = self
+            .max_fee_rate
+            .is_some_and(|max_fee_rate| fee_rate > max_fee_rate);
+        if exceeds_max_fee || exceeds_max_fee_rate {
+            return Err(Error::AbsurdFee {
+                fee,
+                fee_per_kb: fee_rate.sats_per_kb(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn known_output_sum(&self) -> u64 {
+        self
+            .outputs
+            .iter()
+            .map(|output| output.get_value())
+            .sum::<u64>()
+    }
+
+    pub fn input_sum(&self) -> u64 {
+        self
+            .inputs
+            .iter()
+            .map(|input| input.input.value)
+            .sum::<u64>()
+    }
+
+    pub fn estimated_size(&self) -> usize {
+        let leftover_amounts = self
+            .outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| !output.is_known_value())
+            .map(|(idx, _)| (idx, 0))
+            .collect();
+        self.estimate_size(self.make_outputs(&leftover_amounts))
+    }
+}
+
+fn signatory_func_script<'b, S>(input_signatory: S) -> FuncScript<'b>
+where
+    S: Signatory + 'b + Sync + Send,
+{
+    Box::new(
+        move |tx_preimages: &[TxPreimage],
+              estimated_size: Option<usize>,
+              sigs: Option<Box<dyn Any>>,
+              lock_script: &Script,
+              tx_outputs: &[TxOutput]| {
+            let sigs = match sigs {
+                Some(sigs) => *sigs.downcast::<S::Signatures>().expect("Incompatible sigs"),
+                None => input_signatory.placeholder_signatures(),
+            };
+            let tx_preimages = <S::Kind as SignatoryKind>::make_tx_preimages(tx_preimages);
+            let mut ops: Vec<_> = input_signatory
+                .build_script(tx_preimages, estimated_size, sigs, lock_script, tx_outputs)
+                .ops()
+                .into();
+            if input_signatory.is_p2sh() {
+                ops.push(TaggedOp::from_op(lock_script.ser_ops().into()));
+            }
+            Script::new(ops)
+        },
+    )
+}
+
+fn preimages_digest(tx_preimages: &[Vec<TxPreimage>]) -> Sha256d {
+    let mut data = Vec::new();
+    for preimage in tx_preimages.iter().flatten() {
+        data.extend_from_slice(&preimage.ser());
+    }
+    Sha256d::digest(data)
+}
+
+fn script_num(op: &Op) -> Option<u32> {
+    let value = match op {
+        Op::PushInteger(int) => int.value(),
+        Op::PushByteArray { array, .. } => vec_to_int(array).ok()?,
+        _ => return None,
+    };
+    if value < 0 {
+        return None;
+    }
+    Some(value as u32)
+}
+
+impl CpfpParent {
+    pub fn new(size: usize, fee: u64) -> Self {
+        CpfpParent { size, fee }
+    }
+
+    pub fn from_tx(tx: &UnhashedTx) -> Result<Self> {
+        let fee = tx
+            .fee()
+            .ok_or_else(|| Error::Msg("Parent tx is missing input values".to_string()))?;
+        Ok(CpfpParent {
+            size: tx.encoded_size(),
+            fee,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+}
+
+struct SizeEstimator<'a, 'b> {
+    builder: &'a TxBuilder<'b>,
+    // size of the tx without its outputs (including their count) and output dependent inputs
+    base_size: usize,
+    output_dependent_inputs: Vec<&'a TxBuilderInput<'b>>,
+}
+
+impl SizeEstimator<'_, '_> {
+    fn estimate(&self, outputs: &[TxOutput]) -> usize {
+        let inputs_size = self
+            .output_dependent_inputs
+            .iter()
+            .map(|input| self.builder.estimate_input(input, outputs).0.encoded_size())
+            .sum::<usize>();
+        self.base_size + inputs_size + outputs_size(outputs)
+    }
+}
+
+fn outputs_size(outputs: &[TxOutput]) -> usize {
+    var_int_size(outputs.len() as u64) + outputs.iter().map(TxOutput::encoded_size).sum::<usize>()
+}
+
+impl TxBuilderOutput {
+    pub fn get_value(&self) -> u64 {
+        match self {
+            TxBuilderOutput::Leftover { .. }
+            | TxBuilderOutput::Weighted { .. }
+            | TxBuilderOutput::Sweep(_)
+            | TxBuilderOutput::Change(_) => 0,
+            TxBuilderOutput::KnownValue(output) => output.value,
+        }
+    }
+
+    pub fn is_known_value(&self) -> bool {
+        matches!(self, TxBuilderOutput::KnownValue(_))
+    }
+
+    pub fn is_sweep(&self) -> bool {
+        matches!(self, TxBuilderOutput::Sweep(_))
+    }
+
+    pub fn is_change(&self) -> bool {
+        matches!(self, TxBuilderOutput::Change(_))
+    }
+
+    pub fn script(&self) -> &Script {
+        match self {
+            TxBuilderOutput::Leftover { script, .. }
+            | TxBuilderOutput::Weighted { script, .. }
+            | TxBuilderOutput::Sweep(script)
+            | TxBuilderOutput::Change(script) => script,
+            TxBuilderOutput::KnownValue(output) => &output.script,
+        }
+    }
+}
+
+impl ToPreimages for TxBuilderPreimages<'_> {
+    fn version(&self) -> i32 {
+        self.builder.version()
+    }
+    fn fork_id(&self) -> u32 {
+        self.builder.fork_id
+    }
+    fn num_inputs(&self) -> usize {
+        self.builder.inputs.len()
+    }
+    fn input_outpoint_at(&self, input_idx: usize) -> &TxOutpoint {
+        &self.builder.inputs[input_idx].input.prev_out
+    }
     fn input_sequence_at(&self, input_idx: usize) -> u32 {
         self.builder.inputs[input_idx].input.sequence
     }
@@ -458,6 +1510,9 @@ impl ToPreimages for TxBuilderPreimages<'_> {
             None => Script::new(vec![]),
         }
     }
+    fn input_token_at(&self, input_idx: usize) -> Option<&TokenData> {
+        self.builder.inputs[input_idx].input.token.as_ref()
+    }
 
     fn num_outputs(&self) -> usize {
         self.outputs.len()
@@ -484,15 +1539,44 @@ impl<'b> UnsignedTx<'b> {
             outputs,
             tx_preimages,
             estimated_size,
+            change_output_idx: None,
         }
     }
 
+    pub fn change_output_idx(&self) -> Option<usize> {
+        self.change_output_idx
+    }
+
+    pub fn has_change(&self) -> bool {
+        self.change_output_idx.is_some()
+    }
+
+    // rejects signatures once the preimages changed since `input_ref` was made; use
+    // `sign_input_checked` with the generation the signatures were made for after that
     pub fn sign_input<S: Signatory>(
         &mut self,
         input_ref: InputReference<S>,
         sigs: S::Signatures,
     ) -> Result<()> {
-        println!("Signing input {}", input_ref.input_idx());
+        self.sign_input_checked(input_ref.generation, input_ref, sigs)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.builder.generation
+    }
+
+    pub fn sign_input_checked<S: Signatory>(
+        &mut self,
+        generation: u64,
+        input_ref: InputReference<S>,
+        sigs: S::Signatures,
+    ) -> Result<()> {
+        if generation != self.generation() {
+            return Err(Error::StalePreimages {
+                generation,
+                current_generation: self.generation(),
+            });
+        }
         self.sign_input_dyn(input_ref.input_idx, Box::new(sigs))
     }
 
@@ -502,6 +1586,9 @@ impl<'b> UnsignedTx<'b> {
             return Err(Error::InputAlreadySigned { input_idx });
         }
         let builder_input = &self.builder.inputs[input_idx];
+        if !builder_input.has_signatory {
+            return Err(Error::MissingSignatory { input_idx });
+        }
         let preimage = &self.tx_preimages[input_idx];
         *input = Some(TxInput {
             prev_out: builder_input.input.prev_out.clone(),
@@ -562,6 +1649,218 @@ impl<'b> UnsignedTx<'b> {
         Ok(())
     }
 
+    pub fn to_state(&self) -> UnsignedTxState {
+        UnsignedTxState {
+            version: self.builder.version,
+            lock_time: self.builder.lock_time,
+            fee_per_kb: self.builder.fee_rate.sats_per_kb(),
+            fork_id: self.builder.fork_id,
+            generation: self.builder.generation,
+            inputs: self
+                .builder
+                .inputs
+                .iter()
+                .zip(&self.inputs)
+                .map(|(builder_input, signed_input)| UnsignedTxInputState {
+                    input: builder_input.input.clone(),
+                    lock_script: builder_input.lock_script.clone(),
+                    sig_hash_flags: builder_input.sig_hash_flags.clone(),
+                    is_p2sh: builder_input.is_p2sh,
+                    signed_input: signed_input.clone(),
+                    derivation_hint: builder_input.derivation_hint.clone(),
+                })
+                .collect(),
+            outputs: self.outputs.clone(),
+            estimated_size: self.estimated_size,
+        }
+    }
+
+    // signatories aren't part of the state; unsigned inputs have to be re-attached with
+    // attach_signatory, or signed with sign_input_signatory, after resuming. Until then,
+    // signing them otherwise or rebuilding the tx fails with MissingSignatory.
+    pub fn from_state(state: UnsignedTxState) -> Self {
+        let mut builder = TxBuilder::new_with_fee(state.version, state.lock_time, state.fee_per_kb);
+        builder.fork_id = state.fork_id;
+        builder.generation = state.generation;
+        let mut inputs = Vec::with_capacity(state.inputs.len());
+        for input_state in state.inputs {
+            builder.inputs.push(TxBuilderInput {
+                input: input_state.input,
+                func_script: Box::new(|_, _, _, _, _| Script::new(vec![])),
+                sig_hash_flags: input_state.sig_hash_flags,
+                lock_script: input_state.lock_script,
+                is_p2sh: input_state.is_p2sh,
+                estimated_unlock_size: None,
+                signed_input: input_state.signed_input.clone(),
+                has_signatory: false,
+                derivation_hint: input_state.derivation_hint,
+            });
+            inputs.push(input_state.signed_input);
+        }
+        builder.outputs = state
+            .outputs
+            .iter()
+            .cloned()
+            .map(TxBuilderOutput::KnownValue)
+            .collect();
+        let tx_preimages = TxPreimage::build_preimages(&TxBuilderPreimages {
+            builder: &builder,
+            outputs: &state.outputs,
+        });
+        builder.preimages_digest = Some(preimages_digest(&tx_preimages));
+        UnsignedTx::new(state.outputs, builder, tx_preimages, state.estimated_size, inputs)
+    }
+
+    pub fn attach_signatory<S: Signatory + 'b + Sync + Send>(
+        &mut self,
+        input_idx: usize,
+        input_signatory: S,
+    ) -> Result<InputReference<S>> {
+        let sig_hash_flags = input_signatory.sig_hash_flags();
+        let sig_hash_flags = <S::Kind as SignatoryKind>::sig_hash_flags_vec(sig_hash_flags);
+        let is_p2sh = input_signatory.is_p2sh();
+        let builder_input = self.builder.input_mut(input_idx)?;
+        if builder_input.sig_hash_flags != sig_hash_flags {
+            return Err(Error::TxMismatch {
+                field: "sig_hash_flags",
+            });
+        }
+        if builder_input.is_p2sh.is_some_and(|input_is_p2sh| input_is_p2sh != is_p2sh) {
+            return Err(Error::TxMismatch { field: "is_p2sh" });
+        }
+        builder_input.func_script = signatory_func_script(input_signatory);
+        builder_input.has_signatory = true;
+        Ok(InputReference {
+            generation: self.generation(),
+            ..InputReference::new(input_idx)
+        })
+    }
+
+    pub fn merge(&mut self, other: UnsignedTx<'_>) -> Result<()> {
+        if self.builder.version != other.builder.version {
+            return Err(Error::TxMismatch { field: "version" });
+        }
+        if self.builder.lock_time != other.builder.lock_time {
+            return Err(Error::TxMismatch { field: "lock_time" });
+        }
+        if self.outputs != other.outputs {
+            return Err(Error::TxMismatch { field: "outputs" });
+        }
+        if self.builder.inputs.len() != other.builder.inputs.len() {
+            return Err(Error::TxMismatch { field: "inputs" });
+        }
+        let same_inputs = self
+            .builder
+            .inputs
+            .iter()
+            .zip(&other.builder.inputs)
+            .all(|(input, other_input)| {
+                input.input == other_input.input
+                    && input.lock_script == other_input.lock_script
+                    && input.sig_hash_flags == other_input.sig_hash_flags
+            });
+        if !same_inputs {
+            return Err(Error::TxMismatch { field: "inputs" });
+        }
+        for (input_idx, (input, other_input)) in
+            self.inputs.iter().zip(&other.inputs).enumerate()
+        {
+            if let (Some(input), Some(other_input)) = (input, other_input) {
+                if input != other_input {
+                    return Err(Error::ConflictingSignatures { input_idx });
+                }
+            }
+        }
+        for (input, other_input) in self.inputs.iter_mut().zip(other.inputs) {
+            if input.is_none() {
+                *input = other_input;
+            }
+        }
+        Ok(())
+    }
+
+    // None if the tx has no input `input_idx`
+    pub fn is_input_signed(&self, input_idx: usize) -> Option<bool> {
+        Some(self.inputs.get(input_idx)?.is_some())
+    }
+
+    // None if the tx has no input `input_idx`
+    pub fn input_status(&self, input_idx: usize) -> Option<InputSigningStatus> {
+        let builder_input = self.builder.inputs.get(input_idx)?;
+        let status = if self.inputs[input_idx].is_some() {
+            InputSigningStatus::Signed
+        } else if !builder_input.has_signatory {
+            InputSigningStatus::Placeholder
+        } else {
+            // an input gets all of its signatures at once, so an unsigned one has none yet
+            InputSigningStatus::MissingSignatures {
+                num_present: 0,
+                num_required: builder_input.sig_hash_flags.len(),
+            }
+        };
+        Some(status)
+    }
+
+    pub fn signing_status(&self) -> Vec<InputSigningStatus> {
+        (0..self.inputs.len())
+            .filter_map(|input_idx| self.input_status(input_idx))
+            .collect()
+    }
+
+    pub fn fee_report(&self) -> FeeReport {
+        let input_sizes = self
+            .builder
+            .inputs
+            .iter()
+            .zip(&self.inputs)
+            .map(|(builder_input, signed_input)| match signed_input {
+                Some(signed_input) => signed_input.encoded_size(),
+                None => {
+                    let (tx_input, extra_size) =
+                        self.builder.estimate_input(builder_input, &self.outputs);
+                    tx_input.encoded_size() + extra_size
+                }
+            })
+            .collect::<Vec<_>>();
+        let output_sizes = self
+            .outputs
+            .iter()
+            .map(TxOutput::encoded_size)
+            .collect::<Vec<_>>();
+        let size = input_sizes.iter().sum::<usize>() + output_sizes.iter().sum::<usize>();
+        let overhead_size = 4
+            + var_int_size(input_sizes.len() as u64)
+            + var_int_size(output_sizes.len() as u64)
+            + 4;
+        let actual_size = if self.is_fully_signed() {
+            Some(size + overhead_size)
+        } else {
+            None
+        };
+        let output_sum = self.outputs.iter().map(|output| output.value).sum::<u64>();
+        let fee = self.builder.input_sum().saturating_sub(output_sum);
+        let fee_size = actual_size.unwrap_or(self.estimated_size);
+        FeeReport {
+            estimated_size: self.estimated_size,
+            actual_size,
+            fee,
+            fee_per_byte: fee as f64 / fee_size.max(1) as f64,
+            overhead_size,
+            input_sizes,
+            output_sizes,
+        }
+    }
+
+    pub fn unsigned_inputs(&self) -> Vec<usize> {
+        (0..self.inputs.len())
+            .filter(|&input_idx| self.inputs[input_idx].is_none())
+            .collect()
+    }
+
+    pub fn is_fully_signed(&self) -> bool {
+        self.inputs.iter().all(Option::is_some)
+    }
+
     pub fn input_preimages<S: Signatory>(
         &self,
         input_token: InputReference<S>,
@@ -573,7 +1872,39 @@ impl<'b> UnsignedTx<'b> {
         &self.tx_preimages
     }
 
-    pub fn complete_tx(self) -> UnhashedTx {
+    pub fn export_signing_requests(&self) -> Vec<SigningRequest> {
+        self.unsigned_inputs()
+            .into_iter()
+            .flat_map(|input_idx| {
+                let builder_input = &self.builder.inputs[input_idx];
+                self.tx_preimages[input_idx]
+                    .iter()
+                    .zip(&builder_input.sig_hash_flags)
+                    .enumerate()
+                    .map(move |(sig_idx, (preimage, &sig_hash_flags))| SigningRequest {
+                        input_idx,
+                        sig_idx,
+                        sig_hash: Sha256d::digest(preimage.ser()),
+                        sig_hash_flags,
+                        lock_script: builder_input.lock_script.clone(),
+                        value: builder_input.input.value,
+                        derivation_hint: builder_input.derivation_hint.clone(),
+                    })
+            })
+            .collect()
+    }
+
+    pub fn complete_tx(self) -> Result<UnhashedTx> {
+        let unsigned_inputs = self.unsigned_inputs();
+        if !unsigned_inputs.is_empty() {
+            return Err(Error::MissingSignatures {
+                input_indices: unsigned_inputs,
+            });
+        }
+        Ok(self.complete_tx_unchecked())
+    }
+
+    pub fn complete_tx_unchecked(self) -> UnhashedTx {
         let inputs = self
             .inputs
             .into_iter()
@@ -621,17 +1952,68 @@ impl<'b> UnsignedTx<'b> {
     }
 }
 
+impl<'b> CoinJoin<'b> {
+    pub fn num_parties(&self) -> usize {
+        self.party_fees.len()
+    }
+
+    // maps an InputReference from a party's own builder to the merged tx
+    pub fn input_ref<S>(
+        &self,
+        party_idx: usize,
+        input_ref: InputReference<S>,
+    ) -> Result<InputReference<S>> {
+        let input_idx = self
+            .party_input_indices(party_idx)?
+            .get(input_ref.input_idx)
+            .ok_or(Error::InvalidCoinJoin {
+                reason: "party has no such input",
+            })?;
+        Ok(InputReference::new(*input_idx))
+    }
+
+    pub fn party_input_indices(&self, party_idx: usize) -> Result<&[usize]> {
+        match self.input_indices.get(party_idx) {
+            Some(indices) => Ok(indices),
+            None => Err(Error::InvalidCoinJoin {
+                reason: "no such party",
+            }),
+        }
+    }
+
+    pub fn party_fee(&self, party_idx: usize) -> Result<u64> {
+        self.party_fees.get(party_idx).copied().ok_or(Error::InvalidCoinJoin {
+            reason: "no such party",
+        })
+    }
+}
+
 impl<T> InputReference<T> {
     pub fn new(input_idx: usize) -> Self {
         InputReference {
             phantom: PhantomData,
             input_idx,
+            generation: 0,
         }
     }
 
     pub fn input_idx(&self) -> usize {
         self.input_idx
     }
+
+    // the reference to the same input after the one at `removed_idx` was removed with
+    // TxBuilder::remove_input; None if this was the removed input
+    pub fn after_removal(self, removed_idx: usize) -> Option<Self> {
+        if self.input_idx == removed_idx {
+            return None;
+        }
+        let input_idx = if self.input_idx > removed_idx {
+            self.input_idx - 1
+        } else {
+            self.input_idx
+        };
+        Some(InputReference { input_idx, ..self })
+    }
 }
 
 impl SignatoryKind for SignatoryKindOne {
@@ -661,3 +2043,1007 @@ impl SignatoryKind for SignatoryKindMultiple {
         tx_preimages
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CpfpParent, InputReference, InputSigningStatus, Signatory, SignatoryKindOne,
+        SigningRequest, TxBuilder, UnsignedTx, UnsignedTxState, DEFAULT_FEE_RATE, DUST_AMOUNT,
+        MAX_DATA_CARRIER_SIZE,
+    };
+    use crate::error::{Error, Result};
+    use crate::{
+        Address, AddressType, BitcoinCode, ByteArray, CsvTimedelta, Hash160, Integer, LockTime,
+        MultisigSignatory, Op, Opcode, Hashed, P2PKHSignatory, Prefix, Pubkey, Script, Sha256d,
+        SigHashFlags, TaggedOp, TaggedScript, TokenCategory, TokenData, TxInput, TxOutpoint,
+        TxOutput, TxPreimage, UnhashedTx, UnsignedTxInput,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_add_data_output() -> Result<()> {
+        let mut builder = TxBuilder::new_simple();
+        builder.add_data_output(b"TEST".as_ref(), vec![b"hello".as_ref(), b"world".as_ref()])?;
+        let unsigned_tx = builder.build_without_estimation()?;
+        let tx = unsigned_tx.complete_tx()?;
+        assert_eq!(tx.outputs[0].value, 0);
+        assert_eq!(
+            tx.outputs[0].script.ser_ops().as_ref(),
+            b"\x6a\x04TEST\x05hello\x05world".as_ref(),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_data_output_standardness() -> Result<()> {
+        let mut builder = TxBuilder::new_simple();
+        // OP_RETURN, the pushed prefix and a PUSHDATA1 of the data
+        let too_large = vec![0; MAX_DATA_CARRIER_SIZE - 7];
+        match builder.add_data_output(b"TEST".as_ref(), vec![too_large.as_slice()]) {
+            Err(Error::DataCarrierTooLarge { size, max_size }) => {
+                assert_eq!(size, MAX_DATA_CARRIER_SIZE + 1);
+                assert_eq!(max_size, MAX_DATA_CARRIER_SIZE);
+            }
+            _ => panic!("expected DataCarrierTooLarge"),
+        }
+        let max_size = vec![0; MAX_DATA_CARRIER_SIZE - 8];
+        builder.add_data_output(b"TEST".as_ref(), vec![max_size.as_slice()])?;
+        assert!(matches!(
+            builder.add_data_output(b"TEST".as_ref(), Vec::<&[u8]>::new()),
+            Err(Error::MultipleDataCarriers)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cpfp() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let mut parent_input = TxInput::new(TxOutpoint::default(), Script::new(vec![]), 0);
+        parent_input.value = Some(20_000);
+        let parent = UnhashedTx {
+            version: 1,
+            inputs: vec![parent_input],
+            outputs: vec![TxOutput {
+                value: 19_990,
+                script: address.clone().into(),
+                token: None,
+            }],
+            lock_time: 0,
+        };
+        let parent_info = CpfpParent::from_tx(&parent)?;
+        assert_eq!(parent_info.fee(), 10);
+        assert_eq!(parent_info.size(), parent.ser().len());
+
+        let mut builder = TxBuilder::new_with_fee(1, 0, 2000);
+        builder.add_cpfp_parent(parent_info);
+        builder.add_parent_output_input(
+            &parent,
+            0,
+            address.p2pkh_script()?,
+            P2PKHSignatory {
+                pubkey,
+                sig_hash_flags: SigHashFlags::DEFAULT,
+            },
+        )?;
+        builder.add_leftover_output(address.into());
+        let unsigned_tx = builder.build()?;
+        let estimated_size = unsigned_tx.estimated_size();
+        let child_fee = 19_990 - unsigned_tx.partial_tx().outputs[0].value;
+        let package_size = (estimated_size + parent_info.size()) as u64;
+        assert_eq!(child_fee + 10, package_size * 2000 / 1000);
+
+        // a parent paying for more than the package doesn't lower the child's fee
+        let mut builder = TxBuilder::new_with_fee(1, 0, 2000);
+        builder.add_cpfp_parent(CpfpParent {
+            size: 200,
+            fee: 10_000,
+        });
+        assert_eq!(builder.required_fee(300), 600);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parent_output_token() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let category = TokenCategory::from_hash(Sha256d::new([3; 32]));
+        let token = TokenData::fungible(category, 500);
+        let parent = UnhashedTx {
+            version: 2,
+            inputs: vec![TxInput::new(TxOutpoint::default(), Script::new(vec![]), 0)],
+            outputs: vec![TxOutput {
+                value: 20_000,
+                script: address.clone().into(),
+                token: Some(token.clone()),
+            }],
+            lock_time: 0,
+        };
+        let mut builder = TxBuilder::new_simple();
+        builder.add_parent_output_input(
+            &parent,
+            0,
+            address.p2pkh_script()?,
+            P2PKHSignatory {
+                pubkey,
+                sig_hash_flags: SigHashFlags::DEFAULT,
+            },
+        )?;
+        assert_eq!(builder.input(0).unwrap().token, Some(token.clone()));
+        builder.add_token_output(address.clone().into(), token);
+        builder.add_leftover_output(address.into());
+        builder.build()?;
+        Ok(())
+    }
+
+    // spends one P2PKH input of [2; 33] per value, each from its own vout
+    fn p2pkh_builder(values: &[u64]) -> Result<TxBuilder<'static>> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let mut builder = TxBuilder::new_simple();
+        for (vout, &value) in values.iter().enumerate() {
+            builder.add_input(
+                UnsignedTxInput {
+                    prev_out: TxOutpoint {
+                        vout: vout as u32,
+                        ..Default::default()
+                    },
+                    sequence: 0xffff_ffff,
+                    value,
+                    token: None,
+                },
+                address.p2pkh_script()?,
+                P2PKHSignatory {
+                    pubkey,
+                    sig_hash_flags: SigHashFlags::DEFAULT,
+                },
+            );
+        }
+        Ok(builder)
+    }
+
+    #[test]
+    fn test_absurd_fee() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let make_builder = || -> Result<TxBuilder<'static>> {
+            let mut builder = p2pkh_builder(&[1_000_000])?;
+            builder.add_output(TxOutput {
+                value: 10_000,
+                script: address.clone().into(),
+                token: None,
+            });
+            Ok(builder)
+        };
+        match make_builder()?.build() {
+            Err(Error::AbsurdFee { fee, .. }) => assert_eq!(fee, 990_000),
+            _ => panic!("expected AbsurdFee"),
+        }
+        let mut builder = make_builder()?;
+        builder.set_max_fee_per_kb(None);
+        builder.build()?;
+        let mut builder = make_builder()?;
+        builder.add_leftover_output(address.clone().into());
+        builder.set_max_fee(Some(100));
+        assert!(matches!(builder.build(), Err(Error::AbsurdFee { .. })));
+        let mut builder = make_builder()?;
+        builder.add_leftover_output(address.clone().into());
+        builder.build()?;
+        Ok(())
+    }
+
+    fn timelock_builder(lock_value: i32, opcode: Opcode) -> TxBuilder<'static> {
+        let pubkey = Pubkey::new([2; 33]);
+        let lock_script = TaggedScript::new(vec![
+            TaggedOp::from_op(Op::PushInteger(Integer::new(lock_value).unwrap())),
+            TaggedOp::from_op(Op::Code(opcode)),
+            TaggedOp::from_op(Op::Code(Opcode::OP_DROP)),
+        ]);
+        let mut builder = TxBuilder::new(2, 0);
+        builder.add_input(
+            UnsignedTxInput {
+                prev_out: TxOutpoint::default(),
+                sequence: 0xffff_ffff,
+                value: 10_000,
+                token: None,
+            },
+            lock_script,
+            P2PKHSignatory {
+                pubkey,
+                sig_hash_flags: SigHashFlags::DEFAULT,
+            },
+        );
+        builder
+    }
+
+    #[test]
+    fn test_validate_cltv() -> Result<()> {
+        let mut builder = timelock_builder(650_000, Opcode::OP_CHECKLOCKTIMEVERIFY);
+        builder.set_lock_time(LockTime::from_block_height(650_000)?);
+        assert!(matches!(
+            builder.validate_timelocks(),
+            Err(Error::TimelockMismatch { input_idx: 0, .. })
+        ));
+        builder.set_non_final_sequence(0)?;
+        builder.validate_timelocks()?;
+        builder.set_lock_time(LockTime::from_block_height(649_999)?);
+        assert!(builder.validate_timelocks().is_err());
+        builder.set_lock_time(LockTime::from_timestamp(1_600_000_000)?);
+        assert!(builder.validate_timelocks().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_csv() -> Result<()> {
+        let timedelta = CsvTimedelta::from_duration(Duration::from_secs(24 * 60 * 60))?;
+        assert_eq!(timedelta, CsvTimedelta::Seconds512(169));
+        let mut builder =
+            timelock_builder(timedelta.sequence() as i32, Opcode::OP_CHECKSEQUENCEVERIFY);
+        builder.set_relative_lock(0, timedelta)?;
+        builder.validate_timelocks()?;
+        builder.set_relative_lock(0, CsvTimedelta::Seconds512(168))?;
+        assert!(builder.validate_timelocks().is_err());
+        builder.set_relative_lock(0, CsvTimedelta::Blockheight(200))?;
+        assert!(builder.validate_timelocks().is_err());
+        let mut builder = TxBuilder::new(1, 0);
+        assert!(matches!(
+            builder.set_relative_lock(0, timedelta),
+            Err(Error::InvalidTimelock { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sweep_output() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let make_builder = |value| -> Result<TxBuilder<'static>> {
+            let mut builder = p2pkh_builder(&[value])?;
+            builder.add_sweep_output(address.clone().into())?;
+            Ok(builder)
+        };
+        let unsigned_tx = make_builder(100_000)?.build()?;
+        let fee = unsigned_tx.estimated_size() as u64;
+        let tx = unsigned_tx.partial_tx();
+        assert_eq!(tx.outputs.len(), 1);
+        assert_eq!(tx.outputs[0].value, 100_000 - fee);
+        assert!(matches!(
+            make_builder(700)?.build(),
+            Err(Error::DustOutput { .. })
+        ));
+        let mut builder = make_builder(100_000)?;
+        assert!(matches!(
+            builder.add_sweep_output(address.clone().into()),
+            Err(Error::InvalidSweep { .. })
+        ));
+        builder.add_leftover_output(address.clone().into());
+        assert!(matches!(builder.build(), Err(Error::InvalidSweep { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_output() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let make_builder = |value| -> Result<TxBuilder<'static>> {
+            let mut builder = p2pkh_builder(&[value])?;
+            builder.add_change_output(address.clone())?;
+            builder.add_output(TxOutput {
+                value: 10_000,
+                script: address.clone().into(),
+                token: None,
+            });
+            Ok(builder)
+        };
+        let unsigned_tx = make_builder(100_000)?.build()?;
+        assert_eq!(unsigned_tx.change_output_idx(), Some(0));
+        let fee = unsigned_tx.estimated_size() as u64;
+        let tx = unsigned_tx.partial_tx();
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[0].value, 100_000 - 10_000 - fee);
+        // 149 + 34 + 10 byte tx without change, leaving 307 sats of dust
+        let unsigned_tx = make_builder(10_500)?.build()?;
+        assert!(!unsigned_tx.has_change());
+        assert_eq!(unsigned_tx.partial_tx().outputs.len(), 1);
+        let mut builder = make_builder(100_000)?;
+        assert!(matches!(
+            builder.add_change_output(address.clone()),
+            Err(Error::InvalidChangeOutput { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_weighted_leftover_outputs() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let make_builder = |weights: &[u32]| -> Result<TxBuilder<'static>> {
+            let mut builder = p2pkh_builder(&[100_000])?;
+            builder.add_output(TxOutput {
+                value: 10_000,
+                script: address.clone().into(),
+                token: None,
+            });
+            for &weight in weights {
+                builder.add_leftover_output_weighted(weight, address.clone().into());
+            }
+            Ok(builder)
+        };
+        let unsigned_tx = make_builder(&[70, 30])?.build()?;
+        assert_eq!(unsigned_tx.estimated_size(), 149 + 3 * 34 + 10);
+        let distributable = 90_000 - unsigned_tx.estimated_size() as u64;
+        let tx = unsigned_tx.partial_tx();
+        assert_eq!(tx.outputs.len(), 3);
+        assert_eq!(tx.outputs[1].value, distributable * 70 / 100);
+        assert_eq!(tx.outputs[2].value, distributable - distributable * 70 / 100);
+        // the 1/1000 share would be dust, so it is dropped and the other output takes it all
+        let unsigned_tx = make_builder(&[999, 1])?.build()?;
+        assert_eq!(unsigned_tx.estimated_size(), 149 + 2 * 34 + 10);
+        let tx = unsigned_tx.partial_tx();
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[1].value, 90_000 - (149 + 2 * 34 + 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_size_estimation() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let make_builder = || -> Result<TxBuilder<'static>> {
+            let mut builder = p2pkh_builder(&[1_000; 300])?;
+            for precedence in 0..3 {
+                builder.add_leftover_output_bounded(
+                    DUST_AMOUNT,
+                    50_000,
+                    precedence,
+                    address.clone().into(),
+                );
+            }
+            builder.add_change_output(address.clone())?;
+            Ok(builder)
+        };
+        let expected_size = make_builder()?.estimated_size();
+        let unsigned_tx = make_builder()?.build()?;
+        assert_eq!(unsigned_tx.estimated_size(), expected_size);
+        assert_eq!(expected_size, 300 * 149 + 4 * 34 + 10 + 2);
+        Ok(())
+    }
+
+    // unlocks with 20 bytes per tx output, like covenants pushing the outputs they check
+    struct OutputsSignatory;
+
+    impl Signatory for OutputsSignatory {
+        type Script = Script;
+        type Signatures = ();
+        type Kind = SignatoryKindOne;
+        fn sig_hash_flags(&self) -> SigHashFlags {
+            SigHashFlags::DEFAULT
+        }
+        fn placeholder_signatures(&self) {}
+        fn build_script(
+            &self,
+            _tx_preimage: &TxPreimage,
+            _estimated_size: Option<usize>,
+            _sigs: (),
+            _lock_script: &Script,
+            tx_outputs: &[TxOutput],
+        ) -> Script {
+            Script::from_ops(vec![Op::PushByteArray {
+                array: vec![0; 20 * tx_outputs.len()].into(),
+                is_minimal: true,
+            }])
+        }
+    }
+
+    #[test]
+    fn test_output_dependent_size_estimation() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let make_builder = || -> Result<TxBuilder<'static>> {
+            let mut builder = p2pkh_builder(&[100_000])?;
+            builder.add_input(
+                UnsignedTxInput {
+                    prev_out: TxOutpoint::default(),
+                    sequence: 0xffff_ffff,
+                    value: 10_000,
+                    token: None,
+                },
+                TaggedScript::new(vec![]),
+                OutputsSignatory,
+            );
+            builder.add_output(TxOutput {
+                value: 10_000,
+                script: address.clone().into(),
+                token: None,
+            });
+            builder.add_leftover_output(address.clone().into());
+            Ok(builder)
+        };
+        let unsigned_tx = make_builder()?.build()?;
+        let estimated_size = unsigned_tx.estimated_size();
+        let outputs = unsigned_tx.partial_tx().outputs;
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(estimated_size, make_builder()?.estimate_size(outputs));
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_tx_builder() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let mut builder = p2pkh_builder(&[10_000; 3])?;
+        let input_refs = (0..3).map(InputReference::<P2PKHSignatory>::new).collect::<Vec<_>>();
+        builder.add_output(TxOutput {
+            value: 5_000,
+            script: address.clone().into(),
+            token: None,
+        });
+        builder.add_leftover_output(address.clone().into());
+
+        let removed = builder.remove_input(1)?;
+        assert_eq!(removed.prev_out.vout, 1);
+        let vouts = builder.inputs().map(|input| input.prev_out.vout).collect::<Vec<_>>();
+        assert_eq!(vouts, vec![0, 2]);
+        let input_indices = input_refs
+            .iter()
+            .map(|input_ref| Some(input_ref.after_removal(1)?.input_idx()))
+            .collect::<Vec<_>>();
+        assert_eq!(input_indices, vec![Some(0), None, Some(1)]);
+        assert!(matches!(
+            builder.remove_input(2),
+            Err(Error::InputIndexOutOfBounds {
+                input_idx: 2,
+                num_inputs: 2,
+            })
+        ));
+        builder.set_input_sequence(1, 0)?;
+        assert_eq!(builder.input(1).unwrap().sequence, 0);
+        assert!(builder.input_lock_script(1).is_some());
+
+        let replaced = builder.replace_output(
+            0,
+            TxOutput {
+                value: 7_000,
+                script: address.clone().into(),
+                token: None,
+            },
+        )?;
+        assert_eq!(replaced.get_value(), 5_000);
+        assert_eq!(builder.outputs()[0].get_value(), 7_000);
+        assert!(!builder.outputs()[1].is_known_value());
+        assert!(matches!(
+            builder.replace_output(
+                2,
+                TxOutput {
+                    value: 0,
+                    script: Script::new(vec![]),
+                    token: None,
+                },
+            ),
+            Err(Error::OutputIndexOutOfBounds { .. })
+        ));
+
+        let tx = builder.build()?.partial_tx();
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(tx.outputs[0].value, 7_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_from_state() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let signatory = P2PKHSignatory {
+            pubkey,
+            sig_hash_flags: SigHashFlags::DEFAULT,
+        };
+        let mut builder = p2pkh_builder(&[10_000; 2])?;
+        let input_refs = (0..2).map(InputReference::<P2PKHSignatory>::new).collect::<Vec<_>>();
+        builder.add_leftover_output(address.clone().into());
+        let mut unsigned_tx = builder.build()?;
+        let preimages = unsigned_tx.preimages().to_vec();
+        unsigned_tx.sign_input(input_refs[0], ByteArray::from_slice_unnamed(&[1; 72]))?;
+
+        let json = serde_json::to_string(&unsigned_tx.to_state())?;
+        let state = serde_json::from_str::<UnsignedTxState>(&json)?;
+        assert_eq!(state, unsigned_tx.to_state());
+        let mut resumed = UnsignedTx::from_state(state.clone());
+        // the unsigned input can't be signed or rebuilt before its signatory is attached
+        assert!(matches!(
+            resumed.sign_input_dyn(1, Box::new(ByteArray::from_slice_unnamed(&[2; 72]))),
+            Err(Error::MissingSignatory { input_idx: 1 })
+        ));
+        assert!(matches!(
+            UnsignedTx::from_state(state).into_tx_builder().build(),
+            Err(Error::MissingSignatory { input_idx: 1 })
+        ));
+        assert_eq!(
+            resumed.signing_status(),
+            vec![InputSigningStatus::Signed, InputSigningStatus::Placeholder],
+        );
+        assert_eq!(resumed.estimated_size(), unsigned_tx.estimated_size());
+        let resumed_preimages = resumed.preimages().to_vec();
+        for (preimage, resumed_preimage) in preimages.iter().zip(&resumed_preimages) {
+            assert_eq!(preimage[0].ser(), resumed_preimage[0].ser());
+        }
+
+        let input_ref = resumed.attach_signatory(1, signatory.clone())?;
+        assert_eq!(
+            resumed.input_status(1),
+            Some(InputSigningStatus::MissingSignatures {
+                num_present: 0,
+                num_required: 1,
+            }),
+        );
+        assert_eq!(resumed.unsigned_inputs(), vec![1]);
+        assert!(!resumed.is_fully_signed());
+        resumed.sign_input(input_ref, ByteArray::from_slice_unnamed(&[2; 72]))?;
+        assert!(resumed.is_fully_signed());
+        assert!(resumed.unsigned_inputs().is_empty());
+        unsigned_tx.sign_input(input_refs[1], ByteArray::from_slice_unnamed(&[2; 72]))?;
+        assert_eq!(resumed.complete_tx()?, unsigned_tx.complete_tx()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_input_status() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let multisig = MultisigSignatory::new(
+            2,
+            vec![pubkey, Pubkey::new([3; 33])],
+            SigHashFlags::DEFAULT,
+        )?;
+        let mut builder = TxBuilder::new_simple();
+        let input_ref = builder.add_input(
+            UnsignedTxInput {
+                prev_out: TxOutpoint::default(),
+                sequence: 0xffff_ffff,
+                value: 10_000,
+                token: None,
+            },
+            multisig.lock_script(),
+            multisig.clone(),
+        );
+        builder.add_leftover_output(address.into());
+        let mut unsigned_tx = builder.build()?;
+        assert_eq!(
+            unsigned_tx.input_status(0),
+            Some(InputSigningStatus::MissingSignatures {
+                num_present: 0,
+                num_required: 2,
+            }),
+        );
+        assert_eq!(unsigned_tx.input_status(1), None);
+        assert_eq!(unsigned_tx.is_input_signed(0), Some(false));
+        assert_eq!(unsigned_tx.is_input_signed(1), None);
+        unsigned_tx.sign_input(input_ref, multisig.placeholder_signatures())?;
+        assert_eq!(unsigned_tx.input_status(0), Some(InputSigningStatus::Signed));
+        assert_eq!(unsigned_tx.is_input_signed(0), Some(true));
+        assert_eq!(unsigned_tx.signing_status(), vec![InputSigningStatus::Signed]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fee_report() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let mut builder = p2pkh_builder(&[10_000])?;
+        let input_ref = InputReference::<P2PKHSignatory>::new(0);
+        builder.add_leftover_output(address.clone().into());
+        let mut unsigned_tx = builder.build()?;
+        let report = unsigned_tx.fee_report();
+        assert_eq!(report.input_sizes, vec![149]);
+        assert_eq!(report.output_sizes, vec![34]);
+        assert_eq!(report.overhead_size, 10);
+        assert_eq!(report.estimated_size, 149 + 34 + 10);
+        assert_eq!(report.actual_size, None);
+        assert_eq!(report.fee, 193);
+        assert_eq!(report.fee_per_byte, 1.0);
+
+        unsigned_tx.sign_input(input_ref, ByteArray::from_slice_unnamed(&[1; 71]))?;
+        let report = unsigned_tx.fee_report();
+        assert_eq!(report.input_sizes, vec![148]);
+        assert_eq!(report.actual_size, Some(192));
+        assert_eq!(unsigned_tx.complete_tx()?.ser().len(), 192);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_time_with_final_sequences() -> Result<()> {
+        let mut builder = timelock_builder(0, Opcode::OP_NOP);
+        builder.set_lock_time(LockTime::from_block_height(650_000)?);
+        assert!(matches!(
+            builder.validate_timelocks(),
+            Err(Error::InvalidTimelock { .. })
+        ));
+        builder.set_non_final_sequence(0)?;
+        builder.validate_timelocks()?;
+        builder.set_final_sequence(0)?;
+        builder.set_lock_time(0u32);
+        builder.validate_timelocks()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_paired_single_inputs() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let signatory = |sig_hash_flags| P2PKHSignatory {
+            pubkey,
+            sig_hash_flags,
+        };
+        let input = |vout| UnsignedTxInput {
+            prev_out: TxOutpoint {
+                vout,
+                ..Default::default()
+            },
+            sequence: 0xffff_ffff,
+            value: 10_000,
+            token: None,
+        };
+        let output = |value| TxOutput {
+            value,
+            script: address.clone().into(),
+            token: None,
+        };
+        let mut builder = TxBuilder::new_simple();
+        let flags = SigHashFlags::SINGLE_ANYONECANPAY;
+        for vout in 0..2 {
+            builder.add_paired_input(
+                input(vout),
+                address.p2pkh_script()?,
+                signatory(flags),
+                output(5_000 + vout as u64),
+            )?;
+        }
+        assert!(matches!(
+            builder.add_paired_input(
+                input(2),
+                address.p2pkh_script()?,
+                signatory(SigHashFlags::DEFAULT),
+                output(5_000),
+            ),
+            Err(Error::InvalidSigHashFlags { .. })
+        ));
+        builder.add_leftover_output(address.clone().into());
+        assert!(matches!(
+            builder.add_paired_input(
+                input(2),
+                address.p2pkh_script()?,
+                signatory(flags),
+                output(5_000),
+            ),
+            Err(Error::UnpairedSingleInput { input_idx: 2 })
+        ));
+        let unsigned_tx = builder.build()?;
+        for (input_idx, preimages) in unsigned_tx.preimages().iter().enumerate() {
+            let preimage = &preimages[0];
+            assert_eq!(preimage.sig_hash_type, flags.bits());
+            assert_eq!(preimage.hash_prevouts.as_slice(), &[0; 32]);
+            assert_eq!(
+                preimage.hash_outputs,
+                Sha256d::digest(output(5_000 + input_idx as u64).ser()),
+            );
+        }
+
+        let mut builder = TxBuilder::new_simple();
+        builder.add_input(input(0), address.p2pkh_script()?, signatory(flags));
+        assert!(matches!(
+            builder.build_without_estimation(),
+            Err(Error::UnpairedSingleInput { input_idx: 0 })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_preimages() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let mut builder = p2pkh_builder(&[10_000])?;
+        let input_ref = InputReference::<P2PKHSignatory>::new(0);
+        builder.add_leftover_output(address.clone().into());
+        let unsigned_tx = builder.build()?;
+        let generation = unsigned_tx.generation();
+        assert_eq!(generation, 0);
+
+        let unsigned_tx = unsigned_tx.into_tx_builder().build()?;
+        assert_eq!(unsigned_tx.generation(), generation);
+
+        let mut builder = unsigned_tx.into_tx_builder();
+        builder.add_output(TxOutput {
+            value: 1_000,
+            script: address.clone().into(),
+            token: None,
+        });
+        let mut unsigned_tx = builder.build()?;
+        assert_eq!(unsigned_tx.generation(), 1);
+        let sig = ByteArray::from_slice_unnamed(&[1; 71]);
+        assert!(matches!(
+            unsigned_tx.sign_input_checked(generation, input_ref, sig.clone()),
+            Err(Error::StalePreimages {
+                generation: 0,
+                current_generation: 1,
+            })
+        ));
+        assert!(matches!(
+            unsigned_tx.sign_input(input_ref, sig.clone()),
+            Err(Error::StalePreimages { .. })
+        ));
+        unsigned_tx.sign_input_checked(1, input_ref, sig)?;
+
+        // signatures carried over from a saved state can't survive new preimages
+        let resumed = UnsignedTx::from_state(unsigned_tx.to_state());
+        let mut builder = resumed.into_tx_builder();
+        builder.add_output(TxOutput {
+            value: 1_000,
+            script: address.clone().into(),
+            token: None,
+        });
+        assert!(matches!(
+            builder.build_without_estimation(),
+            Err(Error::StalePreimages {
+                generation: 1,
+                current_generation: 2,
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_signing_requests() -> Result<()> {
+        let address = Address::from_pk(Prefix::default(), &Pubkey::new([2; 33]));
+        let mut builder = p2pkh_builder(&[10_000, 10_001])?;
+        let input_refs = (0..2).map(InputReference::<P2PKHSignatory>::new).collect::<Vec<_>>();
+        builder.set_derivation_hint(1, "m/44'/145'/0'/0/1")?;
+        builder.add_leftover_output(address.clone().into());
+        let mut unsigned_tx = builder.build()?;
+        assert_eq!(unsigned_tx.export_signing_requests().len(), 2);
+        unsigned_tx.sign_input(input_refs[0], ByteArray::from_slice_unnamed(&[1; 72]))?;
+
+        let requests = unsigned_tx.export_signing_requests();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!((request.input_idx, request.sig_idx), (1, 0));
+        assert_eq!(request.sig_hash, Sha256d::digest(unsigned_tx.preimages()[1][0].ser()));
+        assert_eq!(request.sig_hash_flags, SigHashFlags::DEFAULT);
+        assert_eq!(request.value, 10_001);
+        assert_eq!(request.derivation_hint.as_deref(), Some("m/44'/145'/0'/0/1"));
+        let json = serde_json::to_string(&requests)?;
+        assert_eq!(serde_json::from_str::<Vec<SigningRequest>>(&json)?, requests);
+        let resumed = UnsignedTx::from_state(unsigned_tx.to_state());
+        assert_eq!(resumed.export_signing_requests(), requests);
+        Ok(())
+    }
+
+    #[test]
+    fn test_coinjoin() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let make_party = |tx_hash: [u8; 32], value: u64| -> Result<_> {
+            let mut builder = TxBuilder::new(2, 0);
+            let input_ref = builder.add_input(
+                UnsignedTxInput {
+                    prev_out: TxOutpoint {
+                        tx_hash: Sha256d::new(tx_hash),
+                        vout: 0,
+                    },
+                    sequence: 0xffff_ffff,
+                    value,
+                    token: None,
+                },
+                address.p2pkh_script()?,
+                P2PKHSignatory {
+                    pubkey,
+                    sig_hash_flags: SigHashFlags::DEFAULT,
+                },
+            );
+            builder.add_output(TxOutput {
+                value: 10_000,
+                script: address.clone().into(),
+                token: None,
+            });
+            builder.add_change_output(address.clone())?;
+            Ok((builder, input_ref))
+        };
+        let (party_a, input_ref_a) = make_party([2; 32], 50_000)?;
+        let (party_b, input_ref_b) = make_party([1; 32], 20_000)?;
+        let mut coinjoin = TxBuilder::coinjoin(vec![party_a, party_b], DEFAULT_FEE_RATE)?;
+        // 149 byte input, two 34 byte outputs and half of the 10 byte overhead
+        assert_eq!(coinjoin.party_fee(0)?, 149 + 2 * 34 + 5);
+        assert_eq!(coinjoin.party_fee(1)?, 149 + 2 * 34 + 5);
+        assert_eq!(coinjoin.party_input_indices(0)?, &[1]);
+        assert_eq!(coinjoin.party_input_indices(1)?, &[0]);
+        assert!(coinjoin.party_fee(2).is_err());
+        assert!(coinjoin.party_input_indices(2).is_err());
+        assert!(coinjoin
+            .input_ref(0, InputReference::<P2PKHSignatory>::new(1))
+            .is_err());
+        let input_ref_a = coinjoin.input_ref(0, input_ref_a)?;
+        let input_ref_b = coinjoin.input_ref(1, input_ref_b)?;
+        let sig = ByteArray::from_slice_unnamed(&[1; 71]);
+        coinjoin.unsigned_tx.sign_input(input_ref_a, sig.clone())?;
+        coinjoin.unsigned_tx.sign_input(input_ref_b, sig)?;
+        let tx = coinjoin.unsigned_tx.complete_tx()?;
+        assert_eq!(tx.inputs[1].prev_out.tx_hash, Sha256d::new([2; 32]));
+        let values = tx.outputs.iter().map(|output| output.value).collect::<Vec<_>>();
+        assert_eq!(values, vec![9_778, 10_000, 10_000, 39_778]);
+        assert_eq!(tx.fee(), Some(2 * (149 + 2 * 34 + 5)));
+
+        let (party_a, _) = make_party([2; 32], 50_000)?;
+        let (party_b, _) = make_party([1; 32], 10_100)?;
+        assert!(matches!(
+            TxBuilder::coinjoin(vec![party_a, party_b], DEFAULT_FEE_RATE),
+            Err(Error::InsufficientInputAmount { .. })
+        ));
+
+        let (party_a, _) = make_party([2; 32], 50_000)?;
+        let (party_b, _) = make_party([2; 32], 20_000)?;
+        assert!(matches!(
+            TxBuilder::coinjoin(vec![party_a, party_b], DEFAULT_FEE_RATE),
+            Err(Error::InvalidCoinJoin { reason: "parties spend the same output" })
+        ));
+
+        // each party's fee limits still apply
+        let (party_a, _) = make_party([2; 32], 50_000)?;
+        let (mut party_b, _) = make_party([1; 32], 20_000)?;
+        party_b.set_max_fee(Some(100));
+        assert!(matches!(
+            TxBuilder::coinjoin(vec![party_a, party_b], DEFAULT_FEE_RATE),
+            Err(Error::AbsurdFee { fee: 222, .. })
+        ));
+
+        // a party with a CPFP parent pays for the parent as well
+        let (mut party_a, _) = make_party([2; 32], 50_000)?;
+        let (party_b, _) = make_party([1; 32], 20_000)?;
+        party_a.add_cpfp_parent(CpfpParent { size: 200, fee: 0 });
+        let coinjoin = TxBuilder::coinjoin(vec![party_a, party_b], DEFAULT_FEE_RATE)?;
+        assert_eq!(coinjoin.party_fee(0)?, 200 + 149 + 2 * 34 + 5);
+        assert_eq!(coinjoin.party_fee(1)?, 149 + 2 * 34 + 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_tx_missing_signatures() -> Result<()> {
+        let unsigned_tx = timelock_builder(0, Opcode::OP_NOP).build_without_estimation()?;
+        match unsigned_tx.complete_tx() {
+            Err(Error::MissingSignatures { input_indices }) => assert_eq!(input_indices, vec![0]),
+            _ => panic!("expected MissingSignatures"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_address_output_tokens() -> Result<()> {
+        let hash = Hash160::new([1; 20]);
+        let address = Address::from_hash(Prefix::default(), AddressType::P2PKH, hash.clone());
+        let token_address =
+            Address::from_hash(Prefix::default(), AddressType::P2PKHWithTokens, hash);
+        let category = TokenCategory::from_hash(Sha256d::new([2; 32]));
+        let token = TokenData::fungible(category, 10);
+        let mut builder = TxBuilder::new_simple();
+        builder.add_address_output(&address, 1_000, None)?;
+        assert!(matches!(
+            builder.add_address_output(&address, 1_000, Some(token.clone())),
+            Err(Error::AddressNotTokenAware { .. })
+        ));
+        builder.add_address_output(&token_address, 1_000, Some(token.clone()))?;
+        builder.add_address_output_unchecked(&address, 1_000, Some(token));
+        assert_eq!(builder.outputs().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_output_fee() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let category = TokenCategory::from_hash(Sha256d::new([2; 32]));
+        let token = TokenData::fungible(category, 10);
+        let build = |token: Option<TokenData>| -> Result<_> {
+            let mut builder = TxBuilder::new_simple();
+            builder.add_input(
+                UnsignedTxInput {
+                    prev_out: TxOutpoint::default(),
+                    sequence: 0xffff_ffff,
+                    value: 10_000,
+                    token: token.clone(),
+                },
+                address.p2pkh_script()?,
+                P2PKHSignatory {
+                    pubkey,
+                    sig_hash_flags: SigHashFlags::DEFAULT,
+                },
+            );
+            builder.add_output(TxOutput {
+                value: 1_000,
+                script: address.p2pkh_script()?.into(),
+                token,
+            });
+            builder.add_leftover_output(address.clone().into());
+            builder.build()
+        };
+        let plain_tx = build(None)?;
+        let token_tx = build(Some(token.clone()))?;
+        // the token prefix is part of the output, the input's token only of its preimage
+        assert_eq!(
+            token_tx.estimated_size(),
+            plain_tx.estimated_size() + token.encoded_size(),
+        );
+        assert_eq!(
+            token_tx.fee_report().fee,
+            plain_tx.fee_report().fee + token.encoded_size() as u64,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_balance() -> Result<()> {
+        let pubkey = Pubkey::new([2; 33]);
+        let address = Address::from_pk(Prefix::default(), &pubkey);
+        let category = TokenCategory::from_hash(Sha256d::new([2; 32]));
+        let build = |amount: u64| -> Result<_> {
+            let mut builder = TxBuilder::new_simple();
+            builder.add_input(
+                UnsignedTxInput {
+                    prev_out: TxOutpoint::default(),
+                    sequence: 0xffff_ffff,
+                    value: 10_000,
+                    token: Some(TokenData::fungible(category.clone(), 10)),
+                },
+                address.p2pkh_script()?,
+                P2PKHSignatory {
+                    pubkey,
+                    sig_hash_flags: SigHashFlags::DEFAULT,
+                },
+            );
+            builder.add_token_output(
+                address.clone().into(),
+                TokenData::fungible(category.clone(), amount),
+            );
+            builder.add_leftover_output(address.clone().into());
+            builder.build()
+        };
+        build(10)?;
+        // fungible tokens may be burned, but never created
+        build(4)?;
+        assert!(matches!(build(11), Err(Error::TokenRuleViolation { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_output_dust_limit() -> Result<()> {
+        let hash = Hash160::new([1; 20]);
+        let address = Address::from_hash(Prefix::default(), AddressType::P2PKH, hash);
+        let script: Script = address.p2pkh_script()?.into();
+        let category = TokenCategory::from_hash(Sha256d::new([2; 32]));
+        let token = TokenData::fungible(category, 10);
+        let plain_output = TxOutput {
+            value: DUST_AMOUNT,
+            script: script.clone(),
+            token: None,
+        };
+        assert_eq!(plain_output.dust_limit(), DUST_AMOUNT);
+
+        // 35 byte token prefix on top of the P2PKH output
+        let mut builder = TxBuilder::new_simple();
+        builder.add_token_output(script.clone(), token.clone());
+        assert_eq!(builder.known_output_sum(), 3 * (69 + 148));
+
+        let mut builder = TxBuilder::new_simple();
+        builder.add_output(TxOutput {
+            value: 600,
+            script,
+            token: Some(token.clone()),
+        });
+        assert!(matches!(
+            builder.validate_tokens(),
+            Err(Error::DustOutput {
+                value: 600,
+                dust_limit: 651,
+            })
+        ));
+
+        let mut builder = TxBuilder::new_simple();
+        builder.add_output(TxOutput {
+            value: 1_000,
+            script: Script::from_ops(vec![Op::Code(Opcode::OP_RETURN)]),
+            token: Some(token),
+        });
+        assert!(matches!(
+            builder.validate_tokens(),
+            Err(Error::NonStandardTokenOutput { .. })
+        ));
+        Ok(())
+    }
+}
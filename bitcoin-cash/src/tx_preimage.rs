@@ -1,6 +1,6 @@
 use crate::{
-    BitcoinByteArray, BitcoinCode, BitcoinDataType, ByteArray, DataType, Hashed, Op, Script,
-    Sha256d, ToPreimages, TxOutpoint,
+    token_prefix_code, BitcoinByteArray, BitcoinCode, BitcoinDataType, ByteArray, DataType, Hashed,
+    Op, Script, Sha256d, ToPreimages, TokenData, TxOutpoint,
 };
 use serde::{Deserialize, Serialize};
 use bitflags::bitflags;
@@ -15,6 +15,8 @@ bitflags! {
         const ANYONECANPAY = 0x80;
         const MASK         = 0x1f;
         const DEFAULT      = Self::ALL.bits | Self::FORKID.bits;
+        const SINGLE_ANYONECANPAY =
+            Self::SINGLE.bits | Self::FORKID.bits | Self::ANYONECANPAY.bits;
     }
 }
 
@@ -25,6 +27,9 @@ pub struct TxPreimage {
     pub hash_prevouts: Sha256d,
     pub hash_sequence: Sha256d,
     pub outpoint: TxOutpoint,
+    // the tokens of the spent output, signed since the 2023 upgrade
+    #[bitcoin_code(with = "token_prefix_code")]
+    pub token_prefix: Option<TokenData>,
     pub script_code: Script,
     pub value: u64,
     pub sequence: u32,
@@ -39,82 +44,186 @@ impl SigHashFlags {
         sig_hash_flags.bits = flags as u32;
         sig_hash_flags
     }
+
+    pub fn is_single(self) -> bool {
+        self & SigHashFlags::MASK == SigHashFlags::SINGLE
+    }
+
+    pub fn is_anyone_can_pay(self) -> bool {
+        self.contains(SigHashFlags::ANYONECANPAY)
+    }
 }
 
-impl TxPreimage {
-    pub fn build_preimages(tx: &impl ToPreimages) -> Vec<Vec<TxPreimage>> {
-        let hash_all_prevouts = {
-            let mut outpoints_serialized = ByteArray::from_slice_unnamed(&[]);
-            for input_idx in 0..tx.num_inputs() {
-                outpoints_serialized = outpoints_serialized.concat(
-                    tx.input_outpoint_at(input_idx)
-                        .ser()
-                        .named(format!("outpoint_{}", input_idx)),
-                );
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TxPreimagePart {
+    Version,
+    HashPrevouts,
+    HashSequence,
+    Outpoint,
+    TokenPrefix,
+    ScriptCode,
+    Value,
+    Sequence,
+    HashOutputs,
+    LockTime,
+    SigHashType,
+}
+
+// the serialized fields of a preimage, in order, for covenants that OP_SPLIT the preimage
+#[derive(Clone, Debug)]
+pub struct TxPreimageParts {
+    pub version: ByteArray,
+    pub hash_prevouts: ByteArray,
+    pub hash_sequence: ByteArray,
+    pub outpoint: ByteArray,
+    pub token_prefix: ByteArray,
+    pub script_code: ByteArray,
+    pub value: ByteArray,
+    pub sequence: ByteArray,
+    pub hash_outputs: ByteArray,
+    pub lock_time: ByteArray,
+    pub sig_hash_type: ByteArray,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SigHashMidstate {
+    pub hash_prevouts: Option<Sha256d>,
+    pub hash_sequence: Option<Sha256d>,
+    pub hash_outputs: Option<Sha256d>,
+}
+
+impl SigHashMidstate {
+    // only computes the hashes required by the sig hash flags used in the tx
+    pub fn new(tx: &impl ToPreimages) -> Self {
+        SigHashMidstate::default().complete(tx)
+    }
+
+    // computes the hashes required by the tx that are still missing
+    fn complete(&self, tx: &impl ToPreimages) -> Self {
+        let mut needs_prevouts = false;
+        let mut needs_sequence = false;
+        let mut needs_outputs = false;
+        for input_idx in 0..tx.num_inputs() {
+            for &sig_hash_flags in tx.input_sig_hash_flags_at(input_idx) {
+                let masked_flags = sig_hash_flags & SigHashFlags::MASK;
+                let is_single_or_none =
+                    masked_flags == SigHashFlags::SINGLE || masked_flags == SigHashFlags::NONE;
+                let anyone_can_pay = sig_hash_flags.contains(SigHashFlags::ANYONECANPAY);
+                needs_prevouts |= !anyone_can_pay;
+                needs_sequence |= !anyone_can_pay && !is_single_or_none;
+                needs_outputs |= !is_single_or_none;
             }
-            Sha256d::digest(outpoints_serialized.named("prevouts")).named("hashPrevouts")
+        }
+        let hash_prevouts = match &self.hash_prevouts {
+            Some(hash_prevouts) => Some(hash_prevouts.clone()),
+            None if needs_prevouts => Some(hash_all_prevouts(tx)),
+            None => None,
         };
-        let hash_all_sequences = {
-            let mut sequences_serialized = ByteArray::from_slice_unnamed(&[]);
-            for input_idx in 0..tx.num_inputs() {
-                sequences_serialized = sequences_serialized.concat(
-                    tx.input_sequence_at(input_idx)
-                        .ser()
-                        .named(format!("sequence_{}", input_idx)),
-                );
-            }
-            Sha256d::digest(sequences_serialized.named("sequences")).named("hashSequence")
+        let hash_sequence = match &self.hash_sequence {
+            Some(hash_sequence) => Some(hash_sequence.clone()),
+            None if needs_sequence => Some(hash_all_sequences(tx)),
+            None => None,
         };
-
-        let hash_all_outputs = {
-            let mut outputs_serialized = ByteArray::from_slice_unnamed(&[]);
-            for output_idx in 0..tx.num_outputs() {
-                let byte_array = tx
-                    .output_at(output_idx)
-                    .ser()
-                    .named(format!("output_{}", output_idx));
-                outputs_serialized = outputs_serialized.concat(byte_array);
-            }
-            Sha256d::digest(outputs_serialized.named("outputs")).named("hashOutputs")
+        let hash_outputs = match &self.hash_outputs {
+            Some(hash_outputs) => Some(hash_outputs.clone()),
+            None if needs_outputs => Some(hash_all_outputs(tx)),
+            None => None,
         };
+        SigHashMidstate {
+            hash_prevouts,
+            hash_sequence,
+            hash_outputs,
+        }
+    }
+}
+
+fn hash_all_prevouts(tx: &impl ToPreimages) -> Sha256d {
+    let mut outpoints_serialized = ByteArray::from_slice_unnamed(&[]);
+    for input_idx in 0..tx.num_inputs() {
+        outpoints_serialized = outpoints_serialized.concat(
+            tx.input_outpoint_at(input_idx)
+                .ser()
+                .named(format!("outpoint_{}", input_idx)),
+        );
+    }
+    Sha256d::digest(outpoints_serialized.named("prevouts")).named("hashPrevouts")
+}
+
+fn hash_all_sequences(tx: &impl ToPreimages) -> Sha256d {
+    let mut sequences_serialized = ByteArray::from_slice_unnamed(&[]);
+    for input_idx in 0..tx.num_inputs() {
+        sequences_serialized = sequences_serialized.concat(
+            tx.input_sequence_at(input_idx)
+                .ser()
+                .named(format!("sequence_{}", input_idx)),
+        );
+    }
+    Sha256d::digest(sequences_serialized.named("sequences")).named("hashSequence")
+}
+
+fn hash_all_outputs(tx: &impl ToPreimages) -> Sha256d {
+    let mut outputs_serialized = ByteArray::from_slice_unnamed(&[]);
+    for output_idx in 0..tx.num_outputs() {
+        let byte_array = tx
+            .output_at(output_idx)
+            .ser()
+            .named(format!("output_{}", output_idx));
+        outputs_serialized = outputs_serialized.concat(byte_array);
+    }
+    Sha256d::digest(outputs_serialized.named("outputs")).named("hashOutputs")
+}
+
+impl TxPreimage {
+    pub fn build_preimages(tx: &impl ToPreimages) -> Vec<Vec<TxPreimage>> {
+        Self::build_preimages_with_midstate(tx, &SigHashMidstate::new(tx))
+    }
+
+    // hashes missing from `midstate` are computed once for the whole tx
+    pub fn build_preimages_with_midstate(
+        tx: &impl ToPreimages,
+        midstate: &SigHashMidstate,
+    ) -> Vec<Vec<TxPreimage>> {
+        let midstate = midstate.complete(tx);
+        let zero_hash = |name| Sha256d::new([0; 32]).named(name);
         let mut inputs_preimages = Vec::with_capacity(tx.num_inputs());
         for input_idx in 0..tx.num_inputs() {
             let sig_hash_flags = tx.input_sig_hash_flags_at(input_idx);
             let mut preimages = Vec::with_capacity(sig_hash_flags.len());
             for &sig_hash_flags in sig_hash_flags {
                 let hash_prevouts = if !sig_hash_flags.contains(SigHashFlags::ANYONECANPAY) {
-                    hash_all_prevouts.clone()
+                    midstate.hash_prevouts.clone().expect("completed midstate")
                 } else {
-                    Sha256d::new([0; 32]).named("hashPrevouts")
+                    zero_hash("hashPrevouts")
                 };
                 let masked_flags = sig_hash_flags & SigHashFlags::MASK;
                 let hash_sequence = if !sig_hash_flags.contains(SigHashFlags::ANYONECANPAY)
                     && masked_flags != SigHashFlags::SINGLE
                     && masked_flags != SigHashFlags::NONE
                 {
-                    hash_all_sequences.clone()
+                    midstate.hash_sequence.clone().expect("completed midstate")
                 } else {
-                    Sha256d::new([0; 32]).named("hashSequence")
+                    zero_hash("hashSequence")
                 };
                 let hash_outputs =
                     if masked_flags != SigHashFlags::SINGLE && masked_flags != SigHashFlags::NONE {
-                        hash_all_outputs.clone()
+                        midstate.hash_outputs.clone().expect("completed midstate")
                     } else if masked_flags == SigHashFlags::SINGLE && input_idx < tx.num_outputs() {
                         Sha256d::digest(tx.output_at(input_idx).ser())
                     } else {
-                        Sha256d::new([0; 32]).named("hashOutputs")
+                        zero_hash("hashOutputs")
                     };
                 preimages.push(TxPreimage {
                     version: tx.version(),
                     hash_prevouts,
                     hash_sequence,
                     outpoint: tx.input_outpoint_at(input_idx).clone(),
+                    token_prefix: tx.input_token_at(input_idx).cloned(),
                     script_code: tx.input_lock_script_at(input_idx).to_script_code_first(),
                     value: tx.input_value_at(input_idx),
                     sequence: tx.input_sequence_at(input_idx),
                     hash_outputs,
                     lock_time: tx.lock_time(),
-                    sig_hash_type: sig_hash_flags.bits(),
+                    sig_hash_type: sig_hash_flags.bits() | tx.fork_id() << 8,
                 });
             }
             inputs_preimages.push(preimages);
@@ -122,6 +231,22 @@ impl TxPreimage {
         inputs_preimages
     }
 
+    pub fn parts(&self) -> TxPreimageParts {
+        TxPreimageParts {
+            version: self.version.ser().named("version"),
+            hash_prevouts: self.hash_prevouts.ser().named("hashPrevouts"),
+            hash_sequence: self.hash_sequence.ser().named("hashSequence"),
+            outpoint: self.outpoint.ser().named("outpoint"),
+            token_prefix: token_prefix_code::ser(&self.token_prefix).named("tokenPrefix"),
+            script_code: self.script_code.ser().named("scriptCode"),
+            value: self.value.ser().named("value"),
+            sequence: self.sequence.ser().named("sequence"),
+            hash_outputs: self.hash_outputs.ser().named("hashOutputs"),
+            lock_time: self.lock_time.ser().named("lockTime"),
+            sig_hash_type: self.sig_hash_type.ser().named("sigHashType"),
+        }
+    }
+
     pub fn empty_with_script(script_code: &Script) -> TxPreimage {
         TxPreimage {
             version: 0,
@@ -131,6 +256,7 @@ impl TxPreimage {
                 tx_hash: Sha256d::new([0; 32]),
                 vout: 0,
             },
+            token_prefix: None,
             script_code: script_code.to_script_code_first(),
             value: 0,
             sequence: 0,
@@ -141,6 +267,71 @@ impl TxPreimage {
     }
 }
 
+impl TxPreimagePart {
+    pub const ALL: [TxPreimagePart; 11] = [
+        TxPreimagePart::Version,
+        TxPreimagePart::HashPrevouts,
+        TxPreimagePart::HashSequence,
+        TxPreimagePart::Outpoint,
+        TxPreimagePart::TokenPrefix,
+        TxPreimagePart::ScriptCode,
+        TxPreimagePart::Value,
+        TxPreimagePart::Sequence,
+        TxPreimagePart::HashOutputs,
+        TxPreimagePart::LockTime,
+        TxPreimagePart::SigHashType,
+    ];
+}
+
+impl TxPreimageParts {
+    pub fn get(&self, part: TxPreimagePart) -> &ByteArray {
+        match part {
+            TxPreimagePart::Version => &self.version,
+            TxPreimagePart::HashPrevouts => &self.hash_prevouts,
+            TxPreimagePart::HashSequence => &self.hash_sequence,
+            TxPreimagePart::Outpoint => &self.outpoint,
+            TxPreimagePart::TokenPrefix => &self.token_prefix,
+            TxPreimagePart::ScriptCode => &self.script_code,
+            TxPreimagePart::Value => &self.value,
+            TxPreimagePart::Sequence => &self.sequence,
+            TxPreimagePart::HashOutputs => &self.hash_outputs,
+            TxPreimagePart::LockTime => &self.lock_time,
+            TxPreimagePart::SigHashType => &self.sig_hash_type,
+        }
+    }
+
+    // byte offset of the part within the serialized preimage
+    pub fn offset(&self, part: TxPreimagePart) -> usize {
+        TxPreimagePart::ALL
+            .iter()
+            .take_while(|&&other| other != part)
+            .map(|&other| self.get(other).len())
+            .sum()
+    }
+
+    // offset of the part counted from the end of the preimage, useful because the
+    // scriptCode before it has a variable length
+    pub fn offset_from_end(&self, part: TxPreimagePart) -> usize {
+        self.len() - self.offset(part)
+    }
+
+    pub fn len(&self) -> usize {
+        TxPreimagePart::ALL.iter().map(|&part| self.get(part).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn concat(&self) -> ByteArray {
+        TxPreimagePart::ALL
+            .iter()
+            .fold(ByteArray::from_slice_unnamed(&[]), |preimage, &part| {
+                preimage.concat(self.get(part).clone())
+            })
+    }
+}
+
 impl BitcoinDataType for TxPreimage {
     type Type = BitcoinByteArray;
     fn to_data(&self) -> Self::Type {
@@ -153,3 +344,137 @@ impl BitcoinDataType for TxPreimage {
         DataType::ByteArray(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SigHashFlags, SigHashMidstate, TxPreimage, TxPreimagePart};
+    use crate::error::Result;
+    use crate::{
+        BitcoinCode, Op, Opcode, Script, Sha256d, ToPreimages, TokenCategory, TokenData,
+        TxOutpoint, TxOutput,
+    };
+
+    // a tx whose inputs each sign with their own flags
+    struct TestTx {
+        outpoints: Vec<TxOutpoint>,
+        sig_hash_flags: Vec<Vec<SigHashFlags>>,
+        outputs: Vec<TxOutput>,
+    }
+
+    impl ToPreimages for TestTx {
+        fn version(&self) -> i32 {
+            2
+        }
+        fn num_inputs(&self) -> usize {
+            self.outpoints.len()
+        }
+        fn input_outpoint_at(&self, input_idx: usize) -> &TxOutpoint {
+            &self.outpoints[input_idx]
+        }
+        fn input_sequence_at(&self, input_idx: usize) -> u32 {
+            input_idx as u32
+        }
+        fn input_sig_hash_flags_at(&self, input_idx: usize) -> &[SigHashFlags] {
+            &self.sig_hash_flags[input_idx]
+        }
+        fn input_value_at(&self, input_idx: usize) -> u64 {
+            1_000 * (input_idx as u64 + 1)
+        }
+        fn input_lock_script_at(&self, _input_idx: usize) -> Script {
+            Script::from_ops(vec![Op::Code(Opcode::OP_1)])
+        }
+        fn num_outputs(&self) -> usize {
+            self.outputs.len()
+        }
+        fn output_at(&self, output_idx: usize) -> &TxOutput {
+            &self.outputs[output_idx]
+        }
+        fn lock_time(&self) -> u32 {
+            0
+        }
+    }
+
+    #[test]
+    fn test_build_preimages_with_midstate() {
+        let tx = TestTx {
+            outpoints: (0..3)
+                .map(|vout| TxOutpoint {
+                    tx_hash: Sha256d::new([1; 32]),
+                    vout,
+                })
+                .collect(),
+            sig_hash_flags: vec![
+                vec![SigHashFlags::DEFAULT],
+                vec![SigHashFlags::SINGLE_ANYONECANPAY],
+                vec![SigHashFlags::NONE | SigHashFlags::FORKID, SigHashFlags::DEFAULT],
+            ],
+            outputs: vec![
+                TxOutput {
+                    value: 500,
+                    script: Script::from_ops(vec![Op::Code(Opcode::OP_2)]),
+                    token: None,
+                };
+                2
+            ],
+        };
+        let preimages = TxPreimage::build_preimages(&tx);
+        let full_midstate = SigHashMidstate::new(&tx);
+        let partial_midstate = SigHashMidstate {
+            hash_outputs: full_midstate.hash_outputs.clone(),
+            ..Default::default()
+        };
+        for midstate in &[SigHashMidstate::default(), partial_midstate, full_midstate] {
+            let midstate_preimages = TxPreimage::build_preimages_with_midstate(&tx, midstate);
+            assert_eq!(midstate_preimages.len(), preimages.len());
+            for (expected, actual) in preimages.iter().zip(&midstate_preimages) {
+                assert_eq!(expected.ser().as_slice(), actual.ser().as_slice());
+            }
+        }
+        // given hashes are used as they are
+        let midstate = SigHashMidstate {
+            hash_prevouts: Some(Sha256d::new([9; 32])),
+            ..Default::default()
+        };
+        let midstate_preimages = TxPreimage::build_preimages_with_midstate(&tx, &midstate);
+        assert_eq!(midstate_preimages[0][0].hash_prevouts, Sha256d::new([9; 32]));
+        assert_eq!(midstate_preimages[0][0].hash_outputs, preimages[0][0].hash_outputs);
+    }
+
+    #[test]
+    fn test_preimage_parts() {
+        let script = Script::from_ops(vec![Op::Code(Opcode::OP_1); 3]);
+        let preimage = TxPreimage::empty_with_script(&script);
+        let parts = preimage.parts();
+        assert_eq!(parts.concat().as_slice(), preimage.ser().as_slice());
+        assert_eq!(parts.len(), preimage.ser().len());
+        assert_eq!(parts.offset(TxPreimagePart::Version), 0);
+        assert_eq!(parts.offset(TxPreimagePart::HashPrevouts), 4);
+        assert_eq!(parts.offset(TxPreimagePart::ScriptCode), 4 + 32 + 32 + 36);
+        assert_eq!(parts.offset(TxPreimagePart::Value), 104 + 1 + 3);
+        assert_eq!(parts.offset_from_end(TxPreimagePart::HashOutputs), 32 + 4 + 4);
+        assert_eq!(parts.get(TxPreimagePart::ScriptCode).name(), Some("scriptCode"));
+    }
+
+    #[test]
+    fn test_preimage_token_prefix() -> Result<()> {
+        let script = Script::from_ops(vec![Op::Code(Opcode::OP_1)]);
+        let mut preimage = TxPreimage::empty_with_script(&script);
+        let category = TokenCategory::from_hash(Sha256d::new([3; 32]));
+        let token = TokenData::fungible(category, 10);
+        preimage.token_prefix = Some(token.clone());
+        let parts = preimage.parts();
+        assert_eq!(parts.concat().as_slice(), preimage.ser().as_slice());
+        assert_eq!(parts.get(TxPreimagePart::TokenPrefix).as_slice(), token.ser().as_slice());
+        assert_eq!(parts.offset(TxPreimagePart::ScriptCode), 104 + token.encoded_size());
+        let deser = TxPreimage::deser(preimage.ser())?;
+        assert_eq!(deser.token_prefix, Some(token));
+
+        let preimages = vec![preimage.clone(), TxPreimage::empty_with_script(&script)];
+        let mut reader = std::io::Cursor::new(preimages.ser().to_vec());
+        let decoded = Vec::<TxPreimage>::decode_from(&mut reader)?;
+        assert_eq!(decoded.ser().as_slice(), preimages.ser().as_slice());
+        assert_eq!(decoded[0].token_prefix, preimage.token_prefix);
+        assert_eq!(decoded[1].token_prefix, None);
+        Ok(())
+    }
+}
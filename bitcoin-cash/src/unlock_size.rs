@@ -0,0 +1,105 @@
+use crate::{encoding_utils::encode_var_int, MAX_SIGNATURE_SIZE};
+
+pub const SCHNORR_SIGNATURE_SIZE: usize = 64;
+pub const COMPRESSED_PUBKEY_SIZE: usize = 33;
+const OUTPOINT_SIZE: usize = 36;
+const SEQUENCE_SIZE: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnlockScriptKind {
+    P2PKHEcdsa,
+    P2PKHSchnorr,
+    P2SHMultisigEcdsa { m: usize, n: usize },
+    P2SHMultisigSchnorr { m: usize, n: usize },
+    Custom(usize),
+}
+
+impl UnlockScriptKind {
+    pub fn unlock_script_size(self) -> usize {
+        use UnlockScriptKind::*;
+        match self {
+            P2PKHEcdsa => push_size(ecdsa_sig_size()) + push_size(COMPRESSED_PUBKEY_SIZE),
+            P2PKHSchnorr => push_size(schnorr_sig_size()) + push_size(COMPRESSED_PUBKEY_SIZE),
+            P2SHMultisigEcdsa { m, n } => {
+                1 + m * push_size(ecdsa_sig_size()) + push_size(multisig_redeem_script_size(n))
+            }
+            P2SHMultisigSchnorr { m, n } => {
+                push_size((n + 7) / 8)
+                    + m * push_size(schnorr_sig_size())
+                    + push_size(multisig_redeem_script_size(n))
+            }
+            Custom(size) => size,
+        }
+    }
+
+    pub fn input_size(self) -> usize {
+        let script_size = self.unlock_script_size();
+        OUTPOINT_SIZE + encode_var_int(script_size as u64).len() + script_size + SEQUENCE_SIZE
+    }
+}
+
+fn ecdsa_sig_size() -> usize {
+    MAX_SIGNATURE_SIZE + 1
+}
+
+fn schnorr_sig_size() -> usize {
+    SCHNORR_SIGNATURE_SIZE + 1
+}
+
+fn multisig_redeem_script_size(n: usize) -> usize {
+    // OP_m <pubkeys...> OP_n OP_CHECKMULTISIG
+    3 + n * push_size(COMPRESSED_PUBKEY_SIZE)
+}
+
+pub fn push_size(data_size: usize) -> usize {
+    match data_size {
+        0..=0x4b => 1 + data_size,
+        0x4c..=0xff => 2 + data_size,
+        0x100..=0xffff => 3 + data_size,
+        _ => 5 + data_size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnlockScriptKind;
+    use crate::{Ops, P2PKHSignatory, Pubkey, Script, SigHashFlags, Signatory, TxPreimage};
+
+    #[test]
+    fn test_p2pkh_unlock_size() {
+        let signatory = P2PKHSignatory {
+            pubkey: Pubkey::new([2; 33]),
+            sig_hash_flags: SigHashFlags::DEFAULT,
+        };
+        let lock_script = Script::new(vec![]);
+        let script = signatory.build_script(
+            &TxPreimage::empty_with_script(&lock_script),
+            None,
+            signatory.placeholder_signatures(),
+            &lock_script,
+            &[],
+        );
+        let script = Script::new(script.ops().into_owned());
+        assert_eq!(
+            script.ser_ops().len(),
+            UnlockScriptKind::P2PKHEcdsa.unlock_script_size(),
+        );
+        assert_eq!(UnlockScriptKind::P2PKHEcdsa.unlock_script_size(), 108);
+        assert_eq!(UnlockScriptKind::P2PKHEcdsa.input_size(), 149);
+        assert_eq!(UnlockScriptKind::P2PKHSchnorr.unlock_script_size(), 100);
+    }
+
+    #[test]
+    fn test_multisig_unlock_size() {
+        // OP_0 + 2 * (1 + 73) + PUSHDATA1 (1 + 1) + 105-byte redeem script
+        assert_eq!(
+            UnlockScriptKind::P2SHMultisigEcdsa { m: 2, n: 3 }.unlock_script_size(),
+            1 + 2 * 74 + 2 + 105,
+        );
+        assert_eq!(
+            UnlockScriptKind::P2SHMultisigSchnorr { m: 2, n: 3 }.unlock_script_size(),
+            2 + 2 * 66 + 2 + 105,
+        );
+        assert_eq!(UnlockScriptKind::Custom(10).input_size(), 36 + 1 + 10 + 4);
+    }
+}
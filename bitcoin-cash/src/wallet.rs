@@ -0,0 +1,141 @@
+use crate::error::Result;
+use crate::{
+    Address, ChainBackend, ExtendedSecretKey, KeyPair, P2pkhSignatory, Prefix, Script, Sha256d,
+    SigHashFlags, TxBuilder, TxHistoryEntry, UnsignedTxInput, Utxo, DEFAULT_SEQUENCE, ECC,
+};
+
+// BIP44's address gap limit
+pub const DEFAULT_WALLET_ADDRESS_COUNT: u32 = 20;
+pub const DEFAULT_CONFIRMATION_TARGET: u32 = 6;
+
+const RECEIVE_CHAIN: u32 = 0;
+const CHANGE_CHAIN: u32 = 1;
+
+// a minimal P2PKH wallet on top of a ChainBackend, with keys derived BIP44-style from an
+// account key: receive addresses at 0/i, change at 1/0. It only handles BCH; utxos carrying
+// tokens are left out of the balance and coin selection, so sending never burns them.
+pub struct Wallet<E: ECC, B: ChainBackend> {
+    ecc: E,
+    account_key: ExtendedSecretKey,
+    backend: B,
+    address_count: u32,
+}
+
+impl<E: ECC, B: ChainBackend> Wallet<E, B> {
+    pub fn new(ecc: E, account_key: ExtendedSecretKey, backend: B) -> Self {
+        Wallet {
+            ecc,
+            account_key,
+            backend,
+            address_count: DEFAULT_WALLET_ADDRESS_COUNT,
+        }
+    }
+
+    // number of receive addresses considered for balance, history and coin selection
+    pub fn set_address_count(&mut self, address_count: u32) {
+        self.address_count = address_count;
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn receive_address(&self, index: u32) -> Result<Address<'static>> {
+        self.address(RECEIVE_CHAIN, index)
+    }
+
+    pub fn change_address(&self) -> Result<Address<'static>> {
+        self.address(CHANGE_CHAIN, 0)
+    }
+
+    pub async fn balance(&self) -> Result<u64> {
+        let utxos = self.utxos().await?;
+        Ok(utxos.iter().map(|(utxo, _)| utxo.value).sum())
+    }
+
+    // oldest first, unconfirmed txs last
+    pub async fn history(&self) -> Result<Vec<TxHistoryEntry>> {
+        let mut history = Vec::new();
+        for keypair in self.keypairs()? {
+            let lock_script: Script = Address::from_pk(Prefix::default(), keypair.pubkey()).into();
+            for entry in self.backend.history(&lock_script).await? {
+                if !history.contains(&entry) {
+                    history.push(entry);
+                }
+            }
+        }
+        history.sort_by_key(|entry| entry.height.unwrap_or(u32::MAX));
+        Ok(history)
+    }
+
+    // pays `amount` to `address` from the largest utxos first, sends the rest to the change
+    // address, and returns the txid of the broadcast tx
+    pub async fn send_to(&self, address: &Address<'_>, amount: u64) -> Result<Sha256d> {
+        let fee_rate = self
+            .backend
+            .estimate_fee(DEFAULT_CONFIRMATION_TARGET)
+            .await?;
+        let mut utxos = self.utxos().await?;
+        utxos.sort_by_key(|(utxo, _)| std::cmp::Reverse(utxo.value));
+        let mut builder = TxBuilder::new_simple();
+        builder.set_fee_rate(fee_rate);
+        builder.add_address_output(address, amount, None)?;
+        builder.add_leftover_output(self.change_address()?.into());
+        let mut selected_amount = 0;
+        let mut inputs = Vec::new();
+        for (utxo, keypair) in utxos {
+            if selected_amount >= amount + fee_rate.fee_for_size(builder.estimated_size()) {
+                break;
+            }
+            let signatory = P2pkhSignatory::new(keypair, SigHashFlags::DEFAULT);
+            let input = UnsignedTxInput {
+                prev_out: utxo.outpoint,
+                sequence: DEFAULT_SEQUENCE,
+                value: utxo.value,
+                token: None,
+            };
+            let input_ref = builder.add_input(input, signatory.lock_script(), signatory.clone());
+            inputs.push((input_ref, signatory));
+            selected_amount += utxo.value;
+        }
+        let mut unsigned_tx = builder.build()?;
+        for (input_ref, signatory) in inputs {
+            signatory.sign_input(&self.ecc, &mut unsigned_tx, input_ref)?;
+        }
+        let tx = unsigned_tx.complete_tx()?.hashed();
+        self.backend.broadcast_tx(&tx).await
+    }
+
+    fn address(&self, chain: u32, index: u32) -> Result<Address<'static>> {
+        let keypair = self.keypair(chain, index)?;
+        Ok(Address::from_pk(Prefix::default(), keypair.pubkey()))
+    }
+
+    fn keypair(&self, chain: u32, index: u32) -> Result<KeyPair> {
+        self.account_key
+            .derive_path(&self.ecc, &[chain, index])?
+            .keypair(&self.ecc)
+    }
+
+    fn keypairs(&self) -> Result<Vec<KeyPair>> {
+        let mut keypairs = (0..self.address_count)
+            .map(|index| self.keypair(RECEIVE_CHAIN, index))
+            .collect::<Result<Vec<_>>>()?;
+        keypairs.push(self.keypair(CHANGE_CHAIN, 0)?);
+        Ok(keypairs)
+    }
+
+    // utxos without tokens
+    async fn utxos(&self) -> Result<Vec<(Utxo, KeyPair)>> {
+        let mut utxos = Vec::new();
+        for keypair in self.keypairs()? {
+            let address = Address::from_pk(Prefix::default(), keypair.pubkey());
+            for utxo in self.backend.address_utxos(&address).await? {
+                if utxo.token.is_none() {
+                    utxos.push((utxo, keypair.clone()));
+                }
+            }
+        }
+        Ok(utxos)
+    }
+}